@@ -0,0 +1,59 @@
+use crate::{OFClient, OFClientBuilder};
+use reqwest_cookie_store::CookieStoreRwLock;
+use std::{collections::HashMap, sync::{Arc, RwLock}};
+
+/// Owns one client per account, each with its own cookie store, proxy, rate limits and rules cache
+#[derive(Debug, Default)]
+pub struct AccountManager {
+	clients: RwLock<HashMap<String, OFClient>>,
+}
+
+impl AccountManager {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Builds and registers the client of account `id`, replacing any previous one.
+	/// Proxies and other connection settings are taken from `builder`
+	pub fn add<S: Into<String>>(&self, id: S, mut builder: OFClientBuilder) -> reqwest::Result<OFClient> {
+		let id = id.into();
+		let mut clients = self.clients.write().unwrap();
+
+		// Cookies handed in through a store that another account already uses are copied,
+		// so that a login on one account can never be seen by another
+		let shared = clients
+			.iter()
+			.filter(|(other, _)| **other != id)
+			.any(|(_, client)| Arc::ptr_eq(&client.headers.load().cookie, &builder.headers.cookie));
+
+		if shared {
+			let cookies = builder.headers.cookie.read().unwrap().clone();
+			builder.headers.cookie = Arc::new(CookieStoreRwLock::new(cookies));
+		}
+
+		let client = builder.build()?;
+		clients.insert(id, client.clone());
+		Ok(client)
+	}
+
+	/// A handle to the client of account `id`
+	pub fn get(&self, id: &str) -> Option<OFClient> {
+		self.clients.read().unwrap().get(id).cloned()
+	}
+
+	pub fn remove(&self, id: &str) -> Option<OFClient> {
+		self.clients.write().unwrap().remove(id)
+	}
+
+	pub fn ids(&self) -> Vec<String> {
+		self.clients.read().unwrap().keys().cloned().collect()
+	}
+
+	pub fn len(&self) -> usize {
+		self.clients.read().unwrap().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.clients.read().unwrap().is_empty()
+	}
+}
@@ -0,0 +1,58 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex};
+use tokio::sync::Notify;
+
+type Hook = Arc<dyn Fn() + Send + Sync>;
+
+/// Holds requests back while the session is expired, until new authentication is provided.
+/// The hook runs once per expiry, on the request that found out, without holding the gate's lock so it may use the gate itself
+#[derive(Default)]
+pub struct AuthGate {
+	expired: AtomicBool,
+	resumed: Notify,
+	hook: Mutex<Option<Hook>>,
+}
+
+impl AuthGate {
+	pub fn set_hook(&self, hook: impl Fn() + Send + Sync + 'static) {
+		*self.hook.lock().unwrap() = Some(Arc::new(hook));
+	}
+
+	pub fn is_expired(&self) -> bool {
+		self.expired.load(Ordering::Acquire)
+	}
+
+	/// Returns whether the session was still considered valid until now
+	pub fn expire(&self) -> bool {
+		let newly = !self.expired.swap(true, Ordering::AcqRel);
+		if newly {
+			warn!("The session expired, holding requests back until the authentication is updated");
+			let hook = self.hook.lock().unwrap().clone();
+			if let Some(hook) = hook { hook() }
+		}
+
+		newly
+	}
+
+	pub fn resume(&self) {
+		if self.expired.swap(false, Ordering::AcqRel) {
+			info!("Authentication updated, resuming requests");
+		}
+		self.resumed.notify_waiters();
+	}
+
+	/// Waits until the session is valid again, right away if it is
+	pub async fn wait(&self) {
+		loop {
+			// Registered before checking, so a resume in between isn't missed
+			let resumed = self.resumed.notified();
+			if !self.is_expired() { return }
+			resumed.await;
+		}
+	}
+}
+
+impl std::fmt::Debug for AuthGate {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("AuthGate").field("expired", &self.is_expired()).finish_non_exhaustive()
+	}
+}
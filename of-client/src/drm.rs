@@ -10,10 +10,11 @@ use reqwest_cookie_store::RawCookie;
 use reqwest::Url;
 use thiserror::Error;
 
-use crate::{media::DRM, OFClient};
+use crate::{media::{DRMSignature, DRM}, OFClient};
 
 const NS: &str = "urn:mpeg:dash:schema:mpd:2011";
 const CENC: &str = "urn:mpeg:cenc:2013";
+const WIDEVINE: &str = "urn:uuid:edef8ba9-79d6-4ace-a3c8-27dcd51d21ed";
 
 #[derive(Error, Debug)]
 pub enum MPDFetchError {
@@ -33,33 +34,101 @@ pub enum KeyFetchError {
 	Widevine(#[from] widevine::Error)
 }
 
+/// The two manifests every DRM-protected media is served with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamingFormat {
+	Dash,
+	Hls,
+}
+
 pub struct MPDData {
 	pub base_url: String,
 	pub pssh: Pssh,
 	pub last_modified: Option<SystemTime>
 }
 
+/// Value of an attribute in an `#EXT-X-...` tag, like `URI` in `#EXT-X-KEY:METHOD=SAMPLE-AES,URI="..."`
+fn playlist_attribute<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+	let (_, attributes) = line.split_once(':')?;
+	let mut rest = attributes;
+
+	while !rest.is_empty() {
+		let (key, value) = rest.split_once('=')?;
+		let (value, next) = match value.strip_prefix('"') {
+			Some(quoted) => {
+				let (value, next) = quoted.split_once('"')?;
+				(value, next.strip_prefix(',').unwrap_or(next))
+			},
+			None => value.split_once(',').unwrap_or((value, ""))
+		};
+
+		if key.trim() == name { return Some(value) }
+		rest = next;
+	}
+
+	None
+}
+
+/// Base64 Widevine PSSH of the first key tag in an HLS playlist
+fn playlist_pssh(playlist: &str) -> Option<&str> {
+	playlist
+	.lines()
+	.filter(|line| line.starts_with("#EXT-X-KEY:") || line.starts_with("#EXT-X-SESSION-KEY:"))
+	.filter(|line| playlist_attribute(line, "KEYFORMAT").is_some_and(|format| format.eq_ignore_ascii_case(WIDEVINE)))
+	.find_map(|line| {
+		let uri = playlist_attribute(line, "URI")?;
+		uri.split_once("base64,").map(|(_, pssh)| pssh)
+	})
+}
+
+/// URI of the variant with the highest bandwidth in an HLS master playlist
+fn best_variant(playlist: &str) -> Option<&str> {
+	let mut lines = playlist.lines();
+	let mut best = None;
+
+	while let Some(line) = lines.next() {
+		if !line.starts_with("#EXT-X-STREAM-INF:") { continue }
+
+		let bandwidth = playlist_attribute(line, "BANDWIDTH").and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+		let Some(uri) = lines.by_ref().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#')) else { break };
+		if best.is_none_or(|(best_bandwidth, _)| bandwidth > best_bandwidth) {
+			best = Some((bandwidth, uri));
+		}
+	}
+
+	best.map(|(_, uri)| uri)
+}
+
 impl OFClient {
+	/// Sets the CloudFront cookies that grant access to `manifest` and the segments next to it
+	fn sign_manifest(&self, manifest: &Url, signature: &DRMSignature) -> HeaderMap {
+		let headers = self.headers.load();
+		let mut write_lock = headers.cookie.write().unwrap();
+		write_lock.insert_raw(&RawCookie::new("CloudFront-Policy", &signature.policy), manifest).unwrap();
+		write_lock.insert_raw(&RawCookie::new("CloudFront-Signature", &signature.signature), manifest).unwrap();
+		write_lock.insert_raw(&RawCookie::new("CloudFront-Key-Pair-Id", &signature.key_pair), manifest).unwrap();
+
+		let mut header_map = HeaderMap::new();
+		header_map.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+		header_map.insert(header::USER_AGENT, HeaderValue::from_str(&headers.user_agent).unwrap());
+		header_map
+	}
+
+	pub async fn get_manifest_data(&self, drm: &DRM, format: StreamingFormat) -> Result<MPDData, MPDFetchError> {
+		match format {
+			StreamingFormat::Dash => self.get_mpd_data(drm).await,
+			StreamingFormat::Hls => self.get_hls_data(drm).await
+		}
+	}
+
 	pub async fn get_mpd_data(&self, drm: &DRM) -> Result<MPDData, MPDFetchError> {
-		let signature = &drm.signature.dash;
 		let mpd = Url::parse(&drm.manifest.dash).unwrap();
-		
-		let header_map = {
-			let headers = self.headers.read().unwrap();
-			let mut write_lock = headers.cookie.write().unwrap();
-			write_lock.insert_raw(&RawCookie::new("CloudFront-Policy", &signature.policy), &mpd).unwrap();
-			write_lock.insert_raw(&RawCookie::new("CloudFront-Signature", &signature.signature), &mpd).unwrap();
-			write_lock.insert_raw(&RawCookie::new("CloudFront-Key-Pair-Id", &signature.key_pair), &mpd).unwrap();
-
-			let mut header_map = HeaderMap::new();
-			header_map.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
-			header_map.insert(header::USER_AGENT, HeaderValue::from_str(&headers.user_agent).unwrap());
-			header_map
-		};
+		let header_map = self.sign_manifest(&mpd, &drm.signature.dash);
 
-		let response = self.client.request(Method::GET, mpd)
-			.headers(header_map)
-			.send()
+		let response = self.send(
+				self.client.request(Method::GET, mpd)
+				.headers(header_map)
+			)
 			.await?;
 
 		let last_modified = response.headers().get(header::LAST_MODIFIED)
@@ -83,7 +152,7 @@ impl OFClient {
 			.children()
 			.find(|e| e.name() == "ContentProtection"
 				&& e.attrs()
-					.any(|(name, value)| name == "schemeIdUri" && value == "urn:uuid:edef8ba9-79d6-4ace-a3c8-27dcd51d21ed")
+					.any(|(name, value)| name == "schemeIdUri" && value == WIDEVINE)
 			)
 			.ok_or_else(|| MPDFetchError::ValueNotFound("ContentProtection".to_string()))?
 			.get_child("pssh", CENC)
@@ -103,6 +172,58 @@ impl OFClient {
 		Ok(MPDData { base_url, pssh, last_modified })
 	}
 
+	/// Reads the Widevine PSSH from the HLS manifest, or from its best variant when the master playlist doesn't list the keys.
+	/// The file name is the manifest's, with an mp4 extension
+	pub async fn get_hls_data(&self, drm: &DRM) -> Result<MPDData, MPDFetchError> {
+		let m3u8 = Url::parse(&drm.manifest.hls).unwrap();
+		let header_map = self.sign_manifest(&m3u8, &drm.signature.hls);
+
+		let response = self.send(
+				self.client.request(Method::GET, m3u8.clone())
+				.headers(header_map.clone())
+			)
+			.await?;
+
+		let last_modified = response.headers().get(header::LAST_MODIFIED)
+			.and_then(|header| header.to_str().ok())
+			.and_then(|v| parse_http_date(v).ok());
+
+		let master = response.text().await?;
+		let pssh = match playlist_pssh(&master) {
+			Some(pssh) => pssh.to_string(),
+			None => {
+				let variant = best_variant(&master)
+					.and_then(|uri| m3u8.join(uri).ok())
+					.ok_or_else(|| MPDFetchError::ValueNotFound("EXT-X-STREAM-INF".to_string()))?;
+
+				let playlist = self.send(
+						self.client.request(Method::GET, variant)
+						.headers(header_map)
+					)
+					.await?
+					.text()
+					.await?;
+
+				playlist_pssh(&playlist)
+					.ok_or_else(|| MPDFetchError::ValueNotFound("EXT-X-KEY".to_string()))?
+					.to_string()
+			}
+		};
+
+		let pssh = Pssh::from_b64(&pssh)
+			.map_err(|_| MPDFetchError::ValueNotFound("pssh".to_string()))?;
+
+		let base_url = m3u8
+			.path_segments()
+			.and_then(Iterator::last)
+			.map(|name| name.rsplit_once('.').map_or(name, |(stem, _)| stem))
+			.filter(|stem| !stem.is_empty())
+			.map(|stem| format!("{stem}.mp4"))
+			.ok_or_else(|| MPDFetchError::ValueNotFound("file name".to_string()))?;
+
+		Ok(MPDData { base_url, pssh, last_modified })
+	}
+
 	pub async fn get_decryption_key(&self, cdm: &Cdm, license_url: &str, pssh: Pssh) -> Result<Key, KeyFetchError> {
 		let request = cdm
 			.open()
@@ -125,7 +246,7 @@ impl OFClient {
 		let mut header_str = String::new();
 		header_str.push_str("Cookie: ");
 		
-		let headers = self.headers.read().unwrap();
+		let headers = self.headers.load();
 
 		for (name, val) in headers.cookie.read().unwrap().get_request_values(&url) {
 			header_str.push_str(name);
@@ -134,8 +255,10 @@ impl OFClient {
 			header_str.push(';');
 		}
 
-		header_str.push_str("User-Agent: ");
+		// ffmpeg expects one header per line
+		header_str.push_str("\r\nUser-Agent: ");
 		header_str.push_str(&headers.user_agent);
+		header_str.push_str("\r\n");
 		header_str
 	}
 }
\ No newline at end of file
@@ -2,6 +2,15 @@
 extern crate log;
 
 pub mod structs;
+pub mod pagination;
+pub mod scheduler;
+pub mod ratelimit;
+pub mod retry;
+pub mod accounts;
+pub mod auth;
+mod singleflight;
+#[cfg(feature = "vcr")]
+pub mod vcr;
 #[cfg(feature = "drm")]
 pub mod drm;
 #[cfg(feature = "drm")]
@@ -16,11 +25,21 @@ use log::*;
 use httpdate::fmt_http_date;
 use reqwest_cookie_store::CookieStoreRwLock;
 use serde::{Deserialize, Serialize};
-use cached::proc_macro::once;
+use arc_swap::ArcSwap;
 use futures::TryFutureExt;
 use sha1_smol::Sha1;
-use reqwest::{header::{self, HeaderValue}, Body, Client, IntoUrl, Method, RequestBuilder, Response, Url};
-use std::{borrow::Cow, sync::{Arc, RwLock}, time::{SystemTime, UNIX_EPOCH}};
+use scheduler::{Priority, RequestScheduler};
+use ratelimit::{endpoint_class, RateLimits};
+use retry::RetryPolicy;
+use auth::AuthGate;
+use singleflight::SingleFlight;
+use user::User;
+use reqwest::{header::{self, HeaderValue}, Body, Client, ClientBuilder, IntoUrl, Method, Request, RequestBuilder, Response, StatusCode, Url};
+use tokio::{sync::Mutex, time::sleep};
+use std::{borrow::Cow, mem, sync::Arc, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+const DYNAMIC_RULES_TTL: Duration = Duration::from_secs(3600);
 
 #[derive(Deserialize, Debug, Clone)]
 struct DynamicRules {
@@ -32,14 +51,36 @@ struct DynamicRules {
 	checksum_indexes: Vec<usize>,
 }
 
-#[once(time = 3600, result = true, sync_writes = true)]
-async fn get_dynamic_rules() -> reqwest::Result<DynamicRules> {
-	reqwest::get("https://raw.githubusercontent.com/deviint/onlyfans-dynamic-rules/main/dynamicRules.json")
+// Through the account's client, so that the rules are fetched over its proxy too
+async fn fetch_dynamic_rules(client: &Client) -> reqwest::Result<DynamicRules> {
+	client.get("https://raw.githubusercontent.com/deviint/onlyfans-dynamic-rules/main/dynamicRules.json")
+	.send()
 	.and_then(Response::json::<DynamicRules>)
 	.await
 	.inspect_err(|err| error!("Error reading dynamic rules: {err:?}"))
 }
 
+/// Dynamic rules fetched at most once an hour, shared by every handle of a client
+#[derive(Debug, Default)]
+struct RulesCache {
+	rules: Mutex<Option<(Instant, DynamicRules)>>,
+}
+
+impl RulesCache {
+	async fn get(&self, client: &Client) -> reqwest::Result<DynamicRules> {
+		// Holding the lock while fetching keeps concurrent requests from refetching
+		let mut cached = self.rules.lock().await;
+		match &*cached {
+			Some((fetched, rules)) if fetched.elapsed() < DYNAMIC_RULES_TTL => Ok(rules.clone()),
+			_ => {
+				let rules = fetch_dynamic_rules(client).await?;
+				*cached = Some((Instant::now(), rules.clone()));
+				Ok(rules)
+			}
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct RequestHeaders {
 	pub cookie: Arc<CookieStoreRwLock>,
@@ -48,27 +89,187 @@ pub struct RequestHeaders {
 	pub user_agent: String,
 }
 
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpVersion {
+	#[default]
+	Auto,
+	Http1,
+	Http2,
+}
+
+pub struct OFClientBuilder {
+	headers: RequestHeaders,
+	builder: ClientBuilder,
+	scheduler: Option<Arc<RequestScheduler>>,
+	retry: RetryPolicy,
+	#[cfg(feature = "vcr")]
+	cassette: Option<Arc<vcr::Cassette>>,
+}
+
+impl OFClientBuilder {
+	pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+		self.builder = self.builder.pool_idle_timeout(timeout);
+		self
+	}
+
+	pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+		self.builder = self.builder.pool_max_idle_per_host(max);
+		self
+	}
+
+	pub fn tcp_keepalive(mut self, interval: Option<Duration>) -> Self {
+		self.builder = self.builder.tcp_keepalive(interval);
+		self
+	}
+
+	pub fn http_version(mut self, version: HttpVersion) -> Self {
+		self.builder = match version {
+			HttpVersion::Auto => self.builder,
+			HttpVersion::Http1 => self.builder.http1_only(),
+			HttpVersion::Http2 => self.builder.http2_prior_knowledge(),
+		};
+		self
+	}
+
+	/// Routes every request of this client through `proxy`
+	pub fn proxy<U: IntoUrl>(mut self, proxy: U) -> reqwest::Result<Self> {
+		self.builder = self.builder.proxy(reqwest::Proxy::all(proxy)?);
+		Ok(self)
+	}
+
+	/// Spreads requests out to at most `max_requests` every `window` on average
+	pub fn schedule(mut self, max_requests: usize, window: Duration) -> Self {
+		self.scheduler = Some(Arc::new(RequestScheduler::new(max_requests, window)));
+		self
+	}
+
+	/// How requests are retried unless a handle overrides it with `OFClient::with_retry`
+	pub fn retry(mut self, policy: RetryPolicy) -> Self {
+		self.retry = policy;
+		self
+	}
+
+	#[cfg(feature = "vcr")]
+	pub fn cassette(mut self, cassette: Arc<vcr::Cassette>) -> Self {
+		self.cassette = Some(cassette);
+		self
+	}
+
+	pub fn build(self) -> reqwest::Result<OFClient> {
+		let client = self.builder
+		.cookie_provider(self.headers.cookie.clone())
+		// Advertised in Accept-Encoding and decoded transparently
+		.gzip(true)
+		.brotli(true)
+		.zstd(true)
+		.build()?;
+
+		Ok(OFClient {
+			client,
+			headers: Arc::new(ArcSwap::from_pointee(self.headers)),
+			user_requests: Arc::default(),
+			scheduler: self.scheduler,
+			priority: Priority::default(),
+			retry: self.retry,
+			rate_limits: Arc::default(),
+			rules: Arc::default(),
+			auth: Arc::default(),
+			#[cfg(feature = "vcr")]
+			cassette: self.cassette,
+		})
+	}
+}
+
 #[derive(Debug, Clone)]
 pub struct OFClient {
 	client: Client,
-	pub headers: Arc<RwLock<RequestHeaders>>,
+	pub headers: Arc<ArcSwap<RequestHeaders>>,
+	user_requests: Arc<SingleFlight<User>>,
+	scheduler: Option<Arc<RequestScheduler>>,
+	priority: Priority,
+	retry: RetryPolicy,
+	rate_limits: Arc<RateLimits>,
+	rules: Arc<RulesCache>,
+	auth: Arc<AuthGate>,
+	#[cfg(feature = "vcr")]
+	cassette: Option<Arc<vcr::Cassette>>,
 }
 
 impl OFClient {
 	pub fn new<H: Into<RequestHeaders>>(headers: H) -> reqwest::Result<Self> {
-		let headers = headers.into();
+		Self::builder(headers).build()
+	}
 
-		let client = reqwest::Client::builder()
-		.cookie_provider(headers.cookie.clone())
-		.gzip(true)
-		.build()?;
+	pub fn builder<H: Into<RequestHeaders>>(headers: H) -> OFClientBuilder {
+		OFClientBuilder {
+			headers: headers.into(),
+			builder: Client::builder(),
+			scheduler: None,
+			retry: RetryPolicy::default(),
+			#[cfg(feature = "vcr")]
+			cassette: None,
+		}
+	}
+
+	pub fn rate_limits(&self) -> Arc<RateLimits> {
+		self.rate_limits.clone()
+	}
 
-		Ok(OFClient { client, headers: Arc::new(RwLock::new(headers)) })
+	/// A handle to the same client whose requests are scheduled with `priority`
+	pub fn with_priority(&self, priority: Priority) -> Self {
+		Self { priority, ..self.clone() }
+	}
+
+	/// A handle to the same client whose requests are retried following `policy`.
+	/// Non-idempotent requests are only retried if `policy` opts in to it
+	pub fn with_retry(&self, policy: RetryPolicy) -> Self {
+		Self { retry: policy, ..self.clone() }
+	}
+
+	pub fn update_headers<H: Into<RequestHeaders>>(&self, headers: H) {
+		let mut headers = headers.into();
+		let current = self.headers.load();
+
+		// The underlying client keeps reading cookies from the store it was built with
+		let cookies = mem::take(&mut *headers.cookie.write().unwrap());
+		*current.cookie.write().unwrap() = cookies;
+		headers.cookie = current.cookie.clone();
+
+		self.headers.store(Arc::new(headers));
+		self.auth.resume();
+	}
+
+	/// Runs `hook` once whenever the session expires, after which requests wait for `update_headers`
+	pub fn on_auth_failure(&self, hook: impl Fn() + Send + Sync + 'static) {
+		self.auth.set_hook(hook);
+	}
+
+	/// Whether requests are held back until the authentication is updated
+	pub fn is_auth_expired(&self) -> bool {
+		self.auth.is_expired()
+	}
+
+	/// Treats the session as expired, like a 401 response does, for failures noticed outside of this client
+	pub fn report_auth_failure(&self) {
+		self.auth.expire();
+	}
+
+	/// Cookies currently sent to onlyfans.com, in `Cookie` header format
+	pub fn cookie_string(&self) -> String {
+		let url = Url::parse("https://onlyfans.com").unwrap();
+		let headers = self.headers.load();
+		let store = headers.cookie.read().unwrap();
+
+		store.get_request_values(&url)
+		.map(|(name, value)| format!("{name}={value}"))
+		.collect::<Vec<_>>()
+		.join("; ")
 	}
 
 	async fn make_headers<U: IntoUrl>(&self, link: U) -> reqwest::Result<header::HeaderMap> {
-		let dynamic_rules = get_dynamic_rules().await?;
-		let headers = self.headers.read().unwrap();
+		let dynamic_rules = self.rules.get(&self.client).await?;
+		let headers = self.headers.load();
 
 		let url: Url = link.into_url()?;
 		let mut url_param: Cow<'_, str> = Cow::Borrowed(url.path());
@@ -120,24 +321,138 @@ impl OFClient {
 	}
 
 	async fn request<U: IntoUrl>(&self, method: Method, link: U) -> reqwest::Result<RequestBuilder> {
+		self.auth.wait().await;
+		self.request_now(method, link).await
+	}
+
+	/// Builds the request without waiting for an expired session to be renewed
+	async fn request_now<U: IntoUrl>(&self, method: Method, link: U) -> reqwest::Result<RequestBuilder> {
+		#[cfg(feature = "vcr")]
+		if self.cassette.as_ref().is_some_and(|cassette| cassette.mode() == vcr::CassetteMode::Replay) {
+			return Ok(self.client.request(method, link))
+		}
+
+		if let Some(scheduler) = &self.scheduler {
+			scheduler.acquire(self.priority).await;
+		}
+
 		let headers = self.make_headers(link.as_str()).await?;
 
 		Ok(self.client.request(method, link)
 			.headers(headers))
 	}
 
+	/// Prepares a request to be sent again, taking a scheduler slot like its first attempt did.
+	/// Signatures are time based, so they have to be renewed after waiting
+	async fn requeue(&self, mut request: Request) -> reqwest::Result<Request> {
+		if let Some(scheduler) = &self.scheduler {
+			scheduler.acquire(self.priority).await;
+		}
+
+		let headers = self.make_headers(request.url().as_str()).await?;
+		request.headers_mut().extend(headers);
+		Ok(request)
+	}
+
+	/// Sends the request, retrying transient failures following the retry policy,
+	/// and waiting out and retrying 429 responses a few times before they are returned
+	pub(crate) async fn send(&self, builder: RequestBuilder) -> reqwest::Result<Response> {
+		#[cfg(feature = "vcr")]
+		if let Some(cassette) = &self.cassette {
+			return cassette.send(&self.client, builder.build()?).await
+		}
+
+		let mut request = builder.build()?;
+		let class = endpoint_class(request.url());
+		let retryable = self.retry.applies_to(request.method());
+
+		let mut attempt = 0;
+		let mut retries = 0;
+		// Every attempt, retries included, waits out the rate limit of its endpoint
+		loop {
+			self.rate_limits.wait(&class).await;
+
+			// Streamed bodies can't be sent again
+			let retry = request.try_clone();
+			let result = self.client.execute(request).await;
+			let failure = match &result {
+				Ok(response) => retry::is_transient_status(response.status()).then(|| response.status().to_string()),
+				Err(err) => retry::is_transient_error(err).then(|| err.to_string())
+			};
+
+			let retry = match (failure, retry) {
+				(Some(failure), Some(retry)) if retryable && retries < self.retry.max_retries => {
+					retries += 1;
+					let delay = self.retry.delay(retries);
+					warn!("Request to {class} failed ({failure}), retrying in {}ms ({retries}/{})", delay.as_millis(), self.retry.max_retries);
+
+					sleep(delay).await;
+					request = self.requeue(retry).await?;
+					continue;
+				},
+				(_, retry) => retry
+			};
+
+			let response = result?;
+			if response.status() == StatusCode::UNAUTHORIZED {
+				self.auth.expire();
+				return Ok(response)
+			}
+
+			attempt += 1;
+			if response.status() != StatusCode::TOO_MANY_REQUESTS {
+				self.rate_limits.succeeded(&class);
+				return Ok(response)
+			}
+
+			let retry_after = response
+				.headers()
+				.get(header::RETRY_AFTER)
+				.and_then(|header| header.to_str().ok())
+				.and_then(|value| value.trim().parse().ok())
+				.map(Duration::from_secs);
+
+			let cooldown = self.rate_limits.limited(&class, retry_after);
+			warn!("Rate limited on {class}, backing off for {}s", cooldown.as_secs());
+
+			match retry {
+				Some(retry) if attempt < MAX_RATE_LIMIT_RETRIES => {
+					request = self.requeue(retry).await?;
+				},
+				_ => return Ok(response)
+			}
+		}
+	}
+
+	/// Like `get`, but error statuses are returned as responses and the request goes out even while the session is expired
+	pub async fn get_unchecked<U: IntoUrl>(&self, link: U) -> reqwest::Result<Response> {
+		let builder = self.request_now(Method::GET, link).await?;
+		self.send(builder).await
+	}
+
 	pub async fn get<U: IntoUrl>(&self, link: U) -> reqwest::Result<Response> {
-		self.request(Method::GET, link)
-		.await?
-		.send()
+		let builder = self.request(Method::GET, link).await?;
+
+		self.send(builder)
 		.and_then(error_for_status_log)
 		.await
 	}
 
 	pub async fn get_if_modified_since<U: IntoUrl>(&self, link: U, modified_date: SystemTime) -> reqwest::Result<Response> {
-		self.request(Method::GET, link).await?
-		.header(header::IF_MODIFIED_SINCE, HeaderValue::from_str(&fmt_http_date(modified_date)).unwrap())
-		.send()
+		let builder = self.request(Method::GET, link).await?
+			.header(header::IF_MODIFIED_SINCE, HeaderValue::from_str(&fmt_http_date(modified_date)).unwrap());
+
+		self.send(builder)
+		.and_then(error_for_status_log)
+		.await
+	}
+
+	/// Requests everything from byte `offset` onwards, servers without range support answer with the whole content
+	pub async fn get_range<U: IntoUrl>(&self, link: U, offset: u64) -> reqwest::Result<Response> {
+		let builder = self.request(Method::GET, link).await?
+			.header(header::RANGE, format!("bytes={offset}-"));
+
+		self.send(builder)
 		.and_then(error_for_status_log)
 		.await
 	}
@@ -146,16 +461,16 @@ impl OFClient {
 		let mut builder = self.request(Method::POST, link).await?;
 		if let Some(body) = body { builder = builder.body(body); }
 
-		builder
-		.send()
+		self.send(builder)
 		.and_then(error_for_status_log)
 		.await
 	}
 
 	pub async fn post_json<U: IntoUrl, T: Serialize>(&self, link: U, body: &T) -> reqwest::Result<Response> {
-		self.request(Method::POST, link).await?
-		.json(body)
-		.send()
+		let builder = self.request(Method::POST, link).await?
+			.json(body);
+
+		self.send(builder)
 		.and_then(error_for_status_log)
 		.await
 	}
@@ -164,8 +479,7 @@ impl OFClient {
 		let mut builder = self.request(Method::PUT, link).await?;
 		if let Some(body) = body { builder = builder.json(body); }
 
-		builder
-		.send()
+		self.send(builder)
 		.and_then(error_for_status_log)
 		.await
 	}
@@ -0,0 +1,78 @@
+use crate::OFClient;
+use futures::{stream, Stream, TryStreamExt};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize};
+
+pub const PAGE_SIZE: usize = 50;
+
+struct Page {
+	offset: usize,
+	done: bool,
+}
+
+struct Cursor {
+	marker: Option<String>,
+	done: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CursorPage<T> {
+	list: Vec<T>,
+	#[serde(default)]
+	has_more: bool,
+	/// Passed back to get the page after this one
+	tail_marker: Option<String>,
+}
+
+impl OFClient {
+	/// Lazily walks an offset paginated endpoint, yielding one item at a time.
+	/// Only a single page is held in memory, pages are fetched as the stream is consumed.
+	pub fn paginate<'a, T: DeserializeOwned + 'a>(&'a self, url: Url, page_size: usize) -> impl Stream<Item = reqwest::Result<T>> + 'a {
+		stream::try_unfold(Page { offset: 0, done: false }, move |page| {
+			let mut url = url.clone();
+			async move {
+				if page.done { return Ok(None) }
+
+				url.query_pairs_mut()
+				.append_pair("limit", &page_size.to_string())
+				.append_pair("offset", &page.offset.to_string());
+
+				let items = self.get(url)
+					.await?
+					.json::<Vec<T>>()
+					.await?;
+
+				let next = Page { offset: page.offset + items.len(), done: items.len() < page_size };
+				Ok(Some((stream::iter(items.into_iter().map(Ok)), next)))
+			}
+		})
+		.try_flatten()
+	}
+
+	/// Lazily walks a cursor paginated endpoint, yielding one item at a time.
+	/// Every page names where the next one starts, which is requested through the `cursor` query parameter
+	pub fn paginate_by_cursor<'a, T: DeserializeOwned + 'a>(&'a self, url: Url, cursor: &'a str, page_size: usize) -> impl Stream<Item = reqwest::Result<T>> + 'a {
+		stream::try_unfold(Cursor { marker: None, done: false }, move |state| {
+			let mut url = url.clone();
+			async move {
+				if state.done { return Ok(None) }
+
+				url.query_pairs_mut()
+				.append_pair("limit", &page_size.to_string())
+				.append_pair("format", "infinite")
+				.extend_pairs(state.marker.as_ref().map(|marker| (cursor, marker)));
+
+				let page = self.get(url)
+					.await?
+					.json::<CursorPage<T>>()
+					.await?;
+
+				let done = !page.has_more || page.list.is_empty() || page.tail_marker.is_none();
+				let next = Cursor { marker: page.tail_marker, done };
+				Ok(Some((stream::iter(page.list.into_iter().map(Ok)), next)))
+			}
+		})
+		.try_flatten()
+	}
+}
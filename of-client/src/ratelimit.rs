@@ -0,0 +1,75 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use reqwest::Url;
+use serde::Serialize;
+use tokio::time::{sleep_until, Instant};
+
+const INITIAL_COOLDOWN: Duration = Duration::from_secs(30);
+const MAX_COOLDOWN: Duration = Duration::from_secs(900);
+
+#[derive(Debug, Clone, Copy)]
+struct Cooldown {
+	until: Instant,
+	strikes: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Backoff {
+	pub endpoint: String,
+	pub remaining_secs: u64,
+}
+
+/// Tracks 429 responses per endpoint class, holding back requests to a class until its cool-down passed
+#[derive(Debug, Default)]
+pub struct RateLimits {
+	cooldowns: Mutex<HashMap<String, Cooldown>>,
+}
+
+/// `users`, `posts`, ... for API endpoints, the host for everything else like media
+pub(crate) fn endpoint_class(url: &Url) -> String {
+	let mut segments = url.path_segments().into_iter().flatten();
+	match (segments.next(), segments.next(), segments.next()) {
+		(Some("api2"), Some(_), Some(resource)) => resource.to_string(),
+		_ => url.host_str().unwrap_or_default().to_string()
+	}
+}
+
+impl RateLimits {
+	pub(crate) async fn wait(&self, class: &str) {
+		let until = self.cooldowns.lock().unwrap().get(class).map(|cooldown| cooldown.until);
+		if let Some(until) = until {
+			sleep_until(until).await;
+		}
+	}
+
+	/// Starts a cool-down for `class`, for as long as the server asked or doubling with every consecutive 429
+	pub(crate) fn limited(&self, class: &str, retry_after: Option<Duration>) -> Duration {
+		let mut cooldowns = self.cooldowns.lock().unwrap();
+		let strikes = cooldowns.get(class).map_or(0, |cooldown| cooldown.strikes);
+		let duration = retry_after.unwrap_or_else(|| (INITIAL_COOLDOWN * 2u32.saturating_pow(strikes)).min(MAX_COOLDOWN));
+
+		cooldowns.insert(class.to_string(), Cooldown { until: Instant::now() + duration, strikes: strikes + 1 });
+		duration
+	}
+
+	pub(crate) fn succeeded(&self, class: &str) {
+		let mut cooldowns = self.cooldowns.lock().unwrap();
+		if cooldowns.get(class).is_some_and(|cooldown| cooldown.until <= Instant::now()) {
+			info!("Rate limit on {class} lifted");
+			cooldowns.remove(class);
+		}
+	}
+
+	/// Endpoint classes currently backing off
+	pub fn backoffs(&self) -> Vec<Backoff> {
+		let now = Instant::now();
+		self.cooldowns.lock().unwrap()
+		.iter()
+		.filter(|(_, cooldown)| cooldown.until > now)
+		.map(|(endpoint, cooldown)| Backoff { endpoint: endpoint.clone(), remaining_secs: (cooldown.until - now).as_secs() })
+		.collect()
+	}
+
+	pub fn is_backing_off(&self) -> bool {
+		!self.backoffs().is_empty()
+	}
+}
@@ -0,0 +1,62 @@
+use std::{error::Error as _, io, time::Duration};
+use rand::Rng;
+use reqwest::{Method, StatusCode};
+
+/// How requests failing with transient errors are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+	pub max_retries: u32,
+	/// Delay before the first retry, doubling with every further one
+	pub base_delay: Duration,
+	pub max_delay: Duration,
+	/// Also retries requests like POST, which the server may have handled before the connection failed.
+	/// Off by default, so that a retry doesn't like or send something twice
+	pub non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self { max_retries: 3, base_delay: Duration::from_millis(500), max_delay: Duration::from_secs(30), non_idempotent: false }
+	}
+}
+
+impl RetryPolicy {
+	/// Failures are returned right away
+	pub const NONE: Self = Self { max_retries: 0, base_delay: Duration::ZERO, max_delay: Duration::ZERO, non_idempotent: false };
+
+	/// Whether requests with `method` are retried at all
+	pub fn applies_to(&self, method: &Method) -> bool {
+		self.non_idempotent || method.is_idempotent()
+	}
+
+	/// Random delay before retry number `retry`, starting at 1, up to the exponential backoff.
+	/// The jitter keeps concurrent requests that failed together from retrying together
+	pub fn delay(&self, retry: u32) -> Duration {
+		let backoff = self.base_delay
+			.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+			.min(self.max_delay);
+
+		backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+	}
+}
+
+/// Server errors that usually go away on their own
+pub(crate) fn is_transient_status(status: StatusCode) -> bool {
+	matches!(status, StatusCode::INTERNAL_SERVER_ERROR | StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT)
+}
+
+/// Timeouts, failed connections and connections dropped by the server
+pub(crate) fn is_transient_error(err: &reqwest::Error) -> bool {
+	if err.is_timeout() || err.is_connect() { return true }
+
+	let mut source = err.source();
+	while let Some(err) = source {
+		if let Some(err) = err.downcast_ref::<io::Error>() {
+			return matches!(err.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof)
+		}
+
+		source = err.source();
+	}
+
+	false
+}
@@ -0,0 +1,91 @@
+use std::{pin::pin, sync::Mutex, time::Duration};
+use tokio::{select, sync::Notify, time::{sleep_until, Instant}};
+
+/// How urgently a request should be sent, higher priorities are sent before any lower ones waiting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+	/// Background traffic like backfills, sent only when nothing else is waiting
+	Bulk,
+	#[default]
+	Normal,
+	/// Requests a user is waiting on, sent right away without waiting for a slot
+	Interactive,
+}
+
+#[derive(Debug)]
+struct State {
+	next_slot: Instant,
+	waiting: [usize; 3],
+}
+
+/// Spreads requests evenly over time, so bursts don't stand out from a browser's traffic
+#[derive(Debug)]
+pub struct RequestScheduler {
+	spacing: Duration,
+	state: Mutex<State>,
+	changed: Notify,
+}
+
+struct Waiting<'a> {
+	scheduler: &'a RequestScheduler,
+	priority: Priority,
+}
+
+impl Drop for Waiting<'_> {
+	// Also runs when the request is cancelled while waiting
+	fn drop(&mut self) {
+		self.scheduler.state.lock().unwrap().waiting[self.priority as usize] -= 1;
+		self.scheduler.changed.notify_waiters();
+	}
+}
+
+impl RequestScheduler {
+	/// Allows at most `max_requests` every `window` on average
+	pub fn new(max_requests: usize, window: Duration) -> Self {
+		Self {
+			spacing: window / max_requests.max(1) as u32,
+			state: Mutex::new(State { next_slot: Instant::now(), waiting: [0; 3] }),
+			changed: Notify::new(),
+		}
+	}
+
+	/// Waits until a request of `priority` may be sent
+	pub async fn acquire(&self, priority: Priority) {
+		if priority == Priority::Interactive {
+			let mut state = self.state.lock().unwrap();
+			state.next_slot = state.next_slot.max(Instant::now()) + self.spacing;
+			return;
+		}
+
+		self.state.lock().unwrap().waiting[priority as usize] += 1;
+		let _waiting = Waiting { scheduler: self, priority };
+
+		loop {
+			let mut notified = pin!(self.changed.notified());
+			notified.as_mut().enable();
+
+			let wait_until = {
+				let mut state = self.state.lock().unwrap();
+				let now = Instant::now();
+				let preempted = state.waiting[priority as usize + 1..].iter().any(|waiting| *waiting > 0);
+
+				match preempted {
+					true => None,
+					false if state.next_slot <= now => {
+						state.next_slot = now + self.spacing;
+						return;
+					},
+					false => Some(state.next_slot)
+				}
+			};
+
+			match wait_until {
+				Some(wait_until) => select! {
+					_ = sleep_until(wait_until) => (),
+					_ = notified => ()
+				},
+				None => notified.await
+			}
+		}
+	}
+}
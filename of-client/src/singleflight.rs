@@ -0,0 +1,90 @@
+use std::{collections::HashMap, future::Future, sync::{atomic::{AtomicU64, Ordering}, Mutex}};
+use futures::channel::oneshot;
+
+#[derive(Debug)]
+struct Flight<T> {
+	id: u64,
+	waiters: Vec<oneshot::Sender<T>>,
+}
+
+/// Coalesces concurrent calls sharing a key, so that only the first caller performs the request
+/// and every caller waiting on it receives a clone of its successful result.
+/// Failures are not shared: waiting callers retry on their own to get their own error.
+#[derive(Debug)]
+pub(crate) struct SingleFlight<T> {
+	flights: Mutex<HashMap<String, Flight<T>>>,
+	next_id: AtomicU64,
+}
+
+impl<T> Default for SingleFlight<T> {
+	fn default() -> Self {
+		Self { flights: Mutex::default(), next_id: AtomicU64::default() }
+	}
+}
+
+impl<T> SingleFlight<T> {
+	/// Ends the flight `id` under `key`, unless a later one already took its place
+	fn land(&self, key: &str, id: u64) -> Vec<oneshot::Sender<T>> {
+		let mut flights = self.flights.lock().unwrap();
+		if !flights.get(key).is_some_and(|flight| flight.id == id) { return Vec::new() }
+
+		flights.remove(key).map(|flight| flight.waiters).unwrap_or_default()
+	}
+}
+
+struct Leader<'a, T> {
+	flight: &'a SingleFlight<T>,
+	key: &'a str,
+	id: u64,
+}
+
+impl<T> Drop for Leader<'_, T> {
+	// Dropping the senders wakes up the waiters if the leader was cancelled or failed
+	fn drop(&mut self) {
+		self.flight.land(self.key, self.id);
+	}
+}
+
+impl<T: Clone> SingleFlight<T> {
+	pub async fn run<E, F, Fut>(&self, key: &str, request: F) -> Result<T, E>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<T, E>>,
+	{
+		let joined = {
+			let mut flights = self.flights.lock().unwrap();
+			match flights.get_mut(key) {
+				Some(flight) => {
+					let (sender, receiver) = oneshot::channel();
+					flight.waiters.push(sender);
+					Err(receiver)
+				},
+				None => {
+					let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+					flights.insert(key.to_string(), Flight { id, waiters: Vec::new() });
+					Ok(id)
+				}
+			}
+		};
+
+		let id = match joined {
+			Ok(id) => id,
+			Err(receiver) => return match receiver.await {
+				Ok(value) => Ok(value),
+				Err(_) => request().await
+			}
+		};
+
+		let leader = Leader { flight: self, key, id };
+		let result = request().await;
+
+		if let Ok(value) = &result {
+			for sender in self.land(key, id) {
+				let _ = sender.send(value.clone());
+			}
+		}
+
+		drop(leader);
+		result
+	}
+}
@@ -1,14 +1,14 @@
 #![allow(dead_code)]
 
 use deserializers::from_str;
-use crate::{OFClient, media, user::User};
-use std::{slice, fmt};
-use futures_util::TryFutureExt;
-use reqwest::IntoUrl;
-use serde::Deserialize;
+use crate::{pagination::PAGE_SIZE, OFClient, media, user::{User, UserId}};
+use std::{slice, fmt, str::FromStr};
+use futures_util::{stream, TryFutureExt, TryStreamExt};
+use reqwest::{IntoUrl, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ContentType {
 	Posts,
 	Chats,
@@ -17,6 +17,21 @@ pub enum ContentType {
 	Streams
 }
 
+impl FromStr for ContentType {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"Posts" => Ok(ContentType::Posts),
+			"Messages" => Ok(ContentType::Chats),
+			"Stories" => Ok(ContentType::Stories),
+			"Notifications" => Ok(ContentType::Notifications),
+			"Streams" => Ok(ContentType::Streams),
+			_ => Err(())
+		}
+	}
+}
+
 impl fmt::Display for ContentType {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.write_str( match self {
@@ -29,7 +44,7 @@ impl fmt::Display for ContentType {
 	}
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Post {
 	id: u64,
@@ -45,7 +60,7 @@ pub struct Post {
 	media: Vec<media::Feed>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Chat {
 	id: u64,
@@ -57,8 +72,32 @@ pub struct Chat {
 	media: Vec<media::Feed>,
 }
 
+/// A message read from a chat, instead of received on the websocket
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
+pub struct ChatMessage {
+	pub from_user: UserId,
+	#[serde(default)]
+	pub is_from_queue: bool,
+	pub queue_id: Option<u64>,
+	#[serde(flatten)]
+	pub content: Chat,
+}
+
+#[derive(Deserialize)]
+struct List<T> { list: Vec<T> }
+
+/// Content bought on its own, a pay-per-view post or message
+#[derive(Deserialize, Debug)]
+#[serde(tag = "responseType", rename_all = "camelCase")]
+pub enum Purchased {
+	/// Only refers to the post, its author isn't complete
+	Post { id: u64, author: UserId },
+	Message(ChatMessage),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct Story {
 	id: u64,
 	#[serde(default)]
@@ -69,6 +108,18 @@ pub struct Story {
 	media: Vec<media::Feed>,
 }
 
+/// A reel of stories a creator keeps on their profile after they expire
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Highlight {
+	pub id: u64,
+	#[serde(default)]
+	pub title: String,
+	/// Only filled in when the highlight is fetched on its own
+	#[serde(default)]
+	pub stories: Vec<Story>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Notification {
@@ -91,6 +142,18 @@ pub struct Stream {
 	media: media::Stream,
 }
 
+/// Playback details of a live stream
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamPlayback {
+	pub id: u64,
+	#[serde(default)]
+	pub is_finished: bool,
+	/// HLS playlist of the live stream, while it is running
+	#[serde(default)]
+	pub playback_url: Option<String>,
+}
+
 pub trait Content {
 	fn id(&self) -> u64;
 	fn timestamp(&self) -> DateTime<Utc>; 
@@ -180,4 +243,147 @@ impl OFClient {
 		.inspect(|content| info!("Got content: {:?}", content))
 		.inspect_err(|err| error!("Error reading content {post_id}: {err:?}"))
 	}
-}
\ No newline at end of file
+
+	pub async fn get_stream(&self, stream_id: u64) -> reqwest::Result<StreamPlayback> {
+		self.get(format!("https://onlyfans.com/api2/v2/streams/{stream_id}"))
+		.and_then(|response| response.json::<StreamPlayback>())
+		.await
+		.inspect(|stream| info!("Got stream: {:?}", stream))
+		.inspect_err(|err| error!("Error reading stream {stream_id}: {err:?}"))
+	}
+
+	pub async fn send_message(&self, user_id: u64, text: &str) -> reqwest::Result<()> {
+		#[derive(Serialize)]
+		struct NewMessage<'a> { text: &'a str }
+
+		self.post_json(format!("https://onlyfans.com/api2/v2/chats/{user_id}/messages"), &NewMessage { text })
+		.await
+		.map(|_| ())
+		.inspect_err(|err| error!("Error sending message to {user_id}: {err:?}"))
+	}
+
+	/// Marks every message in the chat with `user_id` as read
+	pub async fn mark_chat_read(&self, user_id: u64) -> reqwest::Result<()> {
+		self.post(format!("https://onlyfans.com/api2/v2/chats/{user_id}/mark-as-read"), None::<&[u8]>)
+		.await
+		.map(|_| ())
+		.inspect_err(|err| error!("Error marking chat with {user_id} as read: {err:?}"))
+	}
+
+	fn get_profile_posts(&self, user_id: u64, section: &'static str) -> impl futures_util::Stream<Item = reqwest::Result<Post>> + '_ {
+		let url = Url::parse(&format!("https://onlyfans.com/api2/v2/users/{user_id}/{section}?order=publish_date_desc&skip_users=all")).unwrap();
+		self.paginate_by_cursor(url, "beforePublishTime", PAGE_SIZE)
+		.inspect_err(move |err| error!("Error reading {section} of {user_id}: {err:?}"))
+	}
+
+	/// Every post on the profile of `user_id`, newest first
+	pub fn get_user_posts(&self, user_id: u64) -> impl futures_util::Stream<Item = reqwest::Result<Post>> + '_ {
+		self.get_profile_posts(user_id, "posts")
+	}
+
+	/// Every post `user_id` archived, which no longer shows on their profile, newest first
+	pub fn get_archived_posts(&self, user_id: u64) -> impl futures_util::Stream<Item = reqwest::Result<Post>> + '_ {
+		self.get_profile_posts(user_id, "posts/archived")
+	}
+
+	/// Every recorded live stream `user_id` posted, newest first
+	pub fn get_user_streams(&self, user_id: u64) -> impl futures_util::Stream<Item = reqwest::Result<Post>> + '_ {
+		self.get_profile_posts(user_id, "posts/streams")
+	}
+
+	/// Highlights on the profile of `user_id`, without their stories
+	pub async fn get_highlights(&self, user_id: u64) -> reqwest::Result<Vec<Highlight>> {
+		self.get(format!("https://onlyfans.com/api2/v2/users/{user_id}/stories/highlights?limit={PAGE_SIZE}&offset=0"))
+		.and_then(|response| response.json::<Vec<Highlight>>())
+		.await
+		.inspect_err(|err| error!("Error reading highlights of {user_id}: {err:?}"))
+	}
+
+	/// A highlight with its stories
+	pub async fn get_highlight(&self, highlight_id: u64) -> reqwest::Result<Highlight> {
+		self.get(format!("https://onlyfans.com/api2/v2/stories/highlights/{highlight_id}"))
+		.and_then(|response| response.json::<Highlight>())
+		.await
+		.inspect_err(|err| error!("Error reading highlight {highlight_id}: {err:?}"))
+	}
+
+	/// Expired stories of `user_id` that are still visible to subscribers, newest first
+	pub fn get_archived_stories(&self, user_id: u64) -> impl futures_util::Stream<Item = reqwest::Result<Story>> + '_ {
+		let url = Url::parse(&format!("https://onlyfans.com/api2/v2/users/{user_id}/stories/archive?order=publish_date_desc")).unwrap();
+		self.paginate(url, PAGE_SIZE)
+		.inspect_err(move |err| error!("Error reading archived stories of {user_id}: {err:?}"))
+	}
+
+	/// Bought posts and messages, most recent purchase first, `limit` of them from `offset` on
+	pub async fn get_purchased(&self, offset: usize, limit: usize) -> reqwest::Result<Vec<Purchased>> {
+		self.get(format!("https://onlyfans.com/api2/v2/posts/paid?limit={limit}&offset={offset}&format=infinite"))
+		.and_then(|response| response.json::<List<Purchased>>())
+		.await
+		.map(|purchased| purchased.list)
+		.inspect_err(|err| error!("Error reading purchased content: {err:?}"))
+	}
+
+	async fn get_chats(&self, limit: usize, filter: &str) -> reqwest::Result<Vec<u64>> {
+		#[derive(Deserialize)]
+		#[serde(rename_all = "camelCase")]
+		struct ChatSummary { with_user: UserId }
+
+		self.get(format!("https://onlyfans.com/api2/v2/chats?limit={limit}&offset=0&filter={filter}&order=recent"))
+		.and_then(|response| response.json::<List<ChatSummary>>())
+		.await
+		.map(|chats| chats.list.into_iter().map(|chat| chat.with_user.id).collect())
+		.inspect_err(|err| error!("Error reading chats: {err:?}"))
+	}
+
+	/// Users with unread messages, most recent chat first
+	pub async fn get_unread_chats(&self, limit: usize) -> reqwest::Result<Vec<u64>> {
+		self.get_chats(limit, "unread").await
+	}
+
+	/// Users of the most recently active chats, read or not
+	pub async fn get_recent_chats(&self, limit: usize) -> reqwest::Result<Vec<u64>> {
+		self.get_chats(limit, "").await
+	}
+
+	/// Messages of the chat with `user_id`, newest first, from the one sent before `until_id` or from the newest one.
+	/// Pages are fetched as the stream is consumed, each continuing before the oldest message of the last one
+	pub fn get_chat_messages(&self, user_id: u64, until_id: Option<u64>) -> impl futures_util::Stream<Item = reqwest::Result<ChatMessage>> + '_ {
+		#[derive(Deserialize)]
+		#[serde(rename_all = "camelCase")]
+		struct MessagePage {
+			list: Vec<ChatMessage>,
+			#[serde(default)]
+			has_more: bool,
+		}
+
+		stream::try_unfold(Some(until_id), move |before| async move {
+			let Some(before) = before else { return Ok(None) };
+			let url = match before {
+				Some(id) => format!("https://onlyfans.com/api2/v2/chats/{user_id}/messages?limit={PAGE_SIZE}&order=desc&id={id}"),
+				None => format!("https://onlyfans.com/api2/v2/chats/{user_id}/messages?limit={PAGE_SIZE}&order=desc")
+			};
+
+			let page = self.get(url)
+				.and_then(|response| response.json::<MessagePage>())
+				.await?;
+
+			let next = page.has_more
+				.then(|| page.list.last().map(|message| message.content.id()))
+				.flatten()
+				.map(Some);
+
+			Ok(Some((stream::iter(page.list.into_iter().map(Ok)), next)))
+		})
+		.try_flatten()
+		.inspect_err(move |err| error!("Error reading messages of chat with {user_id}: {err:?}"))
+	}
+
+	/// The `limit` most recent notifications, newest first
+	pub async fn get_notifications<T: DeserializeOwned>(&self, limit: usize) -> reqwest::Result<Vec<T>> {
+		self.get(format!("https://onlyfans.com/api2/v2/users/notifications?limit={limit}&offset=0&format=infinite"))
+		.and_then(|response| response.json::<List<T>>())
+		.await
+		.map(|notifications| notifications.list)
+		.inspect_err(|err| error!("Error reading notifications: {err:?}"))
+	}
+}
@@ -1,9 +1,10 @@
 #![allow(dead_code)]
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use std::time::Duration;
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum MediaType {
 	Photo,
@@ -12,12 +13,12 @@ pub enum MediaType {
 	Audio,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 struct File {
 	url: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DRMSignature {
 	#[serde(rename = "CloudFront-Policy")]
 	pub policy: String,
@@ -27,26 +28,28 @@ pub struct DRMSignature {
 	pub key_pair: String
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DRMKVP<T> {
 	pub hls: T,
 	pub dash: T
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DRM {
 	pub manifest: DRMKVP<String>,
 	pub signature: DRMKVP<DRMSignature>
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct Files {
 	full: File,
 	preview: Option<File>,
+	// The signatures are short-lived credentials
+	#[serde(skip_serializing)]
 	pub(crate) drm: Option<DRM>
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Feed {
 	pub id: u64,
@@ -55,6 +58,17 @@ pub struct Feed {
 	pub(crate) files: Files,
 	can_view: bool,
 	created_at: Option<DateTime<Utc>>,
+	#[serde(default)]
+	duration: Option<u64>,
+}
+
+impl Feed {
+	/// Length of audio and video media, when the platform reports it
+	pub fn duration(&self) -> Option<Duration> {
+		self.duration
+		.filter(|&secs| secs > 0)
+		.map(Duration::from_secs)
+	}
 }
 
 // TODO: actually make use of this
@@ -1,7 +1,9 @@
-use crate::OFClient;
+use crate::{pagination::PAGE_SIZE, scheduler::Priority, OFClient};
 use std::fmt;
-use serde::Deserialize;
-use futures_util::TryFutureExt;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use futures_util::{Stream, TryFutureExt, TryStreamExt};
+use reqwest::Url;
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -13,7 +15,7 @@ pub struct Me {
 	pub ws_url: String
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
 	pub id: u64,
@@ -22,6 +24,12 @@ pub struct User {
 	pub avatar: Option<String>,
 }
 
+/// A user the API only refers to by ID
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct UserId {
+	pub id: u64,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct SubscriberCategories {
 	pub active: u32,
@@ -41,14 +49,62 @@ pub struct Subscriptions {
 	pub bookmarks: u32,
 }
 
+/// A subscription with its billing state
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+	#[serde(flatten)]
+	pub user: User,
+	/// Whether the subscription renews automatically when it expires
+	#[serde(default, rename = "subscribedByAutoprolong")]
+	pub auto_renew: bool,
+	#[serde(rename = "subscribedByExpireDate")]
+	pub expires_at: Option<DateTime<Utc>>,
+	#[serde(default, rename = "subscribedIsExpiredNow")]
+	pub expired: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+	pub created_at: DateTime<Utc>,
+	pub amount: f32,
+	pub status: String,
+	#[serde(default)]
+	pub description: String,
+	pub user: Option<User>,
+}
+
+impl Transaction {
+	pub fn failed(&self) -> bool {
+		self.status.eq_ignore_ascii_case("failed") || self.status.eq_ignore_ascii_case("error")
+	}
+
+	/// Whether it paid for a locked message or post, as told by its description
+	pub fn is_ppv(&self) -> bool {
+		let description = self.description.to_lowercase();
+		(description.contains("message") || description.contains("post")) && !description.contains("tip")
+	}
+}
+
 pub trait IDType : fmt::Display {}
 impl IDType for &str {}
 impl IDType for u64 {}
 
 impl OFClient {
+	pub async fn get_me(&self) -> reqwest::Result<Me> {
+		self.get("https://onlyfans.com/api2/v2/users/me")
+		.and_then(|response| response.json::<Me>())
+		.await
+	}
+
 	pub async fn get_user<I: IDType>(&self, user_id: I) -> reqwest::Result<User> {
-		self.get(format!("https://onlyfans.com/api2/v2/users/{user_id}"))
-		.and_then(|response| response.json::<User>().map_err(Into::into))
+		let url = format!("https://onlyfans.com/api2/v2/users/{user_id}");
+		let client = self.with_priority(Priority::Interactive);
+		self.user_requests.run(&url, || {
+			client.get(&url)
+			.and_then(|response| response.json::<User>())
+		})
 		.await
 		.inspect(|user| info!("Got user: {:?}", user))
 		.inspect_err(|err| error!("Error reading user {user_id}: {err:?}"))
@@ -62,15 +118,27 @@ impl OFClient {
 		.inspect_err(|err| error!("Error reading user {user_id}: {err:?}"))
 	}
 
-	pub async fn get_subscriptions(&self) -> reqwest::Result<Vec<User>> {
-		let count = self.get("https://onlyfans.com/api2/v2/subscriptions/count/all")
-		.and_then(|response| response.json::<Subscriptions>())
-		.await
-		.inspect_err(|err| error!("Error reading subscribe counts: {err:?}"))
-		.map(|counts| counts.subscriptions.all)?;
+	pub fn get_subscriptions(&self) -> impl Stream<Item = reqwest::Result<User>> + '_ {
+		let url = Url::parse("https://onlyfans.com/api2/v2/subscriptions/subscribes?type=all").unwrap();
+		self.paginate(url, PAGE_SIZE)
+		.inspect_err(|err| error!("Error reading subscriptions: {err:?}"))
+	}
+
+	pub fn get_subscription_details(&self) -> impl Stream<Item = reqwest::Result<Subscription>> + '_ {
+		let url = Url::parse("https://onlyfans.com/api2/v2/subscriptions/subscribes?type=all").unwrap();
+		self.paginate(url, PAGE_SIZE)
+		.inspect_err(|err| error!("Error reading subscriptions: {err:?}"))
+	}
+
+	/// The `limit` most recent payments, newest first
+	pub async fn get_transactions(&self, limit: usize) -> reqwest::Result<Vec<Transaction>> {
+		#[derive(Deserialize)]
+		struct Transactions { list: Vec<Transaction> }
 
-		self.get(format!("https://onlyfans.com/api2/v2/subscriptions/subscribes?limit={count}&offset=0&type=all"))
-		.and_then(|response| response.json::<Vec<User>>())
+		self.get(format!("https://onlyfans.com/api2/v2/payments/all/transactions?limit={limit}&offset=0"))
+		.and_then(|response| response.json::<Transactions>())
 		.await
+		.map(|transactions| transactions.list)
+		.inspect_err(|err| error!("Error reading transactions: {err:?}"))
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,152 @@
+use std::{fs, io, path::{Path, PathBuf}, sync::Mutex};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use reqwest::{header::{self, HeaderMap, HeaderName, HeaderValue}, Client, Request, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+	Record,
+	Replay,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Interaction {
+	method: String,
+	url: String,
+	status: u16,
+	#[serde(default)]
+	headers: Vec<(String, String)>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	body: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	body_base64: Option<String>,
+}
+
+impl Interaction {
+	fn body(&self) -> Vec<u8> {
+		match (&self.body, &self.body_base64) {
+			(Some(body), _) => body.clone().into_bytes(),
+			(None, Some(body)) => BASE64_STANDARD.decode(body).unwrap_or_default(),
+			(None, None) => Vec::new(),
+		}
+	}
+
+	fn matches(&self, request: &Request) -> bool {
+		self.method == request.method().as_str() && self.url == request.url().as_str()
+	}
+}
+
+/// Records request/response pairs to a JSON file, or replays them without touching the network.
+/// Replayed requests are matched on method and URL, in recording order.
+/// Requests are not signed while replaying, and replaying a request that was never recorded panics.
+#[derive(Debug)]
+pub struct Cassette {
+	path: PathBuf,
+	mode: CassetteMode,
+	interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+impl Cassette {
+	pub fn record<P: Into<PathBuf>>(path: P) -> Self {
+		Self { path: path.into(), mode: CassetteMode::Record, interactions: Mutex::default() }
+	}
+
+	pub fn replay<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let interactions = serde_json::from_slice::<Vec<Interaction>>(&fs::read(&path)?)?;
+
+		Ok(Self {
+			path: path.as_ref().to_path_buf(),
+			mode: CassetteMode::Replay,
+			interactions: Mutex::new(interactions.into_iter().map(|interaction| (interaction, false)).collect())
+		})
+	}
+
+	pub fn mode(&self) -> CassetteMode {
+		self.mode
+	}
+
+	pub fn save(&self) -> io::Result<()> {
+		let interactions: Vec<Interaction> = self.interactions.lock().unwrap()
+			.iter()
+			.map(|(interaction, _)| interaction.clone())
+			.collect();
+
+		if let Some(parent) = self.path.parent() { fs::create_dir_all(parent)?; }
+		fs::write(&self.path, serde_json::to_vec_pretty(&interactions)?)
+	}
+
+	pub(crate) async fn send(&self, client: &Client, request: Request) -> reqwest::Result<Response> {
+		match self.mode {
+			CassetteMode::Replay => {
+				let interaction = self.take(&request)
+					.unwrap_or_else(|| panic!("No recorded interaction for {} {}", request.method(), request.url()));
+
+				Ok(to_response(&interaction, &request))
+			},
+			CassetteMode::Record => {
+				let method = request.method().to_string();
+				let url = request.url().clone();
+				let response = client.execute(request).await?;
+
+				let status = response.status().as_u16();
+				let headers = response.headers()
+					.iter()
+					.filter(|(name, _)| *name != header::SET_COOKIE)
+					.filter_map(|(name, value)| value.to_str().ok().map(|value| (name.to_string(), value.to_string())))
+					.collect();
+
+				let bytes = response.bytes().await?;
+				let (body, body_base64) = match String::from_utf8(bytes.to_vec()) {
+					Ok(body) => (Some(body), None),
+					Err(_) => (None, Some(BASE64_STANDARD.encode(&bytes))),
+				};
+
+				let interaction = Interaction { method, url: url.to_string(), status, headers, body, body_base64 };
+				let response = to_response(&interaction, &Request::new(reqwest::Method::GET, url));
+				self.interactions.lock().unwrap().push((interaction, false));
+				Ok(response)
+			}
+		}
+	}
+
+	fn take(&self, request: &Request) -> Option<Interaction> {
+		let mut interactions = self.interactions.lock().unwrap();
+		// Prefer the next unused recording, falling back to the last one for repeated requests
+		let index = interactions.iter().position(|(interaction, used)| !used && interaction.matches(request))
+			.or_else(|| interactions.iter().rposition(|(interaction, _)| interaction.matches(request)))?;
+
+		interactions[index].1 = true;
+		Some(interactions[index].0.clone())
+	}
+}
+
+impl Drop for Cassette {
+	fn drop(&mut self) {
+		if self.mode == CassetteMode::Record {
+			let _ = self.save()
+				.inspect_err(|err| error!("Error saving cassette {:?}: {err}", self.path));
+		}
+	}
+}
+
+fn to_response(interaction: &Interaction, request: &Request) -> Response {
+	let mut headers = HeaderMap::new();
+	for (name, value) in &interaction.headers {
+		if let (Ok(name), Ok(value)) = (HeaderName::try_from(name.as_str()), HeaderValue::from_str(value)) {
+			headers.append(name, value);
+		}
+	}
+
+	let mut builder = http::Response::builder()
+		.status(interaction.status)
+		.url(request.url().clone());
+
+	if let Some(response_headers) = builder.headers_mut() {
+		*response_headers = headers;
+	}
+
+	builder
+	.body(interaction.body())
+	.unwrap()
+	.into()
+}
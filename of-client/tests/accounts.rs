@@ -0,0 +1,36 @@
+use std::sync::Arc;
+use of_client::{accounts::AccountManager, reqwest_cookie_store::{CookieStore, CookieStoreRwLock}, OFClient, RequestHeaders};
+
+fn headers(cookie: Arc<CookieStoreRwLock>, user_id: &str) -> RequestHeaders {
+	RequestHeaders {
+		cookie,
+		user_id: user_id.to_string(),
+		x_bc: String::new(),
+		user_agent: "of-client tests".to_string(),
+	}
+}
+
+#[test]
+fn accounts_are_handed_out_by_id() {
+	let accounts = AccountManager::new();
+	let cookie = Arc::new(CookieStoreRwLock::new(CookieStore::new(None)));
+	accounts.add("main", OFClient::builder(headers(cookie, "1"))).unwrap();
+
+	assert_eq!(accounts.get("main").unwrap().headers.load().user_id, "1");
+	assert!(accounts.get("alt").is_none());
+
+	accounts.remove("main");
+	assert!(accounts.is_empty());
+}
+
+#[test]
+fn cookie_stores_are_not_shared() {
+	let accounts = AccountManager::new();
+	let cookie = Arc::new(CookieStoreRwLock::new(CookieStore::new(None)));
+	accounts.add("main", OFClient::builder(headers(cookie.clone(), "1"))).unwrap();
+	accounts.add("alt", OFClient::builder(headers(cookie, "2"))).unwrap();
+
+	let main = accounts.get("main").unwrap().headers.load_full();
+	let alt = accounts.get("alt").unwrap().headers.load_full();
+	assert!(!Arc::ptr_eq(&main.cookie, &alt.cookie));
+}
@@ -0,0 +1,26 @@
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+use of_client::auth::AuthGate;
+
+#[test]
+fn hook_can_use_the_gate() {
+	let gate = Arc::new(AuthGate::default());
+	let calls = Arc::new(AtomicUsize::new(0));
+
+	gate.set_hook({
+		let (gate, calls) = (gate.clone(), calls.clone());
+		move || {
+			calls.fetch_add(1, Ordering::SeqCst);
+			// Replacing the hook from inside it would deadlock if it ran under the gate's lock
+			gate.set_hook(|| ());
+			assert!(gate.is_expired());
+		}
+	});
+
+	assert!(gate.expire());
+	assert!(!gate.expire());
+	assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+	gate.resume();
+	assert!(gate.expire());
+	assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,32 @@
+use std::time::Duration;
+use of_client::{reqwest::Method, retry::RetryPolicy};
+
+#[test]
+fn delays_grow_exponentially_with_jitter() {
+	let policy = RetryPolicy { max_retries: 5, base_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1), ..RetryPolicy::default() };
+
+	for (retry, backoff) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000)] {
+		let backoff = Duration::from_millis(backoff);
+		for _ in 0..20 {
+			let delay = policy.delay(retry);
+			assert!(delay >= backoff / 2 && delay <= backoff, "retry {retry} waited {delay:?}");
+		}
+	}
+}
+
+#[test]
+fn no_retries_policy() {
+	assert_eq!(RetryPolicy::NONE.max_retries, 0);
+	assert_eq!(RetryPolicy::NONE.delay(1), Duration::ZERO);
+}
+
+#[test]
+fn only_idempotent_requests_are_retried_by_default() {
+	let policy = RetryPolicy::default();
+	assert!(policy.applies_to(&Method::GET));
+	assert!(policy.applies_to(&Method::PUT));
+	assert!(!policy.applies_to(&Method::POST));
+
+	let policy = RetryPolicy { non_idempotent: true, ..policy };
+	assert!(policy.applies_to(&Method::POST));
+}
@@ -0,0 +1,50 @@
+use std::{sync::Arc, time::Duration};
+use of_client::scheduler::{Priority, RequestScheduler};
+use tokio::{task::yield_now, time::Instant};
+
+#[tokio::test(start_paused = true)]
+async fn requests_are_spread_over_the_window() {
+	let scheduler = RequestScheduler::new(4, Duration::from_secs(4));
+	let start = Instant::now();
+
+	for expected in 0..4 {
+		scheduler.acquire(Priority::Normal).await;
+		assert_eq!(start.elapsed(), Duration::from_secs(expected));
+	}
+}
+
+#[tokio::test(start_paused = true)]
+async fn interactive_requests_skip_the_queue() {
+	let scheduler = RequestScheduler::new(1, Duration::from_secs(10));
+	let start = Instant::now();
+
+	scheduler.acquire(Priority::Normal).await;
+	scheduler.acquire(Priority::Interactive).await;
+	assert_eq!(start.elapsed(), Duration::ZERO);
+}
+
+#[tokio::test(start_paused = true)]
+async fn bulk_requests_wait_for_normal_ones() {
+	let scheduler = Arc::new(RequestScheduler::new(1, Duration::from_secs(1)));
+	scheduler.acquire(Priority::Normal).await;
+
+	let bulk = tokio::spawn({
+		let scheduler = scheduler.clone();
+		async move {
+			scheduler.acquire(Priority::Bulk).await;
+			Instant::now()
+		}
+	});
+
+	let normal = tokio::spawn({
+		let scheduler = scheduler.clone();
+		async move {
+			scheduler.acquire(Priority::Normal).await;
+			Instant::now()
+		}
+	});
+
+	yield_now().await;
+	let (bulk, normal) = (bulk.await.unwrap(), normal.await.unwrap());
+	assert!(normal < bulk);
+}
@@ -0,0 +1,93 @@
+use std::{path::Path, sync::Arc};
+use futures::TryStreamExt;
+use of_client::{content::Content, drm::StreamingFormat, media::{DRMKVP, DRMSignature, DRM}, reqwest_cookie_store::{CookieStore, CookieStoreRwLock}, vcr::Cassette, OFClient, RequestHeaders};
+
+fn replay(name: &str) -> OFClient {
+	let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("cassettes").join(name).with_extension("json");
+	let cassette = Cassette::replay(path).unwrap();
+
+	let headers = RequestHeaders {
+		cookie: Arc::new(CookieStoreRwLock::new(CookieStore::new(None))),
+		user_id: "0".to_string(),
+		x_bc: String::new(),
+		user_agent: "of-client tests".to_string(),
+	};
+
+	OFClient::builder(headers)
+	.cassette(Arc::new(cassette))
+	.build()
+	.unwrap()
+}
+
+#[tokio::test]
+async fn get_post() {
+	let client = replay("get_post");
+	let post = client.get_post(492747400).await.unwrap();
+
+	assert_eq!(post.id(), 492747400);
+	assert_eq!(post.author.username, "onlyfans");
+	assert_eq!(post.price, Some(4.99));
+}
+
+#[tokio::test]
+async fn get_subscriptions() {
+	let client = replay("get_subscriptions");
+	let users: Vec<_> = client.get_subscriptions().try_collect().await.unwrap();
+
+	assert_eq!(users.len(), 62);
+	assert_eq!(users.last().unwrap().username, "creator61");
+}
+
+#[tokio::test]
+async fn get_user_posts() {
+	let client = replay("get_user_posts");
+	let posts: Vec<_> = client.get_user_posts(15585607).try_collect().await.unwrap();
+
+	assert_eq!(posts.iter().map(|post| post.id()).collect::<Vec<_>>(), [3, 2, 1]);
+}
+
+#[tokio::test]
+async fn get_chat_messages() {
+	let client = replay("get_chat_messages");
+	let messages: Vec<_> = client.get_chat_messages(15585607, None).try_collect().await.unwrap();
+
+	assert_eq!(messages.iter().map(|message| message.content.id()).collect::<Vec<_>>(), [30, 20, 10]);
+}
+
+#[tokio::test]
+async fn get_mpd_data() {
+	let client = replay("get_mpd_data");
+	let signature = || DRMSignature { policy: "policy".to_string(), signature: "signature".to_string(), key_pair: "key".to_string() };
+	let drm = DRM {
+		manifest: DRMKVP {
+			hls: "https://cdn3.onlyfans.com/hls/files/manifest.m3u8".to_string(),
+			dash: "https://cdn3.onlyfans.com/dash/files/manifest.mpd".to_string()
+		},
+		signature: DRMKVP { hls: signature(), dash: signature() }
+	};
+
+	let data = client.get_mpd_data(&drm).await.unwrap();
+	assert_eq!(data.base_url, "video_1080.mp4");
+	assert!(data.last_modified.is_some());
+}
+
+#[tokio::test]
+async fn get_hls_data() {
+	let client = replay("get_hls_data");
+	let signature = || DRMSignature { policy: "policy".to_string(), signature: "signature".to_string(), key_pair: "key".to_string() };
+	let drm = DRM {
+		manifest: DRMKVP {
+			hls: "https://cdn3.onlyfans.com/hls/files/manifest.m3u8".to_string(),
+			dash: "https://cdn3.onlyfans.com/dash/files/manifest.mpd".to_string()
+		},
+		signature: DRMKVP { hls: signature(), dash: signature() }
+	};
+
+	let data = client.get_manifest_data(&drm, StreamingFormat::Hls).await.unwrap();
+	assert_eq!(data.base_url, "manifest.mp4");
+	assert!(data.last_modified.is_some());
+
+	let header = client.mpd_header(&drm.manifest.hls);
+	assert!(header.contains("CloudFront-Signature=signature;"));
+	assert!(header.contains("\r\nUser-Agent: of-client tests\r\n"));
+}
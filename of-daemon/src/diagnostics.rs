@@ -0,0 +1,85 @@
+use std::{fmt::Display, fs, path::PathBuf, sync::Mutex, time::{Duration, Instant}};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::task::spawn_blocking;
+
+const REDACTED_KEYS: [&str; 5] = ["token", "auth", "sess", "cookie", "sign"];
+
+#[derive(Serialize)]
+struct UnparsedPayload<'a> {
+	error: String,
+	payload: Value,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	raw: Option<&'a str>,
+}
+
+struct DumpState {
+	last_write: Option<Instant>,
+	written: usize,
+}
+
+pub struct UnparsedDump {
+	dir: PathBuf,
+	min_interval: Duration,
+	max_files: usize,
+	state: Mutex<DumpState>,
+}
+
+impl UnparsedDump {
+	pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+		Self {
+			dir: dir.into(),
+			min_interval: Duration::from_secs(10),
+			max_files: 100,
+			state: Mutex::new(DumpState { last_write: None, written: 0 })
+		}
+	}
+
+	pub fn persist(&self, payload: &str, error: impl Display) {
+		{
+			let mut state = self.state.lock().unwrap();
+			if state.written >= self.max_files { return }
+			if state.last_write.is_some_and(|last| last.elapsed() < self.min_interval) { return }
+
+			state.last_write = Some(Instant::now());
+			state.written += 1;
+		}
+
+		let content = match serde_json::from_str::<Value>(payload) {
+			Ok(mut value) => {
+				redact(&mut value);
+				UnparsedPayload { error: error.to_string(), payload: value, raw: None }
+			},
+			Err(_) => UnparsedPayload { error: error.to_string(), payload: Value::Null, raw: Some(payload) }
+		};
+
+		let path = self.dir
+			.join(Utc::now().format("%Y%m%d_%H%M%S%3f").to_string())
+			.with_extension("json");
+
+		// Messages are decoded on the runtime's worker threads, which must not wait on the disk
+		let (dir, contents) = (self.dir.clone(), serde_json::to_vec_pretty(&content).unwrap());
+		spawn_blocking(move || {
+			let _ = fs::create_dir_all(&dir)
+				.and_then(|_| fs::write(&path, contents))
+				.inspect(|_| debug!("Unparsed message written to {path:?}"))
+				.inspect_err(|err| warn!("Could not write unparsed message to {path:?}: {err}"));
+		});
+	}
+}
+
+fn redact(value: &mut Value) {
+	match value {
+		Value::Object(map) => for (key, value) in map.iter_mut() {
+			let key = key.to_ascii_lowercase();
+			if REDACTED_KEYS.iter().any(|redacted| key.contains(redacted)) && !value.is_object() && !value.is_array() {
+				*value = Value::String("<redacted>".to_string());
+			} else {
+				redact(value);
+			}
+		},
+		Value::Array(values) => values.iter_mut().for_each(redact),
+		_ => ()
+	}
+}
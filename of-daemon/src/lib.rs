@@ -3,20 +3,40 @@ extern crate log;
 
 pub mod structs;
 pub mod socket;
+pub mod stats;
+pub mod diagnostics;
+pub mod timing;
+pub mod telemetry;
 
 pub mod tungstenite { pub use tokio_tungstenite::tungstenite::error; }
 
-use std::{sync::Arc, time::Duration};
+use std::{mem, panic::{self, AssertUnwindSafe}, path::PathBuf, sync::Arc, time::Duration};
 use chrono::Utc;
 use futures::{StreamExt, TryFutureExt};
-use of_client::{OFClient, reqwest, user};
+use of_client::{OFClient, reqwest};
 use rand::{rngs::StdRng, Rng, SeedableRng};
 use rand_distr::{Distribution, Exp1, Standard};
 use serde::Serialize;
 use socket::Connected;
 use thiserror::Error;
-use tokio::{pin, sync::Notify, task::JoinHandle, time::sleep};
-use crate::{socket::{SocketError, WebSocketClient}, structs::Message};
+use tokio_util::sync::CancellationToken;
+use tokio::{pin, sync::{mpsc::{self, error::{SendTimeoutError, TrySendError}}, Notify, OwnedSemaphorePermit, Semaphore}, task::JoinHandle, time::{interval, sleep}};
+use crate::{diagnostics::UnparsedDump, socket::{SocketError, WebSocketClient}, stats::DaemonStats, structs::Message, telemetry::Telemetry, timing::Timing};
+
+const DEFAULT_QUEUE_CAPACITY: usize = 256;
+const DEFAULT_MAX_IN_FLIGHT: usize = 32;
+
+/// Passed along with a message to the message callback, which holds it until the message is dispatched.
+/// No more messages are taken from the queue while all of them are held, so long-running work should not hold it
+#[derive(Debug)]
+pub struct InFlight { _permit: OwnedSemaphorePermit }
+
+impl InFlight {
+	/// Counts a message against a limiter of the host's own, for messages that don't come from the daemon
+	pub fn new(permit: OwnedSemaphorePermit) -> Self {
+		Self { _permit: permit }
+	}
+}
 
 #[derive(Error, Debug)]
 pub enum DaemonError {
@@ -26,10 +46,24 @@ pub enum DaemonError {
 	Request(#[from] reqwest::Error)
 }
 
+impl DaemonError {
+	pub fn is_auth_revoked(&self) -> bool {
+		matches!(self, Self::Socket(err) if err.is_auth_revoked())
+	}
+}
+
 pub struct Daemon {
 	started_callback: Option<Box<dyn Fn() + Send>>,
-	message_callback: Option<Box<dyn Fn(Message) + Send>>,
+	message_callback: Option<Box<dyn Fn(Message, InFlight) + Send>>,
+	observers: Vec<Box<dyn Fn(&Message) + Send>>,
+	unknown_callback: Option<Box<dyn Fn(serde_json::Value) + Send>>,
 	disconnect_callback: Option<Box<dyn Fn(Result<(), DaemonError>) + Send>>,
+	stats: Arc<DaemonStats>,
+	unparsed_dump: Option<Arc<UnparsedDump>>,
+	timing: Timing,
+	queue_capacity: usize,
+	max_in_flight: usize,
+	cancellation: CancellationToken,
 }
 
 impl Daemon {
@@ -37,40 +71,136 @@ impl Daemon {
 		Self {
 			started_callback: None,
 			message_callback: None,
-			disconnect_callback: None
+			observers: Vec::new(),
+			unknown_callback: None,
+			disconnect_callback: None,
+			stats: Arc::default(),
+			unparsed_dump: None,
+			timing: Timing::default(),
+			queue_capacity: DEFAULT_QUEUE_CAPACITY,
+			max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+			cancellation: CancellationToken::new()
 		}
 	}
 
+	pub fn timing(mut self, timing: Timing) -> Self {
+		self.timing = timing;
+		self
+	}
+
+	/// Messages that can wait for the handlers before socket reads are held back
+	pub fn queue_capacity(mut self, capacity: usize) -> Self {
+		self.queue_capacity = capacity.max(1);
+		self
+	}
+
+	/// Messages dispatched at once, counted until the message callback drops their `InFlight`
+	pub fn max_in_flight(mut self, max: usize) -> Self {
+		self.max_in_flight = max.max(1);
+		self
+	}
+
+	/// Closes the connection for good once `token` is cancelled
+	pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+		self.cancellation = token;
+		self
+	}
+
+	pub fn stats(&self) -> Arc<DaemonStats> {
+		self.stats.clone()
+	}
+
+	pub fn dump_unparsed<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+		self.unparsed_dump = Some(Arc::new(UnparsedDump::new(dir)));
+		self
+	}
+
 	pub fn on_start(mut self, f: impl Fn() + Send + 'static) -> Self {
 		self.started_callback = Some(Box::new(f));
 		self
 	}
 
-	pub fn on_message(mut self, f: impl Fn(Message) + Send + 'static) -> Self {
+	pub fn on_message(mut self, f: impl Fn(Message, InFlight) + Send + 'static) -> Self {
 		self.message_callback = Some(Box::new(f));
 		self
 	}
 
+	/// Adds a consumer that sees every message, unknown ones included, before the message callback takes it.
+	/// Any number of them can be added and each runs on its own, a panicking one doesn't affect the others.
+	/// They run on the dispatch task, so slow work should be spawned rather than awaited
+	pub fn observe(mut self, f: impl Fn(&Message) + Send + 'static) -> Self {
+		self.observers.push(Box::new(f));
+		self
+	}
+
+	/// Receives the messages that didn't match any known shape, instead of the message callback
+	pub fn on_unknown_message(mut self, f: impl Fn(serde_json::Value) + Send + 'static) -> Self {
+		self.unknown_callback = Some(Box::new(f));
+		self
+	}
+
 	pub fn on_disconnect(mut self, f: impl Fn(Result<(), DaemonError>) + Send + 'static) -> Self {
 		self.disconnect_callback = Some(Box::new(f));
 		self
 	}
 
-	pub fn build(self, client: OFClient) -> (Arc<Notify>, JoinHandle<()>) {
+	pub fn build(mut self, client: OFClient) -> (Arc<Notify>, JoinHandle<()>) {
 		let notify = Arc::new(Notify::new());
+		self.stats.watch_rate_limits(client.rate_limits());
+
+		let (sender, mut receiver) = mpsc::channel::<Message>(self.queue_capacity);
+		tokio::spawn({
+			let stats = self.stats.clone();
+			let message_callback = self.message_callback.take();
+			let unknown_callback = self.unknown_callback.take();
+			let observers = mem::take(&mut self.observers);
+			let cancellation = self.cancellation.clone();
+			let in_flight = Arc::new(Semaphore::new(self.max_in_flight));
+			async move {
+				// Messages stay queued until the ones before them are dispatched, so that a full queue holds socket reads back
+				while let Some(Ok(permit)) = cancellation.run_until_cancelled(in_flight.clone().acquire_owned()).await {
+					// Messages still queued at shutdown are discarded
+					let Some(msg) = cancellation.run_until_cancelled(receiver.recv()).await.flatten() else { break };
+					stats.record_dequeued();
+
+					for (index, observer) in observers.iter().enumerate() {
+						if panic::catch_unwind(AssertUnwindSafe(|| observer(&msg))).is_err() {
+							error!("Message observer {index} panicked, continuing with the others");
+						}
+					}
+
+					// A callback panicking on one message must not stop the messages after it
+					let handled = panic::catch_unwind(AssertUnwindSafe(|| match msg {
+						Message::Unknown(value) => if let Some(ref callback) = unknown_callback { callback(value) },
+						msg => if let Some(ref callback) = message_callback { callback(msg, InFlight { _permit: permit }) }
+					}));
+
+					if handled.is_err() {
+						error!("Message callback panicked, continuing with the next message");
+					}
+				}
+			}
+		});
 
 		let handle = tokio::spawn({
 			let notify = notify.clone();
 			async move {
 				loop {
-					notify.notified().await;
+					tokio::select! {
+						_ = notify.notified() => (),
+						_ = self.cancellation.cancelled() => return,
+					}
 
 					let mut socket = tokio::select! {
+						_ = self.cancellation.cancelled() => {
+							if let Some(ref callback) = self.disconnect_callback { callback(Ok(())) }
+							return;
+						},
 						_ = notify.notified() => {
 							if let Some(ref callback) = self.disconnect_callback { callback(Ok(())) }
 							continue;
 						},
-						val = connect(&client) => match val {
+						val = connect(&client, &self.stats, &self.unparsed_dump, self.timing) => match val {
 							Ok(val) => val,
 							Err(err) => {
 								if let Some(ref callback) = self.disconnect_callback { callback(Err(err)) }
@@ -81,18 +211,33 @@ impl Daemon {
 	
 					if let Some(ref callback) = self.started_callback { callback(); }
 	
-					let activity = simulate_activity(&client);
+					let activity = simulate_activity(&client, self.timing.activity_mean);
 					pin!(activity);
+
+					let mut telemetry = Telemetry::default();
+					let mut summarize = interval(self.timing.telemetry_interval);
 	
 					loop {
 						tokio::select! {
 							_ = &mut activity => {},
+							_ = summarize.tick() => {
+								for msg in telemetry.flush() {
+									enqueue(&sender, msg, &self.stats, self.timing.backpressure_timeout).await;
+								}
+							},
+							_ = self.cancellation.cancelled() => {
+								info!("Closing websocket for shutdown");
+								if let Some(ref callback) = self.disconnect_callback { callback(Ok(())) }
+								return;
+							},
 							_ = notify.notified() => {
 								if let Some(ref callback) = self.disconnect_callback { callback(Ok(())) }
 								break;
 							},
 							Some(msg) = socket.next() => match msg {
-								Ok(Some(msg)) => { if let Some(ref callback) = self.message_callback { callback(msg) } },
+								Ok(Some(msg)) => if let Some(msg) = telemetry.absorb(msg) {
+									enqueue(&sender, msg, &self.stats, self.timing.backpressure_timeout).await
+								},
 								Ok(None) => (),
 								Err(e) => { 
 									error!("{e:?}");
@@ -111,16 +256,47 @@ impl Daemon {
 	}
 }
 
-async fn connect<'a>(client: &OFClient) -> Result<WebSocketClient<Connected<'a>>, DaemonError> {
+/// Hands a message over to the handlers, holding socket reads back for a moment when they fall behind.
+/// Low value messages are dropped right away instead
+async fn enqueue(sender: &mpsc::Sender<Message>, msg: Message, stats: &DaemonStats, wait: Duration) {
+	// Counted before sending, so that the dispatcher never takes the depth below zero
+	stats.record_queued();
+
+	let msg = match sender.try_send(msg) {
+		Ok(()) => return,
+		Err(TrySendError::Closed(_)) => return stats.record_dequeued(),
+		Err(TrySendError::Full(msg)) => msg,
+	};
+
+	if msg.is_low_value() {
+		debug!("Event queue is full, dropping {msg:?}");
+		stats.record_dequeued();
+		return stats.record_dropped()
+	}
+
+	match sender.send_timeout(msg, wait).await {
+		Ok(()) => (),
+		Err(SendTimeoutError::Timeout(msg)) => {
+			warn!("Event queue is still full after {wait:?}, dropping {msg:?}");
+			stats.record_dequeued();
+			stats.record_dropped();
+		},
+		Err(SendTimeoutError::Closed(_)) => stats.record_dequeued(),
+	}
+}
+
+async fn connect<'a>(client: &OFClient, stats: &Arc<DaemonStats>, unparsed_dump: &Option<Arc<UnparsedDump>>, timing: Timing) -> Result<WebSocketClient<Connected<'a>>, DaemonError> {
 	info!("Fetching user data");
-	let me = client.get("https://onlyfans.com/api2/v2/users/me")
-		.and_then(|response| response.json::<user::Me>())
+	let me = client.get_me()
 		.inspect_err(|err| error!("Error fetching user data: {err}"))
 		.await?;
 	
 	debug!("{me:?}");
 	info!("Connecting as {}", me.name);
 	let socket = WebSocketClient::new()
+		.with_stats(stats.clone())
+		.with_unparsed_dump(unparsed_dump.clone())
+		.with_timing(timing)
 		.connect(&me.ws_url, &me.ws_auth_token)
 		.inspect_err(|err| error!("Error connecting: {err}"))
 		.await?;
@@ -159,9 +335,9 @@ impl Distribution<ClickStats> for Standard {
 	}
 }
 
-async fn simulate_activity(client: &OFClient) {
+async fn simulate_activity(client: &OFClient, mean: Duration) {
 	let rng = StdRng::from_entropy();
-	let mut intervals = rng.sample_iter(Exp1).map(|v: f32| Duration::from_secs_f32(v * 60.0));
+	let mut intervals = rng.sample_iter(Exp1).map(|v: f32| mean.mul_f32(v));
 	loop {
 		sleep(intervals.next().unwrap()).await;
 		let click = rand::random::<ClickStats>();
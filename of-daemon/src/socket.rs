@@ -1,12 +1,16 @@
 #![allow(dead_code)]
 
-use crate::structs;
+use crate::{diagnostics::UnparsedDump, stats::DaemonStats, structs, timing::Timing};
 use thiserror::Error;
-use std::{sync::Arc, task::Poll, time::Duration};
-use futures::{future::BoxFuture, stream::BoxStream, FutureExt, Stream};
-use tokio::{sync::Notify, time::{error::Elapsed, interval, timeout}};
+use std::{sync::Arc, task::Poll, time::{Duration, Instant}};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, Sink, Stream};
+use tokio::{sync::Notify, task::spawn_blocking, time::{error::Elapsed, interval, timeout}};
 use futures_util::{SinkExt, StreamExt};
-use tokio_tungstenite::{connect_async, tungstenite::{self, Message}};
+use tokio_tungstenite::{connect_async, tungstenite::{self, protocol::CloseFrame, Message}};
+
+const LATENCY_WARNING: Duration = Duration::from_millis(2500);
+// Frames above this size are parsed on the blocking pool to keep the socket reader responsive
+const BLOCKING_DECODE_THRESHOLD: usize = 64 * 1024;
 
 #[derive(Error, Debug)]
 pub enum SocketError {
@@ -15,7 +19,29 @@ pub enum SocketError {
 	#[error("Timeout expired")]
 	TimeoutExpired,
 	#[error("Unexpected message")]
-	UnexpectedMessage
+	UnexpectedMessage,
+	#[error("Closed by the server with code {code}: {reason}")]
+	Closed { code: u16, reason: String },
+}
+
+impl SocketError {
+	/// Code sent when a close frame doesn't carry one
+	const NO_STATUS: u16 = 1005;
+	/// Policy violation, used when the session is no longer valid
+	const POLICY_VIOLATION: u16 = 1008;
+
+	fn closed(frame: Option<&CloseFrame>) -> Self {
+		match frame {
+			Some(frame) => Self::Closed { code: frame.code.into(), reason: frame.reason.to_string() },
+			None => Self::Closed { code: Self::NO_STATUS, reason: String::new() }
+		}
+	}
+
+	/// Whether the server closed the connection because the session was revoked,
+	/// in which case reconnecting with the same credentials won't help
+	pub fn is_auth_revoked(&self) -> bool {
+		matches!(self, Self::Closed { code, .. } if *code == Self::POLICY_VIOLATION || (4000..5000).contains(code))
+	}
 }
 
 impl From<Elapsed> for SocketError {
@@ -23,30 +49,101 @@ impl From<Elapsed> for SocketError {
 }
 
 impl structs::Message {
-	fn decode(value: Message) -> Option<Self> {
-		let s = value.to_text().ok()?;
+	async fn decode_frame(value: Message, stats: Arc<DaemonStats>, unparsed_dump: Option<Arc<UnparsedDump>>) -> Option<Self> {
+		if !matches!(value, Message::Text(_) | Message::Binary(_)) { return None }
+
+		let data = value.into_data();
+		if data.len() < BLOCKING_DECODE_THRESHOLD {
+			Self::decode(&data, &stats, unparsed_dump.as_deref())
+		} else {
+			spawn_blocking(move || Self::decode(&data, &stats, unparsed_dump.as_deref()))
+			.await
+			.ok()
+			.flatten()
+		}
+	}
+
+	fn decode(data: &[u8], stats: &DaemonStats, unparsed_dump: Option<&UnparsedDump>) -> Option<Self> {
+		// Only allocates if the frame is not valid UTF-8
+		let s = String::from_utf8_lossy(data);
 		if !s.starts_with("{\"online\":[") { debug!("Received message: {s}") }
 		else { trace!("Received message: {s}") }
 
-		serde_json::from_str(s)
-		.inspect_err(|err| warn!("Message could not be parsed: {s}, reason: {err}"))
+		serde_json::from_slice(data)
+		.inspect(|message| if let Self::Unknown(_) = message {
+			warn!("Unknown message received: {s}");
+			stats.record_unparsed();
+			if let Some(dump) = unparsed_dump { dump.persist(&s, "unknown message") }
+		})
+		.inspect_err(|err| {
+			warn!("Message could not be parsed: {s}, reason: {err}");
+			stats.record_unparsed();
+			if let Some(dump) = unparsed_dump { dump.persist(&s, err) }
+		})
 		.ok()
 	}
 }
 
+/// Sends a heartbeat every `heartbeat_interval` and fails if `ack` isn't notified within `heartbeat_timeout`.
+pub async fn heartbeat<S>(mut sink: S, ack: Arc<Notify>, stats: Arc<DaemonStats>, timing: Timing) -> Result<(), SocketError>
+where
+	S: Sink<Message, Error = tungstenite::Error> + Unpin,
+{
+	let heartbeat = serde_json::to_string(&structs::Heartbeat { act: "get_onlines", ids: &[] }).unwrap();
+	let mut interval = interval(timing.heartbeat_interval);
+	loop {
+		let _ = interval.tick().await;
+
+		trace!("Sending heartbeat: {heartbeat:?}");
+		let sent = Instant::now();
+		sink.send(Message::from(heartbeat.as_str())).await?;
+
+		match timeout(timing.heartbeat_timeout, ack.notified()).await {
+			Ok(_) => {
+				let latency = sent.elapsed();
+				trace!("Heartbeat acknowledged after {latency:?}");
+				stats.record_heartbeat(latency);
+
+				if let Some(summary) = stats.heartbeat_latency().filter(|summary| summary.p95_ms >= LATENCY_WARNING.as_secs_f64() * 1000.0) {
+					warn!("Heartbeat latency is degraded (p50 {:.0} ms, p95 {:.0} ms)", summary.p50_ms, summary.p95_ms);
+				}
+			},
+			Err(_) => break Err(SocketError::TimeoutExpired),
+		}
+	}
+}
+
 pub struct Disconnected;
 pub struct Connected<'a> {
 	heartbeat_fut: BoxFuture<'a, Result<(), SocketError>>,
-	message_fut: BoxStream<'a, Result<Option<structs::Message>, tungstenite::Error>>,
+	message_fut: BoxStream<'a, Result<Option<structs::Message>, SocketError>>,
 }
 
 pub struct WebSocketClient<State = Disconnected> {
 	state: State,
+	stats: Arc<DaemonStats>,
+	unparsed_dump: Option<Arc<UnparsedDump>>,
+	timing: Timing,
 }
 
 impl WebSocketClient {
-	pub const fn new() -> Self {
-		Self { state: Disconnected }
+	pub fn new() -> Self {
+		Self { state: Disconnected, stats: Arc::default(), unparsed_dump: None, timing: Timing::default() }
+	}
+
+	pub fn with_timing(mut self, timing: Timing) -> Self {
+		self.timing = timing;
+		self
+	}
+
+	pub fn with_stats(mut self, stats: Arc<DaemonStats>) -> Self {
+		self.stats = stats;
+		self
+	}
+
+	pub fn with_unparsed_dump(mut self, dump: Option<Arc<UnparsedDump>>) -> Self {
+		self.unparsed_dump = dump;
+		self
 	}
 }
 
@@ -63,47 +160,38 @@ impl WebSocketClient<Disconnected> {
 		.await?;
 	
 		let notify = Arc::new(Notify::new());
-		let heartbeat_fut = {
-			let ack = notify.clone();
-			
-			async move {
-				let heartbeat = serde_json::to_string(&structs::Heartbeat { act: "get_onlines", ids: &[] }).unwrap();
-				let mut interval = interval(Duration::from_secs(20));
-				loop {
-					let _ = interval.tick().await;
-			
-					trace!("Sending heartbeat: {heartbeat:?}");
-					if let Err(e) = sink.send(Message::from(heartbeat.as_str())).await {
-						break Err(e.into());
-					}
-			
-					match timeout(Duration::from_secs(5), ack.notified()).await {
-						Ok(_) => trace!("Heartbeat acknowledged"),
-						Err(_) => break Err(SocketError::TimeoutExpired),
-					}
-				}
-			}
-			.boxed()
-		};
+		let heartbeat_fut = heartbeat(sink, notify.clone(), self.stats.clone(), self.timing).boxed();
 		
+		let stats = self.stats.clone();
+		let unparsed_dump = self.unparsed_dump.clone();
 		let mut message_fut = stream
-			.map(move |rc| 
-				rc.map(|msg| 
-					structs::Message::decode(msg)
-					.inspect(|message| if let structs::Message::Onlines(_) = message {
-						notify.notify_one();
-					})
-				)
-			)
+			.then(move |rc| {
+				let stats = stats.clone();
+				let unparsed_dump = unparsed_dump.clone();
+				let notify = notify.clone();
+				async move {
+					let msg = rc?;
+					if let Message::Close(frame) = &msg {
+						return Err(SocketError::closed(frame.as_ref()))
+					}
+
+					Ok(
+						structs::Message::decode_frame(msg, stats, unparsed_dump).await
+						.inspect(|message| if let structs::Message::Onlines(_) = message {
+							notify.notify_one();
+						})
+					)
+				}
+			})
 			.boxed();
 
-		match timeout(Duration::from_secs(10), message_fut.next().map(|v| v.unwrap())).await {
+		match timeout(self.timing.connect_timeout, message_fut.next().map(|v| v.unwrap())).await {
 			Ok(Ok(Some(structs::Message::Connected(msg)))) => {
 				info!("Connected message received: {:?}", msg); 
 				Ok(())
 			}
 			Err(_) => Err(SocketError::TimeoutExpired),
-			Ok(Err(e)) => Err(e.into()),
+			Ok(Err(e)) => Err(e),
 			Ok(Ok(_)) => Err(SocketError::UnexpectedMessage)
 		}?;
 
@@ -111,14 +199,17 @@ impl WebSocketClient<Disconnected> {
 			state: Connected {
 				heartbeat_fut,
 				message_fut
-			}
+			},
+			stats: self.stats,
+			unparsed_dump: self.unparsed_dump,
+			timing: self.timing
 		})
 	}
 }
 
 impl WebSocketClient<Connected<'_>> {
 	pub fn close(self) -> WebSocketClient<Disconnected> {
-		WebSocketClient { state: Disconnected }
+		WebSocketClient { state: Disconnected, stats: self.stats, unparsed_dump: self.unparsed_dump, timing: self.timing }
 	}
 }
 
@@ -134,9 +225,6 @@ impl Stream for WebSocketClient<Connected<'_>> {
 			return Poll::Ready(Some(Err(err)))
 		}
 
-		match message_poll {
-			Poll::Ready(val) => Poll::Ready(val.map(|inner| inner.map_err(Into::into))),
-			Poll::Pending => Poll::Pending
-		}
+		message_poll
 	}
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::{collections::VecDeque, sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc, Mutex, OnceLock}, time::Duration};
+use of_client::ratelimit::{Backoff, RateLimits};
+use serde::Serialize;
+
+const LATENCY_WINDOW: usize = 50;
+
+#[derive(Debug, Default)]
+pub struct DaemonStats {
+	unparsed_messages: AtomicU64,
+	queue_depth: AtomicUsize,
+	dropped_messages: AtomicU64,
+	heartbeat_latencies: Mutex<VecDeque<Duration>>,
+	rate_limits: OnceLock<Arc<RateLimits>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LatencySummary {
+	pub p50_ms: f64,
+	pub p95_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DaemonStatsSnapshot {
+	pub unparsed_messages: u64,
+	pub heartbeat_latency: Option<LatencySummary>,
+	/// Messages received but not handed to the handlers yet
+	pub queue_depth: usize,
+	/// Messages dropped because the queue was full
+	pub dropped_messages: u64,
+	/// Endpoints the API rate limited that requests are held back from
+	pub backing_off: Vec<Backoff>,
+}
+
+impl DaemonStats {
+	pub fn unparsed_messages(&self) -> u64 {
+		self.unparsed_messages.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn record_unparsed(&self) {
+		self.unparsed_messages.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn queue_depth(&self) -> usize {
+		self.queue_depth.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn record_queued(&self) {
+		self.queue_depth.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn record_dequeued(&self) {
+		self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+	}
+
+	pub fn dropped_messages(&self) -> u64 {
+		self.dropped_messages.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn record_dropped(&self) {
+		self.dropped_messages.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn watch_rate_limits(&self, rate_limits: Arc<RateLimits>) {
+		let _ = self.rate_limits.set(rate_limits);
+	}
+
+	pub fn backing_off(&self) -> Vec<Backoff> {
+		self.rate_limits.get().map(|rate_limits| rate_limits.backoffs()).unwrap_or_default()
+	}
+
+	pub(crate) fn record_heartbeat(&self, latency: Duration) {
+		let mut latencies = self.heartbeat_latencies.lock().unwrap();
+		if latencies.len() == LATENCY_WINDOW { latencies.pop_front(); }
+		latencies.push_back(latency);
+	}
+
+	pub fn heartbeat_latency(&self) -> Option<LatencySummary> {
+		let mut latencies: Vec<Duration> = self.heartbeat_latencies.lock().unwrap().iter().copied().collect();
+		if latencies.is_empty() { return None }
+
+		latencies.sort_unstable();
+		let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p).round() as usize].as_secs_f64() * 1000.0;
+
+		Some(LatencySummary {
+			p50_ms: percentile(0.5),
+			p95_ms: percentile(0.95)
+		})
+	}
+
+	pub fn snapshot(&self) -> DaemonStatsSnapshot {
+		DaemonStatsSnapshot {
+			unparsed_messages: self.unparsed_messages(),
+			heartbeat_latency: self.heartbeat_latency(),
+			queue_depth: self.queue_depth(),
+			dropped_messages: self.dropped_messages(),
+			backing_off: self.backing_off(),
+		}
+	}
+}
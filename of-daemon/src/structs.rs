@@ -39,7 +39,7 @@ pub struct PostPublished {
 	#[serde(deserialize_with = "from_str")]
 	pub id: u64,
 	#[serde(deserialize_with="from_str")]
-	user_id: u64,
+	pub user_id: u64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -62,8 +62,31 @@ pub struct PostFundraisingUpdated {
 #[serde(rename_all = "camelCase")]
 pub struct Chat {
 	pub from_user: User,
+	#[serde(default)]
+	is_from_queue: bool,
+	queue_id: Option<u64>,
 	#[serde(flatten)]
 	pub content: content::Chat,
+	/// Fetched while catching up on what was missed, rather than received live
+	#[serde(skip)]
+	pub catch_up: bool,
+}
+
+impl Chat {
+	pub fn caught_up(from_user: User, message: content::ChatMessage) -> Self {
+		Self {
+			from_user,
+			is_from_queue: message.is_from_queue,
+			queue_id: message.queue_id,
+			content: message.content,
+			catch_up: true
+		}
+	}
+
+	/// Whether the message was broadcast to subscribers in bulk rather than sent personally
+	pub fn is_mass_message(&self) -> bool {
+		self.is_from_queue || self.queue_id.is_some()
+	}
 }
 	
 #[derive(Deserialize, Debug)]
@@ -83,7 +106,7 @@ pub struct Story {
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-struct ShortUser {
+pub struct ShortUser {
 	pub id: u64,
 	pub name: String,
 	pub avatar: Option<String>,
@@ -91,12 +114,35 @@ struct ShortUser {
 
 #[derive(Deserialize, Debug)]
 pub struct StoryTip {
-	id: u64,
-	from_user: ShortUser,
-	story_user_id: u64,
-	story_id: u64,
-	amount: f32,
-	message: Option<String>
+	pub id: u64,
+	pub from_user: ShortUser,
+	pub story_user_id: u64,
+	pub story_id: u64,
+	pub amount: f32,
+	pub message: Option<String>
+}
+
+/// What a notification is about, as far as it matters for filtering
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum NotificationSubType {
+	/// Promotions, e.g. `promoreg_for_expired` offers to renew an expired subscription
+	Promotion,
+	Discount,
+	PriceChange,
+	/// The user's subscription to the creator ran out
+	SubscriptionExpired,
+	Other(String),
+}
+
+impl From<String> for NotificationSubType {
+	fn from(value: String) -> Self {
+		if value.starts_with("promo") { Self::Promotion }
+		else if value.contains("discount") { Self::Discount }
+		else if value.contains("expired") { Self::SubscriptionExpired }
+		else if value.contains("price") { Self::PriceChange }
+		else { Self::Other(value) }
+	}
 }
 
 #[derive(Deserialize, Debug)]
@@ -105,9 +151,13 @@ pub struct Notification {
 	pub user: User,
 	#[serde(rename = "type")]
 	notif_type: String,
-	sub_type: String,
+	sub_type: NotificationSubType,
+	new_price: Option<f32>,
 	#[serde(flatten)]
 	pub content: content::Notification,
+	/// Fetched while catching up on what was missed, rather than received live
+	#[serde(skip)]
+	pub catch_up: bool,
 }
 
 #[derive(Deserialize, Debug)]
@@ -118,6 +168,16 @@ pub struct NewMessage {
 	has_system_notifications: bool
 }
 
+impl Notification {
+	pub fn sub_type(&self) -> &NotificationSubType {
+		// Price changes don't always have a dedicated sub type
+		match (&self.sub_type, self.new_price) {
+			(NotificationSubType::Other(_), Some(_)) => &NotificationSubType::PriceChange,
+			(sub_type, _) => sub_type
+		}
+	}
+}
+
 impl From<NewMessage> for Notification {
 	fn from(value: NewMessage) -> Self {
 		value.new_message
@@ -142,26 +202,26 @@ pub struct Stream {
 #[derive(Deserialize, Debug)]
 pub struct StreamStart {
 	#[serde(deserialize_with = "from_str")]
-	stream_id: u64,
+	pub stream_id: u64,
 	#[serde(rename = "userId")]
-	user_id: u64
+	pub user_id: u64
 }
 
 #[derive(Deserialize, Debug)]
 pub struct StreamStop {
 	#[serde(deserialize_with = "from_str")]
-	stream_id: u64,
+	pub stream_id: u64,
 	#[serde(deserialize_with = "from_str")]
-	stream_user_id: u64
+	pub stream_user_id: u64
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct StreamUpdate {
-	id: u64,
+	pub id: u64,
 	raw_description: String,
 	is_active: bool,
-	is_finished: bool,
+	pub is_finished: bool,
 	started_at: DateTime<Utc>,
 	finished_at: Option<DateTime<Utc>>,
 	room: String,
@@ -181,9 +241,9 @@ pub struct StreamUpdate {
 #[derive(Deserialize, Debug)]
 pub struct StreamLook {
 	#[serde(deserialize_with = "from_str")]
-	stream_user_id: u64,
+	pub stream_user_id: u64,
 	user: User,
-	total: u32,
+	pub total: u32,
 	viewer_instance_count: u32
 }
 
@@ -198,28 +258,50 @@ pub struct StreamComment {
 #[derive(Deserialize, Debug)]
 pub struct StreamLike {
 	#[serde(deserialize_with = "from_str")]
-	stream_user_id: u64
+	pub stream_user_id: u64
+}
+
+/// Viewer and like activity of a live stream over one aggregation period,
+/// sent by the daemon in place of the individual look, unlook and like messages
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StreamSummary {
+	pub stream_user_id: u64,
+	/// Viewer count of the latest look or unlook, `None` if there was none
+	pub viewers: Option<u32>,
+	pub joined: u32,
+	pub left: u32,
+	pub likes: u32,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct StreamTip {
-	id: u64,
-	from_user: User,
-	stream_user_id: u64,
-	stream_id: u64,
-	amount: f32,
-	message: Option<String>
+	pub id: u64,
+	pub from_user: User,
+	pub stream_user_id: u64,
+	pub stream_id: u64,
+	pub amount: f32,
+	pub message: Option<String>
 }
 
 #[derive(Deserialize, Debug)]
 pub struct StreamTips {
-	stream_tips: StreamTip,
+	pub stream_tips: StreamTip,
 	tips_count: u32,
 	tips_goal: String,
 	tips_goal_sum: f32,
 	tips_goal_progress: f32
 }
 
+/// An announcement from the site itself
+#[derive(Deserialize, Debug)]
+pub struct SiteToast {
+	pub id: u64,
+	pub title: String,
+	#[serde(default)]
+	pub text: String,
+	pub link: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum TaggedMessage {
@@ -245,6 +327,7 @@ pub enum TaggedMessage {
 	StreamLike(StreamLike),
 
 	HasNewHints(bool),
+	Toasts(Vec<SiteToast>),
 }
 
 #[derive(Deserialize, Debug)]
@@ -258,5 +341,22 @@ pub enum Message {
 	#[serde(deserialize_with = "from::<_, NewMessage, _>")]
 	Notification(Notification),
 	StreamTips(StreamTips),
+	#[serde(skip)]
+	StreamSummary(StreamSummary),
 	Error(Error),
+	// Must stay last, catches every shape the variants above don't recognize
+	Unknown(serde_json::Value),
+}
+
+impl Message {
+	/// Presence, counters and live stream chatter, which can be lost without missing any content
+	pub fn is_low_value(&self) -> bool {
+		matches!(self,
+			Self::Onlines(_) | Self::ChatCount(_) | Self::NotificationCount(_) | Self::StreamSummary(_) |
+			Self::Tagged(
+				TaggedMessage::StreamLook(_) | TaggedMessage::StreamUnlook(_) | TaggedMessage::StreamComment(_) |
+				TaggedMessage::StreamLike(_)
+			)
+		)
+	}
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use crate::structs::{Message, StreamSummary, TaggedMessage};
+
+/// Coalesces the looks, unlooks and likes of live streams, which arrive many times per second
+/// during popular streams, into one summary per stream and period
+#[derive(Debug, Default)]
+pub struct Telemetry {
+	streams: HashMap<u64, StreamSummary>,
+}
+
+impl Telemetry {
+	fn stream(&mut self, stream_user_id: u64) -> &mut StreamSummary {
+		self.streams.entry(stream_user_id).or_insert_with(|| StreamSummary { stream_user_id, ..Default::default() })
+	}
+
+	/// Takes in the message if it is stream telemetry, otherwise hands it back
+	pub fn absorb(&mut self, msg: Message) -> Option<Message> {
+		match msg {
+			Message::Tagged(TaggedMessage::StreamLook(look)) => {
+				let stream = self.stream(look.stream_user_id);
+				stream.viewers = Some(look.total);
+				stream.joined += 1;
+			},
+			Message::Tagged(TaggedMessage::StreamUnlook(look)) => {
+				let stream = self.stream(look.stream_user_id);
+				stream.viewers = Some(look.total);
+				stream.left += 1;
+			},
+			Message::Tagged(TaggedMessage::StreamLike(like)) => self.stream(like.stream_user_id).likes += 1,
+			msg => return Some(msg)
+		}
+
+		None
+	}
+
+	/// Summaries of the streams with activity since the last flush
+	pub fn flush(&mut self) -> Vec<Message> {
+		self.streams
+		.drain()
+		.map(|(_, summary)| Message::StreamSummary(summary))
+		.collect()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.streams.is_empty()
+	}
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+/// Durations used by the daemon. Every timer runs on `tokio::time`,
+/// so tests can pause the runtime clock and fast-forward through them.
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+	pub heartbeat_interval: Duration,
+	pub heartbeat_timeout: Duration,
+	pub connect_timeout: Duration,
+	pub activity_mean: Duration,
+	/// How long socket reads are held back while the event queue is full
+	pub backpressure_timeout: Duration,
+	/// How often stream looks, unlooks and likes are summarized
+	pub telemetry_interval: Duration,
+}
+
+impl Default for Timing {
+	fn default() -> Self {
+		Self {
+			heartbeat_interval: Duration::from_secs(20),
+			heartbeat_timeout: Duration::from_secs(5),
+			connect_timeout: Duration::from_secs(10),
+			activity_mean: Duration::from_secs(60),
+			backpressure_timeout: Duration::from_secs(2),
+			telemetry_interval: Duration::from_secs(5),
+		}
+	}
+}
@@ -0,0 +1,19 @@
+use of_daemon::{socket::SocketError, DaemonError};
+
+fn closed(code: u16) -> DaemonError {
+	SocketError::Closed { code, reason: String::new() }.into()
+}
+
+#[test]
+fn policy_closes_revoke_auth() {
+	assert!(closed(1008).is_auth_revoked());
+	assert!(closed(4001).is_auth_revoked());
+}
+
+#[test]
+fn other_closes_are_transient() {
+	assert!(!closed(1001).is_auth_revoked());
+	assert!(!closed(1005).is_auth_revoked());
+	assert!(!closed(1011).is_auth_revoked());
+	assert!(!DaemonError::from(SocketError::TimeoutExpired).is_auth_revoked());
+}
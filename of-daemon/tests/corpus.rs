@@ -0,0 +1,101 @@
+use std::{fs, path::Path};
+use of_daemon::structs::{Message, NotificationSubType, TaggedMessage};
+use proptest::prelude::*;
+use serde_json::{Map, Value};
+
+fn corpus(dir: &str) -> impl Iterator<Item = (String, String)> {
+	let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus").join(dir);
+	fs::read_dir(dir)
+	.unwrap()
+	.map(|entry| entry.unwrap().path())
+	.filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+	.map(|path| (path.file_name().unwrap().to_string_lossy().into_owned(), fs::read_to_string(path).unwrap()))
+}
+
+#[test]
+fn known_payloads_parse() {
+	for (name, payload) in corpus("known") {
+		let message = serde_json::from_str::<Message>(&payload)
+			.unwrap_or_else(|err| panic!("{name} could not be parsed: {err}"));
+
+		assert!(!matches!(message, Message::Unknown(_)), "{name} was parsed as an unknown message");
+	}
+}
+
+#[test]
+fn unknown_payloads_are_preserved() {
+	for (name, payload) in corpus("unknown") {
+		let message = serde_json::from_str::<Message>(&payload)
+			.unwrap_or_else(|err| panic!("{name} could not be parsed: {err}"));
+
+		match message {
+			Message::Unknown(value) => assert_eq!(value, serde_json::from_str::<Value>(&payload).unwrap(), "{name} was altered"),
+			other => panic!("{name} was parsed as {other:?}")
+		}
+	}
+}
+
+#[test]
+fn mass_messages_are_flagged() {
+	for (name, payload) in corpus("known") {
+		let Ok(Message::Tagged(TaggedMessage::Api2ChatMessage(chat))) = serde_json::from_str::<Message>(&payload) else { continue };
+		assert_eq!(chat.is_mass_message(), name.contains("mass"), "{name} was flagged incorrectly");
+	}
+}
+
+#[test]
+fn expired_subscriptions_are_detected() {
+	for (name, payload) in corpus("known") {
+		let Ok(Message::Notification(notification)) = serde_json::from_str::<Message>(&payload) else { continue };
+		assert_eq!(notification.sub_type() == &NotificationSubType::SubscriptionExpired, name.contains("subscription_expired"), "{name} was classified incorrectly");
+	}
+}
+
+#[test]
+fn low_value_messages_are_identified() {
+	let low_value = [
+		"onlines", "chat_count", "notification_count",
+		"stream_look", "stream_unlook", "stream_comment", "stream_like"
+	];
+
+	for (name, payload) in corpus("known") {
+		let message = serde_json::from_str::<Message>(&payload).unwrap();
+		let expected = low_value.iter().any(|kind| name.strip_suffix(".json") == Some(kind));
+		assert_eq!(message.is_low_value(), expected, "{name} was classified incorrectly");
+	}
+}
+
+fn json_value() -> impl Strategy<Value = Value> {
+	let leaf = prop_oneof![
+		Just(Value::Null),
+		any::<bool>().prop_map(Value::from),
+		any::<i64>().prop_map(Value::from),
+		any::<f64>().prop_filter("finite", |f| f.is_finite()).prop_map(Value::from),
+		".*".prop_map(Value::from),
+	];
+
+	leaf.prop_recursive(4, 64, 8, |inner| prop_oneof![
+		prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+		prop::collection::hash_map("[a-z_]{1,16}", inner, 0..8).prop_map(|map| Value::Object(map.into_iter().collect())),
+	])
+}
+
+proptest! {
+	#[test]
+	fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+		let _ = serde_json::from_slice::<Message>(&bytes);
+	}
+
+	#[test]
+	fn arbitrary_json_always_parses(value in json_value()) {
+		prop_assert!(serde_json::from_value::<Message>(value).is_ok());
+	}
+
+	#[test]
+	fn unrecognized_keys_are_unknown(key in "x_[a-z_]{1,16}", value in json_value()) {
+		let mut map = Map::new();
+		map.insert(key, value);
+		let message = serde_json::from_value::<Message>(Value::Object(map)).unwrap();
+		prop_assert!(matches!(message, Message::Unknown(_)));
+	}
+}
@@ -0,0 +1,62 @@
+use std::{sync::Arc, time::Duration};
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use of_daemon::{socket::{heartbeat, SocketError}, stats::DaemonStats, timing::Timing};
+use tokio::{sync::Notify, time::{timeout, Instant}};
+use tokio_tungstenite::tungstenite;
+
+fn sink() -> (impl futures::Sink<tungstenite::Message, Error = tungstenite::Error> + Unpin, mpsc::UnboundedReceiver<tungstenite::Message>) {
+	let (tx, rx) = mpsc::unbounded();
+	(tx.sink_map_err(|_| tungstenite::Error::ConnectionClosed), rx)
+}
+
+#[tokio::test(start_paused = true)]
+async fn unacknowledged_heartbeat_times_out() {
+	let (sink, _rx) = sink();
+	let timing = Timing::default();
+	let start = Instant::now();
+
+	let result = heartbeat(sink, Arc::new(Notify::new()), Arc::default(), timing).await;
+
+	assert!(matches!(result, Err(SocketError::TimeoutExpired)));
+	// The first tick is immediate, so only the ack timeout should have elapsed
+	assert_eq!(start.elapsed(), timing.heartbeat_timeout);
+}
+
+#[tokio::test(start_paused = true)]
+async fn acknowledged_heartbeats_keep_running() {
+	let (sink, mut rx) = sink();
+	let ack = Arc::new(Notify::new());
+	let stats = Arc::new(DaemonStats::default());
+	let timing = Timing::default();
+
+	tokio::spawn({
+		let ack = ack.clone();
+		async move {
+			while rx.next().await.is_some() { ack.notify_one() }
+		}
+	});
+
+	let elapsed = timeout(Duration::from_secs(600), heartbeat(sink, ack, stats.clone(), timing)).await;
+
+	assert!(elapsed.is_err(), "heartbeat stopped early: {elapsed:?}");
+	assert!(stats.heartbeat_latency().is_some());
+}
+
+#[tokio::test(start_paused = true)]
+async fn custom_timing_is_respected() {
+	let (sink, mut rx) = sink();
+	let timing = Timing { heartbeat_interval: Duration::from_secs(1), heartbeat_timeout: Duration::from_millis(100), ..Timing::default() };
+	let ack = Arc::new(Notify::new());
+
+	let task = tokio::spawn(heartbeat(sink, ack.clone(), Arc::default(), timing));
+
+	rx.next().await.unwrap();
+	ack.notify_one();
+	let first = Instant::now();
+
+	rx.next().await.unwrap();
+	assert_eq!(first.elapsed(), timing.heartbeat_interval);
+
+	let result = task.await.unwrap();
+	assert!(matches!(result, Err(SocketError::TimeoutExpired)));
+}
@@ -0,0 +1,45 @@
+use of_daemon::{structs::{Message, StreamSummary}, telemetry::Telemetry};
+
+fn parse(payload: &str) -> Message {
+	serde_json::from_str(payload).unwrap()
+}
+
+fn look(kind: &str, total: u32) -> Message {
+	parse(&format!(r#"{{"{kind}": {{
+		"stream_user_id": "15585607",
+		"user": {{ "id": 15585607, "name": "OnlyFans", "username": "onlyfans" }},
+		"total": {total},
+		"viewer_instance_count": 1
+	}}}}"#))
+}
+
+#[test]
+fn stream_activity_is_summarized() {
+	let mut telemetry = Telemetry::default();
+	for msg in [look("stream_look", 10), look("stream_look", 11), look("stream_unlook", 10)] {
+		assert!(telemetry.absorb(msg).is_none());
+	}
+
+	for _ in 0..3 {
+		assert!(telemetry.absorb(parse(r#"{"stream_like": {"stream_user_id": "15585607"}}"#)).is_none());
+	}
+
+	let summaries = telemetry.flush();
+	assert!(telemetry.is_empty());
+	assert!(matches!(summaries.as_slice(), [Message::StreamSummary(summary)] if *summary == StreamSummary {
+		stream_user_id: 15585607,
+		viewers: Some(10),
+		joined: 2,
+		left: 1,
+		likes: 3
+	}));
+}
+
+#[test]
+fn other_messages_pass_through() {
+	let mut telemetry = Telemetry::default();
+	let msg = parse(r#"{"post_expire": "1234"}"#);
+
+	assert!(telemetry.absorb(msg).is_some());
+	assert!(telemetry.flush().is_empty());
+}
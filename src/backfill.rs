@@ -0,0 +1,160 @@
+use log::*;
+use chrono::{DateTime, Utc};
+use futures::{future, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+use of_client::{content::{Content, ContentType, Purchased}, pagination::PAGE_SIZE, scheduler::Priority, user::User};
+use of_daemon::structs::{Chat, Message, PostPublished, TaggedMessage};
+use crate::{handlers::Context, settings::BackfillSettings};
+
+impl Context {
+	async fn backfill_posts(&self, since: DateTime<Utc>, settings: &BackfillSettings) {
+		let client = self.client.with_priority(Priority::Bulk);
+		let Ok(subscriptions) = client.get_subscription_details().try_collect::<Vec<_>>().await else { return };
+
+		for subscription in subscriptions.iter().filter(|subscription| !subscription.expired) {
+			let Ok(posts) = client.get_user_posts(subscription.user.id).take(settings.posts).try_collect::<Vec<_>>().await else { continue };
+			let missed = posts.iter()
+				.filter(|post| post.timestamp() >= since && !self.is_known(ContentType::Posts, post.id()))
+				.collect::<Vec<_>>();
+
+			if missed.is_empty() { continue }
+
+			info!("Backfilling {} posts from {}", missed.len(), subscription.user.username);
+			for post in missed.into_iter().rev() {
+				self.dispatch(Message::Tagged(TaggedMessage::PostPublished(PostPublished { id: post.id(), user_id: subscription.user.id })));
+			}
+		}
+	}
+
+	async fn backfill_chats(&self, since: DateTime<Utc>, settings: &BackfillSettings) {
+		let Ok(user_ids) = self.client.get_recent_chats(settings.chats).await else { return };
+
+		for user_id in user_ids {
+			let Ok(messages) = self.client.get_chat_messages(user_id, None).take(settings.messages).try_collect::<Vec<_>>().await else { continue };
+			let missed = messages.into_iter()
+				.filter(|message| message.from_user.id == user_id && message.content.timestamp() >= since)
+				.filter(|message| !self.is_known(ContentType::Chats, message.content.id()))
+				.collect::<Vec<_>>();
+
+			if missed.is_empty() { continue }
+			let Ok(user) = self.client.get_user(user_id).await else { continue };
+
+			info!("Backfilling {} messages from {}", missed.len(), user.username);
+			for message in missed.into_iter().rev() {
+				let mut chat = Chat::caught_up(user.clone(), message);
+				// Missed while connected before, they are notified like live ones
+				chat.catch_up = false;
+				self.dispatch(Message::Tagged(TaggedMessage::Api2ChatMessage(chat)));
+			}
+		}
+	}
+
+	/// Downloads the media of the `limit` most recent posts of `username`, whatever the download actions are.
+	/// Returns the number of posts
+	pub async fn download_user(&self, username: &str, limit: usize) -> anyhow::Result<usize> {
+		let user = self.client.get_user(username).await?;
+		let posts = self.client.get_user_posts(user.id).take(limit).try_collect::<Vec<_>>().await?;
+
+		info!("Downloading the media of {} posts from {}", posts.len(), user.username);
+		for post in &posts {
+			self.download_bulk(post, &user).await;
+		}
+
+		Ok(posts.len())
+	}
+
+	/// Downloads the media of every message `user` sent in their chat, oldest included, whatever the download actions are.
+	/// Returns the number of messages
+	pub async fn download_chat_history(&self, user: &User) -> anyhow::Result<usize> {
+		let client = self.client.with_priority(Priority::Bulk);
+		let mut messages = client.get_chat_messages(user.id, None)
+			.try_filter(|message| future::ready(message.from_user.id == user.id));
+
+		let mut count = 0;
+		while let Some(message) = messages.try_next().await? {
+			self.download_bulk(&message.content, user).await;
+			count += 1;
+		}
+
+		info!("Downloaded the media of {count} messages from {}", user.username);
+		Ok(count)
+	}
+
+	/// Downloads the stories of the highlights on the profile of `user` that weren't archived before, returns the number of stories
+	pub async fn download_highlights(&self, user: &User) -> anyhow::Result<usize> {
+		let client = self.client.with_priority(Priority::Bulk);
+		let archived = self.catalog.archived_highlights(user.id)
+			.inspect_err(|err| error!("Error reading archived highlights: {err}"))
+			.unwrap_or_default();
+		let mut count = 0;
+
+		for highlight in client.get_highlights(user.id).await? {
+			if archived.contains(&highlight.id) { continue }
+			let Ok(highlight) = client.get_highlight(highlight.id).await else { continue };
+
+			let mut complete = true;
+			for story in &highlight.stories {
+				complete &= self.download_bulk(story, user).await;
+			}
+
+			// Only once every story made it, so a failed one is tried again the next time
+			if complete {
+				let _ = self.catalog.record_highlight(user.id, highlight.id)
+					.inspect_err(|err| error!("Error storing archived highlight: {err}"));
+			}
+
+			count += highlight.stories.len();
+		}
+
+		debug!("Downloaded the highlights of {}, {count} stories", user.username);
+		Ok(count)
+	}
+
+	/// Downloads the media of every bought post and message, or of the `limit` most recent purchases,
+	/// whatever the download actions are. Returns the number of purchases
+	pub async fn download_purchased(&self, limit: Option<usize>) -> anyhow::Result<usize> {
+		let client = self.client.with_priority(Priority::Bulk);
+		let limit = limit.unwrap_or(usize::MAX);
+		let mut users: HashMap<u64, User> = HashMap::new();
+		let mut count = 0;
+
+		while count < limit {
+			let page = client.get_purchased(count, PAGE_SIZE.min(limit - count)).await?;
+			if page.is_empty() { break }
+			count += page.len();
+
+			for purchased in page {
+				match purchased {
+					Purchased::Post { id, .. } => {
+						let Ok(post) = client.get_post(id).await else { continue };
+						self.download_bulk(&post, &post.author).await;
+					},
+					Purchased::Message(message) => {
+						let user = match users.get(&message.from_user.id) {
+							Some(user) => user.clone(),
+							None => {
+								let Ok(user) = client.get_user(message.from_user.id).await else { continue };
+								users.entry(user.id).or_insert(user).clone()
+							}
+						};
+
+						self.download_bulk(&message.content, &user).await;
+					}
+				}
+			}
+		}
+
+		info!("Downloaded the media of {count} purchases");
+		Ok(count)
+	}
+
+	/// Feeds posts and messages sent since `since` that aren't in the catalog yet through the handlers,
+	/// to make up for a websocket disconnection
+	pub async fn backfill(self, since: DateTime<Utc>) {
+		let Some(settings) = self.settings.read().unwrap().backfill.clone() else { return };
+
+		debug!("Backfilling content sent since {since}");
+		self.backfill_posts(since, &settings).await;
+		self.backfill_chats(since, &settings).await;
+	}
+}
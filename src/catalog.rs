@@ -0,0 +1,627 @@
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, str::FromStr, sync::Mutex};
+use chrono::{DateTime, Utc};
+use of_client::{content::ContentType, user::Transaction};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use crate::{pending::PendingWork, plugins::DownloadedFile, stats::EventCounts};
+
+pub struct MediaRecord {
+	pub media_id: u64,
+	pub content_type: String,
+	pub content_id: u64,
+	pub username: String,
+	pub path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaggedMedia {
+	pub media_id: u64,
+	pub username: String,
+	pub content_type: String,
+	pub content_id: u64,
+	pub path: PathBuf,
+	pub bytes: u64,
+	pub downloaded_at: String,
+	pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyDownloads {
+	pub day: String,
+	pub username: String,
+	pub downloads: u64,
+	pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyFailures {
+	pub day: String,
+	pub downloads: u64,
+	pub failures: u64,
+}
+
+pub struct Catalog {
+	connection: Mutex<Connection>,
+}
+
+impl Catalog {
+	pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+		let mut connection = Connection::open(path)?;
+
+		// Migrating the media table renames, recreates, fills and drops tables, stopping halfway would lose the media
+		let transaction = connection.transaction()?;
+
+		// Media used to be keyed by its id alone, so media attached to several posts and messages kept a single row
+		let keyed_by_id = transaction.query_row(
+			"SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'media'",
+			[],
+			|row| row.get::<_, String>(0)
+		)
+		.optional()?
+		.is_some_and(|sql| sql.contains("media_id INTEGER PRIMARY KEY"));
+
+		if keyed_by_id {
+			transaction.execute_batch("
+				DROP INDEX IF EXISTS media_content;
+				ALTER TABLE media RENAME TO media_by_id;
+			")?;
+		}
+
+		transaction.execute_batch("
+			CREATE TABLE IF NOT EXISTS event_counts (
+				username TEXT NOT NULL,
+				content_type TEXT NOT NULL,
+				count INTEGER NOT NULL DEFAULT 0,
+				PRIMARY KEY (username, content_type)
+			);
+			CREATE TABLE IF NOT EXISTS content (
+				content_type TEXT NOT NULL,
+				content_id INTEGER NOT NULL,
+				username TEXT NOT NULL,
+				text TEXT NOT NULL,
+				updated_at TEXT NOT NULL,
+				PRIMARY KEY (content_type, content_id)
+			);
+			CREATE TABLE IF NOT EXISTS media (
+				media_id INTEGER NOT NULL,
+				content_type TEXT NOT NULL,
+				content_id INTEGER NOT NULL,
+				username TEXT NOT NULL,
+				path TEXT NOT NULL,
+				bytes INTEGER NOT NULL,
+				downloaded_at TEXT NOT NULL,
+				remote TEXT,
+				PRIMARY KEY (media_id, content_type, content_id)
+			);
+			CREATE INDEX IF NOT EXISTS media_content ON media (content_type, content_id);
+			CREATE TABLE IF NOT EXISTS expired (
+				content_type TEXT NOT NULL,
+				content_id INTEGER NOT NULL,
+				expired_at TEXT NOT NULL,
+				PRIMARY KEY (content_type, content_id)
+			);
+			CREATE TABLE IF NOT EXISTS deleted (
+				content_type TEXT NOT NULL,
+				content_id INTEGER NOT NULL,
+				deleted_at TEXT NOT NULL,
+				PRIMARY KEY (content_type, content_id)
+			);
+			CREATE TABLE IF NOT EXISTS tags (
+				media_id INTEGER NOT NULL,
+				tag TEXT NOT NULL,
+				PRIMARY KEY (media_id, tag)
+			);
+			CREATE TABLE IF NOT EXISTS event_log (
+				username TEXT NOT NULL,
+				content_type TEXT NOT NULL,
+				received_at TEXT NOT NULL
+			);
+			CREATE INDEX IF NOT EXISTS event_log_received ON event_log (received_at);
+			CREATE TABLE IF NOT EXISTS download_log (
+				username TEXT NOT NULL,
+				success INTEGER NOT NULL,
+				retries INTEGER NOT NULL,
+				finished_at TEXT NOT NULL
+			);
+			CREATE TABLE IF NOT EXISTS transactions (
+				created_at TEXT NOT NULL,
+				amount REAL NOT NULL,
+				status TEXT NOT NULL,
+				description TEXT NOT NULL,
+				username TEXT,
+				ppv INTEGER NOT NULL,
+				PRIMARY KEY (created_at, description, amount)
+			);
+			CREATE TABLE IF NOT EXISTS pending_work (
+				key TEXT PRIMARY KEY,
+				work TEXT NOT NULL,
+				queued_at TEXT NOT NULL
+			);
+			CREATE TABLE IF NOT EXISTS highlights (
+				user_id INTEGER NOT NULL,
+				highlight_id INTEGER NOT NULL,
+				archived_at TEXT NOT NULL,
+				PRIMARY KEY (user_id, highlight_id)
+			);
+		")?;
+
+		let has_remote = transaction.query_row(
+			"SELECT EXISTS (SELECT 1 FROM pragma_table_info('media') WHERE name = 'remote')",
+			[],
+			|row| row.get::<_, bool>(0)
+		)?;
+
+		if !has_remote {
+			transaction.execute_batch("ALTER TABLE media ADD COLUMN remote TEXT")?;
+		}
+
+		if keyed_by_id {
+			transaction.execute_batch("
+				INSERT INTO media (media_id, content_type, content_id, username, path, bytes, downloaded_at)
+				SELECT media_id, content_type, content_id, username, path, bytes, downloaded_at FROM media_by_id;
+				DROP TABLE media_by_id;
+			")?;
+		}
+
+		transaction.commit()?;
+		Ok(Self { connection: Mutex::new(connection) })
+	}
+
+	pub fn record_event(&self, username: &str, content_type: ContentType) -> rusqlite::Result<()> {
+		let connection = self.connection.lock().unwrap();
+		connection.execute(
+			"INSERT INTO event_counts (username, content_type, count) VALUES (?1, ?2, 1)
+			ON CONFLICT (username, content_type) DO UPDATE SET count = count + 1",
+			params![username, content_type.to_string()]
+		)?;
+
+		connection.execute(
+			"INSERT INTO event_log (username, content_type, received_at) VALUES (?1, ?2, ?3)",
+			params![username, content_type.to_string(), Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(())
+	}
+
+	/// Stores the text of a post or message, returns the previously stored text
+	pub fn record_content(&self, username: &str, content_type: ContentType, content_id: u64, text: &str) -> rusqlite::Result<Option<String>> {
+		let connection = self.connection.lock().unwrap();
+		let previous = connection.query_row(
+			"SELECT text FROM content WHERE content_type = ?1 AND content_id = ?2",
+			params![content_type.to_string(), content_id],
+			|row| row.get(0)
+		).optional()?;
+
+		connection.execute(
+			"INSERT INTO content (content_type, content_id, username, text, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+			ON CONFLICT (content_type, content_id) DO UPDATE SET text = excluded.text, updated_at = excluded.updated_at",
+			params![content_type.to_string(), content_id, username, text, Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(previous)
+	}
+
+	/// Whether the text of the content was stored before
+	pub fn has_content(&self, content_type: ContentType, content_id: u64) -> rusqlite::Result<bool> {
+		self.connection.lock().unwrap().query_row(
+			"SELECT EXISTS (SELECT 1 FROM content WHERE content_type = ?1 AND content_id = ?2)",
+			params![content_type.to_string(), content_id],
+			|row| row.get(0)
+		)
+	}
+
+	pub fn record_media(&self, file: &DownloadedFile) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute(
+			"INSERT OR REPLACE INTO media (media_id, content_type, content_id, username, path, bytes, downloaded_at, remote) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL)",
+			params![file.media_id, file.content_type, file.content_id, file.username, file.path.to_string_lossy(), file.bytes, Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(())
+	}
+
+	/// Highlights on the profile of `user_id` whose stories were all downloaded
+	pub fn archived_highlights(&self, user_id: u64) -> rusqlite::Result<HashSet<u64>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("SELECT highlight_id FROM highlights WHERE user_id = ?1")?;
+		let ids = statement.query_map([user_id], |row| row.get(0))?
+			.collect();
+
+		ids
+	}
+
+	pub fn record_highlight(&self, user_id: u64, highlight_id: u64) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute(
+			"INSERT OR REPLACE INTO highlights (user_id, highlight_id, archived_at) VALUES (?1, ?2, ?3)",
+			params![user_id, highlight_id, Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(())
+	}
+
+	/// Ids of the media of a post or message that were downloaded
+	pub fn media_ids(&self, content_type: ContentType, content_id: u64) -> rusqlite::Result<HashSet<u64>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("SELECT media_id FROM media WHERE content_type = ?1 AND content_id = ?2")?;
+		let ids = statement.query_map(params![content_type.to_string(), content_id], |row| row.get(0))?
+			.collect();
+
+		ids
+	}
+
+	/// Where a media was last downloaded to, by any content, if it is still on disk
+	pub fn media_path(&self, media_id: u64) -> rusqlite::Result<Option<PathBuf>> {
+		self.connection.lock().unwrap().query_row(
+			"SELECT path FROM media WHERE media_id = ?1 AND remote IS NULL ORDER BY downloaded_at DESC LIMIT 1",
+			params![media_id],
+			|row| row.get::<_, String>(0).map(PathBuf::from)
+		).optional()
+	}
+
+	/// Flags the media downloaded to `path` as moved to remote storage under `key`
+	pub fn mark_stored(&self, path: &Path, key: &str) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute(
+			"UPDATE media SET remote = ?2 WHERE path = ?1",
+			params![path.to_string_lossy(), key]
+		)?;
+
+		Ok(())
+	}
+
+	/// The key a media was moved to remote storage under, by any content
+	pub fn remote_key(&self, media_id: u64) -> rusqlite::Result<Option<String>> {
+		self.connection.lock().unwrap().query_row(
+			"SELECT remote FROM media WHERE media_id = ?1 AND remote IS NOT NULL ORDER BY downloaded_at DESC LIMIT 1",
+			params![media_id],
+			|row| row.get(0)
+		).optional()
+	}
+
+	pub fn media(&self, content_type: ContentType, content_id: u64) -> rusqlite::Result<Vec<MediaRecord>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("SELECT media_id, content_type, content_id, username, path FROM media WHERE content_type = ?1 AND content_id = ?2")?;
+		let records = statement.query_map(params![content_type.to_string(), content_id], |row| Ok(MediaRecord {
+				media_id: row.get(0)?,
+				content_type: row.get(1)?,
+				content_id: row.get(2)?,
+				username: row.get(3)?,
+				path: PathBuf::from(row.get::<_, String>(4)?),
+			}))?
+			.collect();
+
+		records
+	}
+
+	/// Bytes of all downloaded media still on disk, or of those from `username`
+	pub fn disk_usage(&self, username: Option<&str>) -> rusqlite::Result<u64> {
+		self.connection.lock().unwrap().query_row(
+			"SELECT COALESCE(SUM(bytes), 0) FROM media WHERE remote IS NULL AND (?1 IS NULL OR username = ?1)",
+			params![username],
+			|row| row.get(0)
+		)
+	}
+
+	/// The `count` media still on disk that were downloaded first, of all creators or only from `username`, with their size
+	pub fn oldest_media(&self, username: Option<&str>, count: usize) -> rusqlite::Result<Vec<(MediaRecord, u64)>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare(
+			"SELECT media_id, content_type, content_id, username, path, bytes FROM media WHERE remote IS NULL AND (?1 IS NULL OR username = ?1) ORDER BY downloaded_at LIMIT ?2"
+		)?;
+		let records = statement.query_map(params![username, count], |row| Ok((MediaRecord {
+				media_id: row.get(0)?,
+				content_type: row.get(1)?,
+				content_id: row.get(2)?,
+				username: row.get(3)?,
+				path: PathBuf::from(row.get::<_, String>(4)?),
+			}, row.get(5)?)))?
+			.collect();
+
+		records
+	}
+
+	/// Forgets the download of `record`, and the media's tags once no content has it downloaded anymore
+	pub fn remove_media(&self, record: &MediaRecord) -> rusqlite::Result<()> {
+		let connection = self.connection.lock().unwrap();
+		connection.execute(
+			"DELETE FROM media WHERE media_id = ?1 AND content_type = ?2 AND content_id = ?3",
+			params![record.media_id, record.content_type, record.content_id]
+		)?;
+		connection.execute(
+			"DELETE FROM tags WHERE media_id = ?1 AND NOT EXISTS (SELECT 1 FROM media WHERE media_id = ?1)",
+			params![record.media_id]
+		)?;
+		Ok(())
+	}
+
+	pub fn move_media(&self, record: &MediaRecord, path: &Path) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute(
+			"UPDATE media SET path = ?4 WHERE media_id = ?1 AND content_type = ?2 AND content_id = ?3",
+			params![record.media_id, record.content_type, record.content_id, path.to_string_lossy()]
+		)?;
+
+		Ok(())
+	}
+
+	pub fn tag_media(&self, media_id: u64, tags: &[String]) -> rusqlite::Result<()> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("INSERT OR IGNORE INTO tags (media_id, tag) VALUES (?1, ?2)")?;
+		for tag in tags {
+			statement.execute(params![media_id, tag])?;
+		}
+
+		Ok(())
+	}
+
+	/// Number of media carrying each tag
+	pub fn tags(&self) -> rusqlite::Result<HashMap<String, u64>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("SELECT tag, COUNT(*) FROM tags GROUP BY tag")?;
+		let tags = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+			.collect();
+
+		tags
+	}
+
+	/// Downloaded media carrying `tag`, oldest first, along with all of their tags
+	pub fn tagged_media(&self, tag: &str) -> rusqlite::Result<Vec<TaggedMedia>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("
+			SELECT media.media_id, username, content_type, content_id, path, bytes, downloaded_at, group_concat(tags.tag, char(10))
+			FROM media JOIN tags ON tags.media_id = media.media_id
+			WHERE media.media_id IN (SELECT media_id FROM tags WHERE tag = ?1)
+			GROUP BY media.media_id, content_type, content_id
+			ORDER BY downloaded_at
+		")?;
+
+		let media = statement.query_map(params![tag], |row| Ok(TaggedMedia {
+				media_id: row.get(0)?,
+				username: row.get(1)?,
+				content_type: row.get(2)?,
+				content_id: row.get(3)?,
+				path: PathBuf::from(row.get::<_, String>(4)?),
+				bytes: row.get(5)?,
+				downloaded_at: row.get(6)?,
+				tags: row.get::<_, String>(7)?.split('\n').map(str::to_string).collect(),
+			}))?
+			.collect();
+
+		media
+	}
+
+	/// The `limit` most recently downloaded media, newest first
+	pub fn recent_media(&self, limit: usize) -> rusqlite::Result<Vec<TaggedMedia>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("
+			SELECT media.media_id, username, content_type, content_id, path, bytes, downloaded_at, group_concat(tags.tag, char(10))
+			FROM media LEFT JOIN tags ON tags.media_id = media.media_id
+			GROUP BY media.media_id, content_type, content_id
+			ORDER BY downloaded_at DESC
+			LIMIT ?1
+		")?;
+
+		let media = statement.query_map(params![limit], |row| Ok(TaggedMedia {
+				media_id: row.get(0)?,
+				username: row.get(1)?,
+				content_type: row.get(2)?,
+				content_id: row.get(3)?,
+				path: PathBuf::from(row.get::<_, String>(4)?),
+				bytes: row.get(5)?,
+				downloaded_at: row.get(6)?,
+				tags: row.get::<_, Option<String>>(7)?.map(|tags| tags.split('\n').map(str::to_string).collect()).unwrap_or_default(),
+			}))?
+			.collect();
+
+		media
+	}
+
+	/// Flags content that was removed from the platform
+	pub fn mark_expired(&self, content_type: ContentType, content_id: u64) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute(
+			"INSERT OR IGNORE INTO expired (content_type, content_id, expired_at) VALUES (?1, ?2, ?3)",
+			params![content_type.to_string(), content_id, Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(())
+	}
+
+	pub fn is_expired(&self, content_type: ContentType, content_id: u64) -> rusqlite::Result<bool> {
+		self.connection.lock().unwrap().query_row(
+			"SELECT EXISTS (SELECT 1 FROM expired WHERE content_type = ?1 AND content_id = ?2)",
+			params![content_type.to_string(), content_id],
+			|row| row.get(0)
+		)
+	}
+
+	/// Random sample of stored content that is still believed to be online, as ids and usernames
+	pub fn sample_content(&self, content_type: ContentType, count: usize) -> rusqlite::Result<Vec<(u64, String)>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("
+			SELECT content_id, username FROM content
+			WHERE content_type = ?1
+			AND NOT EXISTS (SELECT 1 FROM expired WHERE expired.content_type = content.content_type AND expired.content_id = content.content_id)
+			AND NOT EXISTS (SELECT 1 FROM deleted WHERE deleted.content_type = content.content_type AND deleted.content_id = content.content_id)
+			ORDER BY random()
+			LIMIT ?2
+		")?;
+
+		let sample = statement.query_map(params![content_type.to_string(), count], |row| Ok((row.get(0)?, row.get(1)?)))?
+			.collect();
+
+		sample
+	}
+
+	/// Flags content that was deleted from the platform without an expiry message
+	pub fn mark_deleted(&self, content_type: ContentType, content_id: u64) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute(
+			"INSERT OR IGNORE INTO deleted (content_type, content_id, deleted_at) VALUES (?1, ?2, ?3)",
+			params![content_type.to_string(), content_id, Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(())
+	}
+
+	pub fn event_totals(&self) -> rusqlite::Result<HashMap<String, EventCounts>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("SELECT username, content_type, count FROM event_counts")?;
+		let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, u64>(2)?)))?;
+
+		let mut totals: HashMap<String, EventCounts> = HashMap::new();
+		for row in rows {
+			let (username, content_type, count) = row?;
+			if let Ok(content_type) = ContentType::from_str(&content_type) {
+				totals.entry(username).or_default().add(content_type, count);
+			}
+		}
+
+		Ok(totals)
+	}
+
+	/// Downloads and bytes per creator and day, oldest first
+	pub fn download_history(&self) -> rusqlite::Result<Vec<DailyDownloads>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare(
+			"SELECT substr(downloaded_at, 1, 10) AS day, username, COUNT(*), SUM(bytes) FROM media
+			GROUP BY day, username
+			ORDER BY day, username"
+		)?;
+
+		statement.query_map([], |row| Ok(DailyDownloads {
+			day: row.get(0)?,
+			username: row.get(1)?,
+			downloads: row.get(2)?,
+			bytes: row.get(3)?,
+		}))?
+		.collect()
+	}
+
+	/// Logs the outcome of a download attempt, failed ones included
+	pub fn record_download_result(&self, username: &str, success: bool, retries: u32) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute(
+			"INSERT INTO download_log (username, success, retries, finished_at) VALUES (?1, ?2, ?3, ?4)",
+			params![username, success, retries, Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(())
+	}
+
+	/// Finished and failed downloads per day, oldest first
+	pub fn failure_history(&self) -> rusqlite::Result<Vec<DailyFailures>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare(
+			"SELECT substr(finished_at, 1, 10) AS day, SUM(success), SUM(NOT success) FROM download_log
+			GROUP BY day
+			ORDER BY day"
+		)?;
+
+		statement.query_map([], |row| Ok(DailyFailures {
+			day: row.get(0)?,
+			downloads: row.get(1)?,
+			failures: row.get(2)?,
+		}))?
+		.collect()
+	}
+
+	/// Stores payments that weren't stored yet
+	pub fn record_transactions(&self, transactions: &[Transaction]) -> rusqlite::Result<()> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare(
+			"INSERT OR IGNORE INTO transactions (created_at, amount, status, description, username, ppv) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+		)?;
+
+		for transaction in transactions {
+			statement.execute(params![
+				transaction.created_at.to_rfc3339(),
+				transaction.amount,
+				transaction.status,
+				transaction.description,
+				transaction.user.as_ref().map(|user| &user.username),
+				transaction.is_ppv()
+			])?;
+		}
+
+		Ok(())
+	}
+
+	/// Dollars spent on PPV messages and posts per creator, most first. Failed payments don't count
+	pub fn ppv_spend(&self) -> rusqlite::Result<Vec<(String, f64)>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare(
+			"SELECT COALESCE(username, ''), SUM(amount) AS spent FROM transactions
+			WHERE ppv AND lower(status) NOT IN ('failed', 'error')
+			GROUP BY username
+			ORDER BY spent DESC"
+		)?;
+
+		statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+		.collect()
+	}
+
+	/// Events received per creator since `since`
+	pub fn events_since(&self, since: DateTime<Utc>) -> rusqlite::Result<HashMap<String, EventCounts>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare(
+			"SELECT username, content_type, COUNT(*) FROM event_log WHERE received_at >= ?1 GROUP BY username, content_type"
+		)?;
+		let rows = statement.query_map([since.to_rfc3339()], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, u64>(2)?)))?;
+
+		let mut totals: HashMap<String, EventCounts> = HashMap::new();
+		for row in rows {
+			let (username, content_type, count) = row?;
+			if let Ok(content_type) = ContentType::from_str(&content_type) {
+				totals.entry(username).or_default().add(content_type, count);
+			}
+		}
+
+		Ok(totals)
+	}
+
+	/// Number and size of the media downloaded since `since`, or ever if `None`
+	pub fn downloads_since(&self, since: Option<DateTime<Utc>>) -> rusqlite::Result<(u64, u64)> {
+		let since = since.map_or_else(String::new, |since| since.to_rfc3339());
+		self.connection.lock().unwrap().query_row(
+			"SELECT COUNT(*), COALESCE(SUM(bytes), 0) FROM media WHERE downloaded_at >= ?1",
+			[since],
+			|row| Ok((row.get(0)?, row.get(1)?))
+		)
+	}
+
+	/// Content type, ID, creator and text of all content with text
+	pub fn content(&self) -> rusqlite::Result<Vec<(String, u64, String, String)>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("SELECT content_type, content_id, username, text FROM content WHERE text != ''")?;
+		let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+		rows.collect()
+	}
+
+	pub fn record_pending(&self, work: &PendingWork) -> rusqlite::Result<()> {
+		let serialized = serde_json::to_string(work).map_err(|err| rusqlite::Error::ToSqlConversionFailure(err.into()))?;
+		self.connection.lock().unwrap().execute(
+			"INSERT OR REPLACE INTO pending_work (key, work, queued_at) VALUES (?1, ?2, ?3)",
+			params![work.key(), serialized, Utc::now().to_rfc3339()]
+		)?;
+
+		Ok(())
+	}
+
+	pub fn clear_pending(&self, work: &PendingWork) -> rusqlite::Result<()> {
+		self.connection.lock().unwrap().execute("DELETE FROM pending_work WHERE key = ?1", [work.key()])?;
+		Ok(())
+	}
+
+	/// Work `account` left unfinished when the application last stopped, oldest first. `None` is the main account
+	pub fn pending_work(&self, account: Option<&str>) -> rusqlite::Result<Vec<PendingWork>> {
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare("SELECT work FROM pending_work ORDER BY queued_at")?;
+		let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+
+		let mut work = Vec::new();
+		for row in rows {
+			match serde_json::from_str(&row?) {
+				Ok(pending) if PendingWork::account(&pending) == account => work.push(pending),
+				Ok(_) => (),
+				Err(err) => log::warn!("Skipping pending work that could not be read: {err}")
+			}
+		}
+
+		Ok(work)
+	}
+}
@@ -0,0 +1,69 @@
+use log::*;
+use futures::{StreamExt, TryStreamExt};
+use of_client::content::{Content, ContentType};
+use of_daemon::structs::{Chat, Message, Notification, TaggedMessage};
+use serde_json::Value;
+use crate::{handlers::Context, settings::CatchUpSettings};
+
+impl Context {
+	pub(crate) fn is_known(&self, content_type: ContentType, content_id: u64) -> bool {
+		self.catalog.has_content(content_type, content_id)
+			.inspect_err(|err| error!("Error reading archived content: {err}"))
+			.unwrap_or(true)
+	}
+
+	async fn catch_up_chats(&self, settings: &CatchUpSettings) {
+		let Ok(user_ids) = self.client.get_unread_chats(settings.chats).await else { return };
+
+		for user_id in user_ids {
+			let Ok(messages) = self.client.get_chat_messages(user_id, None).take(settings.messages).try_collect::<Vec<_>>().await else { continue };
+			let missed = messages.into_iter()
+				.filter(|message| message.from_user.id == user_id && !self.is_known(ContentType::Chats, message.content.id()))
+				.collect::<Vec<_>>();
+
+			if missed.is_empty() { continue }
+			let Ok(user) = self.client.get_user(user_id).await else { continue };
+
+			info!("Catching up on {} messages from {}", missed.len(), user.username);
+			// Oldest first, as they would have arrived
+			for message in missed.into_iter().rev() {
+				self.dispatch(Message::Tagged(TaggedMessage::Api2ChatMessage(Chat::caught_up(user.clone(), message))));
+			}
+		}
+	}
+
+	async fn catch_up_notifications(&self, settings: &CatchUpSettings) {
+		let Ok(notifications) = self.client.get_notifications::<Value>(settings.notifications).await else { return };
+
+		let missed = notifications.into_iter()
+			.filter_map(|mut notification| {
+				// The REST API sends numeric IDs where the websocket sends strings
+				if let Some(id) = notification.get("id").and_then(Value::as_u64) {
+					notification["id"] = Value::String(id.to_string());
+				}
+
+				serde_json::from_value::<Notification>(notification)
+					.inspect_err(|err| debug!("Skipping notification that could not be parsed: {err}"))
+					.ok()
+			})
+			.filter(|notification| !self.is_known(ContentType::Notifications, notification.content.id()))
+			.collect::<Vec<_>>();
+
+		if missed.is_empty() { return }
+
+		info!("Catching up on {} notifications", missed.len());
+		for mut notification in missed.into_iter().rev() {
+			notification.catch_up = true;
+			self.dispatch(Message::Notification(notification));
+		}
+	}
+
+	/// Feeds unread messages and recent notifications that aren't in the catalog yet through the handlers,
+	/// to make up for what happened while the application wasn't connected
+	pub async fn catch_up(self) {
+		let Some(settings) = self.settings.read().unwrap().catch_up.clone() else { return };
+
+		self.catch_up_chats(&settings).await;
+		self.catch_up_notifications(&settings).await;
+	}
+}
@@ -0,0 +1,74 @@
+use log::*;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use zbus::{connection, interface, object_server::SignalEmitter};
+use crate::events::Event;
+use super::{Command, Controller};
+
+const NAME: &str = "org.ofnotifier";
+const PATH: &str = "/org/ofnotifier";
+
+struct NotifierInterface {
+	controller: Controller,
+}
+
+#[interface(name = "org.ofnotifier.Notifier")]
+impl NotifierInterface {
+	fn connect(&self) {
+		self.controller.execute(Command::Connect);
+	}
+
+	fn disconnect(&self) {
+		self.controller.execute(Command::Disconnect);
+	}
+
+	fn pause_downloads(&self) {
+		self.controller.execute(Command::PauseDownloads);
+	}
+
+	fn resume_downloads(&self) {
+		self.controller.execute(Command::ResumeDownloads);
+	}
+
+	fn mute(&self, username: String) {
+		self.controller.execute(Command::Mute(username));
+	}
+
+	fn unmute(&self, username: String) {
+		self.controller.execute(Command::Unmute(username));
+	}
+
+	fn stats(&self) -> String {
+		serde_json::to_string(&self.controller.stats()).unwrap()
+	}
+
+	#[zbus(signal)]
+	async fn event(emitter: &SignalEmitter<'_>, kind: &str, username: &str, text: &str) -> zbus::Result<()>;
+}
+
+pub async fn serve(controller: Controller, mut events: Receiver<Event>) -> zbus::Result<()> {
+	let connection = connection::Builder::session()?
+		.name(NAME)?
+		.serve_at(PATH, NotifierInterface { controller })?
+		.build()
+		.await
+		.inspect_err(|err| error!("Error registering D-Bus interface: {err}"))?;
+
+	info!("D-Bus interface registered as {NAME}");
+	let interface = connection
+		.object_server()
+		.interface::<_, NotifierInterface>(PATH)
+		.await?;
+
+	loop {
+		match events.recv().await {
+			Ok(event) => {
+				let username = event.user.as_ref().map(|user| user.username.as_str()).unwrap_or_default();
+				let text = event.text.as_deref().unwrap_or_default();
+				let _ = NotifierInterface::event(interface.signal_emitter(), event.kind.as_str(), username, text).await
+					.inspect_err(|err| warn!("Error emitting D-Bus signal: {err}"));
+			},
+			Err(RecvError::Lagged(skipped)) => warn!("D-Bus interface is lagging, skipped {skipped} events"),
+			Err(RecvError::Closed) => break Ok(()),
+		}
+	}
+}
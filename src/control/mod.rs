@@ -0,0 +1,150 @@
+pub mod rest;
+pub mod web;
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+pub mod dbus;
+#[cfg(windows)]
+pub mod pipe;
+
+use log::*;
+use std::{collections::HashMap, sync::Arc};
+use chrono::{TimeDelta, Utc};
+use of_daemon::stats::{DaemonStats, DaemonStatsSnapshot};
+use serde::{Deserialize, Serialize};
+use crate::{catalog::TaggedMedia, downloads::TransferStatus, handlers::Context, search::SearchHit, stats::{RecentEvent, StatsSnapshot}};
+
+const SEARCH_LIMIT: usize = 50;
+const RECENT_DOWNLOADS: usize = 50;
+const MAX_BACKFILL_HOURS: u64 = 24 * 30;
+
+/// Compares a token sent by a client without revealing through timing how much of it matched
+pub fn token_matches(candidate: &str, token: &str) -> bool {
+	candidate.len() == token.len() &&
+	candidate.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Commands carried out by the application's event loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppCommand {
+	Connect,
+	Disconnect,
+	Reconnect,
+	ReloadSettings,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "command", content = "argument", rename_all = "snake_case")]
+pub enum Command {
+	Connect,
+	Disconnect,
+	Reconnect,
+	ReloadSettings,
+	PauseDownloads,
+	ResumeDownloads,
+	Mute(String),
+	Unmute(String),
+	Stats,
+	RecentEvents,
+	RecentDownloads,
+	ActiveDownloads,
+	CancelDownload(u64),
+	Tags,
+	Tagged(String),
+	Search(String),
+	/// Handles posts and messages of the past hours that were missed, like after a reconnection
+	Backfill(u64),
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatsResponse {
+	pub app: StatsSnapshot,
+	pub daemon: DaemonStatsSnapshot,
+	pub downloads_paused: bool,
+	pub muted: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum Response {
+	Ok { ok: bool },
+	Stats(StatsResponse),
+	Events(Vec<RecentEvent>),
+	Transfers(Vec<TransferStatus>),
+	Tags(HashMap<String, u64>),
+	Media(Vec<TaggedMedia>),
+	Search(Vec<SearchHit>),
+}
+
+#[derive(Clone)]
+pub struct Controller {
+	context: Context,
+	daemon_stats: Arc<DaemonStats>,
+	app: Arc<dyn Fn(AppCommand) + Send + Sync>,
+}
+
+impl Controller {
+	pub fn new(context: Context, daemon_stats: Arc<DaemonStats>, app: impl Fn(AppCommand) + Send + Sync + 'static) -> Self {
+		Self { context, daemon_stats, app: Arc::new(app) }
+	}
+
+	pub fn stats(&self) -> StatsResponse {
+		StatsResponse {
+			app: self.context.stats.snapshot(),
+			daemon: self.daemon_stats.snapshot(),
+			downloads_paused: self.context.downloads_paused(),
+			muted: self.context.muted()
+		}
+	}
+
+	pub fn execute(&self, command: Command) -> Response {
+		debug!("Executing control command {command:?}");
+		match command {
+			Command::Connect => (self.app)(AppCommand::Connect),
+			Command::Disconnect => (self.app)(AppCommand::Disconnect),
+			Command::Reconnect => (self.app)(AppCommand::Reconnect),
+			Command::ReloadSettings => (self.app)(AppCommand::ReloadSettings),
+			Command::PauseDownloads => self.context.pause_downloads(true),
+			Command::ResumeDownloads => self.context.pause_downloads(false),
+			Command::Mute(username) => self.context.mute(&username),
+			Command::Unmute(username) => self.context.unmute(&username),
+			Command::Stats => return Response::Stats(self.stats()),
+			Command::RecentEvents => return Response::Events(self.context.stats.recent_events()),
+			Command::RecentDownloads => return Response::Media(
+				self.context.catalog.recent_media(RECENT_DOWNLOADS)
+				.inspect_err(|err| error!("Error reading recent downloads: {err}"))
+				.unwrap_or_default()
+			),
+			Command::ActiveDownloads => return Response::Transfers(self.context.downloads.transfers()),
+			Command::CancelDownload(id) => return Response::Ok { ok: self.context.downloads.cancel(id) },
+			Command::Tags => return Response::Tags(
+				self.context.catalog.tags()
+				.inspect_err(|err| error!("Error reading tags: {err}"))
+				.unwrap_or_default()
+			),
+			Command::Tagged(tag) => return Response::Media(
+				self.context.catalog.tagged_media(&tag)
+				.inspect_err(|err| error!("Error reading media tagged {tag}: {err}"))
+				.unwrap_or_default()
+			),
+			Command::Backfill(hours) => {
+				let configured = self.context.settings.read().unwrap().backfill.is_some();
+				if configured {
+					let since = Utc::now() - TimeDelta::hours(hours.min(MAX_BACKFILL_HOURS) as i64);
+					self.context.spawn(self.context.clone().backfill(since));
+				}
+
+				return Response::Ok { ok: configured }
+			},
+			Command::Search(query) => return Response::Search(
+				self.context.search
+				.as_ref()
+				.and_then(|search| search.search(&query, SEARCH_LIMIT)
+					.inspect_err(|err| error!("Error searching for {query}: {err}"))
+					.ok()
+				)
+				.unwrap_or_default()
+			),
+		}
+
+		Response::Ok { ok: true }
+	}
+}
@@ -0,0 +1,59 @@
+use log::*;
+use std::io;
+use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::windows::named_pipe::{ClientOptions, NamedPipeServer, ServerOptions}};
+use super::{Command, Controller};
+
+pub const PIPE_NAME: &str = r"\\.\pipe\of-notifier";
+
+pub async fn serve(controller: Controller) -> io::Result<()> {
+	let mut server = ServerOptions::new()
+		.first_pipe_instance(true)
+		.create(PIPE_NAME)
+		.inspect_err(|err| error!("Error creating named pipe {PIPE_NAME}: {err}"))?;
+
+	info!("Control pipe listening on {PIPE_NAME}");
+	loop {
+		server.connect().await?;
+		let connected = server;
+		server = ServerOptions::new().create(PIPE_NAME)?;
+
+		tokio::spawn({
+			let controller = controller.clone();
+			async move {
+				let _ = handle(connected, controller).await
+					.inspect_err(|err| warn!("Control pipe connection closed: {err}"));
+			}
+		});
+	}
+}
+
+async fn handle(pipe: NamedPipeServer, controller: Controller) -> io::Result<()> {
+	let (reader, mut writer) = tokio::io::split(pipe);
+	let mut lines = BufReader::new(reader).lines();
+
+	while let Some(line) = lines.next_line().await? {
+		if line.trim().is_empty() { continue }
+
+		let mut response = match serde_json::from_str::<Command>(&line) {
+			Ok(command) => serde_json::to_string(&controller.execute(command)),
+			Err(err) => serde_json::to_string(&serde_json::json!({ "error": err.to_string() }))
+		}?;
+
+		response.push('\n');
+		writer.write_all(response.as_bytes()).await?;
+	}
+
+	Ok(())
+}
+
+pub async fn forward(command: &str) -> io::Result<String> {
+	let pipe = ClientOptions::new().open(PIPE_NAME)?;
+	let (reader, mut writer) = tokio::io::split(pipe);
+
+	writer.write_all(command.as_bytes()).await?;
+	writer.write_all(b"\n").await?;
+
+	let mut response = String::new();
+	BufReader::new(reader).read_line(&mut response).await?;
+	Ok(response)
+}
@@ -0,0 +1,64 @@
+use log::*;
+use std::{io, net::Ipv4Addr, sync::Arc};
+use axum::{extract::{Path, Query, Request, State}, http::{header, StatusCode}, middleware::{self, Next}, response::{IntoResponse, Response}, routing::{delete, get, post}, Json, Router};
+use tokio::net::TcpListener;
+use serde::Deserialize;
+use crate::settings::RestSettings;
+use super::{token_matches, Command, Controller};
+
+#[derive(Deserialize)]
+struct SearchParams { q: String }
+
+#[derive(Deserialize)]
+struct BackfillParams { hours: u64 }
+
+pub async fn serve(controller: Controller, settings: RestSettings) -> io::Result<()> {
+	if settings.token.is_empty() {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "control API token must not be empty"))
+			.inspect_err(|err| error!("{err}"));
+	}
+
+	let app = Router::new()
+		.route("/connect", post(|State(controller): State<Controller>| execute(controller, Command::Connect)))
+		.route("/disconnect", post(|State(controller): State<Controller>| execute(controller, Command::Disconnect)))
+		.route("/reconnect", post(|State(controller): State<Controller>| execute(controller, Command::Reconnect)))
+		.route("/settings/reload", post(|State(controller): State<Controller>| execute(controller, Command::ReloadSettings)))
+		.route("/downloads/pause", post(|State(controller): State<Controller>| execute(controller, Command::PauseDownloads)))
+		.route("/downloads/resume", post(|State(controller): State<Controller>| execute(controller, Command::ResumeDownloads)))
+		.route("/creators/{username}/mute",
+			post(|State(controller): State<Controller>, Path(username): Path<String>| execute(controller, Command::Mute(username)))
+			.delete(|State(controller): State<Controller>, Path(username): Path<String>| execute(controller, Command::Unmute(username)))
+		)
+		.route("/stats", get(|State(controller): State<Controller>| execute(controller, Command::Stats)))
+		.route("/events", get(|State(controller): State<Controller>| execute(controller, Command::RecentEvents)))
+		.route("/downloads", get(|State(controller): State<Controller>| execute(controller, Command::RecentDownloads)))
+		.route("/downloads/active", get(|State(controller): State<Controller>| execute(controller, Command::ActiveDownloads)))
+		.route("/downloads/active/{id}", delete(|State(controller): State<Controller>, Path(id): Path<u64>| execute(controller, Command::CancelDownload(id))))
+		.route("/tags", get(|State(controller): State<Controller>| execute(controller, Command::Tags)))
+		.route("/tags/{tag}", get(|State(controller): State<Controller>, Path(tag): Path<String>| execute(controller, Command::Tagged(tag))))
+		.route("/backfill", post(|State(controller): State<Controller>, Query(params): Query<BackfillParams>| execute(controller, Command::Backfill(params.hours))))
+		.route("/search", get(|State(controller): State<Controller>, Query(params): Query<SearchParams>| execute(controller, Command::Search(params.q))))
+		.route_layer(middleware::from_fn_with_state(Arc::new(settings.token), authorize))
+		.with_state(controller);
+
+	let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, settings.port)).await
+		.inspect_err(|err| error!("Error binding control API to port {}: {err}", settings.port))?;
+
+	info!("Control API listening on {}", listener.local_addr()?);
+	axum::serve(listener, app).await
+}
+
+async fn execute(controller: Controller, command: Command) -> impl IntoResponse {
+	Json(controller.execute(command))
+}
+
+async fn authorize(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+	let authorized = request.headers()
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+		.is_some_and(|value| token_matches(value, &token));
+
+	if authorized { next.run(request).await }
+	else { StatusCode::UNAUTHORIZED.into_response() }
+}
@@ -0,0 +1,181 @@
+use log::*;
+use std::{io, net::Ipv4Addr, path::Path as FsPath, sync::Arc};
+use axum::{extract::{Path, Request, State}, http::{header, HeaderValue, StatusCode}, middleware::{self, Next}, response::{Html, IntoResponse, Redirect, Response}, routing::{get, post}, Router};
+use chrono::Local;
+use cookie::Cookie;
+use tokio::{fs as tfs, net::TcpListener};
+use crate::{dashboard::{escape, format_bytes}, settings::WebSettings};
+use super::{token_matches, Command, Controller, RECENT_DOWNLOADS};
+
+const TOKEN_COOKIE: &str = "of_notifier_token";
+const RECENT_EVENTS: usize = 20;
+const REFRESH_SECONDS: u32 = 30;
+
+/// Serves a page with the connection status, recent events and downloads, mirroring the tray menu
+pub async fn serve(controller: Controller, settings: WebSettings) -> io::Result<()> {
+	if settings.token.is_empty() {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "web dashboard token must not be empty"))
+			.inspect_err(|err| error!("{err}"));
+	}
+
+	let app = Router::new()
+		.route("/", get(|State(controller): State<Controller>| async move { Html(render(&controller)) }))
+		.route("/media/{media_id}", get(media))
+		.route("/connect", post(|State(controller): State<Controller>| execute(controller, Command::Connect)))
+		.route("/disconnect", post(|State(controller): State<Controller>| execute(controller, Command::Disconnect)))
+		.route("/reconnect", post(|State(controller): State<Controller>| execute(controller, Command::Reconnect)))
+		.route("/settings/reload", post(|State(controller): State<Controller>| execute(controller, Command::ReloadSettings)))
+		.route("/downloads/pause", post(|State(controller): State<Controller>| execute(controller, Command::PauseDownloads)))
+		.route("/downloads/resume", post(|State(controller): State<Controller>| execute(controller, Command::ResumeDownloads)))
+		.route_layer(middleware::from_fn_with_state(Arc::new(settings.token), authorize))
+		.with_state(controller);
+
+	let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, settings.port)).await
+		.inspect_err(|err| error!("Error binding web dashboard to port {}: {err}", settings.port))?;
+
+	info!("Web dashboard listening on http://{}", listener.local_addr()?);
+	axum::serve(listener, app).await
+}
+
+async fn execute(controller: Controller, command: Command) -> Redirect {
+	controller.execute(command);
+	Redirect::to("/")
+}
+
+/// A downloaded file, for the thumbnails of recent downloads
+async fn media(State(controller): State<Controller>, Path(media_id): Path<u64>) -> Response {
+	let Ok(Some(path)) = controller.context.catalog.media_path(media_id) else { return StatusCode::NOT_FOUND.into_response() };
+	let Ok(data) = tfs::read(&path).await else { return StatusCode::NOT_FOUND.into_response() };
+
+	([(header::CONTENT_TYPE, content_type(&path))], data).into_response()
+}
+
+fn content_type(path: &FsPath) -> &'static str {
+	match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+		Some("jpg" | "jpeg") => "image/jpeg",
+		Some("png") => "image/png",
+		Some("gif") => "image/gif",
+		Some("webp") => "image/webp",
+		Some("mp4") => "video/mp4",
+		_ => "application/octet-stream"
+	}
+}
+
+fn is_image(path: &FsPath) -> bool {
+	content_type(path).starts_with("image/")
+}
+
+fn render(controller: &Controller) -> String {
+	let context = &controller.context;
+	let connected = context.stats.is_connected();
+	let paused = context.downloads_paused();
+
+	let events = context.stats.recent_events()
+		.into_iter()
+		.take(RECENT_EVENTS)
+		.map(|event| format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+			event.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+			escape(&event.username),
+			escape(&event.content_type)
+		))
+		.collect::<String>();
+
+	let downloads = context.catalog.recent_media(RECENT_DOWNLOADS)
+	.inspect_err(|err| error!("Error reading recent downloads: {err}"))
+	.unwrap_or_default()
+	.into_iter()
+	.map(|media| {
+		let name = media.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+		let preview = match is_image(&media.path) {
+			true => format!(r#"<img src="/media/{}" loading="lazy" alt="{}">"#, media.media_id, escape(&name)),
+			false => format!("<div class=\"file\">{}</div>", escape(&name))
+		};
+
+		format!("<figure>{preview}<figcaption>{} · {} · {}<br>{}</figcaption></figure>",
+			escape(&media.username),
+			escape(&media.content_type),
+			format_bytes(media.bytes),
+			escape(&media.downloaded_at.get(..16).unwrap_or(&media.downloaded_at).replace('T', " "))
+		)
+	})
+	.collect::<String>();
+
+	format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{REFRESH_SECONDS}">
+<title>OF Notifier</title>
+<style>
+body {{ font-family: Segoe UI, sans-serif; margin: 2em; color: #222; }}
+form {{ display: inline; }}
+button {{ margin-right: 8px; }}
+td {{ padding: 2px 16px 2px 0; }}
+.status {{ font-weight: bold; color: {status_color}; }}
+.downloads {{ display: flex; flex-wrap: wrap; gap: 12px; }}
+figure {{ margin: 0; width: 160px; font-size: 12px; }}
+figure img, .file {{ width: 160px; height: 160px; object-fit: cover; background: #eee; overflow: hidden; word-break: break-all; }}
+</style>
+</head>
+<body>
+<h1>OF Notifier</h1>
+<p><span class="status">{status}</span>{paused_note}</p>
+<p>
+<form method="post" action="{connection_action}"><button>{connection_label}</button></form>
+<form method="post" action="/reconnect"><button>Reconnect</button></form>
+<form method="post" action="/settings/reload"><button>Reload settings</button></form>
+<form method="post" action="{downloads_action}"><button>{downloads_label}</button></form>
+</p>
+<h2>Recent events</h2>
+<table>{events}</table>
+<h2>Recent downloads</h2>
+<div class="downloads">{downloads}</div>
+</body>
+</html>
+"#,
+		status_color = if connected { "#2a2" } else { "#c22" },
+		status = if connected { "Connected" } else { "Disconnected" },
+		paused_note = if paused { ", downloads are paused" } else { "" },
+		connection_action = if connected { "/disconnect" } else { "/connect" },
+		connection_label = if connected { "Disconnect" } else { "Connect" },
+		downloads_action = if paused { "/downloads/resume" } else { "/downloads/pause" },
+		downloads_label = if paused { "Resume downloads" } else { "Pause downloads" },
+	)
+}
+
+// The token is accepted from the `token` query parameter once, then from a cookie so links and forms don't need to carry it
+async fn authorize(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+	let from_query = request.uri()
+		.query()
+		.into_iter()
+		.flat_map(|query| query.split('&'))
+		.filter_map(|pair| pair.strip_prefix("token="))
+		.any(|value| token_matches(value, &token));
+
+	let from_cookie = request.headers()
+		.get_all(header::COOKIE)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(Cookie::split_parse)
+		.filter_map(Result::ok)
+		.any(|cookie| cookie.name() == TOKEN_COOKIE && token_matches(cookie.value(), &token));
+
+	if !from_query && !from_cookie {
+		return (StatusCode::UNAUTHORIZED, "Open the dashboard with ?token=<token> once").into_response();
+	}
+
+	let mut response = next.run(request).await;
+	if from_query {
+		let cookie = Cookie::build((TOKEN_COOKIE, token.as_str()))
+			.path("/")
+			.http_only(true)
+			.same_site(cookie::SameSite::Strict)
+			.build();
+
+		if let Ok(value) = HeaderValue::from_str(&cookie.to_string()) {
+			response.headers_mut().append(header::SET_COOKIE, value);
+		}
+	}
+
+	response
+}
@@ -0,0 +1,120 @@
+use log::*;
+use std::{fs, io, path::Path};
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+use crate::{auth_path, auth_profiles, settings::AuthStorage, AuthParseError};
+
+const SERVICE: &str = "of-notifier";
+const KEYRING: &str = "keyring";
+
+/// Left in place of an auth file whose contents were moved to the credential store,
+/// so profiles can still be listed without exposing any secrets
+#[derive(Serialize, Deserialize)]
+struct Placeholder<'a> { stored_in: &'a str }
+
+fn user(profile: Option<&str>) -> String {
+	auth_path(profile).to_string_lossy().into_owned()
+}
+
+fn entry(profile: Option<&str>) -> keyring::Result<Entry> {
+	Entry::new(SERVICE, &user(profile))
+}
+
+fn is_placeholder(data: &str) -> bool {
+	serde_json::from_str::<Placeholder>(data).is_ok_and(|placeholder| placeholder.stored_in == KEYRING)
+}
+
+fn decode(secret: Vec<u8>) -> Result<String, AuthParseError> {
+	String::from_utf8(secret).map_err(|err| keyring::Error::BadEncoding(err.into_bytes()).into())
+}
+
+fn get_secret(profile: Option<&str>) -> Result<String, AuthParseError> {
+	decode(entry(profile)?.get_secret()?)
+}
+
+/// Reads the secret of `profile` back from storage that survives a reboot. On Linux the default store
+/// answers from the kernel keyring, which doesn't, so the Secret Service is asked directly
+#[cfg(target_os = "linux")]
+fn get_persisted_secret(profile: Option<&str>) -> Result<String, AuthParseError> {
+	let credential = keyring::secret_service::SsCredential::new_with_target(None, SERVICE, &user(profile))?;
+	decode(Entry::new_with_credential(Box::new(credential)).get_secret()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_persisted_secret(profile: Option<&str>) -> Result<String, AuthParseError> {
+	get_secret(profile)
+}
+
+/// Contents of the auth file of `profile`, read from the credential store if they were moved there
+pub fn read(profile: Option<&str>) -> Result<String, AuthParseError> {
+	let data = fs::read_to_string(auth_path(profile))?;
+	if is_placeholder(&data) { get_secret(profile) }
+	else { Ok(data) }
+}
+
+/// Replaces the contents of the auth file of `profile`, wherever they are stored
+pub fn write(profile: Option<&str>, data: &str) -> Result<(), AuthParseError> {
+	let path = auth_path(profile);
+	if is_placeholder(&fs::read_to_string(&path)?) {
+		entry(profile)?.set_secret(data.as_bytes())?;
+	} else {
+		fs::write(path, data)?;
+	}
+
+	Ok(())
+}
+
+/// Moves the auth file of `profile` into the credential store, returns whether anything was moved
+pub fn migrate(profile: Option<&str>) -> Result<bool, AuthParseError> {
+	let path = auth_path(profile);
+	let data = fs::read_to_string(&path)?;
+	if is_placeholder(&data) { return Ok(false) }
+
+	entry(profile)?.set_secret(data.as_bytes())?;
+	// Only drop the plaintext copy once the stored secret is known to be readable after a reboot
+	if get_persisted_secret(profile)? != data {
+		return Err(keyring::Error::NoEntry.into());
+	}
+
+	fs::write(path, serde_json::to_string_pretty(&Placeholder { stored_in: KEYRING })?)?;
+	Ok(true)
+}
+
+/// Moves the auth data of `profile` from the credential store back into its file, returns whether anything was moved
+pub fn restore(profile: Option<&str>) -> Result<bool, AuthParseError> {
+	let path = auth_path(profile);
+	if !is_placeholder(&fs::read_to_string(&path)?) { return Ok(false) }
+
+	fs::write(path, get_secret(profile)?)?;
+	let _ = entry(profile)?.delete_credential()
+		.inspect_err(|err| warn!("Error removing stored credentials: {err}"));
+
+	Ok(true)
+}
+
+/// Writes a plaintext copy of the auth data of `profile` to `path`
+pub fn export(profile: Option<&str>, path: &Path) -> Result<(), AuthParseError> {
+	fs::write(path, read(profile)?)?;
+	Ok(())
+}
+
+/// Moves the auth data of every profile to where `storage` says it should be
+pub fn apply(storage: AuthStorage) {
+	let profiles = auth_profiles();
+	let profiles = [None].into_iter().chain(profiles.iter().map(|profile| Some(profile.as_str())));
+
+	for profile in profiles {
+		let path = auth_path(profile);
+		let result = match storage {
+			AuthStorage::File => restore(profile),
+			AuthStorage::Keyring => migrate(profile),
+		};
+
+		match result {
+			Ok(true) => info!("Moved auth data of {path:?} to {storage:?} storage"),
+			Ok(false) => (),
+			Err(AuthParseError::IO(err)) if err.kind() == io::ErrorKind::NotFound => (),
+			Err(err) => error!("Error moving auth data of {path:?} to {storage:?} storage: {err}"),
+		}
+	}
+}
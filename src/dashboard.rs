@@ -0,0 +1,196 @@
+use std::{collections::BTreeMap, fmt::Write, fs, path::{Path, PathBuf}};
+use chrono::{Duration, Local, Utc};
+use crate::{catalog::Catalog, stats::Stats};
+
+const HISTORY_DAYS: i64 = 30;
+const TOP_CREATORS: usize = 15;
+
+const BAR_HEIGHT: usize = 22;
+const LABEL_WIDTH: usize = 160;
+const CHART_WIDTH: usize = 720;
+
+pub(crate) fn escape(text: &str) -> String {
+	text
+	.replace('&', "&amp;")
+	.replace('<', "&lt;")
+	.replace('>', "&gt;")
+	.replace('"', "&quot;")
+}
+
+pub(crate) fn format_bytes(bytes: u64) -> String {
+	match bytes {
+		b if b >= 1 << 30 => format!("{:.2} GB", b as f64 / (1u64 << 30) as f64),
+		b if b >= 1 << 20 => format!("{:.1} MB", b as f64 / (1u64 << 20) as f64),
+		b => format!("{:.0} KB", b as f64 / 1024.0)
+	}
+}
+
+/// Horizontal bars, one per row, scaled to the largest value
+fn bar_chart(rows: &[(String, u64)], label: impl Fn(u64) -> String) -> String {
+	let max = rows.iter().map(|(_, value)| *value).max().unwrap_or(0).max(1);
+	let height = rows.len() * BAR_HEIGHT;
+	let mut svg = format!(r#"<svg width="{CHART_WIDTH}" height="{height}">"#);
+
+	for (i, (name, value)) in rows.iter().enumerate() {
+		let y = i * BAR_HEIGHT;
+		let width = ((CHART_WIDTH - LABEL_WIDTH - 100) as f64 * *value as f64 / max as f64).max(1.0);
+		let _ = write!(svg,
+			r#"<text x="{}" y="{}" text-anchor="end">{}</text><rect x="{LABEL_WIDTH}" y="{}" width="{width:.0}" height="{}"/><text x="{}" y="{}">{}</text>"#,
+			LABEL_WIDTH - 8, y + 15, escape(name),
+			y + 3, BAR_HEIGHT - 6,
+			LABEL_WIDTH as f64 + width + 6.0, y + 15, label(*value)
+		);
+	}
+
+	svg.push_str("</svg>");
+	svg
+}
+
+fn format_dollars(cents: u64) -> String {
+	format!("${}.{:02}", cents / 100, cents % 100)
+}
+
+/// Vertical bars of a value on each of the last days, days missing from `values` count as 0
+fn daily_chart<'a>(values: impl IntoIterator<Item = (&'a str, u64)>, label: impl Fn(u64) -> String) -> String {
+	let today = Utc::now().date_naive();
+	let mut days: BTreeMap<String, u64> = (0..HISTORY_DAYS)
+		.map(|offset| ((today - Duration::days(HISTORY_DAYS - 1 - offset)).to_string(), 0))
+		.collect();
+
+	for (day, value) in values {
+		if let Some(total) = days.get_mut(day) { *total += value }
+	}
+
+	let max = days.values().copied().max().unwrap_or(0).max(1);
+	let column = CHART_WIDTH / days.len();
+	let mut svg = format!(r#"<svg width="{CHART_WIDTH}" height="220">"#);
+
+	for (i, (day, value)) in days.iter().enumerate() {
+		let height = 180.0 * *value as f64 / max as f64;
+		let _ = write!(svg,
+			r#"<rect x="{}" y="{:.0}" width="{}" height="{height:.0}"><title>{day}: {}</title></rect>"#,
+			i * column + 2, 190.0 - height, column - 4, label(*value)
+		);
+
+		if i % 7 == 0 {
+			let _ = write!(svg, r#"<text x="{}" y="210">{}</text>"#, i * column, &day[5..]);
+		}
+	}
+
+	svg.push_str("</svg>");
+	svg
+}
+
+/// A self-contained page summarizing the catalog and this session's statistics
+pub fn render(catalog: &Catalog, stats: &Stats) -> anyhow::Result<String> {
+	let history = catalog.download_history()?;
+
+	let mut creators: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+	for entry in &history {
+		let totals = creators.entry(entry.username.as_str()).or_default();
+		totals.0 += entry.downloads;
+		totals.1 += entry.bytes;
+	}
+
+	let mut by_bytes = creators.iter()
+		.map(|(username, (_, bytes))| (username.to_string(), *bytes))
+		.collect::<Vec<_>>();
+	by_bytes.sort_by(|a, b| b.1.cmp(&a.1));
+	by_bytes.truncate(TOP_CREATORS);
+
+	let mut by_events = catalog.event_totals()?
+		.into_iter()
+		.map(|(username, counts)| (username, counts.total()))
+		.collect::<Vec<_>>();
+	by_events.sort_by(|a, b| b.1.cmp(&a.1));
+	by_events.truncate(TOP_CREATORS);
+
+	let mut by_spend = catalog.ppv_spend()?
+		.into_iter()
+		.map(|(username, dollars)| (username, (dollars * 100.0).round() as u64))
+		.collect::<Vec<_>>();
+	let total_spend: u64 = by_spend.iter().map(|(_, cents)| cents).sum();
+	by_spend.truncate(TOP_CREATORS);
+
+	let failure_history = catalog.failure_history()?;
+	let attempts: u64 = failure_history.iter().map(|entry| entry.downloads + entry.failures).sum();
+	let failed: u64 = failure_history.iter().map(|entry| entry.failures).sum();
+
+	let total_downloads: u64 = creators.values().map(|(downloads, _)| downloads).sum();
+	let total_bytes: u64 = creators.values().map(|(_, bytes)| bytes).sum();
+	let session = stats.snapshot();
+
+	Ok(format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>OF Notifier statistics</title>
+<style>
+body {{ font-family: Segoe UI, sans-serif; margin: 2em; color: #222; }}
+svg text {{ font-size: 12px; fill: #444; }}
+rect {{ fill: #00aff0; }}
+td {{ padding: 2px 16px 2px 0; }}
+</style>
+</head>
+<body>
+<h1>Statistics</h1>
+<p>Generated {generated}</p>
+<table>
+<tr><td>Downloaded media</td><td>{total_downloads}</td></tr>
+<tr><td>Downloaded size</td><td>{total_size}</td></tr>
+<tr><td>Creators</td><td>{creator_count}</td></tr>
+<tr><td>Spent on PPV</td><td>{total_spend}</td></tr>
+<tr><td>Failed downloads</td><td>{failed} ({error_rate:.1}%)</td></tr>
+<tr><td>Failed downloads this session</td><td>{failures} ({failure_rate:.1}%)</td></tr>
+<tr><td>Stalled downloads this session</td><td>{stalled}</td></tr>
+<tr><td>Events that failed to process this session</td><td>{handler_failures}</td></tr>
+</table>
+<h2>Downloaded per day</h2>
+{daily}
+<h2>Failed downloads per day</h2>
+{daily_failures}
+<h2>Downloaded per creator</h2>
+{by_bytes}
+<h2>PPV spend per creator</h2>
+{by_spend}
+<h2>Events per creator</h2>
+{by_events}
+</body>
+</html>
+"#,
+		generated = Local::now().format("%Y-%m-%d %H:%M"),
+		total_size = format_bytes(total_bytes),
+		creator_count = creators.len(),
+		total_spend = format_dollars(total_spend),
+		error_rate = if attempts == 0 { 0.0 } else { failed as f64 / attempts as f64 * 100.0 },
+		failures = session.downloads.failures,
+		failure_rate = session.downloads.failure_rate() * 100.0,
+		stalled = session.stalled_downloads,
+		handler_failures = session.handler_failures,
+		daily = daily_chart(history.iter().map(|entry| (entry.day.as_str(), entry.bytes)), format_bytes),
+		daily_failures = daily_chart(failure_history.iter().map(|entry| (entry.day.as_str(), entry.failures)), |count| count.to_string()),
+		by_bytes = bar_chart(&by_bytes, format_bytes),
+		by_spend = bar_chart(&by_spend, format_dollars),
+		by_events = bar_chart(&by_events, |count| count.to_string()),
+	))
+}
+
+/// Downloads and bytes per creator and day, one row each
+pub fn history_csv(catalog: &Catalog) -> anyhow::Result<String> {
+	let mut csv = String::from("day,username,downloads,bytes\n");
+	for entry in catalog.download_history()? {
+		let _ = writeln!(csv, "{},\"{}\",{},{}", entry.day, entry.username.replace('"', "\"\""), entry.downloads, entry.bytes);
+	}
+
+	Ok(csv)
+}
+
+/// Writes the dashboard and the CSV export into `dir`, returns the path of the dashboard
+pub fn export(catalog: &Catalog, stats: &Stats, dir: &Path) -> anyhow::Result<PathBuf> {
+	fs::create_dir_all(dir)?;
+	fs::write(dir.join("downloads.csv"), history_csv(catalog)?)?;
+
+	let path = dir.join("dashboard.html");
+	fs::write(&path, render(catalog, stats)?)?;
+	Ok(path)
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use chrono::{DateTime, Utc};
+use of_client::user::Me;
+use reqwest::{header, StatusCode};
+use serde::Deserialize;
+use crate::{auth_path, get_profile_auth_params, init_client_with, settings::NetworkSettings, AuthParseError};
+
+const DYNAMIC_RULES_COMMITS: &str = "https://api.github.com/repos/deviint/onlyfans-dynamic-rules/commits?path=dynamicRules.json&per_page=1";
+const DYNAMIC_RULES_MAX_AGE_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status { Ok, Warning, Failed }
+
+#[derive(Debug)]
+pub struct Check {
+	pub name: &'static str,
+	pub status: Status,
+	pub detail: String,
+	pub remedy: Option<String>,
+}
+
+impl Check {
+	fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+		Self { name, status: Status::Ok, detail: detail.into(), remedy: None }
+	}
+
+	fn warning(name: &'static str, detail: impl Into<String>, remedy: impl Into<String>) -> Self {
+		Self { name, status: Status::Warning, detail: detail.into(), remedy: Some(remedy.into()) }
+	}
+
+	fn failed(name: &'static str, detail: impl Into<String>, remedy: impl Into<String>) -> Self {
+		Self { name, status: Status::Failed, detail: detail.into(), remedy: Some(remedy.into()) }
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+	pub checks: Vec<Check>,
+}
+
+impl Report {
+	pub fn problems(&self) -> usize {
+		self.checks.iter().filter(|check| check.status != Status::Ok).count()
+	}
+
+	pub fn is_healthy(&self) -> bool {
+		self.checks.iter().all(|check| check.status != Status::Failed)
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Authentication report")?;
+		for (i, check) in self.checks.iter().enumerate() {
+			let status = match check.status {
+				Status::Ok => "OK",
+				Status::Warning => "WARN",
+				Status::Failed => "FAIL",
+			};
+
+			writeln!(f, "{}. [{status}] {}: {}", i + 1, check.name, check.detail)?;
+			if let Some(remedy) = &check.remedy {
+				writeln!(f, "   -> {remedy}")?;
+			}
+		}
+
+		match self.problems() {
+			0 => write!(f, "No problems found"),
+			n => write!(f, "{n} problem(s) found, work through the steps above in order"),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct ApiError { error: ApiErrorMessage }
+
+#[derive(Deserialize)]
+struct ApiErrorMessage { message: String }
+
+#[derive(Deserialize)]
+struct Commit { commit: CommitInner }
+
+#[derive(Deserialize)]
+struct CommitInner { committer: Committer }
+
+#[derive(Deserialize)]
+struct Committer { date: DateTime<Utc> }
+
+/// Walks through everything that commonly breaks authentication and explains how to fix it
+pub async fn check_auth(profile: Option<&str>, network: &NetworkSettings) -> Report {
+	let mut report = Report::default();
+	let path = auth_path(profile);
+
+	let params = match get_profile_auth_params(profile) {
+		Ok(params) => {
+			report.checks.push(Check::ok("Auth file", format!("{path:?} contains a session and user id")));
+			params
+		},
+		Err(err) => {
+			let remedy = match &err {
+				AuthParseError::IO(_) => format!("Create {path:?} from the auth.json template in the release archive"),
+				AuthParseError::Parse(_) => "Make sure the file is valid JSON with non-empty \"cookie\", \"x_bc\" and \"user_agent\" fields inside an \"auth\" object".to_string(),
+				AuthParseError::CookieParse(_) => "Copy the value of the Cookie request header verbatim from your browser's developer tools".to_string(),
+				AuthParseError::IncompleteCookie(field) => format!("The \"{field}\" cookie is only set while logged in. Log in to OnlyFans, then copy the Cookie header of a request to onlyfans.com/api2"),
+				AuthParseError::Keyring(_) => "The auth data was moved to the OS credential store but can't be read back. Restore the auth file from a backup made with --export-auth, or fill it out again".to_string(),
+			};
+
+			report.checks.push(Check::failed("Auth file", format!("{path:?} is unusable: {err}"), remedy));
+			return report;
+		}
+	};
+
+	report.checks.push(
+		if params.x_bc.len() == 40 && params.x_bc.chars().all(|c| c.is_ascii_hexdigit()) {
+			Check::ok("x-bc", "Has the expected format")
+		} else {
+			Check::warning("x-bc", format!("Expected 40 hexadecimal characters, found {:?}", params.x_bc),
+				"Copy the x-bc request header of a request to onlyfans.com/api2, it is not a cookie")
+		}
+	);
+
+	let user_agent = &params.user_agent;
+	report.checks.push(
+		if user_agent.trim() != user_agent || !user_agent.starts_with("Mozilla/5.0") {
+			Check::warning("User agent", format!("{user_agent:?} does not look like a browser user agent"),
+				"Copy the User-Agent request header from the same browser the cookie was copied from")
+		} else {
+			Check::ok("User agent", "Looks like a browser user agent")
+		}
+	);
+
+	report.checks.push(check_dynamic_rules().await);
+
+	let client = match init_client_with(network, profile) {
+		Ok(client) => client,
+		Err(err) => {
+			report.checks.push(Check::failed("Session", format!("The HTTP client could not be created: {err}"), "Check the network section of settings.json"));
+			return report;
+		}
+	};
+
+	let check = match client.get_unchecked("https://onlyfans.com/api2/v2/users/me").await {
+		Err(err) => Check::failed("Session", format!("Request failed: {err}"), "Check your internet connection and proxy settings"),
+		Ok(response) => {
+			let status = response.status();
+			let body = response.text().await.unwrap_or_default();
+
+			match status {
+				StatusCode::OK => match serde_json::from_str::<Me>(&body) {
+					Ok(me) if me.id.to_string() != params.user_id => Check::failed("Session",
+						format!("Wrong user: logged in as {} ({}), but the auth_id cookie is {}", me.username, me.id, params.user_id),
+						"The cookie mixes two sessions. Log out everywhere, log in again and copy the whole Cookie header at once"),
+					Ok(me) => Check::ok("Session", format!("Logged in as {} (@{})", me.name, me.username)),
+					Err(_) => Check::failed("Session", "Not logged in",
+						"The session has expired. Log in again and copy a fresh cookie"),
+				},
+				StatusCode::UNAUTHORIZED => Check::failed("Session", "Not logged in (401)",
+					"The session expired or the user agent doesn't match the browser the cookie came from. Log in again and copy the cookie, x-bc and user agent from the same browser"),
+				StatusCode::BAD_REQUEST | StatusCode::FORBIDDEN => {
+					let message = serde_json::from_str::<ApiError>(&body).map_or(body, |err| err.error.message);
+					Check::failed("Session", format!("OnlyFans rejected the request ({status}): {message}"),
+						"Requests are signed with the dynamic rules, so this usually means they are outdated or x-bc is wrong. Wait for the rules to be updated, or copy x-bc again")
+				},
+				status => Check::failed("Session", format!("Unexpected response ({status})"), "OnlyFans may be down, try again later"),
+			}
+		}
+	};
+
+	report.checks.push(check);
+	report
+}
+
+async fn check_dynamic_rules() -> Check {
+	let commits = reqwest::Client::new()
+		.get(DYNAMIC_RULES_COMMITS)
+		.header(header::USER_AGENT, "of-notifier")
+		.send()
+		.await
+		.and_then(|response| response.error_for_status());
+
+	let updated = match commits {
+		Ok(response) => response.text().await.ok()
+			.and_then(|body| serde_json::from_str::<Vec<Commit>>(&body).ok())
+			.and_then(|commits| commits.into_iter().next())
+			.map(|commit| commit.commit.committer.date),
+		Err(_) => None
+	};
+
+	match updated {
+		Some(date) if (Utc::now() - date).num_days() > DYNAMIC_RULES_MAX_AGE_DAYS =>
+			Check::warning("Dynamic rules", format!("Last updated {}", date.format("%Y-%m-%d")),
+				"The signing rules may be outdated. If requests are rejected, check the dynamic rules repository for updates"),
+		Some(date) => Check::ok("Dynamic rules", format!("Last updated {}", date.format("%Y-%m-%d"))),
+		None => Check::warning("Dynamic rules", "Could not determine when the rules were last updated",
+			"Make sure github.com is reachable, the rules are downloaded from there"),
+	}
+}
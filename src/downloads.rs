@@ -0,0 +1,211 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}, pin::pin, sync::{atomic::{AtomicU32, Ordering}, Mutex}, time::Instant};
+use of_client::media::MediaType;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::{select, sync::Notify};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+use crate::watchdog::Progress;
+
+/// How urgently a download should start, higher priorities start before any lower ones waiting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadPriority {
+	/// Downloads asked for in bulk, like a creator's whole profile or chat history
+	Bulk,
+	/// Videos, which take long enough to hold everything else up
+	Video,
+	/// Photos, audio and GIFs, quick to download and what notifications show first
+	Preview,
+}
+
+impl DownloadPriority {
+	pub fn of(media_type: &MediaType) -> Self {
+		match media_type {
+			MediaType::Video => Self::Video,
+			MediaType::Photo | MediaType::Audio | MediaType::Gif => Self::Preview,
+		}
+	}
+}
+
+#[derive(Error, Debug)]
+#[error("cancelled")]
+pub struct Cancelled;
+
+#[derive(Debug)]
+struct Transfer {
+	path: PathBuf,
+	priority: DownloadPriority,
+	queued: Instant,
+	/// Set once the transfer got a slot
+	started: Option<Instant>,
+	progress: Progress,
+	cancel: CancellationToken,
+}
+
+/// A download waiting for or holding a slot, as reported to the control interfaces
+#[derive(Serialize, Debug)]
+pub struct TransferStatus {
+	pub id: u64,
+	pub path: PathBuf,
+	pub priority: DownloadPriority,
+	pub active: bool,
+	pub waited_secs: u64,
+	pub running_secs: u64,
+	pub bytes: u64,
+}
+
+#[derive(Debug)]
+struct State {
+	available: usize,
+	waiting: [usize; 3],
+	next_id: u64,
+	transfers: BTreeMap<u64, Transfer>,
+}
+
+/// Bounds how many downloads run at once. Waiting downloads start by priority,
+/// their progress can be followed and each of them cancelled
+#[derive(Debug)]
+pub struct DownloadManager {
+	state: Mutex<State>,
+	changed: Notify,
+	shutdown: CancellationToken,
+}
+
+/// A download's slot, given back when dropped
+pub struct DownloadPermit<'a> {
+	manager: &'a DownloadManager,
+	id: u64,
+	progress: Progress,
+	cancel: CancellationToken,
+	retries: AtomicU32,
+}
+
+impl DownloadPermit<'_> {
+	pub fn progress(&self) -> &Progress {
+		&self.progress
+	}
+
+	/// Counts a restart of the download
+	pub fn record_retry(&self) {
+		self.retries.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// How many times the download was restarted while holding this slot
+	pub fn retries(&self) -> u32 {
+		self.retries.load(Ordering::Relaxed)
+	}
+
+	/// Completes when the download was cancelled or the application is shutting down
+	pub fn cancelled(&self) -> WaitForCancellationFuture<'_> {
+		self.cancel.cancelled()
+	}
+}
+
+impl Drop for DownloadPermit<'_> {
+	fn drop(&mut self) {
+		let mut state = self.manager.state.lock().unwrap();
+		state.transfers.remove(&self.id);
+		state.available += 1;
+		drop(state);
+		self.manager.changed.notify_waiters();
+	}
+}
+
+struct Waiting<'a> {
+	manager: &'a DownloadManager,
+	id: u64,
+	priority: DownloadPriority,
+}
+
+impl Drop for Waiting<'_> {
+	// Also runs when the download is cancelled while waiting
+	fn drop(&mut self) {
+		let mut state = self.manager.state.lock().unwrap();
+		state.waiting[self.priority as usize] -= 1;
+		if state.transfers.get(&self.id).is_some_and(|transfer| transfer.started.is_none()) {
+			state.transfers.remove(&self.id);
+		}
+		drop(state);
+		self.manager.changed.notify_waiters();
+	}
+}
+
+impl DownloadManager {
+	/// Runs at most `slots` downloads at once, cancelling all of them when `shutdown` is
+	pub fn new(slots: usize, shutdown: CancellationToken) -> Self {
+		Self {
+			state: Mutex::new(State { available: slots.max(1), waiting: [0; 3], next_id: 0, transfers: BTreeMap::new() }),
+			changed: Notify::new(),
+			shutdown,
+		}
+	}
+
+	/// Waits for a slot to download to `path`
+	pub async fn acquire(&self, path: &Path, priority: DownloadPriority) -> Result<DownloadPermit<'_>, Cancelled> {
+		let cancel = self.shutdown.child_token();
+		let progress = Progress::default();
+		let id = {
+			let mut state = self.state.lock().unwrap();
+			let id = state.next_id;
+			state.next_id += 1;
+			state.waiting[priority as usize] += 1;
+			state.transfers.insert(id, Transfer {
+				path: path.to_path_buf(),
+				priority,
+				queued: Instant::now(),
+				started: None,
+				progress: progress.clone(),
+				cancel: cancel.clone(),
+			});
+			id
+		};
+		let _waiting = Waiting { manager: self, id, priority };
+
+		loop {
+			let mut changed = pin!(self.changed.notified());
+			changed.as_mut().enable();
+
+			{
+				let mut state = self.state.lock().unwrap();
+				let preempted = state.waiting[priority as usize + 1..].iter().any(|waiting| *waiting > 0);
+				if !preempted && state.available > 0 && !cancel.is_cancelled() {
+					state.available -= 1;
+					if let Some(transfer) = state.transfers.get_mut(&id) {
+						transfer.started = Some(Instant::now());
+					}
+
+					return Ok(DownloadPermit { manager: self, id, progress, cancel, retries: AtomicU32::new(0) });
+				}
+			}
+
+			select! {
+				_ = changed => (),
+				_ = cancel.cancelled() => return Err(Cancelled)
+			}
+		}
+	}
+
+	/// Downloads waiting for a slot and running, oldest first
+	pub fn transfers(&self) -> Vec<TransferStatus> {
+		self.state.lock().unwrap().transfers
+			.iter()
+			.map(|(id, transfer)| TransferStatus {
+				id: *id,
+				path: transfer.path.clone(),
+				priority: transfer.priority,
+				active: transfer.started.is_some(),
+				waited_secs: transfer.started.unwrap_or_else(Instant::now).duration_since(transfer.queued).as_secs(),
+				running_secs: transfer.started.map_or(0, |started| started.elapsed().as_secs()),
+				bytes: transfer.progress.bytes(),
+			})
+			.collect()
+	}
+
+	/// Cancels a waiting or running download, returns whether there was one with `id`
+	pub fn cancel(&self, id: u64) -> bool {
+		let state = self.state.lock().unwrap();
+		let Some(transfer) = state.transfers.get(&id) else { return false };
+		transfer.cancel.cancel();
+		true
+	}
+}
@@ -0,0 +1,16 @@
+use sentry::{integrations::log::SentryLogger, ClientInitGuard, ClientOptions};
+use crate::settings::ErrorReporting;
+
+pub fn init(settings: &ErrorReporting) -> ClientInitGuard {
+	sentry::init((settings.dsn.as_str(), ClientOptions {
+		release: sentry::release_name!(),
+		environment: settings.environment.clone().map(Into::into),
+		sample_rate: settings.sample_rate,
+		attach_stacktrace: true,
+		..Default::default()
+	}))
+}
+
+pub fn wrap_logger<L: log::Log + 'static>(logger: L) -> Box<dyn log::Log> {
+	Box::new(SentryLogger::with_dest(logger))
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use nanohtml2text::html2text;
+use of_client::{content::{self, Content, ContentType}, user::User};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+	Post,
+	Message,
+	Story,
+	Stream,
+	Notification,
+	Connected,
+	Disconnected,
+}
+
+impl EventKind {
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			EventKind::Post => "post",
+			EventKind::Message => "message",
+			EventKind::Story => "story",
+			EventKind::Stream => "stream",
+			EventKind::Notification => "notification",
+			EventKind::Connected => "connected",
+			EventKind::Disconnected => "disconnected",
+		}
+	}
+}
+
+impl From<ContentType> for EventKind {
+	fn from(value: ContentType) -> Self {
+		match value {
+			ContentType::Posts => EventKind::Post,
+			ContentType::Chats => EventKind::Message,
+			ContentType::Stories => EventKind::Story,
+			ContentType::Streams => EventKind::Stream,
+			ContentType::Notifications => EventKind::Notification,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventUser {
+	pub id: u64,
+	pub username: String,
+	pub name: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avatar: Option<String>,
+}
+
+impl From<&User> for EventUser {
+	fn from(value: &User) -> Self {
+		Self {
+			id: value.id,
+			username: value.username.clone(),
+			name: value.name.clone(),
+			avatar: value.avatar.clone()
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+	pub kind: EventKind,
+	pub timestamp: DateTime<Utc>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub user: Option<EventUser>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub content_id: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub text: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub price: Option<f32>,
+}
+
+impl Event {
+	pub fn content<T: EventContent>(content: &T, user: &User) -> Self {
+		Self {
+			kind: T::content_type().into(),
+			timestamp: content.timestamp(),
+			user: Some(user.into()),
+			content_id: Some(content.id()),
+			text: content.text().map(html2text),
+			price: content.price().filter(|price| *price > 0f32),
+		}
+	}
+
+	pub fn status(kind: EventKind) -> Self {
+		Self {
+			kind,
+			timestamp: Utc::now(),
+			user: None,
+			content_id: None,
+			text: None,
+			price: None,
+		}
+	}
+}
+
+pub trait EventContent: Content {
+	fn text(&self) -> Option<&str> { None }
+	fn price(&self) -> Option<f32> { None }
+}
+
+impl EventContent for content::Post {
+	fn text(&self) -> Option<&str> { Some(&self.text) }
+	fn price(&self) -> Option<f32> { self.price }
+}
+
+impl EventContent for content::Chat {
+	fn text(&self) -> Option<&str> { Some(&self.text) }
+	fn price(&self) -> Option<f32> { self.price }
+}
+
+impl EventContent for content::Story {}
+
+impl EventContent for content::Notification {
+	fn text(&self) -> Option<&str> { Some(&self.text) }
+}
+
+impl EventContent for content::Stream {
+	fn text(&self) -> Option<&str> { Some(&self.description) }
+}
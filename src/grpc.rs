@@ -0,0 +1,143 @@
+use log::*;
+use std::{net::Ipv4Addr, pin::Pin};
+use anyhow::anyhow;
+use futures::{Stream, StreamExt};
+use tokio::sync::broadcast::Sender;
+use tokio_stream::wrappers::BroadcastStream;
+use tonic::{transport::Server, Request, Response, Status};
+use crate::{control::{token_matches, Command, Controller, StatsResponse}, events::{self, EventUser}, settings::GrpcSettings, stats::{DownloadTotals, EventCounts}};
+
+pub mod proto {
+	tonic::include_proto!("ofnotifier");
+}
+
+use proto::{command, notifier_server::{Notifier, NotifierServer}};
+
+impl From<EventUser> for proto::User {
+	fn from(value: EventUser) -> Self {
+		Self { id: value.id, username: value.username, name: value.name, avatar: value.avatar }
+	}
+}
+
+impl From<events::Event> for proto::Event {
+	fn from(value: events::Event) -> Self {
+		Self {
+			kind: value.kind.as_str().to_string(),
+			timestamp: value.timestamp.timestamp(),
+			user: value.user.map(Into::into),
+			content_id: value.content_id,
+			text: value.text,
+			price: value.price,
+		}
+	}
+}
+
+impl From<DownloadTotals> for proto::DownloadTotals {
+	fn from(value: DownloadTotals) -> Self {
+		Self {
+			downloads: value.downloads,
+			failures: value.failures,
+			retries: value.retries,
+			bytes: value.bytes,
+			seconds: value.seconds,
+		}
+	}
+}
+
+impl From<EventCounts> for proto::EventCounts {
+	fn from(value: EventCounts) -> Self {
+		Self {
+			posts: value.posts,
+			messages: value.messages,
+			stories: value.stories,
+			streams: value.streams,
+			notifications: value.notifications,
+		}
+	}
+}
+
+impl From<StatsResponse> for proto::Stats {
+	fn from(value: StatsResponse) -> Self {
+		Self {
+			downloads: Some(value.app.downloads.into()),
+			events: value.app.events.into_iter().map(|(username, counts)| (username, counts.into())).collect(),
+			unparsed_messages: value.daemon.unparsed_messages,
+			heartbeat_p50_ms: value.daemon.heartbeat_latency.map(|latency| latency.p50_ms),
+			heartbeat_p95_ms: value.daemon.heartbeat_latency.map(|latency| latency.p95_ms),
+			downloads_paused: value.downloads_paused,
+			muted: value.muted,
+			backing_off: value.daemon.backing_off.into_iter().map(|backoff| backoff.endpoint).collect(),
+			queue_depth: value.daemon.queue_depth as u64,
+			dropped_messages: value.daemon.dropped_messages,
+			handler_failures: value.app.handler_failures,
+			stalled_downloads: value.app.stalled_downloads,
+		}
+	}
+}
+
+struct NotifierService {
+	controller: Controller,
+	events: Sender<events::Event>,
+}
+
+#[tonic::async_trait]
+impl Notifier for NotifierService {
+	type StreamEventsStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+	async fn stream_events(&self, _request: Request<proto::StreamEventsRequest>) -> Result<Response<Self::StreamEventsStream>, Status> {
+		let stream = BroadcastStream::new(self.events.subscribe())
+			.filter_map(|event| async move { event.ok().map(|event| Ok(event.into())) });
+
+		Ok(Response::new(Box::pin(stream)))
+	}
+
+	async fn get_stats(&self, _request: Request<proto::GetStatsRequest>) -> Result<Response<proto::Stats>, Status> {
+		Ok(Response::new(self.controller.stats().into()))
+	}
+
+	async fn execute(&self, request: Request<proto::Command>) -> Result<Response<proto::CommandResponse>, Status> {
+		let command = match request.into_inner().command {
+			Some(command::Command::Connect(_)) => Command::Connect,
+			Some(command::Command::Disconnect(_)) => Command::Disconnect,
+			Some(command::Command::PauseDownloads(_)) => Command::PauseDownloads,
+			Some(command::Command::ResumeDownloads(_)) => Command::ResumeDownloads,
+			Some(command::Command::Mute(username)) => Command::Mute(username),
+			Some(command::Command::Unmute(username)) => Command::Unmute(username),
+			None => return Err(Status::invalid_argument("missing command")),
+		};
+
+		self.controller.execute(command);
+		Ok(Response::new(proto::CommandResponse {}))
+	}
+}
+
+// Same bearer token check as the REST API, in the call's metadata
+fn authorize(request: Request<()>, token: &str) -> Result<Request<()>, Status> {
+	let authorized = request.metadata()
+		.get("authorization")
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+		.is_some_and(|value| token_matches(value, token));
+
+	if authorized { Ok(request) }
+	else { Err(Status::unauthenticated("missing or invalid token")) }
+}
+
+pub async fn serve(controller: Controller, events: Sender<events::Event>, settings: GrpcSettings) -> anyhow::Result<()> {
+	if settings.token.is_empty() {
+		return Err(anyhow!("gRPC service token must not be empty"))
+			.inspect_err(|err| error!("{err}"));
+	}
+
+	info!("gRPC service listening on {}:{}", Ipv4Addr::LOCALHOST, settings.port);
+
+	let token = settings.token;
+	let service = NotifierServer::with_interceptor(NotifierService { controller, events }, move |request| authorize(request, &token));
+
+	Server::builder()
+		.add_service(service)
+		.serve((Ipv4Addr::LOCALHOST, settings.port).into())
+		.await
+		.inspect_err(|err| error!("gRPC service stopped: {err}"))
+		.map_err(Into::into)
+}
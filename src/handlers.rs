@@ -1,194 +1,1656 @@
 use crate::{
-	helpers::{fetch_file, filename_from_url, get_avatar, get_thumbnail, handle_download, show_notification},
+	init_cdm,
+	downloads::{Cancelled, DownloadManager, DownloadPermit, DownloadPriority},
+	helpers::{fetch_file, filename_from_url, Fetched, Mismatch, get_avatar, get_thumbnail, handle_download, show_notification, MediaLocation, PathResolver},
+	catalog::Catalog,
+	events::{Event, EventContent},
+	lanes::Lanes,
+	notifier::{NotificationContent, TIPS_GROUP},
+	likes::{LikeHistory, LikeQueue},
+	pending::PendingWork,
+	plugins::{DownloadedFile, PluginManager},
+	recorder::StreamRecorder,
+	renewals::{RenewalAlert, RenewalMonitor},
+	search::SearchIndex,
+	storage::{self, Storage},
+	subscriptions::SubscriptionCache,
+	stats::{DownloadRecord, Stats},
+	watchdog::{watch, Progress, Stalled},
 	settings::{
-		markers::*, ContentActions, MediaContentActions, ResolveContentActions, Settings, StoryContentActions,
-		concrete::{ConcreteMediaSpecificSelection, ConcreteSelection, MediaSelection, Toggle}
+		markers::*, ContentActions, MediaContentActions, OverBudget, OverLimit, ResolveContentActions, Settings, StoryContentActions,
+		concrete::{ConcreteMediaSpecificSelection, ConcreteSelection, MediaSelection, NotificationSpecificSelection, Toggle},
+		creators::{DuplicateMedia, ExpiredContent, GifFormat, MassMessages}
 	}};
 
+#[cfg(feature = "scripting")]
+use crate::scripting::Scripts;
 use log::*;
-use reqwest::Url;
-use tokio::{process as tProcess, task::JoinHandle};
-use std::{io, iter::from_fn, path::Path, process, sync::{Arc, RwLock}};
+use reqwest::{IntoUrl, StatusCode, Url};
+use thiserror::Error;
+use tokio::{fs as tfs, io::AsyncReadExt, process as tProcess, select, sync::broadcast, task::{spawn_blocking, JoinHandle}, time::{interval, sleep, timeout}};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use std::{collections::{HashMap, HashSet}, fs, future::{ready, Future}, io::ErrorKind, iter::from_fn, mem, path::{Path, PathBuf}, process, str::FromStr, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, RwLock}, time::{Duration, Instant}};
 use anyhow::{bail, anyhow};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use ffmpeg_sidecar::{command::FfmpegCommand, event::{FfmpegEvent, LogLevel}, log_parser::FfmpegLogParser};
 use tempfile::TempDir;
-use futures::{future::{join3, join_all, try_join, OptionFuture}, FutureExt};
+use futures::{future::{join, join_all, BoxFuture, OptionFuture, Shared}, FutureExt, StreamExt, TryStreamExt};
 use nanohtml2text::html2text;
-use of_daemon::structs::{self, Message, TaggedMessage};
-use of_client::{content::{self, CanLike, ContentType, HasMedia}, drm::MPDData, media::{Feed, Media, MediaType, Thumbnail, DRM}, user::User, widevine::Cdm, OFClient};
-use winrt_toast::{content::{image::{ImageHintCrop, ImagePlacement}, text::TextPlacement}, Header, Image, Text, Toast};
+use of_daemon::{structs::{self, Message, NotificationSubType, TaggedMessage}, InFlight};
+use of_client::{content::{self, CanLike, Content, ContentType, HasMedia}, drm::{MPDData, StreamingFormat}, media::{Feed, Media, MediaType, Thumbnail, DRM}, scheduler::Priority, user::User, widevine::Cdm, OFClient};
+
+// Long enough to identify the event in the log without dumping its whole payload
+const MAX_EVENT_DESCRIPTION: usize = 200;
+// How far back a chat is searched for a message whose download was interrupted
+const RESTORED_CHAT_DEPTH: usize = 50;
 
 #[derive(Clone)]
 pub struct Context {
 	pub settings: Arc<RwLock<Settings>>,
 	pub client: OFClient,
-	device: Option<Cdm>,
+	/// Set while several accounts run at once
+	pub account: Option<String>,
+	/// Whether this runs alongside the main account
+	secondary: bool,
+	pub stats: Arc<Stats>,
+	pub catalog: Arc<Catalog>,
+	pub search: Option<Arc<SearchIndex>>,
+	pub subscriptions: Arc<SubscriptionCache>,
+	pub events: broadcast::Sender<Event>,
+	pub plugins: Arc<PluginManager>,
+	#[cfg(feature = "scripting")]
+	scripts: Option<Arc<Scripts>>,
+	downloads_paused: Arc<AtomicBool>,
+	pub downloads: Arc<DownloadManager>,
+	storage: Arc<dyn Storage>,
+	archive_fetches: Arc<Mutex<ArchiveFetches>>,
+	muted: Arc<RwLock<HashSet<String>>>,
+	expired_subscriptions: Arc<RwLock<HashSet<String>>>,
+	limit_warnings: Arc<Mutex<HashSet<Option<String>>>>,
+	/// Whether the site last said it has hints, so they are announced once rather than on every repeat
+	new_hints: Arc<AtomicBool>,
+	auto_replies: Arc<Mutex<HashMap<u64, Instant>>>,
+	likes: Arc<LikeQueue>,
+	recorder: Arc<StreamRecorder>,
+	like_counts: Arc<Mutex<HashMap<String, Vec<Instant>>>>,
+	lanes: Arc<Lanes<u64>>,
+	post_authors: Arc<Mutex<HashMap<u64, u64>>>,
+	shutdown: CancellationToken,
+	tasks: TaskTracker,
+	device: Arc<RwLock<Option<Cdm>>>,
+	pending_drm: Arc<Mutex<Vec<PendingDrm>>>,
 	thumbnail_dir: Arc<TempDir>,
 }
 
-impl Context {
-	pub fn new(client: OFClient, device: Option<Cdm>, settings: Arc<RwLock<Settings>>) -> Result<Self, io::Error> {
-		let thumbnail_dir = TempDir::with_prefix("OF_thumbs")
-		.inspect_err(|err| error!("Error creating temporary directory: {err}"))?;
+impl Context {
+	pub fn new(client: OFClient, device: Option<Cdm>, settings: Arc<RwLock<Settings>>) -> anyhow::Result<Self> {
+		let thumbnail_dir = TempDir::with_prefix("OF_thumbs")
+		.inspect_err(|err| error!("Error creating temporary directory: {err}"))?;
+
+		fs::create_dir_all("data")?;
+		let catalog = Catalog::open(Path::new("data").join("catalog.sqlite3"))
+		.inspect_err(|err| error!("Error opening catalog: {err}"))?;
+
+		let search = SearchIndex::open(Path::new("data").join("search"))
+		.inspect_err(|err| error!("Error opening search index, archived text won't be searchable: {err}"))
+		.ok()
+		.map(Arc::new);
+
+		let plugins = PluginManager::load("plugins", &settings.read().unwrap().plugins);
+
+		let storage = storage::open(settings.read().unwrap().storage.as_ref())
+		.inspect_err(|err| error!("Error setting up storage, keeping downloads on this computer only: {err}"))
+		.unwrap_or_else(|_| Arc::new(storage::Local));
+
+		let shutdown = CancellationToken::new();
+		let downloads = DownloadManager::new(settings.read().unwrap().max_concurrent_downloads, shutdown.clone());
+
+		#[cfg(feature = "scripting")]
+		let scripts = settings.read().unwrap().script
+			.as_ref()
+			.and_then(|path| Scripts::load(path)
+				.inspect(|_| info!("Loaded script {path:?}"))
+				.inspect_err(|err| error!("Error loading script {path:?}: {err}"))
+				.ok()
+			)
+			.map(Arc::new);
+
+		#[cfg(not(feature = "scripting"))]
+		if settings.read().unwrap().script.is_some() {
+			warn!("A script is configured, but this build was compiled without the \"scripting\" feature");
+		}
+
+		Ok(Self {
+			client,
+			account: None,
+			secondary: false,
+			device: Arc::new(RwLock::new(device)),
+			pending_drm: Arc::default(),
+			settings,
+			stats: Arc::default(),
+			catalog: Arc::new(catalog),
+			search,
+			subscriptions: Arc::default(),
+			events: broadcast::channel(256).0,
+			plugins: Arc::new(plugins),
+			#[cfg(feature = "scripting")]
+			scripts,
+			downloads_paused: Arc::default(),
+			downloads: Arc::new(downloads),
+			storage,
+			archive_fetches: Arc::default(),
+			muted: Arc::default(),
+			expired_subscriptions: Arc::default(),
+			limit_warnings: Arc::default(),
+			new_hints: Arc::default(),
+			auto_replies: Arc::default(),
+			likes: Arc::default(),
+			recorder: Arc::default(),
+			like_counts: Arc::default(),
+			lanes: Arc::default(),
+			post_authors: Arc::default(),
+			shutdown,
+			tasks: TaskTracker::new(),
+			thumbnail_dir: Arc::new(thumbnail_dir)
+		})
+	}
+
+	pub fn with_account(mut self, account: String) -> Self {
+		self.account = Some(account);
+		self
+	}
+
+	/// A context for another account running alongside this one. Storage, downloads and shutdown are shared,
+	/// what depends on the session or the connection is its own
+	pub fn for_account(&self, client: OFClient, account: String) -> Self {
+		Self {
+			client,
+			secondary: true,
+			stats: Arc::default(),
+			subscriptions: Arc::default(),
+			muted: Arc::default(),
+			expired_subscriptions: Arc::default(),
+			new_hints: Arc::default(),
+			auto_replies: Arc::default(),
+			likes: Arc::default(),
+			like_counts: Arc::default(),
+			post_authors: Arc::default(),
+			..self.clone()
+		}
+		.with_account(account)
+	}
+
+	/// The account that pending work is stored under, the main account's is kept without one
+	fn secondary_account(&self) -> Option<String> {
+		self.account.clone().filter(|_| self.secondary)
+	}
+
+	fn show(&self, toast: &NotificationContent) -> anyhow::Result<()> {
+		match &self.account {
+			Some(account) => {
+				let mut toast = toast.clone();
+				toast.title = format!("{account}: {}", toast.title);
+				show_notification(&toast)
+			},
+			None => show_notification(toast)
+		}
+	}
+
+	fn path_resolver(&self) -> PathResolver {
+		PathResolver::from(&self.settings.read().unwrap().paths).within(self.account.as_deref())
+	}
+
+	pub fn has_cdm(&self) -> bool {
+		self.device.read().unwrap().is_some()
+	}
+
+	/// Loads `device.wvd` again and retries the DRM downloads that were deferred while no CDM was available.
+	/// Blocks while FFmpeg is being installed.
+	pub fn reload_cdm(&self) -> anyhow::Result<()> {
+		let cdm = init_cdm()
+			.inspect_err(|err| warn!("CDM could not be initialized: {err}"))?;
+
+		ffmpeg_sidecar::download::auto_download()
+			.inspect_err(|err| error!("FFmpeg installation failed: {err}"))?;
+
+		*self.device.write().unwrap() = Some(cdm);
+		info!("CDM loaded");
+
+		let pending = mem::take(&mut *self.pending_drm.lock().unwrap());
+		if !pending.is_empty() {
+			info!("Retrying {} deferred DRM downloads", pending.len());
+			let context = self.clone();
+			self.spawn(async move {
+				join_all(pending.into_iter().map(|job| context.retry_drm(job))).await;
+			});
+		}
+
+		Ok(())
+	}
+
+	pub async fn watch_cdm(self, period: Duration) {
+		let path = Path::new("device.wvd");
+		let modified = || path.metadata().and_then(|metadata| metadata.modified()).ok();
+
+		let mut last_modified = modified();
+		let mut interval = interval(period);
+
+		loop {
+			interval.tick().await;
+			let current = modified();
+			if current.is_none() || current == last_modified { continue }
+			last_modified = current;
+
+			info!("{path:?} changed, reloading CDM");
+			let context = self.clone();
+			let _ = spawn_blocking(move || context.reload_cdm()).await;
+		}
+	}
+
+	/// Loads the account's subscriptions, then reloads them every `period`
+	pub async fn refresh_subscriptions(self, period: Duration) {
+		let mut interval = interval(period);
+
+		loop {
+			interval.tick().await;
+			let client = self.client.with_priority(Priority::Bulk);
+			let Ok(subscriptions) = client.get_subscription_details().try_collect::<Vec<_>>().await else { continue };
+
+			let first_load = !self.subscriptions.is_loaded();
+			self.subscriptions.update(&subscriptions);
+			debug!("Subscription cache refreshed, {} subscriptions", subscriptions.len());
+
+			// With several accounts, exceptions can name creators that only another account is subscribed to
+			if first_load && self.account.is_none() {
+				let usernames = self.subscriptions.usernames();
+				let unknown = self.settings.read().unwrap().unknown_creators(usernames.iter().map(String::as_str));
+				for err in &unknown {
+					warn!("{err}");
+				}
+
+				if !unknown.is_empty() {
+					let mut toast = NotificationContent::new();
+					toast.title("Settings problems").body(unknown.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"));
+
+					let _ = self.show(&toast);
+				}
+			}
+		}
+	}
+
+	/// Periodically checks whether subscriptions still renew and whether payments failed, notifying about every one that stopped
+	pub async fn monitor_renewals(self, period: Duration) {
+		let mut monitor = RenewalMonitor::default();
+		let mut interval = interval(period);
+
+		loop {
+			interval.tick().await;
+			let Some(renewals) = self.settings.read().unwrap().renewals.clone() else { continue };
+			let client = self.client.with_priority(Priority::Bulk);
+
+			if let Ok(subscriptions) = client.get_subscription_details().try_collect::<Vec<_>>().await {
+				for (user, alert) in monitor.check(&subscriptions) {
+					let text = match alert {
+						RenewalAlert::Lapsed => {
+							warn!("Subscription to {} lapsed although it was set to renew", user.username);
+							self.flag_expired_subscription(&user.username);
+							"Subscription lapsed, the renewal failed"
+						},
+						RenewalAlert::AutoRenewDisabled => {
+							warn!("Subscription to {} is no longer set to renew", user.username);
+							"Auto-renew was turned off"
+						}
+					};
+
+					if self.is_muted(&user.username) { continue }
+
+					let mut toast = NotificationContent::new();
+					toast.title(&user.name).body(text);
+					let _ = self.show_with_avatar(toast, &user).await;
+				}
+			}
+
+			let Ok(transactions) = client.get_transactions(renewals.transactions).await else { continue };
+			let _ = self.catalog.record_transactions(&transactions)
+				.inspect_err(|err| error!("Error storing transactions: {err}"));
+
+			for transaction in monitor.failed_payments(&transactions) {
+				warn!("Payment failed: {transaction:?}");
+
+				let mut toast = NotificationContent::new();
+				toast
+				.title(transaction.user.as_ref().map_or("Payment failed", |user| user.name.as_str()))
+				.body(format!("Payment of ${:.2} failed", transaction.amount))
+				.attribution(&transaction.description);
+
+				match &transaction.user {
+					Some(user) => { let _ = self.show_with_avatar(toast, user).await; },
+					None => { let _ = self.show(&toast); }
+				}
+			}
+		}
+	}
+
+	/// Periodically checks a random sample of archived posts against the platform and flags the ones that were deleted
+	pub async fn reconcile_archive(self, period: Duration) {
+		let mut interval = interval(period);
+		interval.tick().await;
+
+		loop {
+			interval.tick().await;
+			let Some(reconcile) = self.settings.read().unwrap().reconcile.clone() else { continue };
+
+			let Ok(sample) = self.catalog.sample_content(ContentType::Posts, reconcile.sample_size)
+				.inspect_err(|err| error!("Error sampling archived posts: {err}"))
+			else { continue };
+
+			debug!("Checking {} archived posts for deletions", sample.len());
+			let client = self.client.with_priority(Priority::Bulk);
+			for (post_id, username) in sample {
+				let Err(err) = client.get_post(post_id).await else { continue };
+				if err.status() != Some(StatusCode::NOT_FOUND) { continue }
+
+				info!("Archived post {post_id} from {username} was deleted");
+				let _ = self.catalog.mark_deleted(ContentType::Posts, post_id)
+					.inspect_err(|err| error!("Error flagging deleted post: {err}"));
+
+				if reconcile.notify && !self.is_muted(&username) {
+					let mut toast = NotificationContent::new();
+					toast
+					.title(&username)
+					.body("Deleted a post you archived")
+					.attribution(post_id.to_string());
+
+					let _ = self.show(&toast);
+				}
+			}
+		}
+	}
+
+	pub fn pause_downloads(&self, paused: bool) {
+		info!("Downloads {}", if paused { "paused" } else { "resumed" });
+		self.downloads_paused.store(paused, Ordering::Relaxed);
+	}
+
+	pub fn downloads_paused(&self) -> bool {
+		self.downloads_paused.load(Ordering::Relaxed)
+	}
+
+	pub fn mute(&self, username: &str) {
+		info!("Muting {username}");
+		self.muted.write().unwrap().insert(username.to_string());
+	}
+
+	pub fn unmute(&self, username: &str) {
+		info!("Unmuting {username}");
+		self.muted.write().unwrap().remove(username);
+	}
+
+	pub fn muted(&self) -> Vec<String> {
+		self.muted.read().unwrap().iter().cloned().collect()
+	}
+
+	/// Sends likes once their delay has passed, spacing them out and keeping them within the like budget
+	pub async fn run_likes(self) {
+		let mut history = LikeHistory::default();
+
+		loop {
+			let like = self.likes.next().await;
+			let (settings, max_per_creator) = {
+				let settings = self.settings.read().unwrap();
+				(settings.likes.clone(), settings.creator(&like.username).max_likes_per_day)
+			};
+
+			// Both limits are checked before either is charged, so a dropped like doesn't count against the creator
+			if let Some(max) = max_per_creator && self.likes_today(&like.username) >= max {
+				debug!("Already liked {max} pieces of content from {} today, dropping like", like.username);
+				self.finish_pending(&PendingWork::Like { account: self.secondary_account(), username: like.username, url: like.url.to_string() });
+				continue;
+			}
+
+			let now = tokio::time::Instant::now();
+			let budget_wait = history.budget_wait(&settings, now);
+			if !budget_wait.is_zero() && settings.over_budget == OverBudget::Drop {
+				info!("Like budget exhausted, dropping like of content from {}", like.username);
+				self.finish_pending(&PendingWork::Like { account: self.secondary_account(), username: like.username, url: like.url.to_string() });
+				continue;
+			}
+
+			let wait = budget_wait.max(history.spacing_wait(settings.spacing(), now));
+			if !wait.is_zero() {
+				debug!("Waiting {}s before liking content from {}", wait.as_secs(), like.username);
+				sleep(wait).await;
+			}
+
+			debug!("Liking content from {}", like.username);
+			let pending = PendingWork::Like { account: self.secondary_account(), username: like.username.clone(), url: like.url.to_string() };
+			// A failed like is kept pending and retried on the next start, it isn't charged against either limit
+			let liked = self.client.post(like.url, None::<&[u8]>).await
+				.inspect_err(|err| error!("Error liking content from {}: {err}", like.username))
+				.is_ok();
+			if liked {
+				history.record(tokio::time::Instant::now());
+				self.like_counts.lock().unwrap().entry(like.username).or_default().push(Instant::now());
+				self.finish_pending(&pending);
+			}
+		}
+	}
+
+	/// Likes sent to `username` during the last day
+	fn likes_today(&self, username: &str) -> usize {
+		let mut counts = self.like_counts.lock().unwrap();
+		let Some(sent) = counts.get_mut(username) else { return 0 };
+		sent.retain(|at| at.elapsed() < Duration::from_secs(24 * 3600));
+		sent.len()
+	}
+
+	fn queue_pending(&self, work: &PendingWork) {
+		let _ = self.catalog.record_pending(work)
+			.inspect_err(|err| error!("Error storing pending work: {err}"));
+	}
+
+	fn finish_pending(&self, work: &PendingWork) {
+		let _ = self.catalog.clear_pending(work)
+			.inspect_err(|err| error!("Error clearing finished work: {err}"));
+	}
+
+	/// Picks up the downloads and likes that were left unfinished when the application last stopped
+	pub async fn restore_pending(self) {
+		let Ok(pending) = self.catalog.pending_work(self.secondary_account().as_deref())
+			.inspect_err(|err| error!("Error reading pending work: {err}"))
+		else { return };
+
+		if pending.is_empty() { return }
+		info!("Restoring {} unfinished downloads and likes", pending.len());
+
+		for work in pending {
+			match &work {
+				PendingWork::Like { username, url, .. } => match Url::parse(url) {
+					Ok(url) => self.likes.schedule(username, url, Duration::ZERO),
+					Err(_) => self.finish_pending(&work)
+				},
+				PendingWork::Download { content_type, content_id, user_id, .. } => match ContentType::from_str(content_type) {
+					Ok(ContentType::Posts) => {
+						let Ok(post) = self.client.get_post(*content_id).await else { continue };
+						self.download_bulk(&post, &post.author).await;
+					},
+					Ok(ContentType::Chats) => {
+						let Ok(messages) = self.client.get_chat_messages(*user_id, None).take(RESTORED_CHAT_DEPTH).try_collect::<Vec<_>>().await else { continue };
+						let Some(message) = messages.into_iter().find(|message| message.content.id() == *content_id) else {
+							warn!("Message {content_id} is no longer in the chat with {user_id}, dropping its download");
+							self.finish_pending(&work);
+							continue
+						};
+
+						let Ok(user) = self.client.get_user(*user_id).await else { continue };
+						self.download_bulk(&message.content, &user).await;
+					},
+					_ => self.finish_pending(&work)
+				}
+			}
+		}
+	}
+
+	fn is_muted(&self, username: &str) -> bool {
+		self.muted.read().unwrap().contains(username)
+	}
+
+	/// Stops downloading and liking a creator's content until restarted, returns whether the creator wasn't flagged yet
+	fn flag_expired_subscription(&self, username: &str) -> bool {
+		let flagged = self.expired_subscriptions.write().unwrap().insert(username.to_string());
+		if flagged {
+			warn!("Subscription to {username} expired, only notifying about their content from now on");
+		}
+
+		flagged
+	}
+
+	fn restrict_expired(&self, actions: ResolvedContentActions, username: &str) -> ResolvedContentActions {
+		match self.expired_subscriptions.read().unwrap().contains(username) {
+			true => ResolvedContentActions { download: false, like: false, ..actions },
+			false => actions
+		}
+	}
+
+	async fn notify_expired_subscription(&self, user: &User) {
+		if self.is_muted(&user.username) { return }
+
+		let mut toast = NotificationContent::new();
+		toast
+		.title(&user.name)
+		.body("Subscription expired, downloads are disabled for this creator until restarted");
+
+		let _ = self.show_with_avatar(toast, user).await;
+	}
+
+	/// Notifies about a tip of `amount` dollars sent on a story or stream of `creator_id`, `on` names which
+	async fn notify_tip(&self, tipper: &str, creator_id: u64, amount: f32, message: Option<&str>, on: &str) {
+		let Ok(creator) = self.client.get_user(creator_id).await else { return };
+		if self.is_muted(&creator.username) || !self.settings.read().unwrap().tips(&creator.username) { return }
+
+		let mut toast = NotificationContent::new();
+		toast
+		.title(&creator.name)
+		.body(match message.filter(|message| !message.is_empty()) {
+			Some(message) => format!("{tipper} tipped ${amount:.2} on {on}: {message}"),
+			None => format!("{tipper} tipped ${amount:.2} on {on}")
+		})
+		.group(TIPS_GROUP);
+
+		let _ = self.show_with_avatar(toast, &creator).await;
+	}
+
+	/// Spawns handler work, which stops when the application shuts down and is waited for while draining
+	pub fn spawn<F>(&self, task: F) -> JoinHandle<()>
+	where F: Future<Output = ()> + Send + 'static {
+		let shutdown = self.shutdown.clone();
+		tokio::spawn(self.tasks.track_future(async move {
+			select! {
+				_ = shutdown.cancelled() => (),
+				_ = task => ()
+			}
+		}))
+	}
+
+	/// Spawns the catalog steps of a handler behind earlier ones of the same creator,
+	/// the downloads and notifications they return run outside of the lane once they are done
+	fn spawn_in_lane<F, R>(&self, user_id: u64, ordered: F) -> JoinHandle<()>
+	where F: Future<Output = R> + Send + 'static, R: Future<Output = ()> + Send + 'static {
+		let ordered = self.lanes.queue(user_id, ordered);
+		self.spawn(async move { ordered.await.await })
+	}
+
+	/// Runs `ordered` behind the post's publication if that is still being handled,
+	/// so that an edit or expiry arriving right after it is not handled first
+	fn post_lane<F, R>(&self, post_id: u64, ordered: F) -> JoinHandle<()>
+	where F: Future<Output = R> + Send + 'static, R: Future<Output = ()> + Send + 'static {
+		let author = self.post_authors.lock().unwrap().get(&post_id).copied();
+		match author {
+			Some(user_id) => self.spawn_in_lane(user_id, ordered),
+			None => self.spawn(async move { ordered.await.await })
+		}
+	}
+
+	/// Number of handlers and downloads still in progress
+	pub fn active_tasks(&self) -> usize {
+		self.tasks.len()
+	}
+
+	/// Cancelled once the application shuts down
+	pub fn cancellation(&self) -> CancellationToken {
+		self.shutdown.clone()
+	}
+
+	/// Stops accepting work and waits up to `drain` for in-flight handlers and downloads to finish,
+	/// then cancels whatever is left and waits for it to wind down
+	pub async fn shutdown(&self, drain: Duration) {
+		self.tasks.close();
+		self.recorder.stop_all();
+		if timeout(drain, self.tasks.wait()).await.is_err() {
+			info!("Cancelling {} unfinished tasks", self.tasks.len());
+		}
+
+		self.shutdown.cancel();
+		self.tasks.wait().await;
+	}
+
+	/// Like `dispatch`, letting the daemon hand out the next message once this one is dispatched.
+	/// Downloads and recordings it starts keep running without holding up the messages after it
+	pub fn dispatch_in_flight(&self, message: Message, in_flight: InFlight) {
+		self.dispatch(message);
+		drop(in_flight);
+	}
+
+	/// Handles `message`, reporting it if its handler fails or panics
+	pub fn dispatch(&self, message: Message) {
+		let mut description = format!("{message:?}");
+		if let Some((end, _)) = description.char_indices().nth(MAX_EVENT_DESCRIPTION) {
+			description.truncate(end);
+			description.push('…');
+		}
+
+		match message.handle(self) {
+			Ok(Some(handle)) => {
+				let context = self.clone();
+				tokio::spawn(async move {
+					if let Err(err) = handle.await && err.is_panic() {
+						let panic = err.into_panic();
+						let reason = panic.downcast_ref::<&str>().copied()
+							.or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+							.unwrap_or("unknown panic");
+
+						context.handler_failed(&description, &format!("panicked: {reason}"));
+					}
+				});
+			},
+			Ok(None) => (),
+			Err(err) => self.handler_failed(&description, &err.to_string()),
+		}
+	}
+
+	fn handler_failed(&self, description: &str, reason: &str) {
+		error!("Error processing event {description}: {reason}");
+		self.stats.record_handler_failure();
+
+		if self.settings.read().unwrap().error_toasts {
+			let mut toast = NotificationContent::new();
+			toast
+			.title("Event failed to process")
+			.body(reason)
+			.attribution(description);
+
+			let _ = self.show(&toast)
+				.inspect_err(|err| error!("{err}"));
+		}
+	}
+
+	pub fn publish(&self, event: Event) {
+		self.plugins.on_event(&event);
+		let _ = self.events.send(event);
+	}
+
+	fn should_notify(&self, _event: &Event) -> bool {
+		#[cfg(feature = "scripting")]
+		if let Some(scripts) = &self.scripts {
+			return scripts.should_notify(_event)
+		}
+
+		true
+	}
+
+	fn should_download(&self, _event: &Event) -> bool {
+		#[cfg(feature = "scripting")]
+		if let Some(scripts) = &self.scripts {
+			return scripts.should_download(_event)
+		}
+
+		true
+	}
+
+	fn toast_text(&self, _event: &Event) -> Option<String> {
+		#[cfg(feature = "scripting")]
+		if let Some(scripts) = &self.scripts {
+			return scripts.toast_text(_event)
+		}
+
+		None
+	}
+
+	fn downloaded(&self, file: DownloadedFile) {
+		#[cfg(feature = "scripting")]
+		if let Some(scripts) = &self.scripts {
+			scripts.on_downloaded(&file);
+		}
+
+		self.plugins.on_download_complete(&file);
+	}
+
+	fn record_event<T: EventContent>(&self, content: &T, user: &User) {
+		self.stats.record_event(&user.username, T::content_type());
+		let _ = self.catalog.record_event(&user.username, T::content_type())
+			.inspect_err(|err| error!("Error updating event counts: {err}"));
+
+		if let Some(text) = content.text() {
+			let _ = self.catalog.record_content(&user.username, T::content_type(), content.id(), text)
+				.inspect_err(|err| error!("Error storing content text: {err}"));
+			self.index_text(&user.username, T::content_type(), content.id(), text);
+		}
+
+		self.publish(Event::content(content, user));
+	}
+
+	fn notify_caught_up(&self) -> bool {
+		self.settings.read().unwrap().catch_up.as_ref().is_some_and(|catch_up| catch_up.notify)
+	}
+
+	fn index_text(&self, username: &str, content_type: ContentType, content_id: u64, text: &str) {
+		let Some(search) = &self.search else { return };
+		let _ = search.add(username, content_type, content_id, &html2text(text))
+			.inspect_err(|err| error!("Error indexing content text: {err}"));
+	}
+
+	fn setup_toast<T: EventContent + ToNotification>(&self, content: &T, user: &User) -> Option<NotificationContent> {
+		if self.is_muted(&user.username) { return None }
+
+		let event = Event::content(content, user);
+		if !self.should_notify(&event) { return None }
+
+		let mut toast = content.setup_notification(user);
+		if let Some(text) = self.toast_text(&event) {
+			toast.body(text);
+		}
+
+		Some(toast)
+	}
+
+	async fn notify<T: EventContent + ToNotification>(&self, content: &T, user: &User) -> anyhow::Result<()> {
+		let Some(toast) = self.setup_toast(content, user) else { return Ok(()) };
+		self.show_with_avatar(toast, user).await
+	}
+
+	async fn show_with_avatar(&self, mut toast: NotificationContent, user: &User) -> anyhow::Result<()> {
+		let avatar = get_avatar(user, &self.client).await?;
+	
+		if let Some(avatar) = avatar {
+			toast.avatar(avatar);
+		}
+	
+		self.show(&toast)?;
+		Ok(())
+	}
+
+	async fn notify_with_thumbnail<T: EventContent + content::HasMedia + ToNotification>(&self, content: &T, user: &User, downloading: bool) -> anyhow::Result<()> {
+		let Some(mut toast) = self.setup_toast(content, user) else { return Ok(()) };
+		let downloading = downloading && !self.downloads_paused() && self.should_download(&Event::content(content, user));
+
+		let thumbnail = async {
+			match self.archived_thumbnail(content, user, downloading).await {
+				Some(path) => Ok(Some(path)),
+				None => get_thumbnail(content, &self.client, self.thumbnail_dir.path()).await
+			}
+		};
+
+		let (avatar, thumbnail) = join(get_avatar(user, &self.client), thumbnail).await;
+		let (avatar, thumbnail) = (avatar?, thumbnail?);
+
+		if let Some(avatar) = avatar {
+			toast.avatar(avatar);
+		}
+	
+		if let Some(thumbnail) = thumbnail {
+			toast.image(thumbnail);
+		}
+	
+		self.show(&toast)?;
+		Ok(())
+	}
+	
+	/// Uses the full size photo from the archive as the thumbnail, sharing the transfer with `download` when the photo is being downloaded anyway.
+	/// Only fetches it when `download_items` would, so that it is recorded in the catalog
+	async fn archived_thumbnail<T: EventContent + content::HasMedia>(&self, content: &T, user: &User, downloading: bool) -> Option<PathBuf> {
+		let media = content.media()
+			.iter()
+			.find(|media| media.media_type() != &MediaType::Audio)
+			.filter(|media| media.media_type() == &MediaType::Photo)?;
+
+		let url = Url::parse(media.source()?).ok()?;
+		let dir = self.media_dir(content, user, media.media_type());
+		let path = dir.join(filename_from_url(&url)?);
+
+		let path = match self.existing_copy(media.id, &user.username, &dir).await {
+			Some(existing) => existing,
+			None if downloading && !self.over_limit(&user.username) => self.fetch_archived(url, path, DownloadPriority::Preview).await.0.ok()?.path,
+			None => path
+		};
+
+		let metadata = tfs::metadata(&path).await.ok()?;
+		(metadata.len() <= MAX_TOAST_IMAGE_BYTES).then_some(path)
+	}
+
+	// Concurrent fetches of the same archive file share a single transfer
+	fn fetch_archived(&self, url: Url, path: PathBuf, priority: DownloadPriority) -> ArchiveFetch {
+		let mut fetches = self.archive_fetches.lock().unwrap();
+		if let Some((_, fetch)) = fetches.fetches.get(&path) { return fetch.clone() }
+
+		let id = fetches.next_id;
+		fetches.next_id += 1;
+
+		let fetch = {
+			let context = self.clone();
+			let path = path.clone();
+
+			async move {
+				let result = match context.downloads.acquire(&path, priority).await {
+					Ok(permit) => {
+						let result = context.with_watchdog(&permit, &path, |progress| {
+							let (url, path) = (url.clone(), path.clone());
+							let client = context.client.clone();
+							let verify = context.settings.read().unwrap().downloads.verify_checksums;
+							async move { fetch_file(&client, url, &path, &progress, verify).await }
+						})
+						.await
+						.map_err(Arc::new);
+
+						(result, permit.retries())
+					},
+					Err(err) => (Err(Arc::new(anyhow::Error::new(err))), 0)
+				};
+
+				// A later fetch of the same file may already have taken its place
+				let mut fetches = context.archive_fetches.lock().unwrap();
+				if fetches.fetches.get(&path).is_some_and(|(fetch_id, _)| *fetch_id == id) {
+					fetches.fetches.remove(&path);
+				}
+
+				result
+			}
+			.boxed()
+			.shared()
+		};
+
+		fetches.fetches.insert(path, (id, fetch.clone()));
+		fetch
+	}
+
+	/// Runs a download, restarting it whenever it makes no progress for too long or doesn't match what the server announced,
+	/// up to the configured number of retries. Stops it when it is cancelled
+	async fn with_watchdog<T, F, Fut>(&self, permit: &DownloadPermit<'_>, path: &Path, job: F) -> anyhow::Result<T>
+	where
+		F: Fn(Progress) -> Fut,
+		Fut: Future<Output = anyhow::Result<T>>,
+	{
+		let downloads = self.settings.read().unwrap().downloads;
+		let progress = permit.progress();
+		let mut retries = 0;
+
+		loop {
+			progress.restart();
+			let attempt = select! {
+				result = watch(progress, downloads.stall_timeout(), job(progress.clone())) => result,
+				_ = permit.cancelled() => {
+					info!("Download of {path:?} cancelled");
+					return Err(Cancelled.into())
+				}
+			};
+
+			match attempt {
+				Err(err) if err.is::<Stalled>() || err.is::<Mismatch>() => {
+					if err.is::<Stalled>() { self.stats.record_stall() }
+					if retries >= downloads.stall_retries {
+						error!("Download of {path:?} {err}, giving up");
+						return Err(err)
+					}
+
+					retries += 1;
+					permit.record_retry();
+					warn!("Download of {path:?} {err}, restarting ({retries}/{})", downloads.stall_retries);
+				},
+				result => return result
+			}
+		}
+	}
+
+	pub(crate) async fn download<T: EventContent + content::HasMedia<Media = Feed> + Serialize>(&self, content: &T, user: &User) -> bool {
+		self.download_items(content, user, content.media(), false).await
+	}
+
+	/// Downloads without holding up the downloads of live events, for content fetched in bulk
+	pub(crate) async fn download_bulk<T: EventContent + content::HasMedia<Media = Feed> + Serialize>(&self, content: &T, user: &User) -> bool {
+		self.download_items(content, user, content.media(), true).await
+	}
+
+	/// Returns whether all of the media is archived now, media skipped on purpose counts as archived
+	async fn download_items<'a, T: EventContent + Serialize>(&self, content: &T, user: &User, media: impl IntoIterator<Item = &'a Feed>, bulk: bool) -> bool {
+		if self.downloads_paused() {
+			info!("Downloads are paused, skipping {} {} from {}", T::content_type(), content.id(), user.username);
+			return false;
+		}
+
+		if !self.should_download(&Event::content(content, user)) {
+			debug!("Script rejected download of {} {} from {}", T::content_type(), content.id(), user.username);
+			return true;
+		}
+
+		// Only posts and messages can be fetched again after a restart
+		let pending = matches!(T::content_type(), ContentType::Posts | ContentType::Chats).then(|| PendingWork::Download {
+			account: self.secondary_account(),
+			content_type: T::content_type().to_string(),
+			content_id: content.id(),
+			user_id: user.id
+		});
+
+		if let Some(pending) = &pending { self.queue_pending(pending) }
+
+		let media = media.into_iter().collect::<Vec<_>>();
+		if self.settings.read().unwrap().save_metadata {
+			let dirs = media.iter()
+				.map(|media| self.media_dir(content, user, media.media_type()))
+				.collect::<HashSet<_>>();
+
+			for dir in dirs {
+				let _ = save_metadata(content, user, &dir).await
+					.inspect_err(|err| error!("Error saving metadata of {} {} to {dir:?}: {err}", T::content_type(), content.id()));
+			}
+		}
+
+		let archived = join_all(media.into_iter().map(|media| async {
+			let path = self.media_dir(content, user, media.media_type());
+			if self.deduplicate(media.id, &user.username, &path).await { return true }
+			if !self.within_limits(&user.username).await {
+				info!("Disk usage limit reached, skipping media {} from {}", media.id, user.username);
+				return false;
+			}
+
+			let origin = MediaOrigin {
+				username: user.username.clone(),
+				content_type: T::content_type(),
+				content_id: content.id(),
+				media_id: media.id,
+				tags: self.tags_for(content, user, media.media_type())
+			};
+
+			let priority = match bulk {
+				true => DownloadPriority::Bulk,
+				false => DownloadPriority::of(media.media_type())
+			};
+
+			let start = Instant::now();
+			let (result, retries) = match media.drm() {
+				Some(drm) if self.has_cdm() => {
+					let Ok(permit) = self.downloads.acquire(&path, priority).await else { return false };
+					let result = self.download_media_drm(drm, &origin.license_url(), &path, &permit).await;
+					(result, permit.retries())
+				},
+				Some(drm) if media.source().is_none() => {
+					self.defer_drm(PendingDrm { drm: drm.clone(), path, origin });
+					return false;
+				},
+				_ => self.download_media(media, &path, priority).await
+			};
+
+			let result = match result {
+				Ok(Some(downloaded)) if media.media_type() == &MediaType::Gif => self.convert_gif(downloaded, &user.username).await.map(Some),
+				result => result
+			};
+
+			if let Err(err) = &result && err.is::<Forbidden>() && self.flag_expired_subscription(&user.username) {
+				self.notify_expired_subscription(user).await;
+			}
+
+			let downloaded = result.is_ok();
+			self.finish_download(result, retries, start, &origin);
+			downloaded
+		}))
+		.await
+		.into_iter()
+		.all(|archived| archived);
+
+		// Content with media that didn't make it stays pending, so it is fetched again on the next start
+		if archived && let Some(pending) = &pending { self.finish_pending(pending) }
+		archived
+	}
+	
+	fn media_dir<T: EventContent>(&self, content: &T, user: &User, media_type: &MediaType) -> PathBuf {
+		self.path_resolver().media_dir(&MediaLocation {
+			username: &user.username,
+			content_type: T::content_type(),
+			media_type,
+			content_id: content.id(),
+			timestamp: content.timestamp(),
+		})
+	}
+
+	fn tags_for<T: EventContent>(&self, content: &T, user: &User, media_type: &MediaType) -> Vec<String> {
+		let text = content.text().map(html2text);
+		self.settings.read().unwrap().tags
+			.iter()
+			.filter(|rule| rule.matches(&user.username, text.as_deref(), content.price(), media_type))
+			.map(|rule| rule.tag.clone())
+			.collect()
+	}
+
+	/// The copy of media that was already downloaded as part of other content outside of `dir`, if the settings reuse it
+	async fn existing_copy(&self, media_id: u64, username: &str, dir: &Path) -> Option<PathBuf> {
+		let mode = self.settings.read().unwrap().creator(username).duplicate_media;
+		if mode == DuplicateMedia::Download { return None }
+
+		let existing = self.catalog.media_path(media_id)
+			.inspect_err(|err| error!("Error reading downloaded media: {err}"))
+			.ok()
+			.flatten()
+			.filter(|existing| existing.parent() != Some(dir))?;
+
+		tfs::metadata(&existing).await.ok().map(|_| existing)
+	}
+
+	/// Handles media that was already downloaded as part of other content, returns whether downloading it again can be skipped
+	async fn deduplicate(&self, media_id: u64, username: &str, dir: &Path) -> bool {
+		let mode = self.settings.read().unwrap().creator(username).duplicate_media;
+		if mode == DuplicateMedia::Download { return false }
+
+		let Some(existing) = self.existing_copy(media_id, username, dir).await else {
+			// Copies moved to remote storage can't be linked, but don't need to be stored again either
+			let Some(key) = self.catalog.remote_key(media_id)
+				.inspect_err(|err| error!("Error reading downloaded media: {err}"))
+				.ok()
+				.flatten()
+			else { return false };
+
+			info!("Media {media_id} from {username} was already stored as {key}, skipping");
+			return true;
+		};
+
+		match mode {
+			DuplicateMedia::Download => false,
+			DuplicateMedia::Skip => {
+				info!("Media {media_id} from {username} was already downloaded to {existing:?}, skipping");
+				true
+			},
+			DuplicateMedia::Link => {
+				let Some(filename) = existing.file_name() else { return false };
+				let link = dir.join(filename);
+				let linked = async {
+					tfs::create_dir_all(dir).await?;
+					match tfs::hard_link(&existing, &link).await {
+						Err(err) if err.kind() == ErrorKind::AlreadyExists => Ok(()),
+						result => result
+					}
+				}.await;
+
+				match linked {
+					Ok(()) => {
+						info!("Linked already downloaded media {existing:?} to {link:?}");
+						true
+					},
+					Err(err) => {
+						warn!("Error linking {existing:?} to {link:?}, downloading instead: {err}");
+						false
+					}
+				}
+			}
+		}
+	}
+
+	/// Checks the disk usage of `username` and of all creators against the limits, trimming old downloads if the settings ask for it.
+	/// Returns whether new media can be downloaded
+	async fn within_limits(&self, username: &str) -> bool {
+		let limits = self.settings.read().unwrap().limits.clone();
+
+		for (scope, max_mb) in [(Some(username), limits.max_user_mb), (None, limits.max_total_mb)] {
+			let Some(max_mb) = max_mb else { continue };
+			let Ok(usage) = self.catalog.disk_usage(scope)
+				.inspect_err(|err| error!("Error reading disk usage: {err}"))
+			else { continue };
+
+			let max = max_mb << 20;
+			if usage < max { continue }
+
+			self.notify_limit(scope, max_mb, limits.over_limit);
+			if limits.over_limit == OverLimit::Skip || !self.trim_downloads(scope, usage - max + 1).await { return false }
+		}
 
-		Ok(Self { client, device, settings, thumbnail_dir: Arc::new(thumbnail_dir) })
+		true
 	}
 
-	async fn notify<T: content::Content + ToToast>(&self, content: &T, user: &User) -> anyhow::Result<()> {
-		let mut toast = content.setup_notification(user);
-		let avatar = get_avatar(user, &self.client).await?;
-	
-		if let Some(avatar) = avatar {
-			toast.image(1, 
-				Image::new_local(avatar.canonicalize()?)?
-				.with_hint_crop(ImageHintCrop::Circle)
-				.with_placement(ImagePlacement::AppLogoOverride)
-			);
+	/// Whether a limit is reached, without trimming anything
+	fn over_limit(&self, username: &str) -> bool {
+		let limits = self.settings.read().unwrap().limits.clone();
+		[(Some(username), limits.max_user_mb), (None, limits.max_total_mb)]
+			.into_iter()
+			.filter_map(|(scope, max_mb)| Some((scope, max_mb?)))
+			.any(|(scope, max_mb)| self.catalog.disk_usage(scope).is_ok_and(|usage| usage >= max_mb << 20))
+	}
+
+	/// Deletes the oldest downloads of `username`, or of all creators, until at least `bytes` were freed. Returns whether enough was freed
+	async fn trim_downloads(&self, username: Option<&str>, bytes: u64) -> bool {
+		const BATCH: usize = 20;
+		let mut freed = 0;
+
+		while freed < bytes {
+			let Ok(oldest) = self.catalog.oldest_media(username, BATCH)
+				.inspect_err(|err| error!("Error reading downloaded media: {err}"))
+			else { return false };
+
+			if oldest.is_empty() { return false }
+
+			for (record, size) in oldest {
+				match tfs::remove_file(&record.path).await {
+					Err(err) if err.kind() != ErrorKind::NotFound => {
+						warn!("Error deleting {:?} to stay within the disk usage limit: {err}", record.path);
+						return false;
+					},
+					_ => info!("Deleted {:?} from {} to stay within the disk usage limit", record.path, record.username)
+				}
+
+				if self.catalog.remove_media(&record)
+					.inspect_err(|err| error!("Error removing media from the catalog: {err}"))
+					.is_err()
+				{ return false }
+
+				freed += size;
+				if freed >= bytes { break }
+			}
 		}
-	
-		show_notification(&toast)?;
-		Ok(())
+
+		true
 	}
 
-	async fn notify_with_thumbnail<T: content::Content + content::HasMedia + ToToast>(&self, content: &T, user: &User) -> anyhow::Result<()> {
-		let mut toast = content.setup_notification(user);
-		let (avatar, thumbnail) = try_join(get_avatar(user, &self.client), get_thumbnail(content, &self.client, self.thumbnail_dir.path())).await?;
+	/// Shows a notification the first time a limit is hit
+	fn notify_limit(&self, username: Option<&str>, max_mb: u64, over_limit: OverLimit) {
+		if !self.limit_warnings.lock().unwrap().insert(username.map(str::to_string)) { return }
 
-		if let Some(avatar) = avatar {
-			toast.image(1, 
-				Image::new_local(avatar.canonicalize()?)?
-				.with_hint_crop(ImageHintCrop::Circle)
-				.with_placement(ImagePlacement::AppLogoOverride)
-			);
+		let usage = match username {
+			Some(username) => format!("Media from {username} uses more than {max_mb} MB"),
+			None => format!("Downloaded media uses more than {max_mb} MB")
+		};
+		let action = match over_limit {
+			OverLimit::Skip => "new media is not downloaded",
+			OverLimit::Trim => "the oldest downloads are deleted to make room"
+		};
+		warn!("{usage}, {action}");
+
+		let mut toast = NotificationContent::new();
+		toast
+		.title("Disk usage limit reached")
+		.body(format!("{usage}, {action}"));
+
+		let _ = self.show(&toast);
+	}
+
+	fn finish_download(&self, result: anyhow::Result<Option<Downloaded>>, retries: u32, start: Instant, origin: &MediaOrigin) {
+		if !matches!(result, Ok(None)) {
+			let _ = self.catalog.record_download_result(&origin.username, result.is_ok(), retries)
+				.inspect_err(|err| error!("Error logging download: {err}"));
 		}
-	
-		if let Some(thumbnail) = thumbnail {
-			toast.image(2, Image::new_local(thumbnail)?);
+
+		match result {
+			Ok(Some(downloaded)) => {
+				self.stats.record_download(DownloadRecord::success(downloaded.bytes, start.elapsed(), retries));
+				let file = DownloadedFile {
+					path: downloaded.path,
+					username: origin.username.clone(),
+					content_type: origin.content_type.to_string(),
+					content_id: origin.content_id,
+					media_id: origin.media_id,
+					bytes: downloaded.bytes
+				};
+
+				let _ = self.catalog.record_media(&file)
+					.inspect_err(|err| error!("Error recording downloaded media: {err}"));
+
+				if !origin.tags.is_empty() {
+					let _ = self.catalog.tag_media(origin.media_id, &origin.tags)
+						.inspect_err(|err| error!("Error tagging downloaded media: {err}"));
+				}
+				let path = file.path.clone();
+				self.downloaded(file);
+				self.store(path);
+			},
+			Ok(None) => (),
+			Err(_) => self.stats.record_download(DownloadRecord::failure(start.elapsed(), retries)),
 		}
-	
-		show_notification(&toast)?;
-		Ok(())
 	}
-	
-	async fn download<T: content::Content + content::HasMedia<Media = Feed>>(&self, content: &T, user: &User) {
-		let header = T::content_type().to_string();
-		let content_path = Path::new("data").join(&user.username).join(&header);
-	
-		let _ = join_all(content.media().iter().map(|media| async {
-			let path = content_path.join(match media.media_type() {
-				MediaType::Photo => "Images",
-				MediaType::Audio => "Audios",
-				MediaType::Video | MediaType::Gif => "Videos",
-			});
-	
-			if let Some(drm) = media.drm() && self.device.is_some() {
-				let license_url = format!("https://onlyfans.com/api2/v2/users/media/{}/drm/{}/{}?type=widevine",
-					media.id,
-					match T::content_type() {
-						ContentType::Chats => "message",
-						_ => "post"
-					},
-					content.id()
-				);
-	
-			self.download_media_drm(drm, &license_url, &path).await
-			} else { self.download_media(media, &path).await }
-		}))
-		.await;
+
+	/// Hands a finished download to the configured storage. It stays on disk if that fails
+	fn store(&self, path: PathBuf) {
+		let root = self.settings.read().unwrap().paths.root.clone();
+		let key = path.strip_prefix(&root).unwrap_or(&path)
+			.components()
+			.map(|component| component.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+
+		let storage = self.storage.clone();
+		let catalog = self.catalog.clone();
+		self.spawn(async move {
+			match storage.store(&path, &key).await {
+				Ok(()) if !storage.keeps_local() => {
+					debug!("Stored {path:?} as {key}");
+					match tfs::remove_file(&path).await {
+						Ok(()) => {
+							let _ = catalog.mark_stored(&path, &key)
+								.inspect_err(|err| error!("Error recording stored media: {err}"));
+						},
+						Err(err) => warn!("Error removing {path:?} after storing it: {err}")
+					}
+				},
+				Ok(()) => (),
+				Err(err) => error!("Error storing {path:?}, keeping it on disk: {err}")
+			}
+		});
 	}
-	
-	async fn download_media_drm(&self, media: &DRM, license_url: &str, path: &Path) -> anyhow::Result<()> {
+
+	fn defer_drm(&self, job: PendingDrm) {
+		let mut pending = self.pending_drm.lock().unwrap();
+		if pending.len() >= MAX_PENDING_DRM {
+			warn!("Too many DRM downloads waiting for a CDM, dropping media {} from {}", job.origin.media_id, job.origin.username);
+			return;
+		}
+
+		info!("No CDM loaded, deferring DRM media {} from {}", job.origin.media_id, job.origin.username);
+		pending.push(job);
+	}
+
+	async fn retry_drm(&self, job: PendingDrm) {
+		let Ok(permit) = self.downloads.acquire(&job.path, DownloadPriority::Video).await else { return };
+
+		let start = Instant::now();
+		let result = self.download_media_drm(&job.drm, &job.origin.license_url(), &job.path, &permit).await;
+		self.finish_download(result, permit.retries(), start, &job.origin);
+	}
+
+	/// Downloads from the DASH manifest, or from the HLS one when that fails
+	async fn download_media_drm(&self, media: &DRM, license_url: &str, path: &Path, permit: &DownloadPermit<'_>) -> anyhow::Result<Option<Downloaded>> {
+		let device = self.device.read().unwrap().clone()
+			.ok_or_else(|| anyhow!("CDM is not loaded"))?;
+
+		match self.download_drm_stream(&device, media, StreamingFormat::Dash, license_url, path, permit).await {
+			Err(err) if err.is::<Cancelled>() => Err(err),
+			Err(err) => {
+				warn!("Downloading the DASH stream failed, falling back to HLS: {err}");
+				self.download_drm_stream(&device, media, StreamingFormat::Hls, license_url, path, permit).await
+			},
+			downloaded => downloaded
+		}
+	}
+
+	async fn download_drm_stream(&self, device: &Cdm, media: &DRM, format: StreamingFormat, license_url: &str, path: &Path, permit: &DownloadPermit<'_>) -> anyhow::Result<Option<Downloaded>> {
 		let MPDData { base_url: fname, pssh, last_modified } = self.client
-			.get_mpd_data(media)
+			.get_manifest_data(media, format)
 			.await
 			.inspect_err(|err| error!("{err}"))?;
 
 		let path = &path.join(fname);
 
 		if  let Some(remote_modified) = last_modified &&
-			let Ok(local_modified) = path.metadata().and_then(|metadata| metadata.modified()) &&
+			let Ok(local_modified) = tfs::metadata(path).await.and_then(|metadata| metadata.modified()) &&
 			local_modified >= remote_modified
 		{
-			return Ok(())
+			return Ok(None)
 		}
 
 		handle_download(path, last_modified, || async move {
-			let key = self.client
-				.get_decryption_key(self.device.as_ref().unwrap(), license_url, pssh)
-				.await?;
-			
-			let manifest = &media.manifest.dash;
+			let key = &base16::encode_lower(&self.client
+				.get_decryption_key(device, license_url, pssh)
+				.await?
+				.key);
+
+			self.with_watchdog(permit, path, |progress| async move {
+				let manifest = match format {
+					StreamingFormat::Dash => &media.manifest.dash,
+					StreamingFormat::Hls => &media.manifest.hls
+				};
 
-			let mut command: tProcess::Command = {
 				let mut ffmpeg_command = FfmpegCommand::new();
 				ffmpeg_command
 				.hide_banner()
-				.args(["-cenc_decryption_key", &base16::encode_lower(&key.key)])
+				.args(["-cenc_decryption_key", key])
 				.args(["-headers", &self.client.mpd_header(manifest)])
 				.overwrite()
 				.input(manifest)
 				.args(["-c", "copy"])
 				.as_inner_mut()
 				.arg(path);
-	
-				let std_command: process::Command = ffmpeg_command.into();
-				std_command.into()
-			};
 
-			let output = command
-				.spawn()?
-				.wait_with_output()
-				.await?;
+				run_ffmpeg(ffmpeg_command, &progress).await?;
+				Ok(Some(Downloaded { path: path.clone(), bytes: tfs::metadata(path).await?.len() }))
+			}).await
+		})
+		.await
+	}
+
+	/// Converts a freshly downloaded GIF clip into the creator's preferred format, removing the original
+	async fn convert_gif(&self, downloaded: Downloaded, username: &str) -> anyhow::Result<Downloaded> {
+		let format = self.settings.read().unwrap().creator(username).gifs;
+		let Some(ext) = format.extension() else { return Ok(downloaded) };
+
+		let path = downloaded.path.with_extension(ext);
+		if path == downloaded.path { return Ok(downloaded) }
+
+		if format == GifFormat::Mp4 {
+			tfs::rename(&downloaded.path, &path).await?;
+			return Ok(Downloaded { path, ..downloaded })
+		}
+
+		let modified = tfs::metadata(&downloaded.path).await.and_then(|metadata| metadata.modified()).ok();
+		let progress = Progress::default();
+		let timeout = self.settings.read().unwrap().downloads.stall_timeout();
+		let converted = handle_download(&path, modified, || watch(&progress, timeout, async {
+			let mut ffmpeg_command = FfmpegCommand::new();
+			ffmpeg_command
+			.hide_banner()
+			.overwrite()
+			.input(downloaded.path.to_string_lossy())
+			.args(match format {
+				GifFormat::Webp => ["-c:v", "libwebp", "-lossless", "0", "-q:v", "75", "-loop", "0", "-an"].as_slice(),
+				_ => ["-vf", "fps=15,split[a][b];[a]palettegen[p];[b][p]paletteuse", "-loop", "0"].as_slice()
+			})
+			.as_inner_mut()
+			.arg(&path);
 
-			let mut log_parser = FfmpegLogParser::new(output.stderr.as_slice());
-			let first_error = from_fn(|| match log_parser.parse_next_event() {
-					Ok(entry) if !matches!(entry, FfmpegEvent::LogEOF) => Some(entry),
-					_ => None,
-				})
-				.find(|entry| matches!(entry, FfmpegEvent::Log(LogLevel::Error, _)));
+			run_ffmpeg(ffmpeg_command, &progress).await?;
+			Ok(tfs::metadata(&path).await?.len())
+		}))
+		.await;
 
-			if let Some(FfmpegEvent::Log(_, error)) = first_error {
-				bail!(error)
+		match converted {
+			Ok(bytes) => {
+				debug!("Converted {:?} to {path:?}", downloaded.path);
+				tfs::remove_file(&downloaded.path).await?;
+				Ok(Downloaded { path, bytes })
+			},
+			Err(err) => {
+				warn!("Keeping {:?}, conversion failed: {err}", downloaded.path);
+				let _ = tfs::remove_file(&path).await;
+				Ok(downloaded)
 			}
+		}
+	}
+	
+	/// Downloads `media` into `path`, also returning how many times the transfer was restarted
+	async fn download_media(&self, media: &Feed, path: &Path, priority: DownloadPriority) -> (anyhow::Result<Option<Downloaded>>, u32) {
+		let Some(url) = media.source() else { return (Ok(None), 0) };
+		let url = match Url::parse(url) {
+			Ok(url) => url,
+			Err(err) => return (Err(err.into()), 0)
+		};
+		let Some(filename) = filename_from_url(&url) else { return (Err(anyhow!("Filename unknown")), 0) };
 
-			Ok(())
-		})
-		.await
+		let (result, retries) = self.fetch_archived(url, path.join(filename), priority).await;
+		let result = result
+			.map(|Fetched { bytes, path }| (bytes > 0).then_some(Downloaded { path, bytes }))
+			.map_err(|err| match is_forbidden(&err) {
+				true => anyhow::Error::new(Forbidden),
+				false => anyhow!("{err}")
+			});
+
+		(result, retries)
 	}
 	
-	async fn download_media(&self, media: &Feed, path: &Path) -> anyhow::Result<()> {
-		if let Some(url) = media.source() {
-			let url = Url::parse(url)?;
-			let filename = filename_from_url(&url)
-				.ok_or_else(|| anyhow!("Filename unknown"))?;
+	/// Stores the new text of an edited post, what else changed is handled by `handle_post_edit`
+	async fn record_post_edit(&self, post_id: u64) -> Option<PostEdit> {
+		let content = self.client.get_post(post_id).await.ok()?;
+		let username = &content.author.username;
+
+		let known = self.catalog.media_ids(ContentType::Posts, post_id)
+			.inspect_err(|err| error!("Error reading downloaded media: {err}"))
+			.unwrap_or_default();
+
+		let previous = self.catalog.record_content(username, ContentType::Posts, post_id, &content.text)
+			.inspect_err(|err| error!("Error storing content text: {err}"))
+			.ok()
+			.flatten();
+
+		self.index_text(username, ContentType::Posts, post_id, &content.text);
+
+		let text_changed = previous.is_some_and(|previous| previous != content.text);
+		Some(PostEdit { content, known, text_changed })
+	}
+
+	/// Notifies about an edited post and downloads the media added to it
+	async fn handle_post_edit(&self, edit: PostEdit) {
+		let PostEdit { content, known, text_changed } = edit;
+		let (post_id, user) = (content.id(), &content.author);
+		let added = content.media()
+			.iter()
+			.filter(|media| !known.contains(&media.id))
+			.collect::<Vec<_>>();
+
+		info!("Post {post_id} from {} was edited, text changed: {text_changed}, {} media not downloaded yet", user.username, added.len());
+
+		let actions = ContentActions::<PostMarker>::content_actions(&self.settings, &user.username)
+			.resolve(&content);
+		let actions = self.restrict_expired(actions, &user.username);
 
-			let path = path.join(filename);
-			let _ = fetch_file(&self.client, url, &path).await;
+		let notify_edits = self.settings.read().unwrap().creator(&user.username).notify_post_edits;
+		let toast = (notify_edits && (text_changed || !added.is_empty()))
+			.then(|| self.setup_toast(&content, user))
+			.flatten()
+			.map(|mut toast| {
+				toast.title(format!("{} edited a post", user.name));
+				self.show_with_avatar(toast, user).map(|_| ())
+			});
+
+		join(
+			Into::<OptionFuture<_>>::into(toast),
+			Into::<OptionFuture<_>>::into((actions.download && !added.is_empty())
+			.then(|| self.download_items(&content, user, added.iter().copied(), false))),
+		).await;
+	}
+
+	/// Records the live stream `stream_id` of `user` until it ends, if the settings ask for it
+	async fn record_stream(&self, stream_id: u64, user: &User) {
+		if !self.settings.read().unwrap().record_streams || self.recorder.is_recording(stream_id) { return }
+
+		let Ok(stream) = self.client.get_stream(stream_id).await else { return };
+		let Some(url) = stream.playback_url.filter(|_| !stream.is_finished) else {
+			warn!("Stream {stream_id} from {} has no playback URL, not recording it", user.username);
+			return;
+		};
+
+		let now = Utc::now();
+		let dir = self.path_resolver().media_dir(&MediaLocation {
+			username: &user.username,
+			content_type: ContentType::Streams,
+			media_type: &MediaType::Video,
+			content_id: stream_id,
+			timestamp: now,
+		});
+
+		let path = dir.join(format!("{stream_id}_{}.ts", now.format("%Y-%m-%d_%H-%M-%S")));
+		if let Some(recording) = self.recorder.start(stream_id, url, path) {
+			recording.await;
 		}
-	
-		Ok(())
 	}
-	
-	async fn like<T: content::CanLike>(&self, content: &T) {
-		let _ = self.client.post(content.like_url(), None::<&[u8]>).await;
+
+	/// Flags a post that was removed from the platform, then moves or marks its files or notifies about it if the creator's options ask for it
+	async fn expire_post(&self, post_id: u64) {
+		let _ = self.catalog.mark_expired(ContentType::Posts, post_id)
+			.inspect_err(|err| error!("Error flagging expired post: {err}"));
+
+		let Ok(media) = self.catalog.media(ContentType::Posts, post_id)
+			.inspect_err(|err| error!("Error reading downloaded media: {err}"))
+		else { return };
+
+		if let Some(record) = media.first()
+		&& self.settings.read().unwrap().creator(&record.username).expired_posts == ExpiredContent::Notify {
+			let mut toast = NotificationContent::new();
+			toast
+			.title(&record.username)
+			.body(format!("A post you archived expired, {} downloaded files kept", media.len()))
+			.attribution(post_id.to_string());
+
+			let _ = self.show(&toast);
+		}
+
+		for record in media {
+			let expired_posts = self.settings.read().unwrap().creator(&record.username).expired_posts;
+			let (Some(dir), Some(filename)) = (record.path.parent(), record.path.file_name()) else { continue };
+
+			if expired_posts == ExpiredContent::Mark {
+				let mut marker = filename.to_os_string();
+				marker.push(".expired");
+				let _ = tfs::write(dir.join(marker), []).await
+					.inspect_err(|err| warn!("Error marking expired media {:?}: {err}", record.path));
+				continue;
+			}
+
+			if expired_posts != ExpiredContent::Move { continue }
+
+			let expired_dir = dir.join("Expired");
+			let path = expired_dir.join(filename);
+			let moved = async {
+				tfs::create_dir_all(&expired_dir).await?;
+				tfs::rename(&record.path, &path).await
+			}.await;
+
+			match moved {
+				Ok(()) => {
+					info!("Moved expired media {:?} to {path:?}", record.path);
+					let _ = self.catalog.move_media(&record, &path)
+						.inspect_err(|err| error!("Error updating media path: {err}"));
+				},
+				Err(err) => warn!("Error moving expired media {:?}: {err}", record.path)
+			}
+		}
+	}
+
+	async fn auto_reply(&self, user: &User, mass_message: bool) {
+		let Some(reply) = self.settings.read().unwrap().creator(&user.username).auto_reply.clone() else { return };
+		if mass_message && reply.personal_only { return }
+
+		{
+			let mut replies = self.auto_replies.lock().unwrap();
+			if replies.get(&user.id).is_some_and(|last| last.elapsed() < Duration::from_secs(reply.cooldown)) {
+				debug!("Already replied to {} recently", user.username);
+				return;
+			}
+
+			replies.insert(user.id, Instant::now());
+		}
+
+		info!("Sending auto-reply to {}", user.username);
+		let _ = self.client.send_message(user.id, &reply.render(&user.name, &user.username)).await;
+	}
+
+	fn like<T: content::CanLike>(&self, content: &T, username: &str) {
+		let Ok(url) = content.like_url().into_url() else { return };
+		let (delay, max_per_day) = {
+			let settings = self.settings.read().unwrap();
+			let creator = settings.creator(username);
+			(creator.like_delay.map_or(Duration::ZERO, |delay| delay.sample()), creator.max_likes_per_day)
+		};
+
+		// Likes are counted once sent, this only avoids queueing one that `run_likes` would drop
+		if let Some(max) = max_per_day && self.likes_today(username) >= max {
+			debug!("Already liked {max} pieces of content from {username} today, skipping");
+			return;
+		}
+
+		debug!("Liking content from {username} in {}s", delay.as_secs());
+		self.queue_pending(&PendingWork::Like { account: self.secondary_account(), username: username.to_string(), url: url.to_string() });
+		self.likes.schedule(username, url, delay);
+	}
+}
+
+/// The platform refuses to serve a creator's media once the subscription to them expired
+#[derive(Debug, Error)]
+#[error("access to the media was denied")]
+struct Forbidden;
+
+fn is_forbidden(err: &anyhow::Error) -> bool {
+	err.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status) == Some(StatusCode::FORBIDDEN)
+}
+
+struct Downloaded {
+	path: PathBuf,
+	bytes: u64,
+}
+
+#[derive(Serialize)]
+struct Metadata<'a, T> {
+	content_type: String,
+	author: &'a User,
+	content: &'a T,
+	saved_at: DateTime<Utc>,
+}
+
+/// Writes `content` as `<content id>.json` into `dir`, next to its media
+async fn save_metadata<T: EventContent + Serialize>(content: &T, user: &User, dir: &Path) -> anyhow::Result<()> {
+	let metadata = Metadata { content_type: T::content_type().to_string(), author: user, content, saved_at: Utc::now() };
+	tfs::create_dir_all(dir).await?;
+	tfs::write(dir.join(format!("{}.json", content.id())), serde_json::to_vec_pretty(&metadata)?).await?;
+	Ok(())
+}
+
+// Along with how many times the transfer was restarted
+type ArchiveFetch = Shared<BoxFuture<'static, (Result<Fetched, Arc<anyhow::Error>>, u32)>>;
+
+/// Fetches in progress per file, numbered so that a finished one only removes itself
+#[derive(Default)]
+struct ArchiveFetches {
+	fetches: HashMap<PathBuf, (u64, ArchiveFetch)>,
+	next_id: u64,
+}
+
+// Larger images are rejected by the notification platform
+const MAX_TOAST_IMAGE_BYTES: u64 = 3 * 1024 * 1024;
+
+// Runs FFmpeg to completion, failing with the first error it logs.
+// Its periodic statistics on stderr count as progress, and it is killed if the future is dropped
+async fn run_ffmpeg(command: FfmpegCommand, progress: &Progress) -> anyhow::Result<()> {
+	let mut command: tProcess::Command = {
+		let std_command: process::Command = command.into();
+		std_command.into()
+	};
+
+	let mut child = command
+		.stdout(process::Stdio::null())
+		.stderr(process::Stdio::piped())
+		.kill_on_drop(true)
+		.spawn()?;
+
+	let mut stderr = Vec::new();
+	if let Some(mut pipe) = child.stderr.take() {
+		let mut buffer = [0; 4096];
+		loop {
+			let read = pipe.read(&mut buffer).await?;
+			if read == 0 { break }
+
+			stderr.extend_from_slice(&buffer[..read]);
+			progress.touch();
+		}
+	}
+
+	child.wait().await?;
+
+	let mut log_parser = FfmpegLogParser::new(stderr.as_slice());
+	let first_error = from_fn(|| match log_parser.parse_next_event() {
+			Ok(entry) if !matches!(entry, FfmpegEvent::LogEOF) => Some(entry),
+			_ => None,
+		})
+		.find(|entry| matches!(entry, FfmpegEvent::Log(LogLevel::Error, _)));
+
+	if let Some(FfmpegEvent::Log(_, error)) = first_error {
+		bail!(error)
+	}
+
+	Ok(())
+}
+
+// Signed manifest URLs expire, so deferred jobs only live until the CDM shows up or the application exits
+const MAX_PENDING_DRM: usize = 500;
+
+struct MediaOrigin {
+	username: String,
+	content_type: ContentType,
+	content_id: u64,
+	media_id: u64,
+	tags: Vec<String>,
+}
+
+impl MediaOrigin {
+	fn license_url(&self) -> String {
+		format!("https://onlyfans.com/api2/v2/users/media/{}/drm/{}/{}?type=widevine",
+			self.media_id,
+			match self.content_type {
+				ContentType::Chats => "message",
+				_ => "post"
+			},
+			self.content_id
+		)
 	}
 }
 
+struct PostEdit {
+	content: content::Post,
+	known: HashSet<u64>,
+	text_changed: bool,
+}
+
+struct PendingDrm {
+	drm: DRM,
+	path: PathBuf,
+	origin: MediaOrigin,
+}
+
 pub struct ResolvedContentActions {
 	pub notify: bool,
 	pub download: bool,
 	pub like: bool,
 }
 
-impl<T: HasMedia + CanLike> ResolveContentActions<T> for MediaContentActions<ConcreteMediaSpecificSelection> {
+impl<T: HasMedia + CanLike + EventContent> ResolveContentActions<T> for MediaContentActions<ConcreteMediaSpecificSelection> {
 	type Resolved = ResolvedContentActions;
 	fn resolve(&self, data: &T) -> Self::Resolved {
 		let has_media = !data.media().is_empty();
 		let has_thumbnail = data.media().thumbnail().is_some();
+		let paid = data.price().is_some_and(|price| price > 0f32);
 
 		let resolver = |concrete: &ConcreteSelection<ConcreteMediaSpecificSelection>| match concrete {
 			ConcreteSelection::Toggle(toggle) => **toggle,
-			ConcreteSelection::Specific(specific) => match specific.media {
+			ConcreteSelection::Specific(specific) => (specific.paid || !paid) && match specific.media {
 				MediaSelection::Any => has_media,
 				MediaSelection::Thumbnail => has_thumbnail,
 				MediaSelection::None => !has_media,
@@ -214,6 +1676,21 @@ impl ResolveContentActions<content::Story> for StoryContentActions {
 	}
 }
 
+impl ResolveContentActions<structs::Notification> for ConcreteSelection<NotificationSpecificSelection> {
+	type Resolved = bool;
+	fn resolve(&self, data: &structs::Notification) -> Self::Resolved {
+		match self {
+			ConcreteSelection::Toggle(toggle) => **toggle,
+			ConcreteSelection::Specific(specific) => match data.sub_type() {
+				NotificationSubType::Promotion => specific.promotions,
+				NotificationSubType::Discount => specific.discounts,
+				NotificationSubType::PriceChange => specific.price_changes,
+				NotificationSubType::SubscriptionExpired | NotificationSubType::Other(_) => specific.other,
+			}
+		}
+	}
+}
+
 impl<T> ResolveContentActions<T> for Toggle {
 	type Resolved = bool;
 	fn resolve(&self, _data: &T) -> Self::Resolved { **self }
@@ -238,10 +1715,57 @@ impl Handler for Message {
 				info!("Stream message received: {:?}", msg);
 				msg.handle(context)
 			},
+			Message::Tagged(TaggedMessage::StreamStart(msg)) => {
+				info!("Stream start message received: {:?}", msg);
+				if !context.settings.read().unwrap().record_streams { return Ok(None) }
+
+				// Recordings last for hours, they are tracked for shutdown rather than as handling of the message
+				context.spawn({
+					let context = context.clone();
+					async move {
+						if let Ok(user) = context.client.get_user(msg.user_id).await {
+							context.record_stream(msg.stream_id, &user).await;
+						}
+					}
+				});
+				Ok(None)
+			},
+			Message::Tagged(TaggedMessage::StreamStop(msg)) => {
+				info!("Stream stop message received: {:?}", msg);
+				if context.recorder.stop(msg.stream_id) {
+					info!("Stopping the recording of stream {}", msg.stream_id);
+				}
+				Ok(None)
+			},
+			Message::Tagged(TaggedMessage::StreamUpdate(msg)) => {
+				if msg.is_finished && context.recorder.stop(msg.id) {
+					info!("Stream {} finished, stopping its recording", msg.id);
+				}
+				Ok(None)
+			},
 			Message::Tagged(TaggedMessage::PostPublished(msg)) => {
 				info!("Post message received: {:?}", msg);
 				msg.handle(context)
 			},
+			Message::Tagged(TaggedMessage::PostExpire(post_id)) => {
+				info!("Post expire message received: {post_id}");
+				Ok(Some(context.post_lane(post_id, {
+					let context = context.clone();
+					async move { context.expire_post(post_id).await; ready(()) }
+				})))
+			},
+			Message::Tagged(TaggedMessage::PostUpdated(post_id)) => {
+				info!("Post update message received: {post_id}");
+				Ok(Some(context.post_lane(post_id, {
+					let context = context.clone();
+					async move {
+						let edit = context.record_post_edit(post_id).await;
+						async move {
+							if let Some(edit) = edit { context.handle_post_edit(edit).await }
+						}
+					}
+				})))
+			},
 			Message::Tagged(TaggedMessage::Api2ChatMessage(msg)) => {
 				info!("Chat message received: {:?}", msg);
 				msg.handle(context)
@@ -250,6 +1774,43 @@ impl Handler for Message {
 				info!("Story message received: {:?}", msg);
 				msg.handle(context)
 			},
+			Message::Tagged(TaggedMessage::HasNewHints(has_new_hints)) => {
+				debug!("New hints message received: {has_new_hints}");
+				let announced = context.new_hints.swap(has_new_hints, Ordering::Relaxed);
+				if !has_new_hints || announced || !context.settings.read().unwrap().site_toasts { return Ok(None) }
+
+				let mut toast = NotificationContent::new();
+				toast
+				.title("OnlyFans")
+				.body("There are new hints on the site");
+
+				let _ = context.show(&toast)
+				.inspect_err(|err| error!("Error showing new hints notification: {err}"));
+				Ok(None)
+			},
+			Message::Tagged(TaggedMessage::Toasts(msg)) => {
+				info!("Site toast received: {:?}", msg);
+				msg.handle(context)
+			},
+			Message::Tagged(TaggedMessage::StoryTips(tip)) => {
+				info!("Story tip message received: {:?}", tip);
+				Ok(Some(context.spawn({
+					let context = context.clone();
+					async move { context.notify_tip(&tip.from_user.name, tip.story_user_id, tip.amount, tip.message.as_deref(), "a story").await }
+				})))
+			},
+			Message::StreamTips(tips) => {
+				info!("Stream tip message received: {:?}", tips);
+				let tip = tips.stream_tips;
+				Ok(Some(context.spawn({
+					let context = context.clone();
+					async move { context.notify_tip(&tip.from_user.name, tip.stream_user_id, tip.amount, tip.message.as_deref(), "a live stream").await }
+				})))
+			},
+			Message::StreamSummary(summary) => {
+				debug!("Stream summary received: {summary:?}");
+				Ok(None)
+			},
 			_ => Ok(None)
 		}
 	}
@@ -257,10 +1818,19 @@ impl Handler for Message {
 
 impl Handler for structs::Notification {
 	fn handle(self, context: &Context) -> anyhow::Result<Option<JoinHandle<()>>> {
+		context.record_event(&self.content, &self.user);
+		let notify = !self.catch_up || context.notify_caught_up();
+
+		if self.sub_type() == &NotificationSubType::SubscriptionExpired && context.flag_expired_subscription(&self.user.username) && notify {
+			return Ok(Some(context.spawn({
+				let context = context.clone();
+				async move { context.notify_expired_subscription(&self.user).await }
+			})))
+		}
+
 		Ok(
-			ContentActions::<NotificationMarker>::content_actions(&context.settings, &self.user.username)
-			.resolve(&self.content)
-			.then(|| tokio::spawn({
+			(notify && ContentActions::<NotificationMarker>::content_actions(&context.settings, &self.user.username).resolve(&self))
+			.then(|| context.spawn({
 				let context = context.clone();
 				async move { let _ = context.notify(&self.content, &self.user).await; }
 			})))
@@ -269,32 +1839,59 @@ impl Handler for structs::Notification {
 
 impl Handler for structs::Stream {
 	fn handle(self, context: &Context) -> anyhow::Result<Option<JoinHandle<()>>> {
+		if !context.subscriptions.is_subscribed(self.user.id) {
+			debug!("Ignoring stream from {}, not subscribed", self.user.username);
+			return Ok(None);
+		}
+
+		context.record_event(&self.content, &self.user);
+
+		if context.settings.read().unwrap().record_streams {
+			let recording = context.clone();
+			let (stream_id, user) = (self.content.id(), self.user.clone());
+			context.spawn(async move { recording.record_stream(stream_id, &user).await });
+		}
+
 		Ok(
 			ContentActions::<StreamMarker>::content_actions(&context.settings, &self.user.username)
 			.resolve(&self.content)
-			.then(|| tokio::spawn({
+			.then(|| context.spawn({
 				let context = context.clone();
-				async move { let _ = context.notify_with_thumbnail(&self.content, &self.user).await; }
+				async move { let _ = context.notify_with_thumbnail(&self.content, &self.user, false).await; }
 			})))
 	}
 }
 
 impl Handler for structs::PostPublished {
 	fn handle(self, context: &Context) -> anyhow::Result<Option<JoinHandle<()>>> {
-		Ok(Some(tokio::spawn({
+		context.post_authors.lock().unwrap().insert(self.id, self.user_id);
+
+		Ok(Some(context.spawn_in_lane(self.user_id, {
 			let context = context.clone();
 			async move {
-				if let Ok(content) = context.client.get_post(self.id).await {
+				let content = context.client.get_post(self.id).await.ok();
+				if let Some(content) = &content {
+					context.record_event(content, &content.author);
+				}
+
+				// Edits and expiries queued so far already run after this
+				context.post_authors.lock().unwrap().remove(&self.id);
+
+				async move {
+					let Some(content) = content else { return };
 					let actions = ContentActions::<PostMarker>::content_actions(&context.settings, &content.author.username)
 						.resolve(&content);
+					let actions = context.restrict_expired(actions, &content.author.username);
+
+					if actions.like {
+						context.like(&content, &content.author.username);
+					}
 
-					join3(
+					join(
 						Into::<OptionFuture<_>>::into(actions.notify
-						.then(|| context.notify_with_thumbnail(&content, &content.author).map(|_| ()))),
+						.then(|| context.notify_with_thumbnail(&content, &content.author, actions.download).map(|_| ()))),
 						Into::<OptionFuture<_>>::into(actions.download
 						.then(|| context.download(&content, &content.author))),
-						Into::<OptionFuture<_>>::into(actions.like
-						.then(|| context.like(&content))),
 					).await;
 				}
 			}
@@ -304,117 +1901,207 @@ impl Handler for structs::PostPublished {
 
 impl Handler for structs::Chat {
 	fn handle(self, context: &Context) -> anyhow::Result<Option<JoinHandle<()>>> {
+		context.record_event(&self.content, &self.from_user);
+
 		let actions = ContentActions::<MessageMarker>::content_actions(&context.settings, &self.from_user.username)
 			.resolve(&self.content);
+		let mut actions = context.restrict_expired(actions, &self.from_user.username);
+		if self.catch_up && !context.notify_caught_up() {
+			actions.notify = false;
+		}
+
+		if self.is_mass_message() {
+			match context.settings.read().unwrap().creator(&self.from_user.username).mass_messages {
+				MassMessages::Notify => (),
+				MassMessages::Download => actions.notify = false,
+				MassMessages::Ignore => actions = ResolvedContentActions { notify: false, download: false, like: false },
+			}
+		}
 
-		Ok(Some(tokio::spawn({
+		let mark_read = context.settings.read().unwrap().creator(&self.from_user.username).mark_messages_read;
+		let mass_message = self.is_mass_message();
+
+		Ok(Some(context.spawn({
 			let context = context.clone();
 			async move {
-				join3(
+				if actions.like {
+					context.like(&self.content, &self.from_user.username);
+				}
+
+				let (_, archived) = join(
 					Into::<OptionFuture<_>>::into(actions.notify
-					.then(|| context.notify_with_thumbnail(&self.content, &self.from_user).map(|_| ()))),
+					.then(|| context.notify_with_thumbnail(&self.content, &self.from_user, actions.download).map(|_| ()))),
 					Into::<OptionFuture<_>>::into(actions.download
 					.then(|| context.download(&self.content, &self.from_user))),
-					Into::<OptionFuture<_>>::into(actions.like
-					.then(|| context.like(&self.content))),
 				).await;
+
+				// Only once everything is archived, so the unread badge never hides a message whose media is missing
+				if mark_read && archived.unwrap_or(true) {
+					let _ = context.client.mark_chat_read(self.from_user.id).await;
+				}
+
+				context.auto_reply(&self.from_user, mass_message).await;
 			}
 		})))
 	}
 }
 
-impl Handler for Vec<structs::Story> {
+impl Handler for Vec<structs::SiteToast> {
 	fn handle(self, context: &Context) -> anyhow::Result<Option<JoinHandle<()>>> {
-		Ok(Some(tokio::spawn({
+		if !context.settings.read().unwrap().site_toasts { return Ok(None) }
+
+		for site_toast in self {
+			let mut toast = NotificationContent::new();
+			toast
+			.title(&site_toast.title)
+			.body(html2text(&site_toast.text));
+
+			if let Some(link) = &site_toast.link {
+				toast.attribution(link);
+			}
+
+			let _ = context.show(&toast)
+			.inspect_err(|err| error!("Error showing site toast {}: {err}", site_toast.id));
+		}
+
+		Ok(None)
+	}
+}
+
+impl Handler for Vec<structs::Story> {
+	fn handle(mut self, context: &Context) -> anyhow::Result<Option<JoinHandle<()>>> {
+		self.retain(|story| context.subscriptions.is_subscribed(story.user_id));
+		if self.is_empty() { return Ok(None) }
+
+		let creator = self.first()
+			.map(|story| story.user_id)
+			.filter(|user_id| self.iter().all(|story| story.user_id == *user_id));
+
+		let task = {
 			let context = context.clone();
 			async move {
-				join_all(self.iter().map(|story| async {
-					if let Ok(author) = context.client.get_user(story.user_id).await {
+				let authors = join_all(self.iter().map(|story| async {
+					let author = context.client.get_user(story.user_id).await.ok();
+					if let Some(author) = &author {
+						context.record_event(&story.content, author);
+					}
+					author
+				})).await;
+
+				async move {
+					let highlights = join_all(self.iter().zip(authors).map(|(story, author)| async {
+						let author = author?;
 						let actions = ContentActions::<StoryMarker>::content_actions(&context.settings, &author.username)
 							.resolve(&story.content);
+						let actions = context.restrict_expired(actions, &author.username);
+
+						if actions.like {
+							context.like(&story.content, &author.username);
+						}
 
-						join3(
+						join(
 							Into::<OptionFuture<_>>::into(actions.notify
-							.then(|| context.notify_with_thumbnail(&story.content, &author).map(|_| ()))),
+							.then(|| context.notify_with_thumbnail(&story.content, &author, actions.download).map(|_| ()))),
 							Into::<OptionFuture<_>>::into(actions.download
 							.then(|| context.download(&story.content, &author))),
-							Into::<OptionFuture<_>>::into(actions.like
-							.then(|| context.like(&story.content))),
 						).await;
+
+						(actions.download && context.settings.read().unwrap().creator(&author.username).download_highlights)
+							.then_some(author)
+					})).await;
+
+					// Once per creator, however many of their stories the event holds
+					let mut seen = HashSet::new();
+					for author in highlights.into_iter().flatten().filter(|author| seen.insert(author.id)) {
+						let _ = context.download_highlights(&author).await;
 					}
-				})).await;
+				}
 			}
-		})))
+		};
+
+		// Stories of several creators at once are not ordered against any of them
+		Ok(Some(match creator {
+			Some(user_id) => context.spawn_in_lane(user_id, task),
+			None => context.spawn(async move { task.await.await })
+		}))
 	}
 }
 
-trait ToToast {
-	fn to_toast(&self) -> Toast;
-	fn setup_notification(&self, user: &User) -> Toast
+trait ToNotification {
+	fn to_notification(&self) -> NotificationContent;
+	fn setup_notification(&self, user: &User) -> NotificationContent
 	where Self: content::Content,
 	{
 		let header = Self::content_type().to_string();
-		let mut toast = self.to_toast();
+		let mut toast = self.to_notification();
 		toast
-		.header(Header::new(&header, &header, ""))
 		.group(header)
 		.tag(self.id().to_string())
 		.timestamp(self.timestamp())
-		.text1(&user.name);
+		.title(&user.name);
 	
 		toast
 	}
 }
 
-impl ToToast for content::Post {
-	fn to_toast(&self) -> Toast {
-		let mut toast = Toast::new();
+impl ToNotification for content::Post {
+	fn to_notification(&self) -> NotificationContent {
+		let mut toast = NotificationContent::new();
 		toast
-		.text2(html2text(&self.text));
+		.body(html2text(&self.text));
 
 		if let Some(price) = self.price && price > 0f32 {
-			toast
-			.text3(Text::new(format!("${price:.2}"))
-			.with_placement(TextPlacement::Attribution));
+			toast.attribution(format!("${price:.2}"));
 		}
 
 		toast
 	}
 }
 
-impl ToToast for content::Chat {
-	fn to_toast(&self) -> Toast {
-		let mut toast = Toast::new();
-		toast.text2(html2text(&self.text));
+impl ToNotification for content::Chat {
+	fn to_notification(&self) -> NotificationContent {
+		let mut toast = NotificationContent::new();
+		let text = html2text(&self.text);
+		let voice = self.media()
+			.iter()
+			.find(|media| media.media_type() == &MediaType::Audio)
+			.map(|audio| match audio.duration() {
+				Some(duration) => format!("Voice message ({}:{:02})", duration.as_secs() / 60, duration.as_secs() % 60),
+				None => "Voice message".to_string()
+			});
+
+		toast.body(match voice {
+			Some(voice) if text.trim().is_empty() => voice,
+			Some(voice) => format!("{voice}\n{text}"),
+			None => text
+		});
 
 		if let Some(price) = self.price && price > 0f32 {
-			toast
-			.text3(Text::new(format!("${price:.2}"))
-			.with_placement(TextPlacement::Attribution));
+			toast.attribution(format!("${price:.2}"));
 		}
 
 		toast
 	}
 }
 
-impl ToToast for content::Story {
-	fn to_toast(&self) -> Toast {
-		Toast::new()
+impl ToNotification for content::Story {
+	fn to_notification(&self) -> NotificationContent {
+		NotificationContent::new()
 	}
 }
 
-impl ToToast for content::Notification {
-	fn to_toast(&self) -> Toast {
-		let mut toast = Toast::new();
-		toast.text2(html2text(&self.text));
+impl ToNotification for content::Notification {
+	fn to_notification(&self) -> NotificationContent {
+		let mut toast = NotificationContent::new();
+		toast.body(html2text(&self.text));
 		toast
 	}
 }
 
-impl ToToast for content::Stream {
-	fn to_toast(&self) -> Toast {
-		let mut toast = Toast::new();
-		toast.text2(html2text(&self.description));
+impl ToNotification for content::Stream {
+	fn to_notification(&self) -> NotificationContent {
+		let mut toast = NotificationContent::new();
+		toast.body(html2text(&self.description));
 		toast
 	}
 }
@@ -1,12 +1,16 @@
 use log::*;
-use tokio::{fs as tfs, io::copy_buf};
-use tokio_util::io::StreamReader;
-use std::{fs, future::Future, io::{Error, ErrorKind}, path::{Path, PathBuf}, sync::{Mutex, OnceLock}, time::SystemTime};
+use tokio::{fs as tfs, io::AsyncWriteExt, task::spawn_blocking};
+use std::{fs, future::Future, io, iter, num::NonZeroUsize, path::{Path, PathBuf}, sync::{Mutex, OnceLock}, time::{Duration, Instant, SystemTime}};
 use anyhow::{anyhow, Context};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use md5::{Digest, Md5};
+use thiserror::Error;
+use chrono::{DateTime, Utc};
 use filetime::{set_file_mtime, FileTime};
 use futures::TryStreamExt;
-use of_client::{content, httpdate::parse_http_date, media::Thumbnail, reqwest::{header, IntoUrl, StatusCode, Url}, user::User, OFClient};
-use winrt_toast::{register, Toast, ToastManager};
+use lru::LruCache;
+use of_client::{content::{self, ContentType}, httpdate::parse_http_date, media::{MediaType, Thumbnail}, reqwest::{header::{self, HeaderMap}, IntoUrl, StatusCode, Url}, scheduler::Priority, user::User, OFClient};
+use crate::{notifier::{forward, notifier, NotificationContent}, settings::PathSettings, watchdog::Progress};
 
 pub fn filename_from_url(url: &Url) -> Option<&str> {
 	url
@@ -15,6 +19,124 @@ pub fn filename_from_url(url: &Url) -> Option<&str> {
 	.and_then(|name| (!name.is_empty()).then_some(name))
 }
 
+// Audio is often served from URLs with video or no extensions at all
+fn audio_extension(content_type: &str) -> Option<&'static str> {
+	match content_type.split(';').next()?.trim() {
+		"audio/mp4" | "audio/x-m4a" | "audio/aac" => Some("m4a"),
+		"audio/mpeg" | "audio/mp3" => Some("mp3"),
+		"audio/ogg" => Some("ogg"),
+		"audio/webm" => Some("webm"),
+		"audio/wav" | "audio/x-wav" => Some("wav"),
+		_ => None
+	}
+}
+
+/// When `path` was last modified, or the file an earlier download corrected its extension to for audio
+async fn downloaded_file(path: &Path) -> Option<(PathBuf, SystemTime)> {
+	let candidates = ["m4a", "mp3", "ogg", "webm", "wav"].map(|ext| path.with_extension(ext));
+	for candidate in iter::once(path.to_path_buf()).chain(candidates) {
+		if let Ok(modified) = tfs::metadata(&candidate).await.and_then(|metadata| metadata.modified()) {
+			return Some((candidate, modified))
+		}
+	}
+
+	None
+}
+
+/// What a downloaded file's location can depend on
+pub struct MediaLocation<'a> {
+	pub username: &'a str,
+	pub content_type: ContentType,
+	pub media_type: &'a MediaType,
+	pub content_id: u64,
+	pub timestamp: DateTime<Utc>,
+}
+
+/// Resolves the directory media is downloaded to from the `paths` settings.
+/// The template is a `/` separated list of directories ending with `{filename}`, which the downloads fill in themselves
+#[derive(Debug, Clone)]
+pub struct PathResolver {
+	root: PathBuf,
+	template: String,
+}
+
+impl From<&PathSettings> for PathResolver {
+	fn from(settings: &PathSettings) -> Self {
+		Self { root: settings.root.clone(), template: settings.template.clone() }
+	}
+}
+
+impl PathResolver {
+	/// Keeps the downloads of `account` apart from those of other accounts
+	pub fn within(mut self, account: Option<&str>) -> Self {
+		if let Some(account) = account { self.root.push(account) }
+		self
+	}
+
+	pub fn media_dir(&self, location: &MediaLocation) -> PathBuf {
+		let media_type = match location.media_type {
+			MediaType::Photo => "Images",
+			MediaType::Audio => "Audios",
+			MediaType::Video | MediaType::Gif => "Videos",
+		};
+
+		// Values never introduce directories of their own
+		let value = |value: &str| value.replace(['/', '\\'], "_");
+
+		self.template
+			.split('/')
+			.filter(|segment| !segment.is_empty() && *segment != "{filename}")
+			.fold(self.root.clone(), |path, segment| path.join(
+				segment
+				.replace("{username}", &value(location.username))
+				.replace("{content_type}", &location.content_type.to_string())
+				.replace("{media_type}", media_type)
+				.replace("{date}", &location.timestamp.format("%Y-%m-%d").to_string())
+				.replace("{year}", &location.timestamp.format("%Y").to_string())
+				.replace("{month}", &location.timestamp.format("%m").to_string())
+				.replace("{content_id}", &location.content_id.to_string())
+			))
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Fetched {
+	pub bytes: u64,
+	/// Differs from the requested path when the extension was corrected from the response's content type
+	pub path: PathBuf,
+}
+
+const MEDIA_CACHE_SIZE: usize = 256;
+const MEDIA_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+struct CachedFile {
+	path: PathBuf,
+	fetched: Instant,
+}
+
+fn media_cache() -> &'static Mutex<LruCache<String, CachedFile>> {
+	static CACHE: OnceLock<Mutex<LruCache<String, CachedFile>>> = OnceLock::new();
+	CACHE.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(MEDIA_CACHE_SIZE).unwrap())))
+}
+
+// Skips the request entirely while a recently fetched copy of the URL is still on disk
+async fn fetch_cached(client: &OFClient, url: &str, path: &Path) -> anyhow::Result<PathBuf> {
+	{
+		let mut cache = media_cache().lock().unwrap();
+		if let Some(cached) = cache.get(url) {
+			if cached.fetched.elapsed() < MEDIA_CACHE_TTL && cached.path.exists() {
+				return Ok(cached.path.clone())
+			}
+
+			cache.pop(url);
+		}
+	}
+
+	let fetched = fetch_file(&client.with_priority(Priority::Interactive), url, path, &Progress::default(), true).await?;
+	media_cache().lock().unwrap().put(url.to_string(), CachedFile { path: fetched.path.clone(), fetched: Instant::now() });
+	Ok(fetched.path)
+}
+
 pub async fn get_avatar(user: &User, client: &OFClient) -> anyhow::Result<Option<PathBuf>> {
 	match &user.avatar {
 		Some(avatar) => {
@@ -37,9 +159,7 @@ pub async fn get_avatar(user: &User, client: &OFClient) -> anyhow::Result<Option
 				.join(filename)
 				.with_extension(ext);
 
-			fetch_file(client, avatar, &path).await?;
-			Ok(Some(path))
-			
+			fetch_cached(client, avatar, &path).await.map(Some)
 		},
 		None => Ok(None)
 	}
@@ -54,43 +174,125 @@ pub async fn get_thumbnail<T: content::HasMedia>(content: &T, client: &OFClient,
 				.ok_or_else(|| anyhow!("Filename unknown"))?;
 
 			let path = temp_dir.join(filename);
-			fetch_file(client, thumb, &path).await?;
-			Ok(Some(path))
+			fetch_cached(client, thumb, &path).await.map(Some)
 		},
 		None => Ok(None)
 	}
 }
 
-pub async fn handle_download<'a, F, Fut>(path: &'a Path, modified: Option<SystemTime>, fetch_fn: F) -> anyhow::Result<()>
+pub async fn handle_download<'a, T, F, Fut>(path: &'a Path, modified: Option<SystemTime>, fetch_fn: F) -> anyhow::Result<T>
 where
 	F: FnOnce() -> Fut,
-	Fut: Future<Output = anyhow::Result<()>> + 'a,
+	Fut: Future<Output = anyhow::Result<T>> + 'a,
 {
-	if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
+	if let Some(parent) = path.parent() { tfs::create_dir_all(parent).await?; }
 
-	fetch_fn().await
+	let output = fetch_fn().await
 	.inspect_err(|err| error!("Downloading {:?} failed: {err}", path.file_name().unwrap()))?;
 
 	if let Some(date) = modified {
-		set_file_mtime(path, FileTime::from_system_time(date))
+		let path = path.to_path_buf();
+		spawn_blocking(move || set_file_mtime(path, FileTime::from_system_time(date)))
+		.await?
 		.context("Setting file modified date")?;
 	}
 
-	Ok(())
+	Ok(output)
+}
+
+/// A download that doesn't match what the server announced, restarted like a stalled one
+#[derive(Error, Debug)]
+pub enum Mismatch {
+	#[error("was incomplete, received {received} of {expected} bytes")]
+	Size { expected: u64, received: u64 },
+	#[error("did not match its MD5 checksum")]
+	Checksum,
+}
+
+/// MD5 digest of the whole file announced by `Content-MD5`, or by an `ETag` that is a plain MD5 digest,
+/// as S3 and CloudFront send for files uploaded in one part. `Content-MD5` of a partial response only covers the part
+pub fn expected_md5(headers: &HeaderMap, partial: bool) -> Option<[u8; 16]> {
+	let content_md5 = headers.get("content-md5")
+		.filter(|_| !partial)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| BASE64_STANDARD.decode(value.trim()).ok());
+
+	let etag = || headers.get(header::ETAG)
+		.and_then(|value| value.to_str().ok())
+		.filter(|value| !value.starts_with("W/"))
+		.map(|value| value.trim_matches('"'))
+		.filter(|value| value.len() == 32)
+		.and_then(|value| base16::decode(value).ok());
+
+	content_md5.or_else(etag).and_then(|digest| digest.try_into().ok())
+}
+
+async fn file_md5(path: &Path) -> io::Result<[u8; 16]> {
+	let path = path.to_path_buf();
+	spawn_blocking(move || {
+		let mut hasher = Md5::new();
+		io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+		Ok(hasher.finalize().into())
+	})
+	.await
+	.map_err(io::Error::other)?
+}
+
+// Total size of the content from a `Content-Range: bytes 100-999/1000` header
+fn content_range_total(content_range: &str) -> Option<u64> {
+	content_range
+	.rsplit_once('/')
+	.and_then(|(_, total)| total.parse().ok())
 }
 
-pub async fn fetch_file<U: IntoUrl>(client: &OFClient, link: U, path: &Path) -> anyhow::Result<()> {
+/// Downloads `link` to `path`, touching `progress` whenever data arrives.
+/// Fails with [`Mismatch`] when the file is incomplete, or doesn't match its checksum if `verify` is set
+pub async fn fetch_file<U: IntoUrl>(client: &OFClient, link: U, path: &Path, progress: &Progress, verify: bool) -> anyhow::Result<Fetched> {
 	let url = link.into_url()?;
 
-	let response = match path.metadata().and_then(|metadata| metadata.modified()) {
-		Ok(date) => {
+	// The extension is replaced, so a partial download is found even if the extension gets corrected below
+	let temp_path = path.with_extension("temp");
+	let partial = tfs::metadata(&temp_path).await.map_or(0, |metadata| metadata.len());
+
+	let response = match downloaded_file(path).await {
+		Some((existing, date)) => {
 			let response = client.get_if_modified_since(url, date).await?;
-			if response.status() == StatusCode::NOT_MODIFIED { return Ok(()) }
+			if response.status() == StatusCode::NOT_MODIFIED { return Ok(Fetched { bytes: 0, path: existing }) }
 			response
 		},
-		Err(_) => client.get(url).await?
+		None if partial > 0 => match client.get_range(url.clone(), partial).await {
+			Err(err) if err.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE) => {
+				let _ = tfs::remove_file(&temp_path).await;
+				client.get(url).await?
+			},
+			response => response?
+		},
+		None => client.get(url).await?
+	};
+
+	let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+	let expected = match resumed {
+		true => response
+			.headers()
+			.get(header::CONTENT_RANGE)
+			.and_then(|header| header.to_str().ok())
+			.and_then(content_range_total),
+		false => response.content_length()
 	};
 
+	if resumed {
+		debug!("Resuming download of {path:?} from byte {partial}");
+	}
+
+	let digest = verify.then(|| expected_md5(response.headers(), resumed)).flatten();
+
+	let path = &response
+		.headers()
+		.get(header::CONTENT_TYPE)
+		.and_then(|header| header.to_str().ok())
+		.and_then(audio_extension)
+		.map_or_else(|| path.to_path_buf(), |ext| path.with_extension(ext));
+
 	let modified = response
 		.headers()
 		.get(header::LAST_MODIFIED)
@@ -98,36 +300,51 @@ pub async fn fetch_file<U: IntoUrl>(client: &OFClient, link: U, path: &Path) ->
 		.and_then(|s| parse_http_date(s).ok());
 
 	handle_download(path, modified, || async move {
-		let temp_path = path.with_extension("temp");
-		let mut file = tfs::File::from_std(fs::File::create(&temp_path)?);
-		let mut reader = StreamReader::new(
-			response
-			.bytes_stream()
-			.map_err(|e| Error::new(ErrorKind::Other, e))
-		);
-	
-		copy_buf(&mut reader, &mut file).await?;
+		let mut file = match resumed {
+			true => tfs::OpenOptions::new().append(true).open(&temp_path).await?,
+			false => tfs::File::create(&temp_path).await?
+		};
+
+		let mut stream = response.bytes_stream();
+		while let Some(chunk) = stream.try_next().await? {
+			file.write_all(&chunk).await?;
+			progress.advance(chunk.len() as u64);
+		}
+
+		file.flush().await?;
+		drop(file);
+
+		// A short file is kept to be resumed later, anything else can't be trusted
+		let bytes = tfs::metadata(&temp_path).await?.len();
+		if let Some(expected) = expected && bytes != expected {
+			if bytes > expected { tfs::remove_file(&temp_path).await?; }
+			return Err(Mismatch::Size { expected, received: bytes }.into());
+		}
+
+		if let Some(digest) = digest && file_md5(&temp_path).await? != digest {
+			tfs::remove_file(&temp_path).await?;
+			return Err(Mismatch::Checksum.into());
+		}
 	
-		fs::rename(&temp_path, path).map_err(Into::into)
+		tfs::rename(&temp_path, path).await?;
+		Ok(Fetched { bytes, path: path.clone() })
 	}).await
 	.inspect_err(|err| error!("Download failed: {err}"))
 }
 
-pub fn show_notification(toast: &Toast) -> winrt_toast::Result<()> {
-	static MANAGER: OnceLock<Mutex<ToastManager>> = OnceLock::new();
-	let manager_mutex = MANAGER.get_or_init(|| {
-		let aum_id = "OFNotifier";
-		let icon_path = Path::new("icons").join("icon.ico").canonicalize()
-			.inspect_err(|err| error!("{err}"))
-			.unwrap();
-	
-		register(aum_id, "OF notifier", Some(icon_path.as_path()))
-		.inspect_err(|err| error!("{err}"))
-		.unwrap();
-		
-		Mutex::new(ToastManager::new(aum_id))
-	});
+/// Opens a file with the application the OS associates with it
+pub fn open_path(path: &Path) -> io::Result<()> {
+	#[cfg(target_os = "windows")]
+	let mut command = std::process::Command::new("explorer");
+	#[cfg(target_os = "macos")]
+	let mut command = std::process::Command::new("open");
+	#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+	let mut command = std::process::Command::new("xdg-open");
+
+	command.arg(path).spawn().map(|_| ())
+}
 
-	let manager = manager_mutex.lock().unwrap();
-	manager.show(toast)
+pub fn show_notification(content: &NotificationContent) -> anyhow::Result<()> {
+	forward(content);
+	notifier().show(content)
 }
\ No newline at end of file
@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, fmt, fs, io, path::{Path, PathBuf}, str::FromStr};
+use serde::{Deserialize, Serialize};
+use serde_json::{ser::PrettyFormatter, Serializer};
+use thiserror::Error;
+use crate::{auth_path, credentials, get_profile_auth_params, AuthParseError};
+
+const DOMAIN: &str = "onlyfans.com";
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+	#[error("{0}")]
+	IO(#[from] io::Error),
+	#[error("{0}")]
+	Parse(#[from] serde_json::Error),
+	#[error("Not a Cookie-Editor JSON export or a Netscape cookies.txt file")]
+	UnknownFormat,
+	#[error("The file contains no cookies for {DOMAIN}")]
+	NoCookies,
+	#[error("{0}")]
+	Auth(#[from] AuthParseError),
+	#[error("Unknown browser {0}, expected firefox, chrome or edge")]
+	UnknownBrowser(String),
+	#[error("Error reading cookies from {0}: {1}")]
+	Browser(Browser, String),
+	#[error("No browser has a session for {DOMAIN}, log in first")]
+	NoSession,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser { Firefox, Chrome, Edge }
+
+impl Browser {
+	pub const ALL: [Browser; 3] = [Browser::Firefox, Browser::Chrome, Browser::Edge];
+}
+
+impl FromStr for Browser {
+	type Err = ImportError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"firefox" => Ok(Browser::Firefox),
+			"chrome" => Ok(Browser::Chrome),
+			"edge" => Ok(Browser::Edge),
+			_ => Err(ImportError::UnknownBrowser(s.to_string()))
+		}
+	}
+}
+
+impl fmt::Display for Browser {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Browser::Firefox => "Firefox",
+			Browser::Chrome => "Chrome",
+			Browser::Edge => "Edge",
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieFormat { CookieEditor, Netscape }
+
+#[derive(Deserialize)]
+struct ExportedCookie {
+	domain: String,
+	name: String,
+	value: String,
+}
+
+fn is_onlyfans(domain: &str) -> bool {
+	let domain = domain.trim_start_matches('.');
+	domain == DOMAIN || domain.ends_with(".onlyfans.com")
+}
+
+pub fn detect_format(data: &str) -> Option<CookieFormat> {
+	let data = data.trim_start();
+	if data.starts_with('[') { Some(CookieFormat::CookieEditor) }
+	else if data.starts_with("# Netscape HTTP Cookie File") || data.starts_with("# HTTP Cookie File") || data.lines().any(|line| line.split('\t').count() == 7) {
+		Some(CookieFormat::Netscape)
+	}
+	else { None }
+}
+
+fn cookie_editor(data: &str) -> Result<Vec<(String, String)>, ImportError> {
+	Ok(
+		serde_json::from_str::<Vec<ExportedCookie>>(data)?
+		.into_iter()
+		.filter(|cookie| is_onlyfans(&cookie.domain))
+		.map(|cookie| (cookie.name, cookie.value))
+		.collect()
+	)
+}
+
+fn netscape(data: &str) -> Vec<(String, String)> {
+	data
+	.lines()
+	// HttpOnly cookies are prefixed like a comment
+	.map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+	.filter(|line| !line.starts_with('#'))
+	.filter_map(|line| {
+		let fields = line.trim_end_matches('\r').split('\t').collect::<Vec<_>>();
+		match fields[..] {
+			[domain, _, _, _, _, name, value] if is_onlyfans(domain) => Some((name.to_string(), value.to_string())),
+			_ => None
+		}
+	})
+	.collect()
+}
+
+/// Cookies for onlyfans.com from an exported cookie file, formatted like the `cookie` field of the auth file
+pub fn parse_cookies(data: &str) -> Result<String, ImportError> {
+	let cookies = match detect_format(data).ok_or(ImportError::UnknownFormat)? {
+		CookieFormat::CookieEditor => cookie_editor(data)?,
+		CookieFormat::Netscape => netscape(data),
+	};
+
+	join_cookies(cookies)
+}
+
+fn join_cookies(cookies: Vec<(String, String)>) -> Result<String, ImportError> {
+	// Later duplicates (e.g. a subdomain's cookie) shouldn't produce the same name twice
+	let cookies = cookies.into_iter().collect::<BTreeMap<_, _>>();
+	if cookies.is_empty() { return Err(ImportError::NoCookies) }
+
+	Ok(
+		cookies
+		.into_iter()
+		.map(|(name, value)| format!("{name}={value}"))
+		.collect::<Vec<_>>()
+		.join("; ")
+	)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct AuthFields {
+	#[serde(default)]
+	cookie: String,
+	#[serde(default)]
+	x_bc: String,
+	#[serde(default)]
+	user_agent: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthFile { auth: AuthFields }
+
+pub struct Imported {
+	pub path: PathBuf,
+	/// Set when the auth data can't be used yet, e.g. because x_bc is missing
+	pub problem: Option<AuthParseError>,
+}
+
+/// Writes the cookies of an exported cookie file into the auth file of `profile`.
+/// Cookie exports don't contain x_bc or the user agent, so those are kept from the existing auth file unless given.
+pub fn import(source: &Path, profile: Option<&str>, user_agent: Option<String>, x_bc: Option<String>) -> Result<Imported, ImportError> {
+	let cookie = parse_cookies(&fs::read_to_string(source)?)?;
+	write_auth(cookie, profile, user_agent, x_bc)
+}
+
+/// Cookies for onlyfans.com stored by `browser`, in all of its profiles
+#[cfg(feature = "browser-import")]
+fn browser_cookies(browser: Browser) -> Result<Vec<(String, String)>, ImportError> {
+	let domains = Some(vec![DOMAIN.to_string()]);
+	let cookies = match browser {
+		Browser::Firefox => rookie::firefox(domains),
+		Browser::Chrome => rookie::chrome(domains),
+		Browser::Edge => rookie::edge(domains),
+	}
+	.map_err(|err| ImportError::Browser(browser, err.to_string()))?;
+
+	Ok(
+		cookies
+		.into_iter()
+		.filter(|cookie| is_onlyfans(&cookie.domain))
+		.map(|cookie| (cookie.name, cookie.value))
+		.collect()
+	)
+}
+
+/// Writes the OnlyFans session cookies of `browser` into the auth file of `profile`, or of the first browser that is logged in.
+/// Browsers don't store x_bc or the user agent with their cookies, so those are kept from the existing auth file unless given.
+/// Returns the browser the cookies came from
+#[cfg(feature = "browser-import")]
+pub fn from_browser(browser: Option<Browser>, profile: Option<&str>, user_agent: Option<String>, x_bc: Option<String>) -> Result<(Browser, Imported), ImportError> {
+	let candidates = match browser {
+		Some(browser) => vec![browser],
+		None => Browser::ALL.to_vec()
+	};
+
+	for browser in candidates {
+		let cookies = match browser_cookies(browser) {
+			Ok(cookies) => cookies,
+			Err(err) => {
+				log::debug!("{err}");
+				continue
+			}
+		};
+
+		// Without the session cookie the browser isn't logged in
+		if !cookies.iter().any(|(name, _)| name == "sess") { continue }
+		return Ok((browser, write_auth(join_cookies(cookies)?, profile, user_agent, x_bc)?));
+	}
+
+	Err(ImportError::NoSession)
+}
+
+pub(crate) fn write_auth(cookie: String, profile: Option<&str>, user_agent: Option<String>, x_bc: Option<String>) -> Result<Imported, ImportError> {
+	let mut auth = credentials::read(profile).ok()
+		.and_then(|data| serde_json::from_str::<AuthFile>(&data).ok())
+		.map(|file| file.auth)
+		.unwrap_or_default();
+
+	auth.cookie = cookie;
+	if let Some(user_agent) = user_agent { auth.user_agent = user_agent }
+	if let Some(x_bc) = x_bc { auth.x_bc = x_bc }
+
+	let mut data = Vec::new();
+	AuthFile { auth }.serialize(&mut Serializer::with_formatter(&mut data, PrettyFormatter::with_indent(b"\t")))?;
+	let data = String::from_utf8(data).unwrap();
+
+	let path = auth_path(profile);
+	match credentials::write(profile, &data) {
+		Err(AuthParseError::IO(err)) if err.kind() == io::ErrorKind::NotFound => {
+			if let Some(parent) = path.parent() { fs::create_dir_all(parent)? }
+			fs::write(&path, &data)?;
+		},
+		result => result?
+	}
+
+	let problem = get_profile_auth_params(profile).err();
+	Ok(Imported { path, problem })
+}
@@ -0,0 +1,83 @@
+use std::{collections::HashMap, future::Future, hash::Hash, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}};
+use futures::{channel::oneshot, future::Shared, FutureExt};
+use tokio::task::JoinHandle;
+
+struct Lane {
+	id: u64,
+	tail: Shared<oneshot::Receiver<()>>,
+}
+
+struct Turn<K: Hash + Eq> {
+	lanes: Arc<Lanes<K>>,
+	key: K,
+	id: u64,
+	_done: oneshot::Sender<()>,
+}
+
+impl<K: Hash + Eq> Drop for Turn<K> {
+	// Runs when the task completes or unwinds, letting the next one in.
+	// Nothing was queued behind this task if the lane still ends with it, so the lane can go
+	fn drop(&mut self) {
+		let mut lanes = self.lanes.lanes.lock().unwrap();
+		if lanes.get(&self.key).is_some_and(|lane| lane.id == self.id) {
+			lanes.remove(&self.key);
+		}
+	}
+}
+
+/// Runs tasks sharing a key one after another, in the order they were queued,
+/// while tasks of different keys still run concurrently
+pub struct Lanes<K> {
+	lanes: Mutex<HashMap<K, Lane>>,
+	next_id: AtomicU64,
+}
+
+impl<K> Default for Lanes<K> {
+	fn default() -> Self {
+		Self { lanes: Mutex::default(), next_id: AtomicU64::default() }
+	}
+}
+
+impl<K: Hash + Eq + Clone + Send + 'static> Lanes<K> {
+	/// Spawns `task` once every task queued before it under `key` has finished
+	pub fn run<F>(self: &Arc<Self>, key: K, task: F) -> JoinHandle<()>
+	where F: Future<Output = ()> + Send + 'static {
+		tokio::spawn(self.queue(key, task))
+	}
+
+	/// Takes the place of `task` in the lane right away, the returned future runs it once it is its turn
+	/// and leaves the lane as soon as it finished. Dropping the future gives up its place
+	pub fn queue<F>(self: &Arc<Self>, key: K, task: F) -> impl Future<Output = F::Output> + Send + 'static
+	where F: Future + Send + 'static, F::Output: Send {
+		let (done, tail) = oneshot::channel::<()>();
+
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+		let previous = self.lanes.lock().unwrap()
+			.insert(key.clone(), Lane { id, tail: tail.shared() })
+			.map(|lane| lane.tail);
+
+		let turn = Turn { lanes: self.clone(), key, id, _done: done };
+		async move {
+			let _turn = turn;
+			if let Some(previous) = previous {
+				let _ = previous.await;
+			}
+
+			task.await
+		}
+	}
+
+	/// Whether tasks are still queued or running under `key`
+	pub fn is_busy(&self, key: &K) -> bool {
+		self.lanes.lock().unwrap().contains_key(key)
+	}
+
+	pub fn len(&self) -> usize {
+		self.lanes.lock().unwrap().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.lanes.lock().unwrap().is_empty()
+	}
+}
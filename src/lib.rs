@@ -1,14 +1,48 @@
 #![feature(let_chains)]
 
 pub mod helpers;
+pub mod notifier;
+pub mod catalog;
+pub mod catchup;
+pub mod backfill;
 pub mod handlers;
 pub mod settings;
+pub mod stats;
+pub mod control;
+pub mod events;
+pub mod relay;
+pub mod mqtt;
+pub mod plugins;
+pub mod pending;
+pub mod session;
+pub mod doctor;
+pub mod credentials;
+pub mod import;
+pub mod setup;
+pub mod likes;
+pub mod lanes;
+pub mod watchdog;
+pub mod dashboard;
+pub mod downloads;
+pub mod report;
+pub mod recorder;
+pub mod renewals;
+pub mod subscriptions;
+pub mod search;
+pub mod storage;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "sentry")]
+pub mod error_reporting;
+#[cfg(feature = "scripting")]
+pub mod scripting;
 
 use log::*;
-use std::{fs::{self, File}, io, sync::Arc};
+use std::{fs::{self, File}, io, path::PathBuf, sync::Arc, time::Duration};
 use cookie::{Cookie, ParseError};
 use of_client::{reqwest_cookie_store::{CookieStore, CookieStoreRwLock}, widevine::{Cdm, Device}, OFClient, RequestHeaders};
 use reqwest::Url;
+use settings::NetworkSettings;
 use serde::{Deserialize, Deserializer, de::Error};
 use thiserror::Error;
 
@@ -17,7 +51,13 @@ pub enum FileParseError {
 	#[error("{0}")]
 	IO(#[from] io::Error),
 	#[error("{0}")]
-	Parse(#[from] serde_json::Error)
+	Parse(#[from] serde_json::Error),
+	#[cfg(feature = "toml")]
+	#[error("{0}")]
+	Toml(#[from] toml::de::Error),
+	#[cfg(feature = "yaml")]
+	#[error("{0}")]
+	Yaml(#[from] serde_yaml::Error)
 }
 
 #[derive(Error, Debug)]
@@ -29,7 +69,9 @@ pub enum AuthParseError {
 	#[error("{0}")]
 	CookieParse(#[from] ParseError),
 	#[error("Cookie is missing '{0}' field")]
-	IncompleteCookie(&'static str)
+	IncompleteCookie(&'static str),
+	#[error("{0}")]
+	Keyring(#[from] keyring::Error)
 }
 
 fn non_empty_str<'de, D>(deserializer: D) -> Result<&'de str, D::Error>
@@ -60,7 +102,36 @@ impl From<AuthParams> for RequestHeaders {
 	}
 }
 
+const AUTH_DIR: &str = "auth";
+
+/// Auth file of `profile`, `auth.json` when no profile is selected
+pub fn auth_path(profile: Option<&str>) -> PathBuf {
+	match profile {
+		Some(profile) => PathBuf::from(AUTH_DIR).join(profile).with_extension("json"),
+		None => PathBuf::from("auth.json")
+	}
+}
+
+/// Names of the profiles in the auth directory, sorted alphabetically
+pub fn auth_profiles() -> Vec<String> {
+	let mut profiles = fs::read_dir(AUTH_DIR)
+		.into_iter()
+		.flatten()
+		.filter_map(Result::ok)
+		.map(|entry| entry.path())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+		.filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+		.collect::<Vec<_>>();
+
+	profiles.sort();
+	profiles
+}
+
 pub fn get_auth_params() -> Result<AuthParams, AuthParseError> {
+	get_profile_auth_params(None)
+}
+
+pub fn get_profile_auth_params(profile: Option<&str>) -> Result<AuthParams, AuthParseError> {
 	#[derive(Debug, Deserialize)]
 	struct AuthFileInner<'a> {
 		#[serde[borrow]]
@@ -77,8 +148,8 @@ pub fn get_auth_params() -> Result<AuthParams, AuthParseError> {
 	#[derive(Deserialize)]
 	struct AuthFile<'a> { #[serde(borrow)] auth: AuthFileInner<'a> }
 
-	let data = fs::read_to_string("auth.json")
-		.inspect_err(|err| error!("Error reading auth file: {err}"))?;
+	let data = credentials::read(profile)
+		.inspect_err(|err| error!("Error reading auth file {:?}: {err}", auth_path(profile)))?;
 
 	let parsed = serde_json::from_str::<AuthFile>(&data)
 		.inspect_err(|err| error!("Error parsing auth data: {err}"))?
@@ -118,9 +189,27 @@ pub fn get_auth_params() -> Result<AuthParams, AuthParseError> {
 }
 
 pub fn init_client() -> anyhow::Result<OFClient> {
+	init_client_with(&NetworkSettings::default(), None)
+}
+
+pub fn init_client_with(network: &NetworkSettings, profile: Option<&str>) -> anyhow::Result<OFClient> {
 	info!("Reading authentication parameters");
-	let auth_params = get_auth_params()?;
-	let client = OFClient::new(auth_params)?;
+	let auth_params = get_profile_auth_params(profile)?;
+
+	let mut builder = OFClient::builder(auth_params)
+		.pool_idle_timeout(network.pool_idle_timeout.map(Duration::from_secs))
+		.tcp_keepalive(network.tcp_keepalive.map(Duration::from_secs))
+		.http_version(network.http_version);
+
+	if let Some(max) = network.pool_max_idle_per_host {
+		builder = builder.pool_max_idle_per_host(max);
+	}
+
+	if let Some(schedule) = &network.schedule {
+		builder = builder.schedule(schedule.max_requests, Duration::from_secs(schedule.window));
+	}
+
+	let client = builder.build()?;
 	Ok(client)
 }
 
@@ -0,0 +1,96 @@
+use std::{cmp::Reverse, collections::{BinaryHeap, VecDeque}, sync::Mutex, time::Duration};
+use reqwest::Url;
+use crate::settings::LikeSettings;
+use tokio::{select, sync::Notify, time::{sleep_until, Instant}};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PendingLike {
+	pub due: Instant,
+	pub username: String,
+	pub url: Url,
+}
+
+/// Likes waiting for their delay to pass, kept for as long as the application runs
+#[derive(Default)]
+pub struct LikeQueue {
+	pending: Mutex<BinaryHeap<Reverse<PendingLike>>>,
+	changed: Notify,
+}
+
+impl LikeQueue {
+	pub fn schedule(&self, username: &str, url: Url, delay: Duration) {
+		self.pending.lock().unwrap().push(Reverse(PendingLike {
+			due: Instant::now() + delay,
+			username: username.to_string(),
+			url
+		}));
+
+		self.changed.notify_one();
+	}
+
+	pub fn len(&self) -> usize {
+		self.pending.lock().unwrap().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Waits until the earliest like is due and takes it out of the queue
+	pub async fn next(&self) -> PendingLike {
+		loop {
+			let due = self.pending.lock().unwrap().peek().map(|Reverse(like)| like.due);
+			match due {
+				Some(due) if due <= Instant::now() => {
+					if let Some(Reverse(like)) = self.pending.lock().unwrap().pop() { return like }
+				},
+				Some(due) => select! {
+					_ = sleep_until(due) => (),
+					_ = self.changed.notified() => ()
+				},
+				None => self.changed.notified().await
+			}
+		}
+	}
+}
+
+const HOUR: Duration = Duration::from_secs(3600);
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+/// Times of the likes sent during the last day, for enforcing the like budget
+#[derive(Debug, Default)]
+pub struct LikeHistory {
+	sent: VecDeque<Instant>,
+}
+
+impl LikeHistory {
+	pub fn record(&mut self, at: Instant) {
+		self.sent.push_back(at);
+	}
+
+	/// Time left until the last like is `spacing` ago
+	pub fn spacing_wait(&self, spacing: Duration, now: Instant) -> Duration {
+		self.sent
+		.back()
+		.map_or(Duration::ZERO, |last| (*last + spacing).saturating_duration_since(now))
+	}
+
+	/// Time left until another like fits into the hourly and daily budgets
+	pub fn budget_wait(&mut self, settings: &LikeSettings, now: Instant) -> Duration {
+		while self.sent.front().is_some_and(|sent| now.saturating_duration_since(*sent) >= DAY) {
+			self.sent.pop_front();
+		}
+
+		let wait = |max: Option<usize>, window: Duration| {
+			let recent = self.sent.iter().filter(|sent| now.saturating_duration_since(**sent) < window).collect::<Vec<_>>();
+			match max {
+				Some(max) if recent.len() >= max => recent
+					.get(recent.len() - max)
+					.map_or(window, |oldest| (**oldest + window).saturating_duration_since(now)),
+				_ => Duration::ZERO
+			}
+		};
+
+		wait(settings.max_per_hour, HOUR).max(wait(settings.max_per_day, DAY))
+	}
+}
@@ -1,19 +1,124 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use log::*;
-use of_client::RequestHeaders;
-use of_notifier::{get_auth_params, handlers::{Context, Handler}, helpers::show_notification, init_cdm, init_client, settings::Settings, FileParseError};
+use of_notifier::{catalog::Catalog, control::{self, AppCommand, Controller}, credentials, doctor, import, events::{Event, EventKind}, auth_profiles, get_profile_auth_params, auth_path, handlers::{Context, Handler}, dashboard, helpers::{open_path, show_notification}, init_cdm, notifier::{self, NotificationContent}, init_client_with, mqtt, relay, report, search::SearchIndex, session, setup, settings::{validation::SettingsError, Settings, SettingsFormat}, stats, FileParseError};
+use of_client::OFClient;
 use of_daemon::{socket::SocketError, tungstenite::error::{Error as WSError, ProtocolError}, Daemon, DaemonError};
-use tray_icon::{menu::{Menu, MenuEvent, MenuItem}, Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tray_icon::{menu::{CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuItem, Submenu}, Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
 use winit::{application::ApplicationHandler, event, event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy}, window::WindowId};
-use winrt_toast::{Toast, ToastDuration};
-use std::{fs::{self, File}, path::Path, sync::{Arc, RwLock}};
+use std::{fs::{self, File}, io::{self, IsTerminal}, mem, path::{Path, PathBuf}, process::ExitCode, sync::{Arc, RwLock}, time::Duration};
 use simplelog::{ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode, WriteLogger};
 use chrono::Local;
-use tokio::sync::Notify;
+use clap::{Parser, Subcommand};
+use tokio::{select, signal, sync::{mpsc, Notify}};
+
+#[derive(Parser)]
+#[command(version, about = "Notifications and downloads of OnlyFans content")]
+struct Cli {
+	#[command(subcommand)]
+	command: Option<Command>,
+	/// Cookie export to import, as passed when dropping it onto the executable
+	file: Option<PathBuf>,
+	/// Same as the `daemon --headless` command
+	#[arg(long)]
+	headless: bool,
+	/// Imports a cookie export into the active profile's auth data
+	#[arg(long)]
+	import_auth: Option<PathBuf>,
+	/// User agent to store along with imported cookies
+	#[arg(long)]
+	user_agent: Option<String>,
+	/// x-bc header to store along with imported cookies
+	#[arg(long)]
+	x_bc: Option<String>,
+	/// Writes a plaintext backup of the active profile's auth data
+	#[arg(long)]
+	export_auth: Option<PathBuf>,
+	/// Same as the `test-auth` command
+	#[arg(long)]
+	check_auth: bool,
+	/// Writes the downloaded media carrying a tag to a JSON file
+	#[arg(long)]
+	export_tag: Option<String>,
+	/// Where `--export-tag` writes to, `<tag>.json` by default
+	#[arg(long)]
+	output: Option<PathBuf>,
+	/// Prints the archived text best matching a query
+	#[arg(long)]
+	search: Option<String>,
+	/// Maximum number of `--search` results
+	#[arg(long, default_value_t = 20)]
+	limit: usize,
+}
+
+#[derive(Subcommand, Clone)]
+enum Command {
+	/// Runs in the tray, the default
+	Run,
+	/// Connects and handles events until interrupted
+	Daemon {
+		/// Without a tray icon, event loop or desktop notifications, for servers without a desktop session.
+		/// Notifications are still sent to the configured channels
+		#[arg(long)]
+		headless: bool,
+	},
+	/// Downloads the media of a creator's recent posts, then exits
+	DownloadUser {
+		username: String,
+		/// Number of posts, newest first
+		#[arg(long, default_value_t = 50)]
+		posts: usize,
+	},
+	/// Downloads the media of every message in a creator's chat, then exits
+	DownloadChat {
+		username: String,
+	},
+	/// Downloads the media of bought posts and messages, including those bought before the notifier ran, then exits
+	DownloadPurchased {
+		/// Number of purchases, most recent first. All of them by default
+		#[arg(long)]
+		limit: Option<usize>,
+	},
+	/// Reads the OnlyFans cookies of a logged in browser into the auth file, then exits
+	#[cfg(feature = "browser-import")]
+	ImportBrowserAuth {
+		/// firefox, chrome or edge. Every browser is tried in turn by default
+		#[arg(long)]
+		browser: Option<import::Browser>,
+	},
+	/// Asks for the cookie, user agent and x-bc, checks them against OnlyFans and saves them to the auth file
+	SetupAuth,
+	/// Checks the authentication data step by step and writes a report to logs/auth_report.txt
+	TestAuth,
+	/// Checks that the settings file is valid without running anything
+	ValidateSettings,
+}
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> anyhow::Result<ExitCode> {
+	let cli = Cli::parse();
+	if matches!(cli.command, Some(Command::ValidateSettings)) {
+		let problems = match read_settings() {
+			Ok(settings) => settings.validate(),
+			Err(FileParseError::IO(err)) => {
+				println!("{} could not be read: {err}", settings_file().display());
+				return Ok(ExitCode::FAILURE);
+			},
+			Err(err) => vec![SettingsError::from(&err)]
+		};
+
+		if problems.is_empty() {
+			println!("{} is valid", settings_file().display());
+			return Ok(ExitCode::SUCCESS);
+		}
+
+		println!("{} has {} problem(s):", settings_file().display(), problems.len());
+		for problem in problems {
+			println!("- {problem}");
+		}
+		return Ok(ExitCode::FAILURE);
+	}
+
 	let settings = get_settings()
 		.expect("Reading settings");
 
@@ -28,65 +133,555 @@ async fn main() -> anyhow::Result<()> {
 		.add_filter_ignore_str("tungstenite")
 		.build();
 
+	#[cfg(feature = "sentry")]
+	let _sentry_guard = settings.error_reporting
+		.as_ref()
+		.map(of_notifier::error_reporting::init);
+
 	let log_path = log_folder.join(Local::now().format("%Y%m%d_%H%M%S").to_string()).with_extension("log");
 	let log_level = settings.log_level;
-	CombinedLogger::init(vec![
+	let logger = CombinedLogger::new(vec![
 		TermLogger::new(log_level, log_config.clone(), TerminalMode::Mixed, ColorChoice::Auto),
 		WriteLogger::new(log_level, log_config, File::create(log_path)?)
-	])?;
+	]);
+
+	#[cfg(feature = "sentry")]
+	let logger = of_notifier::error_reporting::wrap_logger(logger);
+
+	log::set_max_level(log_level);
+	log::set_boxed_logger(logger)?;
+
+	#[cfg(not(feature = "sentry"))]
+	if settings.error_reporting.is_some() {
+		warn!("Error reporting is configured, but this build was compiled without the \"sentry\" feature");
+	}
+
+	#[cfg(windows)]
+	if settings.control.pipe {
+		if let Ok(response) = control::pipe::forward(r#"{"command":"connect"}"#).await {
+			info!("Another instance is already running, forwarded connect command: {}", response.trim());
+			return Ok(ExitCode::SUCCESS);
+		}
+	}
+
+	credentials::apply(settings.auth_storage);
+
+	let headless = cli.headless || matches!(cli.command, Some(Command::Daemon { headless: true }));
+	if headless {
+		notifier::disable_desktop();
+	}
+
+	// Dropping a cookie export onto the executable passes its path as the only argument
+	let dropped = cli.file.filter(|path| path.is_file());
+	if let Some(path) = cli.import_auth.or(dropped) {
+		let imported = import::import(&path, settings.profile.as_deref(), cli.user_agent, cli.x_bc)?;
+		println!("Imported cookies from {path:?} into {:?}", imported.path);
+		if let Some(problem) = imported.problem {
+			println!("The auth data is not usable yet: {problem}");
+			println!("Cookie exports don't include x_bc and the user agent, pass them with --x-bc and --user-agent or fill them out in the auth file");
+		}
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	#[cfg(feature = "browser-import")]
+	if let Some(Command::ImportBrowserAuth { browser }) = cli.command {
+		let (browser, imported) = import::from_browser(browser, settings.profile.as_deref(), cli.user_agent, cli.x_bc)?;
+		println!("Imported cookies from {browser} into {:?}", imported.path);
+		if let Some(problem) = imported.problem {
+			println!("The auth data is not usable yet: {problem}");
+			println!("Browsers don't store x_bc and the user agent with their cookies, pass them with --x-bc and --user-agent or fill them out in the auth file");
+		}
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	if matches!(cli.command, Some(Command::SetupAuth)) {
+		let working = setup::run(settings.profile.as_deref(), &settings.network).await?;
+		return Ok(if working { ExitCode::SUCCESS } else { ExitCode::FAILURE });
+	}
 
-	let client = init_client()?;
-	let client_params = client.headers.clone();
+	if let Some(path) = cli.export_auth {
+		credentials::export(settings.profile.as_deref(), &path)?;
+		println!("Exported auth data to {path:?}");
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	if cli.check_auth || matches!(cli.command, Some(Command::TestAuth)) {
+		let report = doctor::check_auth(settings.profile.as_deref(), &settings.network).await;
+		println!("{report}");
+		fs::write(log_folder.join("auth_report.txt"), report.to_string())?;
+		return Ok(match report.problems() {
+			0 => ExitCode::SUCCESS,
+			_ => ExitCode::FAILURE
+		});
+	}
+
+	if let Some(tag) = cli.export_tag {
+		let catalog = Catalog::open(Path::new("data").join("catalog.sqlite3"))?;
+		let media = catalog.tagged_media(&tag)?;
+		let path = cli.output.unwrap_or_else(|| PathBuf::from(format!("{tag}.json")));
+		fs::write(&path, serde_json::to_string_pretty(&media)?)?;
+		println!("Exported {} media tagged {tag} to {path:?}", media.len());
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	if let Some(query) = cli.search {
+		let index = SearchIndex::open_read_only(Path::new("data").join("search"))?;
+		for hit in index.search(&query, cli.limit)? {
+			println!("{} {} {} from {}:\n{}\n", hit.content_type, hit.content_id, hit.score, hit.username, hit.text.trim());
+		}
+		return Ok(ExitCode::SUCCESS);
+	}
 
+	// On the first run there is no usable auth file yet, walk through creating one when there is someone to ask
+	if io::stdin().is_terminal() {
+		if let Err(err) = get_profile_auth_params(settings.profile.as_deref()) {
+			println!("{:?} is not usable: {err}\n", auth_path(settings.profile.as_deref()));
+			if !setup::run(settings.profile.as_deref(), &settings.network).await? {
+				return Ok(ExitCode::FAILURE);
+			}
+		}
+	}
+
+	let client = init_client_with(&settings.network, settings.profile.as_deref())?;
 	let cdm = init_cdm()
 		.inspect_err(|e| warn!("CDM could not be initialized: {e}"))
 		.ok();
 
-	if cdm.is_some() {
+	// Decrypting DRM media, recording live streams and converting GIF clips all go through FFmpeg
+	if cdm.is_some() || settings.record_streams || settings.convert_gifs() {
 		ffmpeg_sidecar::download::auto_download()
 		.inspect_err(|e| error!("FFmpeg installation failed: {e}"))?;
 	}
 
+	let rest_settings = settings.control.rest.clone();
+	let web_settings = settings.control.web.clone();
+	let grpc_settings = settings.control.grpc.clone();
+	let dbus_enabled = settings.control.dbus;
+	#[cfg(windows)]
+	let pipe_enabled = settings.control.pipe;
+	let relay_settings = settings.relay.clone();
+	let mqtt_settings = settings.mqtt.clone();
+	notifier::configure_channels(&settings);
 	let settings = Arc::new(RwLock::new(settings));
+	let context = Context::new(client.clone(), cdm, settings.clone())?;
+	let accounts = settings.read().unwrap().accounts.clone();
+	let context = match accounts.is_empty() {
+		true => context,
+		false => {
+			let main = settings.read().unwrap().profile.clone().unwrap_or_else(|| "main".to_string());
+			context.with_account(main)
+		}
+	};
+
+	if let Some(Command::DownloadUser { username, posts }) = &cli.command {
+		let downloaded = context.download_user(username, *posts).await;
+		context.shutdown(Duration::MAX).await;
+		println!("Downloaded the media of {} posts from {username}", downloaded?);
+		return Ok(ExitCode::SUCCESS);
+	}
 
-	let event_loop = EventLoop::<Events>::with_user_event()
-		.build()
-		.unwrap();
+	if let Some(Command::DownloadChat { username }) = &cli.command {
+		let user = client.get_user(username.as_str()).await?;
+		let downloaded = context.download_chat_history(&user).await;
+		context.shutdown(Duration::MAX).await;
+		println!("Downloaded the media of {} messages from {username}", downloaded?);
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	if let Some(Command::DownloadPurchased { limit }) = &cli.command {
+		let downloaded = context.download_purchased(*limit).await;
+		context.shutdown(Duration::MAX).await;
+		println!("Downloaded the media of {} purchases", downloaded?);
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	tokio::spawn(context.stats.clone().log_rollups(Duration::from_secs(3600)));
+	tokio::spawn(context.clone().watch_cdm(Duration::from_secs(10)));
+	tokio::spawn(context.clone().run_likes());
+	context.spawn(context.clone().restore_pending());
+	tokio::spawn(session::maintain(client.clone(), settings.clone(), None));
+	if let Some(search) = context.search.clone() {
+		let catalog = context.catalog.clone();
+		tokio::task::spawn_blocking(move || search.backfill(&catalog)
+			.inspect_err(|err| error!("Error indexing archived text: {err}"))
+		);
+	}
+	tokio::spawn(context.clone().refresh_subscriptions(Duration::from_secs(3600)));
+	tokio::spawn(report::schedule(context.catalog.clone(), context.stats.clone(), settings.clone(), Path::new("data").join("reports"), Duration::from_secs(3600)));
+	if let Some(reconcile) = &settings.read().unwrap().reconcile {
+		tokio::spawn(context.clone().reconcile_archive(Duration::from_secs(reconcile.interval.max(60))));
+	}
+	if let Some(renewals) = &settings.read().unwrap().renewals {
+		tokio::spawn(context.clone().monitor_renewals(Duration::from_secs(renewals.interval.max(600))));
+	}
+
+	let (frontend, sender) = match headless {
+		true => {
+			let (sender, receiver) = mpsc::unbounded_channel();
+			(Frontend::Headless(receiver), EventSender::Headless(sender))
+		},
+		false => {
+			let event_loop = EventLoop::<Events>::with_user_event()
+				.build()
+				.unwrap();
 
-	let (toggle_daemon, _) = Daemon::new()
-		.on_start({
 			let proxy = event_loop.create_proxy();
-			move || { let _ = proxy.send_event(Events::Connected); }
+			(Frontend::Tray(event_loop), EventSender::Tray(proxy))
+		}
+	};
+
+	watch_auth(&client, &settings, None, &sender);
+	let daemon = build_daemon(&context, &sender, log_folder);
+
+	let controller = Controller::new(context.clone(), daemon.stats(), {
+		let events = sender.clone();
+		move |command| events.send(Events::Control(command))
+	});
+
+	let (toggle_daemon, _) = daemon.build(client);
+
+	for account in accounts {
+		let _ = start_account(&context, account.clone(), log_folder)
+			.inspect_err(|err| error!("Account {account} could not be started: {err}"));
+	}
+
+	if let Some(rest_settings) = rest_settings {
+		tokio::spawn(control::rest::serve(controller.clone(), rest_settings));
+	}
+
+	if let Some(web_settings) = web_settings {
+		tokio::spawn(control::web::serve(controller.clone(), web_settings));
+	}
+
+	#[cfg(feature = "grpc")]
+	if let Some(grpc_settings) = grpc_settings {
+		tokio::spawn(of_notifier::grpc::serve(controller.clone(), context.events.clone(), grpc_settings));
+	}
+
+	#[cfg(not(feature = "grpc"))]
+	if grpc_settings.is_some() {
+		warn!("The gRPC service is configured, but this build was compiled without the \"grpc\" feature");
+	}
+
+	#[cfg(windows)]
+	if pipe_enabled {
+		tokio::spawn(control::pipe::serve(controller.clone()));
+	}
+
+	#[cfg(all(target_os = "linux", feature = "dbus"))]
+	if dbus_enabled {
+		tokio::spawn(control::dbus::serve(controller.clone(), context.events.subscribe()));
+	}
+
+	#[cfg(not(all(target_os = "linux", feature = "dbus")))]
+	if dbus_enabled {
+		warn!("The D-Bus interface is enabled, but it is only available on Linux builds compiled with the \"dbus\" feature");
+	}
+
+	if let Some(relay_settings) = relay_settings {
+		tokio::spawn(relay::serve(context.events.clone(), relay_settings));
+	}
+
+	if let Some(mqtt_settings) = mqtt_settings {
+		tokio::spawn(mqtt::run(mqtt_settings, context.events.subscribe()));
+	}
+
+	match frontend {
+		Frontend::Tray(event_loop) => {
+			let mut app = App {
+				should_quit: false,
+				draining: false,
+				reconnect_after_close: false,
+				state: AppState::Disconnected,
+				tray: None,
+				event_loop: event_loop.create_proxy(),
+				settings,
+				context: context.clone(),
+				toggle_daemon,
+			};
+
+			event_loop.run_app(&mut app).unwrap();
+
+			// In-flight handlers and downloads are stopped cleanly rather than aborted with the runtime
+			context.shutdown(Duration::ZERO).await;
+		},
+		Frontend::Headless(events) => run_headless(settings, context, toggle_daemon, events).await
+	}
+
+	Ok(ExitCode::SUCCESS)
+}
+
+enum Frontend {
+	Tray(EventLoop<Events>),
+	Headless(mpsc::UnboundedReceiver<Events>),
+}
+
+/// Where the daemon and the control interfaces report to, the tray's event loop or the headless loop
+#[derive(Clone)]
+enum EventSender {
+	Tray(EventLoopProxy<Events>),
+	Headless(mpsc::UnboundedSender<Events>),
+}
+
+impl EventSender {
+	fn send(&self, event: Events) {
+		match self {
+			Self::Tray(proxy) => { let _ = proxy.send_event(event); },
+			Self::Headless(sender) => { let _ = sender.send(event); }
+		}
+	}
+}
+
+/// Disconnections worth reconnecting after right away
+fn is_transient(err: &DaemonError) -> bool {
+	let transient = matches!(err, DaemonError::Socket(
+		SocketError::TimeoutExpired |
+		SocketError::Closed { .. } |
+		SocketError::Socket(WSError::Protocol(ProtocolError::ResetWithoutClosingHandshake))
+	));
+
+	transient && !err.is_auth_revoked()
+}
+
+/// Pauses requests when the session of `account` (the main one when None) expires and reconnects once its auth file is updated
+fn watch_auth(client: &OFClient, settings: &Arc<RwLock<Settings>>, account: Option<String>, events: &EventSender) {
+	client.on_auth_failure({
+		let client = client.clone();
+		let settings = settings.clone();
+		let events = events.clone();
+		move || {
+			let profile = account.clone().or_else(|| settings.read().unwrap().profile.clone());
+			let mut toast = NotificationContent::new();
+			toast
+			.title("Session expired")
+			.body(format!("Requests are paused until {} is updated with a new cookie", auth_path(profile.as_deref()).display()))
+			.long();
+
+			let _ = show_notification(&toast);
+			tokio::spawn({
+				let (client, settings, account, events) = (client.clone(), settings.clone(), account.clone(), events.clone());
+				async move {
+					if session::reload_expired_auth(client, settings, account).await {
+						events.send(Events::Control(AppCommand::Reconnect));
+					}
+				}
+			});
+		}
+	});
+}
+
+fn build_daemon(context: &Context, events: &EventSender, log_folder: &Path) -> Daemon {
+	Daemon::new()
+		.on_start({
+			let events = events.clone();
+			let context = context.clone();
+			move || {
+				let disconnected_at = context.stats.record_connected();
+				tokio::spawn({
+					let context = context.clone();
+					async move {
+						context.clone().catch_up().await;
+						// After catching up, so that the unread messages it handled are known by then
+						if let Some(since) = disconnected_at {
+							context.backfill(since).await;
+						}
+					}
+				});
+				context.publish(Event::status(EventKind::Connected));
+				events.send(Events::Connected);
+			}
 		})
 		.on_disconnect({
-			let proxy = event_loop.create_proxy();
-			move |e| { let _ = proxy.send_event(Events::Disconnected(e)); }
+			let events = events.clone();
+			let context = context.clone();
+			move |e| {
+				if e.as_ref().is_err_and(DaemonError::is_auth_revoked) {
+					context.client.report_auth_failure();
+				}
+				context.stats.record_disconnected();
+				context.publish(Event::status(EventKind::Disconnected));
+				events.send(Events::Disconnected(e));
+			}
 		})
 		.on_message({
-			let context = Context::new(client.clone(), cdm, settings.clone()).unwrap();
-			move |message| { let _ = message.handle(&context); }
+			let context = context.clone();
+			move |message, in_flight| context.dispatch_in_flight(message, in_flight)
 		})
-		.build(client);
-
-	let mut app = App {
-		should_quit: false,
-		state: AppState::Disconnected,
-		tray: None,
-		event_loop: event_loop.create_proxy(),
-		settings,
-		client_params,
-		toggle_daemon,
-	};
+		.on_unknown_message(|value| {
+			let keys = value.as_object().map(|object| object.keys().cloned().collect::<Vec<_>>().join(", ")).unwrap_or_default();
+			debug!("Skipped message of unknown type with keys [{keys}]");
+		})
+		.dump_unparsed(log_folder.join("unparsed"))
+		.cancel_on(context.cancellation())
+}
+
+/// Runs `account` alongside the main one, with a client and connection of its own that reconnects on its own
+fn start_account(main: &Context, account: String, log_folder: &Path) -> anyhow::Result<()> {
+	let network = main.settings.read().unwrap().network.clone();
+	let client = init_client_with(&network, Some(&account))?;
+	let context = main.for_account(client.clone(), account.clone());
+
+	tokio::spawn(context.clone().run_likes());
+	context.spawn(context.clone().restore_pending());
+	tokio::spawn(context.clone().refresh_subscriptions(Duration::from_secs(3600)));
+	tokio::spawn(session::maintain(client.clone(), context.settings.clone(), Some(account.clone())));
+
+	let (sender, events) = mpsc::unbounded_channel();
+	let sender = EventSender::Headless(sender);
+	watch_auth(&client, &context.settings, Some(account.clone()), &sender);
 
-	event_loop.run_app(&mut app).unwrap();
+	let (toggle_daemon, _) = build_daemon(&context, &sender, &log_folder.join(&account)).build(client);
+	tokio::spawn(run_account(account, context, toggle_daemon, events));
 	Ok(())
 }
 
+/// Keeps the connection of an account running alongside the main one up, with the same reconnect policy as the main one.
+/// It has no tray state, so lost connections are notified
+async fn run_account(account: String, context: Context, toggle_daemon: Arc<Notify>, mut events: mpsc::UnboundedReceiver<Events>) {
+	info!("Connecting {account}");
+	let mut state = AppState::Connecting;
+	let mut reconnect_after_close = false;
+	toggle_daemon.notify_one();
+
+	while let Some(event) = events.recv().await {
+		match event {
+			Events::Connected => {
+				info!("Connected {account}");
+				state = AppState::Connected;
+			},
+			Events::Disconnected(result) => {
+				info!("Disconnected {account}");
+				state = AppState::Disconnected;
+				if context.cancellation().is_cancelled() { break }
+
+				match result {
+					_ if mem::take(&mut reconnect_after_close) => {
+						state = AppState::Connecting;
+						toggle_daemon.notify_one();
+					},
+					Err(err) if context.settings.read().unwrap().reconnect && is_transient(&err) => {
+						info!("Attempting to reconnect {account}");
+						state = AppState::Connecting;
+						toggle_daemon.notify_one();
+					},
+					Err(err) if err.is_auth_revoked() => error!("The session of {account} was revoked, reconnecting once its auth file is updated"),
+					Err(err) => {
+						error!("Connection of {account} lost: {err}");
+						let mut toast = NotificationContent::new();
+						toast
+						.title("OF Notifier")
+						.body(format!("The connection of {account} was lost"))
+						.long();
+
+						let _ = show_notification(&toast);
+					},
+					Ok(()) => ()
+				}
+			},
+			Events::Control(AppCommand::Reconnect) => match state {
+				AppState::Disconnected => {
+					state = AppState::Connecting;
+					toggle_daemon.notify_one();
+				},
+				AppState::Connected | AppState::Connecting => {
+					reconnect_after_close = true;
+					state = AppState::Disconnecting;
+					toggle_daemon.notify_one();
+				},
+				AppState::Disconnecting => reconnect_after_close = true
+			},
+			_ => ()
+		}
+	}
+}
+
+/// Keeps the connection up without a tray icon until interrupted, with the same reconnect policy as the tray.
+/// Connection problems are only logged
+async fn run_headless(settings: Arc<RwLock<Settings>>, context: Context, toggle_daemon: Arc<Notify>, mut events: mpsc::UnboundedReceiver<Events>) {
+	let interrupted = signal::ctrl_c();
+	tokio::pin!(interrupted);
+
+	info!("Connecting");
+	let mut state = AppState::Connecting;
+	let mut reconnect_after_close = false;
+	toggle_daemon.notify_one();
+
+	loop {
+		let event = select! {
+			_ = &mut interrupted => break,
+			event = events.recv() => match event {
+				Some(event) => event,
+				None => break
+			}
+		};
+
+		match event {
+			Events::Connected => {
+				info!("Connected");
+				state = AppState::Connected;
+			},
+			Events::Disconnected(result) => {
+				info!("Disconnected");
+				state = AppState::Disconnected;
+
+				match result {
+					_ if mem::take(&mut reconnect_after_close) => {
+						info!("Connecting");
+						state = AppState::Connecting;
+						toggle_daemon.notify_one();
+					},
+					Err(err) if settings.read().unwrap().reconnect && is_transient(&err) => {
+						info!("Attempting to reconnect");
+						state = AppState::Connecting;
+						toggle_daemon.notify_one();
+					},
+					Err(err) if err.is_auth_revoked() => error!("The session was revoked, reconnecting once the auth file is updated"),
+					Err(err) => error!("Connection lost: {err}"),
+					Ok(()) => ()
+				}
+			},
+			Events::Control(command) => match (command, &state) {
+				(AppCommand::Connect | AppCommand::Reconnect, AppState::Disconnected) => {
+					info!("Connecting");
+					state = AppState::Connecting;
+					toggle_daemon.notify_one();
+				},
+				(AppCommand::Disconnect | AppCommand::Reconnect, AppState::Connected | AppState::Connecting) => {
+					info!("Closing connection");
+					reconnect_after_close = command == AppCommand::Reconnect;
+					state = AppState::Disconnecting;
+					toggle_daemon.notify_one();
+				},
+				(AppCommand::ReloadSettings, _) => {
+					let Some(profile) = reload_settings(&settings) else { continue };
+					info!("Switching to profile {}", profile.as_deref().unwrap_or("auth.json"));
+					let Ok(auth) = get_profile_auth_params(profile.as_deref()) else { continue };
+					context.client.update_headers(auth);
+
+					if matches!(state, AppState::Connected | AppState::Connecting) {
+						reconnect_after_close = true;
+						state = AppState::Disconnecting;
+						toggle_daemon.notify_one();
+					}
+				},
+				_ => ()
+			},
+			_ => ()
+		}
+	}
+
+	info!("Closing application");
+	let timeout = settings.read().unwrap().downloads.quit_timeout();
+	context.shutdown(timeout).await;
+}
+
 enum Events {
 	Connected,
 	Disconnected(Result<(), DaemonError>),
 	TrayEvent(TrayIconEvent),
 	MenuEvent(MenuEvent),
+	Control(AppCommand),
+	Drained,
 }
 
 #[derive(Debug, PartialEq)]
@@ -96,6 +691,14 @@ struct MenuItems {
 	quit: MenuItem,
 	reload_settings: MenuItem,
 	reload_auth: MenuItem,
+	#[cfg(feature = "browser-import")]
+	import_browser_auth: MenuItem,
+	reload_cdm: MenuItem,
+	check_auth: MenuItem,
+	show_statistics: MenuItem,
+	show_dashboard: MenuItem,
+	export_statistics: MenuItem,
+	profiles: Vec<(String, CheckMenuItem)>,
 }
 
 struct Icons {
@@ -111,11 +714,13 @@ struct Tray {
 
 struct App {
 	should_quit: bool,
+	draining: bool,
+	reconnect_after_close: bool,
 	state: AppState,
 	tray: Option<Tray>,
 	event_loop: EventLoopProxy<Events>,
 	settings: Arc<RwLock<Settings>>,
-	client_params: Arc<RwLock<RequestHeaders>>,
+	context: Context,
 	toggle_daemon: Arc<Notify>,
 }
 
@@ -131,6 +736,146 @@ impl App {
 		self.state = AppState::Disconnecting;
 		self.toggle_daemon.notify_one();
 	}
+
+	/// Lets handlers and downloads in progress finish before quitting, up to the configured timeout
+	fn drain(&mut self) {
+		info!("Finishing {} pending tasks before closing", self.context.active_tasks());
+		self.draining = true;
+
+		if let Some(Tray { tray, menu_items, .. }) = &self.tray {
+			let _ = tray.set_tooltip(Some("OF Notifier - finishing downloads…"));
+			for item in [
+				&menu_items.quit, &menu_items.reload_settings, &menu_items.reload_auth, &menu_items.reload_cdm,
+				&menu_items.check_auth, &menu_items.show_statistics, &menu_items.show_dashboard, &menu_items.export_statistics
+			] {
+				item.set_enabled(false);
+			}
+
+			#[cfg(feature = "browser-import")]
+			menu_items.import_browser_auth.set_enabled(false);
+
+			for (_, item) in &menu_items.profiles {
+				item.set_enabled(false);
+			}
+		}
+
+		let timeout = self.settings.read().unwrap().downloads.quit_timeout();
+		let context = self.context.clone();
+		let event_loop = self.event_loop.clone();
+		tokio::spawn(async move {
+			context.shutdown(timeout).await;
+			let _ = event_loop.send_event(Events::Drained);
+		});
+	}
+
+	fn reload_settings(&mut self) {
+		if let Some(profile) = reload_settings(&self.settings) {
+			self.switch_profile(profile);
+		}
+	}
+
+	fn switch_profile(&mut self, profile: Option<String>) {
+		info!("Switching to profile {}", profile.as_deref().unwrap_or("auth.json"));
+		if let Ok(new_auth) = get_profile_auth_params(profile.as_deref()) {
+			self.context.client.update_headers(new_auth);
+			self.settings.write().unwrap().profile = profile;
+			info!("Successfully switched profile");
+
+			if matches!(self.state, AppState::Connected | AppState::Connecting) {
+				self.reconnect_after_close = true;
+				self.close_connection();
+			}
+		}
+
+		if let Some(tray) = &self.tray {
+			let active = self.settings.read().unwrap().profile.clone();
+			for (name, item) in &tray.menu_items.profiles {
+				item.set_checked(active.as_ref() == Some(name));
+			}
+		}
+	}
+
+	fn check_auth(&self) {
+		let (profile, network) = {
+			let settings = self.settings.read().unwrap();
+			(settings.profile.clone(), settings.network.clone())
+		};
+
+		tokio::spawn(async move {
+			let report = doctor::check_auth(profile.as_deref(), &network).await;
+			info!("{report}");
+
+			let path = Path::new("logs").join("auth_report.txt");
+			let _ = fs::write(&path, report.to_string())
+				.inspect_err(|err| error!("Error writing authentication report: {err}"));
+
+			let mut toast = NotificationContent::new();
+			toast
+			.title("Authentication check")
+			.body(match report.problems() {
+				0 => "No problems found".to_string(),
+				n => format!("{n} problem(s) found, see {}", path.display()),
+			});
+
+			let _ = show_notification(&toast);
+		});
+	}
+
+	#[cfg(feature = "browser-import")]
+	fn import_browser_auth(&self) {
+		info!("Importing authentication from the browser");
+		let profile = self.settings.read().unwrap().profile.clone();
+		let client = self.context.client.clone();
+
+		tokio::spawn(async move {
+			let imported = {
+				let profile = profile.clone();
+				tokio::task::spawn_blocking(move || import::from_browser(None, profile.as_deref(), None, None)).await.unwrap()
+			};
+
+			let body = match imported {
+				Ok((browser, import::Imported { problem: Some(problem), .. })) => {
+					warn!("Cookies from {browser} were imported, but the auth data is not usable yet: {problem}");
+					format!("Cookies from {browser} were imported, fill out x_bc and the user agent in the auth file")
+				},
+				Ok((browser, import::Imported { path, .. })) => {
+					info!("Imported cookies from {browser} into {path:?}");
+					if let Ok(new_auth) = get_profile_auth_params(profile.as_deref()) {
+						client.update_headers(new_auth);
+					}
+					format!("Signed in with the {browser} session")
+				},
+				Err(err) => {
+					error!("Error importing authentication from the browser: {err}");
+					err.to_string()
+				}
+			};
+
+			let mut toast = NotificationContent::new();
+			toast
+			.title("Import auth from browser")
+			.body(body);
+
+			let _ = show_notification(&toast);
+		});
+	}
+
+	fn show_statistics(&self) {
+		let counts = self.context.stats.creator_counts();
+		let total: u64 = counts.values().map(|counts| counts.total()).sum();
+		let downloads = self.context.stats.downloads();
+
+		let mut toast = NotificationContent::new();
+		toast
+		.title("Statistics")
+		.body(format!("{total} events from {} creators, {} downloads this session", counts.len(), downloads.downloads));
+
+		if let Some((username, top)) = counts.iter().max_by_key(|(_, counts)| counts.total()) {
+			toast.attribution(format!("Most active: {username} ({} events)", top.total()));
+		}
+
+		let _ = show_notification(&toast);
+	}
 }
 
 macro_rules! exit {
@@ -158,10 +903,43 @@ impl ApplicationHandler<Events> for App {
 			let tray_menu = Menu::new();
 			let reload_settings_item = MenuItem::new("Reload settings", true, None);
 			let reload_auth_item = MenuItem::new("Reload auth", true, None);
+			#[cfg(feature = "browser-import")]
+			let import_browser_auth_item = MenuItem::new("Import auth from browser", true, None);
+			let reload_cdm_item = MenuItem::new("Reload CDM", true, None);
+			let check_auth_item = MenuItem::new("Check auth", true, None);
+			let show_statistics_item = MenuItem::new("Show summary", true, None);
+			let show_dashboard_item = MenuItem::new("Dashboard", true, None);
+			let export_statistics_item = MenuItem::new("Export", true, None);
+			let statistics_menu = Submenu::with_items("Statistics", true, &[
+				&show_statistics_item,
+				&show_dashboard_item,
+				&export_statistics_item,
+			]).unwrap();
 			let quit_item = MenuItem::new("Quit", true, None);
+
+			let active_profile = self.settings.read().unwrap().profile.clone();
+			let profile_items = auth_profiles()
+				.into_iter()
+				.map(|profile| {
+					let item = CheckMenuItem::new(&profile, true, active_profile.as_ref() == Some(&profile), None);
+					(profile, item)
+				})
+				.collect::<Vec<_>>();
+
+			if !profile_items.is_empty() {
+				let items = profile_items.iter().map(|(_, item)| item as &dyn IsMenuItem).collect::<Vec<_>>();
+				tray_menu.append(&Submenu::with_items("Accounts", true, &items).unwrap()).unwrap();
+			}
+
+			#[cfg(feature = "browser-import")]
+			tray_menu.append(&import_browser_auth_item).unwrap();
+
 			tray_menu.append_items(&[
 				&reload_auth_item,
 				&reload_settings_item,
+				&reload_cdm_item,
+				&check_auth_item,
+				&statistics_menu,
 				&quit_item,
 			]).unwrap();
 		
@@ -192,7 +970,15 @@ impl ApplicationHandler<Events> for App {
 				menu_items: MenuItems {
 					reload_settings: reload_settings_item,
 					quit: quit_item,
-					reload_auth: reload_auth_item
+					reload_auth: reload_auth_item,
+					#[cfg(feature = "browser-import")]
+					import_browser_auth: import_browser_auth_item,
+					reload_cdm: reload_cdm_item,
+					check_auth: check_auth_item,
+					show_statistics: show_statistics_item,
+					show_dashboard: show_dashboard_item,
+					export_statistics: export_statistics_item,
+					profiles: profile_items,
 				},
 				icons: Icons {
 					connected: connected_icon,
@@ -222,26 +1008,31 @@ impl ApplicationHandler<Events> for App {
 				info!("Disconnected");
 				self.state = AppState::Disconnected;
 
-				if self.should_quit { exit!(event_loop); }
+				if self.should_quit {
+					if !self.draining { exit!(event_loop); }
+					return;
+				}
+
+				if mem::take(&mut self.reconnect_after_close) {
+					self.init_connection();
+					return;
+				}
 
 				if let Err(err) = result {
-					if self.settings.read().unwrap().reconnect {
-						if let DaemonError::Socket(
-								SocketError::TimeoutExpired |
-								SocketError::Socket(WSError::Protocol(ProtocolError::ResetWithoutClosingHandshake))
-							) = err
-						{
-							info!("Attempting to reconnect");
-							self.init_connection();
-							return;
-						}
+					if self.settings.read().unwrap().reconnect && is_transient(&err) {
+						info!("Attempting to reconnect");
+						self.init_connection();
+						return;
 					}
-	
-					let mut toast = Toast::new();
+
+					// The auth failure hook already asked for a new cookie and reconnects once there is one
+					if err.is_auth_revoked() { return }
+
+					let mut toast = NotificationContent::new();
 					toast
-					.text1("OF Notifier")
-					.text2("An error occurred")
-					.duration(ToastDuration::Long);
+					.title("OF Notifier")
+					.body("An error occurred")
+					.long();
 	
 					let _ = show_notification(&toast);
 				} 
@@ -249,32 +1040,71 @@ impl ApplicationHandler<Events> for App {
 			Events::MenuEvent(MenuEvent { id }) => {
 				let menu_items = &self.tray.as_ref().unwrap().menu_items;
 
+				#[cfg(feature = "browser-import")]
+				if id == menu_items.import_browser_auth.id() {
+					self.import_browser_auth();
+					return;
+				}
+
 				if id == menu_items.quit.id() {
+					if self.should_quit { return }
 					self.should_quit = true;
+
+					if self.context.active_tasks() > 0 {
+						self.drain();
+					}
+
 					match self.state {
 						AppState::Connected | AppState::Connecting => self.close_connection(),
-						AppState::Disconnected => exit!(event_loop),
-						AppState::Disconnecting => ()
+						AppState::Disconnected if !self.draining => exit!(event_loop),
+						AppState::Disconnected | AppState::Disconnecting => ()
 					}
 				} else if id == menu_items.reload_settings.id() {
-					info!("Reloading settings");
-					if let Ok(new_settings) = get_settings() {
-						*self.settings.write().unwrap() = new_settings;
-						info!("Successfully updated settings");
-					}
+					self.reload_settings();
 				} else if id == menu_items.reload_auth.id() {
 					info!("Reloading authentication parameters");
-					if let Ok(new_auth) = get_auth_params() {
-						let mut params_lock = self.client_params.write().unwrap();
-						params_lock.x_bc = new_auth.x_bc;
-						params_lock.user_id = new_auth.user_id;
-						params_lock.user_agent = new_auth.user_agent;
-						*params_lock.cookie.write().unwrap() = new_auth.cookie;
-
+					let profile = self.settings.read().unwrap().profile.clone();
+					if let Ok(new_auth) = get_profile_auth_params(profile.as_deref()) {
+						self.context.client.update_headers(new_auth);
 						info!("Successfully updated authentication parameters");
 					}
+				} else if id == menu_items.reload_cdm.id() {
+					info!("Reloading CDM");
+					let context = self.context.clone();
+					tokio::task::spawn_blocking(move || context.reload_cdm());
+				} else if let Some(profile) = menu_items.profiles.iter().find(|(_, item)| id == item.id()).map(|(profile, _)| profile.clone()) {
+					self.switch_profile(Some(profile));
+				} else if id == menu_items.check_auth.id() {
+					self.check_auth();
+				} else if id == menu_items.show_statistics.id() {
+					self.show_statistics();
+				} else if id == menu_items.show_dashboard.id() {
+					let _ = dashboard::export(&self.context.catalog, &self.context.stats, Path::new("logs"))
+						.and_then(|path| open_path(&path).map_err(Into::into))
+						.inspect_err(|err| error!("Error showing the dashboard: {err}"));
+				} else if id == menu_items.export_statistics.id() {
+					let _ = stats::export(&self.context.stats, &self.context.catalog, Path::new("logs"))
+						.inspect(|path| info!("Statistics exported to {path:?}"))
+						.inspect_err(|err| error!("Error exporting statistics: {err}"));
 				}
 			},
+			Events::Drained => {
+				info!("Pending tasks finished");
+				self.draining = false;
+				if self.state == AppState::Disconnected { exit!(event_loop); }
+			},
+			Events::Control(_) if self.should_quit => (),
+			Events::Control(command) => match (command, &self.state) {
+				(AppCommand::Connect | AppCommand::Reconnect, AppState::Disconnected) => self.init_connection(),
+				(AppCommand::Disconnect, AppState::Connected | AppState::Connecting) => self.close_connection(),
+				(AppCommand::Reconnect, AppState::Connected | AppState::Connecting) => {
+					self.reconnect_after_close = true;
+					self.close_connection();
+				},
+				(AppCommand::ReloadSettings, _) => self.reload_settings(),
+				_ => ()
+			},
+			Events::TrayEvent(_) if self.should_quit => (),
 			Events::TrayEvent(tray_event) => {
 				 if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Down, .. } = tray_event {
 					match self.state {
@@ -288,10 +1118,48 @@ impl ApplicationHandler<Events> for App {
 	}
 }
 
+/// Reads the settings file again, returns the profile to switch to if it changed
+fn reload_settings(settings: &RwLock<Settings>) -> Option<Option<String>> {
+	info!("Reloading settings");
+	let new_settings = get_settings()
+		.inspect_err(|err| show_settings_problems(&[match err {
+			FileParseError::IO(err) => err.to_string(),
+			err => SettingsError::from(err).to_string()
+		}]))
+		.ok()?;
+
+	let problems = new_settings.validate();
+	if !problems.is_empty() {
+		show_settings_problems(&problems.iter().map(ToString::to_string).collect::<Vec<_>>());
+	}
+
+	let profile = new_settings.profile.clone();
+	notifier::configure_channels(&new_settings);
+	let previous = mem::replace(&mut *settings.write().unwrap(), new_settings).profile;
+	info!("Successfully updated settings");
+
+	(profile != previous).then_some(profile)
+}
+
+fn settings_file() -> PathBuf {
+	SettingsFormat::detect(Path::new(".")).0
+}
+
+fn read_settings() -> Result<Settings, FileParseError> {
+	Settings::read(Path::new("."))
+}
+
 fn get_settings() -> Result<Settings, FileParseError> {
-	let data = fs::read_to_string("settings.json")
-	.inspect_err(|err| error!("Error reading settings: {err}"))?;
+	read_settings()
+	.inspect(|settings| for problem in settings.validate() { warn!("Settings problem: {problem}") })
+	.inspect_err(|err| error!("Error reading settings: {err}"))
+}
+
+fn show_settings_problems(problems: &[String]) {
+	let mut toast = NotificationContent::new();
+	toast
+	.title("Settings problems")
+	.body(problems.join("\n"));
 
-	serde_json::from_str::<Settings>(&data).map_err(Into::into)
-	.inspect_err(|err| error!("Error parsing settings: {err}"))
+	let _ = show_notification(&toast);
 }
\ No newline at end of file
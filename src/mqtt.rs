@@ -0,0 +1,167 @@
+use log::*;
+use std::time::Duration;
+use rumqttc::{AsyncClient, Event as MqttEvent, LastWill, MqttOptions, Packet, QoS};
+use serde_json::json;
+use tokio::{sync::broadcast::{error::RecvError, Receiver}, time::sleep};
+use crate::{events::{Event, EventKind}, settings::{HomeAssistantSettings, MqttSettings}};
+
+struct Topics<'a> {
+	settings: &'a MqttSettings,
+}
+
+impl Topics<'_> {
+	fn status(&self) -> String {
+		format!("{}/status", self.settings.topic_prefix)
+	}
+
+	fn connection(&self) -> String {
+		format!("{}/connection", self.settings.topic_prefix)
+	}
+
+	fn creator(&self, username: &str) -> String {
+		format!("{}/creators/{username}", self.settings.topic_prefix)
+	}
+
+	fn event(&self, kind: EventKind) -> String {
+		self.settings.topics
+			.get(&kind)
+			.cloned()
+			.unwrap_or_else(|| format!("{}/events/{}", self.settings.topic_prefix, kind.as_str()))
+	}
+}
+
+fn qos(value: u8) -> QoS {
+	match value {
+		0 => QoS::AtMostOnce,
+		1 => QoS::AtLeastOnce,
+		_ => QoS::ExactlyOnce
+	}
+}
+
+// Entity ids follow `{domain}.{client_id}_{name}`, e.g. `binary_sensor.of_notifier_connection`
+fn object_id(node_id: &str, name: &str) -> String {
+	format!("{node_id}_{name}")
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+		.collect()
+}
+
+fn discovery_messages(settings: &MqttSettings) -> Vec<(String, String)> {
+	let Some(HomeAssistantSettings { discovery_prefix: prefix, device_name, creators, off_delay }) = &settings.home_assistant else { return Vec::new() };
+	let topics = Topics { settings };
+	let node_id = &settings.client_id;
+	let device = json!({ "identifiers": [node_id], "name": device_name });
+
+	let mut messages = vec![
+		(
+			format!("{prefix}/binary_sensor/{node_id}/connection/config"),
+			json!({
+				"name": "Connection",
+				"unique_id": object_id(node_id, "connection"),
+				"object_id": object_id(node_id, "connection"),
+				"device_class": "connectivity",
+				"state_topic": topics.connection(),
+				"payload_on": "connected",
+				"payload_off": "disconnected",
+				"availability_topic": topics.status(),
+				"device": device
+			})
+		),
+		(
+			format!("{prefix}/binary_sensor/{node_id}/running/config"),
+			json!({
+				"name": "Running",
+				"unique_id": object_id(node_id, "running"),
+				"object_id": object_id(node_id, "running"),
+				"device_class": "running",
+				"entity_category": "diagnostic",
+				"state_topic": topics.status(),
+				"payload_on": "online",
+				"payload_off": "offline",
+				"device": device
+			})
+		),
+	];
+
+	messages.extend(creators.iter().map(|username| {
+		let name = format!("{username}_new_content");
+		(
+			format!("{prefix}/binary_sensor/{node_id}/{}/config", object_id(username, "new_content")),
+			json!({
+				"name": format!("{username} new content"),
+				"unique_id": object_id(node_id, &name),
+				"object_id": object_id(node_id, &name),
+				"icon": "mdi:bell-ring",
+				"state_topic": topics.creator(username),
+				"value_template": "ON",
+				"json_attributes_topic": topics.creator(username),
+				"off_delay": off_delay,
+				"availability_topic": topics.status(),
+				"device": device
+			})
+		)
+	}));
+
+	messages
+		.into_iter()
+		.map(|(topic, payload)| (topic, payload.to_string()))
+		.collect()
+}
+
+pub async fn run(settings: MqttSettings, mut events: Receiver<Event>) {
+	let topics = Topics { settings: &settings };
+	let qos = qos(settings.qos);
+
+	let mut options = MqttOptions::new(&settings.client_id, &settings.host, settings.port);
+	options.set_keep_alive(Duration::from_secs(30));
+	options.set_last_will(LastWill::new(topics.status(), "offline", QoS::AtLeastOnce, true));
+	if let Some(username) = &settings.username {
+		options.set_credentials(username, settings.password.as_deref().unwrap_or_default());
+	}
+
+	let (client, mut event_loop) = AsyncClient::new(options, 64);
+
+	tokio::spawn({
+		let client = client.clone();
+		let status = topics.status();
+		let discovery = discovery_messages(&settings);
+		async move {
+			loop {
+				match event_loop.poll().await {
+					Ok(MqttEvent::Incoming(Packet::ConnAck(_))) => {
+						info!("Connected to MQTT broker");
+						let _ = client.try_publish(&status, QoS::AtLeastOnce, true, "online");
+						for (topic, payload) in &discovery {
+							let _ = client.try_publish(topic, QoS::AtLeastOnce, true, payload.as_str());
+						}
+					},
+					Ok(_) => (),
+					Err(err) => {
+						warn!("MQTT connection error: {err}");
+						sleep(Duration::from_secs(5)).await;
+					}
+				}
+			}
+		}
+	});
+
+	loop {
+		match events.recv().await {
+			Ok(event) => {
+				if let EventKind::Connected | EventKind::Disconnected = event.kind {
+					let _ = client.publish(topics.connection(), QoS::AtLeastOnce, true, event.kind.as_str()).await;
+				}
+
+				let payload = serde_json::to_vec(&event).unwrap();
+				if settings.home_assistant.is_some() && let Some(user) = &event.user {
+					let _ = client.publish(topics.creator(&user.username), qos, false, payload.clone()).await;
+				}
+
+				let _ = client.publish(topics.event(event.kind), qos, false, payload).await
+					.inspect_err(|err| warn!("Error publishing event to MQTT: {err}"));
+			},
+			Err(RecvError::Lagged(skipped)) => warn!("MQTT publisher is lagging, skipped {skipped} events"),
+			Err(RecvError::Closed) => break,
+		}
+	}
+}
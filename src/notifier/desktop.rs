@@ -0,0 +1,40 @@
+use std::time::Duration;
+use notify_rust::{Notification, Timeout};
+use super::{NotificationContent, Notifier};
+
+const LONG_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Notifications through the desktop's notification server on Linux, or Notification Center on macOS
+#[derive(Default)]
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+	fn show(&self, content: &NotificationContent) -> anyhow::Result<()> {
+		let body = match &content.attribution {
+			Some(attribution) => format!("{}\n{attribution}", content.body),
+			None => content.body.clone()
+		};
+
+		let mut notification = Notification::new();
+		notification
+		.appname("OF notifier")
+		.summary(&content.title)
+		.body(&body);
+
+		if let Some(avatar) = content.avatar.as_ref().and_then(|avatar| avatar.to_str()) {
+			notification.icon(avatar);
+		}
+
+		#[cfg(all(unix, not(target_os = "macos")))]
+		if let Some(image) = content.image.as_ref().and_then(|image| image.to_str()) {
+			notification.image_path(image);
+		}
+
+		if content.long {
+			notification.timeout(Timeout::from(LONG_TIMEOUT));
+		}
+
+		notification.show()?;
+		Ok(())
+	}
+}
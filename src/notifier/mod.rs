@@ -0,0 +1,174 @@
+#[cfg(all(windows, feature = "toasts"))]
+mod toast;
+#[cfg(all(not(windows), feature = "desktop-notifications"))]
+mod desktop;
+pub mod telegram;
+pub mod webhook;
+
+use log::*;
+use std::{future::Future, path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, OnceLock, RwLock}};
+use chrono::{DateTime, Utc};
+use tokio::runtime::Handle;
+use crate::settings::Settings;
+
+/// Group of notifications about tips on stories and streams, which aren't content of their own
+pub const TIPS_GROUP: &str = "Tips";
+
+/// What a notification shows, independently of the platform showing it
+#[derive(Debug, Clone, Default)]
+pub struct NotificationContent {
+	pub title: String,
+	pub body: String,
+	/// Secondary line, like the price of a post
+	pub attribution: Option<String>,
+	/// Notifications of a group are shown together, under the group as a header where supported
+	pub group: Option<String>,
+	/// Identifies the notification within its group
+	pub tag: Option<String>,
+	pub timestamp: Option<DateTime<Utc>>,
+	pub avatar: Option<PathBuf>,
+	pub image: Option<PathBuf>,
+	/// Stays on screen longer than usual
+	pub long: bool,
+}
+
+impl NotificationContent {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn title(&mut self, title: impl Into<String>) -> &mut Self {
+		self.title = title.into();
+		self
+	}
+
+	pub fn body(&mut self, body: impl Into<String>) -> &mut Self {
+		self.body = body.into();
+		self
+	}
+
+	pub fn attribution(&mut self, attribution: impl Into<String>) -> &mut Self {
+		self.attribution = Some(attribution.into());
+		self
+	}
+
+	pub fn group(&mut self, group: impl Into<String>) -> &mut Self {
+		self.group = Some(group.into());
+		self
+	}
+
+	pub fn tag(&mut self, tag: impl Into<String>) -> &mut Self {
+		self.tag = Some(tag.into());
+		self
+	}
+
+	pub fn timestamp(&mut self, timestamp: DateTime<Utc>) -> &mut Self {
+		self.timestamp = Some(timestamp);
+		self
+	}
+
+	pub fn avatar(&mut self, avatar: PathBuf) -> &mut Self {
+		self.avatar = Some(avatar);
+		self
+	}
+
+	pub fn image(&mut self, image: PathBuf) -> &mut Self {
+		self.image = Some(image);
+		self
+	}
+
+	pub fn long(&mut self) -> &mut Self {
+		self.long = true;
+		self
+	}
+
+	/// Title, body and attribution on separate lines, for channels without layout
+	pub fn plain_text(&self) -> String {
+		[Some(self.title.as_str()), Some(self.body.as_str()), self.attribution.as_deref()]
+		.into_iter()
+		.flatten()
+		.filter(|line| !line.trim().is_empty())
+		.collect::<Vec<_>>()
+		.join("\n")
+	}
+}
+
+/// Shows notifications on the desktop
+pub trait Notifier: Send + Sync {
+	fn show(&self, content: &NotificationContent) -> anyhow::Result<()>;
+}
+
+/// Used when the build has no backend for the platform or when running headless, notifications are only logged
+#[derive(Default)]
+pub struct NoopNotifier;
+
+impl Notifier for NoopNotifier {
+	fn show(&self, content: &NotificationContent) -> anyhow::Result<()> {
+		debug!("Notification not shown on the desktop: {} - {}", content.title, content.body);
+		Ok(())
+	}
+}
+
+#[cfg(all(windows, feature = "toasts"))]
+type Backend = toast::ToastNotifier;
+#[cfg(all(not(windows), feature = "desktop-notifications"))]
+type Backend = desktop::DesktopNotifier;
+#[cfg(not(any(all(windows, feature = "toasts"), all(not(windows), feature = "desktop-notifications"))))]
+type Backend = NoopNotifier;
+
+static DESKTOP_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Stops showing notifications on the desktop, for sessions without one. They are still forwarded to the configured channels
+pub fn disable_desktop() {
+	DESKTOP_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// The backend selected for this platform and build
+pub fn notifier() -> &'static dyn Notifier {
+	static NOOP: NoopNotifier = NoopNotifier;
+	static NOTIFIER: OnceLock<Backend> = OnceLock::new();
+
+	match DESKTOP_DISABLED.load(Ordering::Relaxed) {
+		true => &NOOP,
+		false => NOTIFIER.get_or_init(Backend::default)
+	}
+}
+
+/// Runs the sending of a notification to a remote `channel` in the background,
+/// so slow or unreachable servers don't hold up the desktop notification
+fn send_in_background<F>(channel: &'static str, send: F) -> anyhow::Result<()>
+where F: Future<Output = anyhow::Result<()>> + Send + 'static {
+	Handle::try_current()?.spawn(async move {
+		let _ = send.await
+			.inspect_err(|err| error!("Error sending {channel} notification: {err}"));
+	});
+
+	Ok(())
+}
+
+fn channels() -> &'static RwLock<Vec<Box<dyn Notifier>>> {
+	static CHANNELS: OnceLock<RwLock<Vec<Box<dyn Notifier>>>> = OnceLock::new();
+	CHANNELS.get_or_init(RwLock::default)
+}
+
+/// Sets up the channels notifications are sent to in addition to the desktop, replacing the previous ones
+pub fn configure_channels(settings: &Settings) {
+	let mut configured: Vec<Box<dyn Notifier>> = Vec::new();
+	if let Some(telegram) = &settings.telegram {
+		configured.push(Box::new(telegram::TelegramNotifier::new(telegram.clone())));
+	}
+
+	if let Some(webhook) = &settings.webhook {
+		configured.push(Box::new(webhook::WebhookNotifier::new(webhook.clone())));
+	}
+
+	*channels().write().unwrap() = configured;
+}
+
+/// Sends `content` to every configured channel, failures are only logged
+pub fn forward(content: &NotificationContent) {
+	for channel in channels().read().unwrap().iter() {
+		let _ = channel.show(content)
+			.inspect_err(|err| error!("Error forwarding notification: {err}"));
+	}
+}
@@ -0,0 +1,59 @@
+use reqwest::{multipart::{Form, Part}, Client};
+use serde::Serialize;
+use tokio::fs as tfs;
+use crate::settings::TelegramSettings;
+use super::{send_in_background, NotificationContent, Notifier};
+
+const MAX_CAPTION_CHARS: usize = 1024;
+const MAX_MESSAGE_CHARS: usize = 4096;
+
+pub(super) fn truncate(text: &str, max_chars: usize) -> String {
+	if text.chars().count() <= max_chars { return text.to_string() }
+
+	let end = text.char_indices().nth(max_chars - 1).map_or(text.len(), |(end, _)| end);
+	format!("{}…", &text[..end])
+}
+
+/// Sends notifications to a chat through a Telegram bot
+pub struct TelegramNotifier {
+	client: Client,
+	settings: TelegramSettings,
+}
+
+impl TelegramNotifier {
+	pub fn new(settings: TelegramSettings) -> Self {
+		Self { client: Client::new(), settings }
+	}
+}
+
+async fn send(client: &Client, settings: &TelegramSettings, content: &NotificationContent) -> anyhow::Result<()> {
+	#[derive(Serialize)]
+	struct Message<'a> { chat_id: &'a str, text: &'a str }
+
+	let url = |method: &str| format!("https://api.telegram.org/bot{}/{method}", settings.bot_token);
+	let text = content.plain_text();
+
+	let request = match &content.image {
+		Some(image) => {
+			let filename = image.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+			let photo = Part::bytes(tfs::read(image).await?).file_name(filename);
+			let form = Form::new()
+				.text("chat_id", settings.chat_id.clone())
+				.text("caption", truncate(&text, MAX_CAPTION_CHARS))
+				.part("photo", photo);
+
+			client.post(url("sendPhoto")).multipart(form)
+		},
+		None => client.post(url("sendMessage")).json(&Message { chat_id: &settings.chat_id, text: &truncate(&text, MAX_MESSAGE_CHARS) })
+	};
+
+	request.send().await?.error_for_status()?;
+	Ok(())
+}
+
+impl Notifier for TelegramNotifier {
+	fn show(&self, content: &NotificationContent) -> anyhow::Result<()> {
+		let (client, settings, content) = (self.client.clone(), self.settings.clone(), content.clone());
+		send_in_background("Telegram", async move { send(&client, &settings, &content).await })
+	}
+}
@@ -0,0 +1,67 @@
+use log::*;
+use std::{path::Path, sync::Mutex};
+use winrt_toast::{content::{image::{ImageHintCrop, ImagePlacement}, text::TextPlacement}, register, Header, Image, Text, Toast, ToastDuration, ToastManager};
+use super::{NotificationContent, Notifier};
+
+/// Windows toasts, shown under the application's own name and icon
+pub struct ToastNotifier {
+	manager: Mutex<ToastManager>,
+}
+
+impl Default for ToastNotifier {
+	fn default() -> Self {
+		let aum_id = "OFNotifier";
+		let icon_path = Path::new("icons").join("icon.ico").canonicalize()
+			.inspect_err(|err| error!("{err}"))
+			.unwrap();
+
+		register(aum_id, "OF notifier", Some(icon_path.as_path()))
+		.inspect_err(|err| error!("{err}"))
+		.unwrap();
+
+		Self { manager: Mutex::new(ToastManager::new(aum_id)) }
+	}
+}
+
+fn to_toast(content: &NotificationContent) -> anyhow::Result<Toast> {
+	let mut toast = Toast::new();
+	toast
+	.text1(&content.title)
+	.text2(&content.body);
+
+	if let Some(attribution) = &content.attribution {
+		toast.text3(Text::new(attribution).with_placement(TextPlacement::Attribution));
+	}
+
+	if let Some(group) = &content.group {
+		toast
+		.header(Header::new(group, group, ""))
+		.group(group);
+	}
+
+	if let Some(tag) = &content.tag { toast.tag(tag); }
+	if let Some(timestamp) = content.timestamp { toast.timestamp(timestamp); }
+
+	if let Some(avatar) = &content.avatar {
+		toast.image(1,
+			Image::new_local(avatar.canonicalize()?)?
+			.with_hint_crop(ImageHintCrop::Circle)
+			.with_placement(ImagePlacement::AppLogoOverride)
+		);
+	}
+
+	if let Some(image) = &content.image {
+		toast.image(2, Image::new_local(image)?);
+	}
+
+	if content.long { toast.duration(ToastDuration::Long); }
+	Ok(toast)
+}
+
+impl Notifier for ToastNotifier {
+	fn show(&self, content: &NotificationContent) -> anyhow::Result<()> {
+		let toast = to_toast(content)?;
+		self.manager.lock().unwrap().show(&toast)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,114 @@
+use std::{path::Path, str::FromStr};
+use chrono::{DateTime, Utc};
+use of_client::content::ContentType;
+use reqwest::{multipart::{Form, Part}, Client};
+use serde::Serialize;
+use tokio::fs as tfs;
+use crate::settings::WebhookSettings;
+use super::{send_in_background, telegram::truncate, NotificationContent, Notifier, TIPS_GROUP};
+
+const EMBED_COLOR: u32 = 0x00aff0;
+const MAX_DESCRIPTION_CHARS: usize = 4096;
+
+#[derive(Serialize)]
+struct EmbedAuthor {
+	name: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	icon_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EmbedText {
+	text: String,
+}
+
+#[derive(Serialize)]
+struct EmbedImage {
+	url: String,
+}
+
+#[derive(Serialize)]
+struct Embed {
+	author: EmbedAuthor,
+	description: String,
+	color: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	footer: Option<EmbedText>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	timestamp: Option<DateTime<Utc>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	image: Option<EmbedImage>,
+}
+
+#[derive(Serialize)]
+struct Payload {
+	embeds: [Embed; 1],
+}
+
+/// Posts notifications as embeds to a Discord webhook
+pub struct WebhookNotifier {
+	client: Client,
+	settings: WebhookSettings,
+}
+
+impl WebhookNotifier {
+	pub fn new(settings: WebhookSettings) -> Self {
+		Self { client: Client::new(), settings }
+	}
+
+	/// Content notifications are grouped by their content type, everything else counts as other
+	fn is_enabled(&self, content: &NotificationContent) -> bool {
+		if content.group.as_deref() == Some(TIPS_GROUP) { return self.settings.tips }
+
+		match content.group.as_deref().map(ContentType::from_str) {
+			Some(Ok(ContentType::Posts)) => self.settings.posts,
+			Some(Ok(ContentType::Chats)) => self.settings.messages,
+			Some(Ok(ContentType::Stories)) => self.settings.stories,
+			Some(Ok(ContentType::Streams)) => self.settings.streams,
+			_ => self.settings.other
+		}
+	}
+}
+
+/// Uploaded images are referenced as `attachment://<name>` from the embed
+fn attachment_name(prefix: &str, path: &Path) -> String {
+	let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("jpg");
+	format!("{prefix}.{extension}")
+}
+
+fn embed(content: &NotificationContent) -> Embed {
+	Embed {
+		author: EmbedAuthor {
+			name: content.title.clone(),
+			icon_url: content.avatar.as_deref().map(|avatar| format!("attachment://{}", attachment_name("avatar", avatar)))
+		},
+		description: truncate(&content.body, MAX_DESCRIPTION_CHARS),
+		color: EMBED_COLOR,
+		footer: content.attribution.clone().map(|text| EmbedText { text }),
+		timestamp: content.timestamp,
+		image: content.image.as_deref().map(|image| EmbedImage { url: format!("attachment://{}", attachment_name("image", image)) }),
+	}
+}
+
+async fn send(client: &Client, url: &str, content: &NotificationContent) -> anyhow::Result<()> {
+	let payload = Payload { embeds: [embed(content)] };
+	let mut form = Form::new().text("payload_json", serde_json::to_string(&payload)?);
+
+	let attachments = [("avatar", content.avatar.as_deref()), ("image", content.image.as_deref())];
+	for (i, (prefix, path)) in attachments.into_iter().enumerate() {
+		let Some(path) = path else { continue };
+		form = form.part(format!("files[{i}]"), Part::bytes(tfs::read(path).await?).file_name(attachment_name(prefix, path)));
+	}
+
+	client.post(url).multipart(form).send().await?.error_for_status()?;
+	Ok(())
+}
+
+impl Notifier for WebhookNotifier {
+	fn show(&self, content: &NotificationContent) -> anyhow::Result<()> {
+		if !self.is_enabled(content) { return Ok(()) }
+
+		let (client, url, content) = (self.client.clone(), self.settings.url.clone(), content.clone());
+		send_in_background("Discord", async move { send(&client, &url, &content).await })
+	}
+}
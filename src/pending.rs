@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Work that was queued but not finished yet, kept in the catalog so it can be picked up again after a restart.
+/// `account` is the account that queued it, `None` for the main one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PendingWork {
+	/// Media of a post or message that wasn't completely downloaded
+	Download {
+		#[serde(default)]
+		account: Option<String>,
+		content_type: String,
+		content_id: u64,
+		user_id: u64
+	},
+	Like {
+		#[serde(default)]
+		account: Option<String>,
+		username: String,
+		url: String
+	},
+}
+
+impl PendingWork {
+	/// Identifies the work, so queuing it again replaces the earlier entry
+	pub fn key(&self) -> String {
+		let key = match self {
+			Self::Download { content_type, content_id, .. } => format!("download:{content_type}:{content_id}"),
+			Self::Like { url, .. } => format!("like:{url}"),
+		};
+
+		match self.account() {
+			Some(account) => format!("{account}/{key}"),
+			None => key
+		}
+	}
+
+	pub fn account(&self) -> Option<&str> {
+		match self {
+			Self::Download { account, .. } | Self::Like { account, .. } => account.as_deref()
+		}
+	}
+}
@@ -0,0 +1,107 @@
+use log::*;
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use libloading::{Library, Symbol};
+use serde::Serialize;
+use serde_json::Value;
+use crate::events::Event;
+
+pub const API_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadedFile {
+	pub path: PathBuf,
+	pub username: String,
+	pub content_type: String,
+	pub content_id: u64,
+	pub media_id: u64,
+	pub bytes: u64,
+}
+
+pub trait EventPlugin: Send + Sync {
+	fn name(&self) -> &str;
+	fn settings_schema(&self) -> Option<Value> { None }
+	fn configure(&mut self, _settings: &Value) {}
+	fn on_event(&self, _event: &Event) {}
+	fn on_download_complete(&self, _file: &DownloadedFile) {}
+}
+
+type PluginCreate = fn() -> Box<dyn EventPlugin>;
+
+#[macro_export]
+macro_rules! declare_plugin {
+	($constructor: path) => {
+		#[no_mangle]
+		pub static _OF_PLUGIN_API_VERSION: u32 = $crate::plugins::API_VERSION;
+
+		#[no_mangle]
+		pub fn _of_plugin_create() -> Box<dyn $crate::plugins::EventPlugin> {
+			Box::new($constructor())
+		}
+	};
+}
+
+#[derive(Default)]
+pub struct PluginManager {
+	plugins: Vec<Box<dyn EventPlugin>>,
+	// Must be dropped after the plugins created from them
+	libraries: Vec<Library>,
+}
+
+impl PluginManager {
+	pub fn load<P: AsRef<Path>>(dir: P, settings: &HashMap<String, Value>) -> Self {
+		let mut manager = Self::default();
+		let Ok(entries) = fs::read_dir(dir.as_ref()) else { return manager };
+
+		for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+			let is_library = path.extension()
+				.and_then(|ext| ext.to_str())
+				.is_some_and(|ext| matches!(ext, "dll" | "so" | "dylib"));
+
+			if !is_library { continue }
+
+			match unsafe { load_plugin(&path) } {
+				Ok((library, mut plugin)) => {
+					if let Some(plugin_settings) = settings.get(plugin.name()) {
+						plugin.configure(plugin_settings);
+					}
+
+					info!("Loaded plugin {} from {path:?}", plugin.name());
+					manager.plugins.push(plugin);
+					manager.libraries.push(library);
+				},
+				Err(err) => error!("Error loading plugin {path:?}: {err}")
+			}
+		}
+
+		manager
+	}
+
+	pub fn settings_schemas(&self) -> HashMap<String, Value> {
+		self.plugins
+			.iter()
+			.filter_map(|plugin| plugin.settings_schema().map(|schema| (plugin.name().to_string(), schema)))
+			.collect()
+	}
+
+	pub fn on_event(&self, event: &Event) {
+		self.plugins.iter().for_each(|plugin| plugin.on_event(event));
+	}
+
+	pub fn on_download_complete(&self, file: &DownloadedFile) {
+		self.plugins.iter().for_each(|plugin| plugin.on_download_complete(file));
+	}
+}
+
+// Safety: plugins have to be built against the same version of this crate and compiler, which is checked on a best effort basis through `API_VERSION`
+unsafe fn load_plugin(path: &Path) -> anyhow::Result<(Library, Box<dyn EventPlugin>)> {
+	let library = Library::new(path)?;
+
+	let version: Symbol<*const u32> = library.get(b"_OF_PLUGIN_API_VERSION")?;
+	if **version != API_VERSION {
+		anyhow::bail!("plugin API version {} does not match {API_VERSION}", **version);
+	}
+
+	let create: Symbol<PluginCreate> = library.get(b"_of_plugin_create")?;
+	let plugin = create();
+	Ok((library, plugin))
+}
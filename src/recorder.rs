@@ -0,0 +1,102 @@
+use log::*;
+use std::{collections::HashMap, future::Future, path::{Path, PathBuf}, process::Stdio, sync::{Arc, Mutex}, time::Duration};
+use ffmpeg_sidecar::command::FfmpegCommand;
+use tokio::{fs as tfs, io::AsyncWriteExt, process::Command, select, time::timeout};
+use tokio_util::sync::CancellationToken;
+
+// How long FFmpeg is given to finish writing the recording after being asked to stop
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Captures live streams with FFmpeg, one process per stream
+#[derive(Default)]
+pub struct StreamRecorder {
+	recordings: Mutex<HashMap<u64, CancellationToken>>,
+}
+
+impl StreamRecorder {
+	pub fn is_recording(&self, stream_id: u64) -> bool {
+		self.recordings.lock().unwrap().contains_key(&stream_id)
+	}
+
+	/// Records the HLS playlist at `url` into `path` until the stream ends or is stopped.
+	/// Returns `None` if the stream is already being recorded
+	pub fn start(self: &Arc<Self>, stream_id: u64, url: String, path: PathBuf) -> Option<impl Future<Output = ()> + Send + 'static> {
+		let stop = CancellationToken::new();
+		{
+			let mut recordings = self.recordings.lock().unwrap();
+			if recordings.contains_key(&stream_id) { return None }
+			recordings.insert(stream_id, stop.clone());
+		}
+
+		let recorder = self.clone();
+		Some(async move {
+			match record(&url, &path, stop).await {
+				Ok(()) => info!("Recording of stream {stream_id} saved to {path:?}"),
+				Err(err) => error!("Error recording stream {stream_id} to {path:?}: {err}")
+			}
+
+			recorder.recordings.lock().unwrap().remove(&stream_id);
+		})
+	}
+
+	/// Asks FFmpeg to finish the recording of `stream_id`, returns whether it was being recorded
+	pub fn stop(&self, stream_id: u64) -> bool {
+		self.recordings.lock().unwrap()
+			.get(&stream_id)
+			.inspect(|stop| stop.cancel())
+			.is_some()
+	}
+
+	pub fn stop_all(&self) {
+		for stop in self.recordings.lock().unwrap().values() {
+			stop.cancel();
+		}
+	}
+}
+
+async fn record(url: &str, path: &Path, stop: CancellationToken) -> anyhow::Result<()> {
+	if let Some(dir) = path.parent() {
+		tfs::create_dir_all(dir).await?;
+	}
+
+	let mut ffmpeg_command = FfmpegCommand::new();
+	ffmpeg_command
+	.hide_banner()
+	.input(url)
+	.args(["-c", "copy"])
+	.as_inner_mut()
+	.arg(path);
+
+	let mut command: Command = {
+		let std_command: std::process::Command = ffmpeg_command.into();
+		std_command.into()
+	};
+
+	let mut child = command
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.kill_on_drop(true)
+		.spawn()?;
+
+	info!("Recording stream to {path:?}");
+	select! {
+		status = child.wait() => {
+			status?;
+			return Ok(())
+		},
+		_ = stop.cancelled() => ()
+	}
+
+	// FFmpeg finalizes the file when `q` is typed
+	if let Some(mut stdin) = child.stdin.take() {
+		let _ = stdin.write_all(b"q").await;
+	}
+
+	if timeout(STOP_TIMEOUT, child.wait()).await.is_err() {
+		warn!("FFmpeg didn't stop recording {path:?} in time, killing it");
+		child.kill().await?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,83 @@
+use log::*;
+use std::{io, net::{Ipv4Addr, SocketAddr}, sync::Arc};
+use futures_util::{SinkExt, StreamExt};
+use tokio::{net::{TcpListener, TcpStream}, sync::broadcast::{error::RecvError, Receiver, Sender}};
+use tokio_tungstenite::{accept_hdr_async, tungstenite::{self, handshake::server::{ErrorResponse, Request, Response}, http::{header, StatusCode}, Message}};
+use crate::{control::token_matches, events::Event, settings::RelaySettings};
+
+pub async fn serve(events: Sender<Event>, settings: RelaySettings) -> io::Result<()> {
+	let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, settings.port)).await
+		.inspect_err(|err| error!("Error binding event relay to port {}: {err}", settings.port))?;
+
+	info!("Event relay listening on {}", listener.local_addr()?);
+	let settings = Arc::new(settings);
+	loop {
+		let (stream, address) = listener.accept().await?;
+		let receiver = events.subscribe();
+		let settings = settings.clone();
+		tokio::spawn(async move {
+			let _ = relay(stream, address, receiver, &settings).await
+				.inspect_err(|err| warn!("Event relay connection {address} closed: {err}"));
+		});
+	}
+}
+
+/// Any page the browser has open can connect to localhost, so browsers have to come from an allowed origin.
+/// They can't set headers on websockets, the token is accepted from the `token` query parameter as well
+fn authorize(request: &Request, settings: &RelaySettings) -> Result<(), ErrorResponse> {
+	let origin = request.headers().get(header::ORIGIN).map(|value| value.to_str().unwrap_or_default());
+	if let Some(origin) = origin && !settings.allowed_origins.iter().any(|allowed| allowed == origin) {
+		warn!("Event relay rejected a connection from {origin:?}, which isn't in relay.allowed_origins");
+		return Err(reject(StatusCode::FORBIDDEN));
+	}
+
+	let from_header = request.headers()
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+		.is_some_and(|value| token_matches(value, &settings.token));
+
+	let from_query = request.uri()
+		.query()
+		.into_iter()
+		.flat_map(|query| query.split('&'))
+		.filter_map(|pair| pair.strip_prefix("token="))
+		.any(|value| token_matches(value, &settings.token));
+
+	match from_header || from_query {
+		true => Ok(()),
+		false => Err(reject(StatusCode::UNAUTHORIZED))
+	}
+}
+
+fn reject(status: StatusCode) -> ErrorResponse {
+	let mut response = ErrorResponse::new(None);
+	*response.status_mut() = status;
+	response
+}
+
+async fn relay(stream: TcpStream, address: SocketAddr, mut receiver: Receiver<Event>, settings: &RelaySettings) -> Result<(), tungstenite::Error> {
+	let socket = accept_hdr_async(stream, |request: &Request, response: Response| {
+		authorize(request, settings).map(|_| response)
+	}).await?;
+	info!("Event relay client connected: {address}");
+
+	let (mut sink, mut stream) = socket.split();
+	loop {
+		tokio::select! {
+			event = receiver.recv() => match event {
+				Ok(event) => sink.send(Message::text(serde_json::to_string(&event).unwrap())).await?,
+				Err(RecvError::Lagged(skipped)) => warn!("Event relay client {address} is lagging, skipped {skipped} events"),
+				Err(RecvError::Closed) => break,
+			},
+			message = stream.next() => match message {
+				Some(Ok(Message::Close(_))) | None => break,
+				Some(Err(err)) => return Err(err),
+				Some(Ok(_)) => ()
+			}
+		}
+	}
+
+	info!("Event relay client disconnected: {address}");
+	Ok(())
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use chrono::{DateTime, TimeDelta, Utc};
+use of_client::user::{Subscription, Transaction, User};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenewalAlert {
+	/// Expired although it was set to renew
+	Lapsed,
+	/// Still active, but won't renew anymore
+	AutoRenewDisabled,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RenewalState {
+	expired: bool,
+	auto_renew: bool,
+}
+
+/// Remembers the subscriptions between two checks, to tell which ones stopped renewing since
+pub struct RenewalMonitor {
+	states: HashMap<u64, RenewalState>,
+	seen_until: DateTime<Utc>,
+}
+
+impl Default for RenewalMonitor {
+	fn default() -> Self {
+		// Payments that failed shortly before starting are still worth a notification
+		Self { states: HashMap::new(), seen_until: Utc::now() - TimeDelta::days(1) }
+	}
+}
+
+impl RenewalMonitor {
+	/// Subscriptions that stopped renewing since the last check.
+	/// On the first check, expired subscriptions that were still set to renew count as lapsed
+	pub fn check(&mut self, subscriptions: &[Subscription]) -> Vec<(User, RenewalAlert)> {
+		let mut alerts = Vec::new();
+
+		for subscription in subscriptions {
+			let previous = self.states.insert(subscription.user.id, RenewalState {
+				expired: subscription.expired,
+				auto_renew: subscription.auto_renew
+			});
+
+			let alert = match previous {
+				Some(previous) if previous.expired || !previous.auto_renew => None,
+				Some(_) if subscription.expired => Some(RenewalAlert::Lapsed),
+				Some(_) if !subscription.auto_renew => Some(RenewalAlert::AutoRenewDisabled),
+				None if subscription.expired && subscription.auto_renew => Some(RenewalAlert::Lapsed),
+				_ => None
+			};
+
+			if let Some(alert) = alert {
+				alerts.push((subscription.user.clone(), alert));
+			}
+		}
+
+		alerts
+	}
+
+	/// Failed payments that weren't reported yet
+	pub fn failed_payments<'a>(&mut self, transactions: &'a [Transaction]) -> Vec<&'a Transaction> {
+		let failed = transactions.iter()
+			.filter(|transaction| transaction.created_at > self.seen_until && transaction.failed())
+			.collect();
+
+		if let Some(newest) = transactions.iter().map(|transaction| transaction.created_at).max() {
+			self.seen_until = self.seen_until.max(newest);
+		}
+
+		failed
+	}
+}
@@ -0,0 +1,125 @@
+use log::*;
+use std::{fmt::Write, fs, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::Duration};
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc};
+use tokio::time::interval;
+use crate::{catalog::Catalog, dashboard::format_bytes, settings::Settings, stats::{EventCounts, Stats}};
+
+const REPORT_DAYS: i64 = 7;
+const TOP_CREATORS: usize = 10;
+const PREFIX: &str = "weekly_";
+
+fn format_duration(duration: TimeDelta) -> String {
+	let minutes = duration.num_minutes();
+	match minutes {
+		m if m >= 60 => format!("{}h {}m", m / 60, m % 60),
+		m => format!("{m}m")
+	}
+}
+
+/// A Markdown summary of the activity between `since` and now
+pub fn render(catalog: &Catalog, stats: &Stats, since: DateTime<Utc>) -> anyhow::Result<String> {
+	let events = catalog.events_since(since)?;
+	let (downloads, bytes) = catalog.downloads_since(Some(since))?;
+	let (_, total_bytes) = catalog.downloads_since(None)?;
+	let session = stats.snapshot();
+
+	let mut report = String::new();
+	let _ = writeln!(report, "# Weekly report\n");
+	let _ = writeln!(report, "{} to {}\n", since.with_timezone(&Local).format("%Y-%m-%d %H:%M"), Local::now().format("%Y-%m-%d %H:%M"));
+
+	let mut creators = events.iter().collect::<Vec<_>>();
+	creators.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then(a.0.cmp(b.0)));
+
+	let _ = writeln!(report, "## Top creators\n");
+	if creators.is_empty() {
+		let _ = writeln!(report, "No activity.\n");
+	} else {
+		let _ = writeln!(report, "| Creator | Events | Posts | Messages | Stories | Streams |\n|---|---|---|---|---|---|");
+		for (username, counts) in creators.iter().take(TOP_CREATORS) {
+			let _ = writeln!(report, "| {username} | {} | {} | {} | {} | {} |", counts.total(), counts.posts, counts.messages, counts.stories, counts.streams);
+		}
+		report.push('\n');
+	}
+
+	let totals = events.values().fold(EventCounts::default(), |mut totals, counts| {
+		totals.posts += counts.posts;
+		totals.messages += counts.messages;
+		totals.stories += counts.stories;
+		totals.streams += counts.streams;
+		totals.notifications += counts.notifications;
+		totals
+	});
+
+	let _ = writeln!(report, "## New content\n");
+	let _ = writeln!(report, "- Posts: {}\n- Messages: {}\n- Stories: {}\n- Streams: {}\n- Notifications: {}\n", totals.posts, totals.messages, totals.stories, totals.streams, totals.notifications);
+
+	let _ = writeln!(report, "## Storage\n");
+	let _ = writeln!(report, "- Downloaded: {downloads} files, {}", format_bytes(bytes));
+	let _ = writeln!(report, "- Archive size: {}\n", format_bytes(total_bytes));
+
+	let _ = writeln!(report, "## Errors\n");
+	let _ = writeln!(report, "Since the application was started:\n");
+	let _ = writeln!(report, "- Failed downloads: {}", session.downloads.failures);
+	let _ = writeln!(report, "- Stalled downloads: {}", session.stalled_downloads);
+	let _ = writeln!(report, "- Events that failed to process: {}\n", session.handler_failures);
+
+	let outages = stats.outages_since(since);
+	let _ = writeln!(report, "## Downtime\n");
+	if outages.is_empty() {
+		let _ = writeln!(report, "No disconnections while the application was running. Events sent while it was closed are not included.");
+	} else {
+		let _ = writeln!(report, "Events sent during these periods were missed:\n");
+		for outage in outages {
+			let end = outage.end.unwrap_or_else(Utc::now);
+			let _ = writeln!(report, "- {} for {}{}",
+				outage.start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+				format_duration(end - outage.start.max(since)),
+				if outage.end.is_none() { ", still disconnected" } else { "" }
+			);
+		}
+	}
+
+	Ok(report)
+}
+
+/// Date of the newest report in `dir`
+pub fn last_report(dir: &Path) -> Option<NaiveDate> {
+	fs::read_dir(dir)
+		.into_iter()
+		.flatten()
+		.filter_map(Result::ok)
+		.filter_map(|entry| {
+			let name = entry.file_name();
+			let date = name.to_str()?.strip_prefix(PREFIX)?.strip_suffix(".md")?;
+			NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+		})
+		.max()
+}
+
+/// Writes the report of the last week into `dir`, returns its path
+pub fn write(catalog: &Catalog, stats: &Stats, dir: &Path) -> anyhow::Result<PathBuf> {
+	let now = Utc::now();
+	let report = render(catalog, stats, now - TimeDelta::days(REPORT_DAYS))?;
+
+	fs::create_dir_all(dir)?;
+	let path = dir.join(format!("{PREFIX}{}.md", now.with_timezone(&Local).format("%Y-%m-%d")));
+	fs::write(&path, report)?;
+	Ok(path)
+}
+
+/// Writes a report into `dir` whenever the last one is a week old, checking every `period`
+pub async fn schedule(catalog: Arc<Catalog>, stats: Arc<Stats>, settings: Arc<RwLock<Settings>>, dir: PathBuf, period: Duration) {
+	let mut interval = interval(period);
+
+	loop {
+		interval.tick().await;
+		if !settings.read().unwrap().weekly_report { continue }
+
+		let today = Local::now().date_naive();
+		if last_report(&dir).is_some_and(|last| today - last < TimeDelta::days(REPORT_DAYS)) { continue }
+
+		let _ = write(&catalog, &stats, &dir)
+			.inspect(|path| info!("Weekly report written to {path:?}"))
+			.inspect_err(|err| error!("Error writing the weekly report: {err}"));
+	}
+}
@@ -0,0 +1,59 @@
+use log::*;
+use std::path::Path;
+use rhai::{serde::to_dynamic, Dynamic, Engine, Scope, AST};
+use serde::Serialize;
+use crate::{events::Event, plugins::DownloadedFile};
+
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct Scripts {
+	engine: Engine,
+	ast: AST,
+}
+
+impl Scripts {
+	pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+		let mut engine = Engine::new();
+		engine
+		.set_max_operations(MAX_OPERATIONS)
+		.on_print(|text| info!("[script] {text}"))
+		.on_debug(|text, _, pos| debug!("[script] {pos:?} {text}"));
+
+		let ast = engine.compile_file(path.as_ref().to_path_buf())?;
+		Ok(Self { engine, ast })
+	}
+
+	fn has_hook(&self, hook: &str) -> bool {
+		self.ast.iter_functions().any(|function| function.name == hook && function.params.len() == 1)
+	}
+
+	fn call<T: Serialize>(&self, hook: &str, argument: &T) -> Option<Dynamic> {
+		if !self.has_hook(hook) { return None }
+
+		to_dynamic(argument)
+		.and_then(|argument| self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, hook, (argument,)))
+		.inspect_err(|err| warn!("Script hook {hook} failed: {err}"))
+		.ok()
+	}
+
+	pub fn should_notify(&self, event: &Event) -> bool {
+		self.call("should_notify", event)
+		.and_then(|result| result.as_bool().ok())
+		.unwrap_or(true)
+	}
+
+	pub fn should_download(&self, event: &Event) -> bool {
+		self.call("should_download", event)
+		.and_then(|result| result.as_bool().ok())
+		.unwrap_or(true)
+	}
+
+	pub fn toast_text(&self, event: &Event) -> Option<String> {
+		self.call("toast_text", event)
+		.and_then(|result| result.into_string().ok())
+	}
+
+	pub fn on_downloaded(&self, file: &DownloadedFile) {
+		self.call("on_downloaded", file);
+	}
+}
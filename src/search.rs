@@ -0,0 +1,164 @@
+use log::*;
+use std::{fs, path::Path, sync::Mutex};
+use nanohtml2text::html2text;
+use of_client::content::ContentType;
+use serde::Serialize;
+use tantivy::{
+	collector::TopDocs, directory::MmapDirectory, doc, query::QueryParser,
+	schema::{Field, Schema, Value, INDEXED, STORED, STRING, TEXT},
+	Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, TantivyError, Term
+};
+use crate::catalog::Catalog;
+
+const WRITER_MEMORY: usize = 50_000_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+	pub username: String,
+	pub content_type: String,
+	pub content_id: u64,
+	pub text: String,
+	pub score: f32,
+}
+
+struct Fields {
+	/// `content_type:content_id`, to replace the text of edited content
+	key: Field,
+	username: Field,
+	content_type: Field,
+	content_id: Field,
+	text: Field,
+}
+
+/// Full-text index over the text of archived posts, messages and notifications
+pub struct SearchIndex {
+	index: Index,
+	reader: IndexReader,
+	writer: Option<Mutex<IndexWriter>>,
+	fields: Fields,
+}
+
+fn schema() -> (Schema, Fields) {
+	let mut builder = Schema::builder();
+	let fields = Fields {
+		key: builder.add_text_field("key", STRING),
+		username: builder.add_text_field("username", TEXT | STORED),
+		content_type: builder.add_text_field("content_type", STRING | STORED),
+		content_id: builder.add_u64_field("content_id", INDEXED | STORED),
+		text: builder.add_text_field("text", TEXT | STORED),
+	};
+
+	(builder.build(), fields)
+}
+
+impl SearchIndex {
+	fn new(index: Index, fields: Fields, writable: bool) -> tantivy::Result<Self> {
+		let reader = index.reader_builder()
+			.reload_policy(ReloadPolicy::OnCommitWithDelay)
+			.try_into()?;
+
+		let writer = match writable {
+			true => Some(Mutex::new(index.writer(WRITER_MEMORY)?)),
+			false => None
+		};
+
+		Ok(Self { index, reader, writer, fields })
+	}
+
+	/// Opens the index in `dir` for reading and writing, creating it if needed.
+	/// Only one process can write to an index at a time
+	pub fn open<P: AsRef<Path>>(dir: P) -> tantivy::Result<Self> {
+		let (schema, fields) = schema();
+		fs::create_dir_all(&dir)?;
+		let index = Index::open_or_create(MmapDirectory::open(dir)?, schema)?;
+		Self::new(index, fields, true)
+	}
+
+	/// Opens the index in `dir` for searching only, while another process may be writing to it
+	pub fn open_read_only<P: AsRef<Path>>(dir: P) -> tantivy::Result<Self> {
+		let (_, fields) = schema();
+		let index = Index::open_in_dir(dir)?;
+		Self::new(index, fields, false)
+	}
+
+	pub fn in_memory() -> tantivy::Result<Self> {
+		let (schema, fields) = schema();
+		Self::new(Index::create_in_ram(schema), fields, true)
+	}
+
+	fn writer(&self) -> tantivy::Result<&Mutex<IndexWriter>> {
+		self.writer.as_ref().ok_or_else(|| TantivyError::InvalidArgument("the search index was opened read only".to_string()))
+	}
+
+	fn add_document(&self, writer: &IndexWriter, username: &str, content_type: &str, content_id: u64, text: &str) -> tantivy::Result<()> {
+		let key = format!("{content_type}:{content_id}");
+		writer.delete_term(Term::from_field_text(self.fields.key, &key));
+		writer.add_document(doc!(
+			self.fields.key => key,
+			self.fields.username => username,
+			self.fields.content_type => content_type,
+			self.fields.content_id => content_id,
+			self.fields.text => text,
+		))?;
+
+		Ok(())
+	}
+
+	/// Indexes the text of a piece of content, replacing what was indexed for it before
+	pub fn add(&self, username: &str, content_type: ContentType, content_id: u64, text: &str) -> tantivy::Result<()> {
+		let mut writer = self.writer()?.lock().unwrap();
+		self.add_document(&writer, username, &content_type.to_string(), content_id, text)?;
+		writer.commit()?;
+		self.reader.reload()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.reader.searcher().num_docs() == 0
+	}
+
+	/// Indexes all text stored in the catalog, for archives that were created before the index existed
+	pub fn backfill(&self, catalog: &Catalog) -> anyhow::Result<()> {
+		if !self.is_empty() { return Ok(()) }
+
+		let content = catalog.content()?;
+		if content.is_empty() { return Ok(()) }
+
+		info!("Indexing the text of {} archived posts and messages", content.len());
+		let mut writer = self.writer()?.lock().unwrap();
+		for (content_type, content_id, username, text) in content {
+			self.add_document(&writer, &username, &content_type, content_id, &html2text(&text))?;
+		}
+
+		writer.commit()?;
+		self.reader.reload()?;
+		Ok(())
+	}
+
+	/// Best matches of `query` in the text and usernames, best first. Malformed queries are searched as far as they can be parsed
+	pub fn search(&self, query: &str, limit: usize) -> tantivy::Result<Vec<SearchHit>> {
+		let searcher = self.reader.searcher();
+		let mut parser = QueryParser::for_index(&self.index, vec![self.fields.text, self.fields.username]);
+		parser.set_conjunction_by_default();
+
+		let (query, errors) = parser.parse_query_lenient(query);
+		for err in errors {
+			debug!("Ignoring part of search query: {err}");
+		}
+
+		searcher.search(&query, &TopDocs::with_limit(limit.max(1)))?
+		.into_iter()
+		.map(|(score, address)| {
+			let document = searcher.doc::<TantivyDocument>(address)?;
+			let text = |field| document.get_first(field).and_then(|value| value.as_str()).unwrap_or_default().to_string();
+
+			Ok(SearchHit {
+				username: text(self.fields.username),
+				content_type: text(self.fields.content_type),
+				content_id: document.get_first(self.fields.content_id).and_then(|value| value.as_u64()).unwrap_or_default(),
+				text: text(self.fields.text),
+				score
+			})
+		})
+		.collect()
+	}
+}
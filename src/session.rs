@@ -0,0 +1,94 @@
+use log::*;
+use std::{sync::{Arc, RwLock}, time::{Duration, Instant}};
+use of_client::OFClient;
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+use serde::Serialize;
+use tokio::time::interval;
+use crate::{auth_path, credentials, get_profile_auth_params, settings::Settings, AuthParseError};
+
+const COOKIE_CHECK_PERIOD: Duration = Duration::from_secs(60);
+const AUTH_POLL_PERIOD: Duration = Duration::from_secs(10);
+
+/// Replaces the cookie in the auth data of `profile`, leaving the other fields untouched
+pub fn persist_cookie(profile: Option<&str>, cookie: &str) -> Result<(), AuthParseError> {
+	let mut auth: Value = serde_json::from_str(&credentials::read(profile)?)?;
+	auth["auth"]["cookie"] = Value::from(cookie);
+
+	let mut data = Vec::new();
+	auth.serialize(&mut Serializer::with_formatter(&mut data, PrettyFormatter::with_indent(b"\t")))?;
+	credentials::write(profile, &String::from_utf8(data).unwrap())
+}
+
+/// The profile used by the client of `account`, the active one for the main account
+fn profile_of(settings: &RwLock<Settings>, account: Option<&str>) -> Option<String> {
+	account.map(str::to_string).or_else(|| settings.read().unwrap().profile.clone())
+}
+
+/// Keeps the session warm and writes cookies rotated by the server back to the auth file of `account`,
+/// or of the active profile for the main account
+pub async fn maintain(client: OFClient, settings: Arc<RwLock<Settings>>, account: Option<String>) {
+	let mut interval = interval(COOKIE_CHECK_PERIOD);
+	let mut last_ping = Instant::now();
+	let mut known = (profile_of(&settings, account.as_deref()), client.cookie_string());
+
+	loop {
+		interval.tick().await;
+		let profile = profile_of(&settings, account.as_deref());
+		let session = settings.read().unwrap().session.clone();
+
+		if let Some(keep_alive) = session.keep_alive.map(Duration::from_secs) && last_ping.elapsed() >= keep_alive {
+			last_ping = Instant::now();
+			let _ = client.get_me().await
+				.inspect(|me| debug!("Session of {} is alive", me.username))
+				.inspect_err(|err| warn!("Keep-alive request failed: {err}"));
+		}
+
+		let cookie = client.cookie_string();
+		if profile != known.0 {
+			// Switched accounts, the new cookies were just read from disk
+			known = (profile, cookie);
+			continue;
+		}
+
+		// Cookies set by the responses that found the session expired aren't worth keeping
+		if session.persist_cookies && cookie != known.1 && !client.is_auth_expired() {
+			let path = auth_path(profile.as_deref());
+			match persist_cookie(profile.as_deref(), &cookie) {
+				Ok(()) => {
+					info!("Saved refreshed session cookies to {path:?}");
+					known.1 = cookie;
+				},
+				Err(err) => error!("Error saving session cookies to {path:?}: {err}")
+			}
+		}
+	}
+}
+
+/// While the session is expired, waits for the auth data of `account` (or the active profile) to change and hands it to the client,
+/// which resumes its requests. Returns whether the new auth data was read here, rather than updated some other way
+pub async fn reload_expired_auth(client: OFClient, settings: Arc<RwLock<Settings>>, account: Option<String>) -> bool {
+	let mut interval = interval(AUTH_POLL_PERIOD);
+	let profile = profile_of(&settings, account.as_deref());
+	let mut stale = credentials::read(profile.as_deref()).ok();
+
+	while client.is_auth_expired() {
+		interval.tick().await;
+		let profile = profile_of(&settings, account.as_deref());
+		let current = credentials::read(profile.as_deref()).ok();
+		if current.is_none() || current == stale { continue }
+
+		match get_profile_auth_params(profile.as_deref()) {
+			Ok(auth) => {
+				info!("Read the updated authentication parameters from {:?}", auth_path(profile.as_deref()));
+				client.update_headers(auth);
+				return true;
+			},
+			Err(err) => {
+				warn!("The updated authentication parameters can't be used yet: {err}");
+				stale = current;
+			}
+		}
+	}
+
+	false
+}
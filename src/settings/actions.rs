@@ -2,7 +2,7 @@ use std::{collections::{HashMap, HashSet}, marker::PhantomData};
 use serde::{de::{self, Visitor}, Deserialize, Deserializer};
 use crate::settings::concrete::{ConcreteSelection, Toggle};
 
-use super::concrete::{MessageSpecificSelection, PostSpecificSelection};
+use super::concrete::{MessageSpecificSelection, NotificationSpecificSelection, PostSpecificSelection};
 
 #[derive(Debug, Clone)]
 pub enum ContentAction<T> {
@@ -83,7 +83,7 @@ pub struct AllContent {
 	pub messages: ConcreteSelection<MessageSpecificSelection>,
 	pub stories: Toggle,
 	pub streams: Toggle,
-	pub notifications: Toggle
+	pub notifications: ConcreteSelection<NotificationSpecificSelection>
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -93,7 +93,7 @@ struct PartialAllContent {
 	messages: Option<ConcreteSelection<MessageSpecificSelection>>,
 	stories: Option<Toggle>,
 	streams: Option<Toggle>,
-	notifications: Option<Toggle>
+	notifications: Option<ConcreteSelection<NotificationSpecificSelection>>
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -119,7 +119,7 @@ impl Merge<AllContent> for PartialAllContent {
 			messages: self.messages.as_ref().unwrap_or(&base.messages).clone(),
 			stories: self.stories.unwrap_or(base.stories),
 			streams: self.streams.unwrap_or(base.streams),
-			notifications: self.notifications.unwrap_or(base.notifications)
+			notifications: self.notifications.as_ref().unwrap_or(&base.notifications).clone()
 		}
 	}
 }
@@ -131,7 +131,7 @@ impl Merge<PartialAllContent> for PartialAllContent {
 			messages: self.messages.as_ref().or(base.messages.as_ref()).cloned(),
 			stories: self.stories.or(base.stories),
 			streams: self.streams.or(base.streams),
-			notifications: self.notifications.or(base.notifications)
+			notifications: self.notifications.as_ref().or(base.notifications.as_ref()).cloned()
 		}
 	}
 }
@@ -163,7 +163,7 @@ impl From<Toggle> for AllContent {
 			messages: ConcreteSelection::Toggle(value),
 			stories: value,
 			streams: value,
-			notifications: value
+			notifications: ConcreteSelection::Toggle(value)
 		}
 	}
 }
@@ -175,7 +175,7 @@ impl From<Toggle> for PartialAllContent {
 			messages: Some(ConcreteSelection::Toggle(value)),
 			stories: Some(value),
 			streams: Some(value),
-			notifications: Some(value)
+			notifications: Some(ConcreteSelection::Toggle(value))
 		}
 	}
 }
@@ -68,6 +68,12 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for ConcreteSelection<T> {
 				Ok(ConcreteSelection::Toggle(Toggle(v)))
 			}
 
+			fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+				v.parse::<Toggle>()
+				.map(ConcreteSelection::Toggle)
+				.map_err(|_| de::Error::unknown_variant(v, &["all", "none"]))
+			}
+
 			fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
 				Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))
 				.map(ConcreteSelection::Specific)
@@ -78,8 +84,9 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for ConcreteSelection<T> {
 	}
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub enum MediaSelection {
+	#[default]
 	Any,
 	Thumbnail,
 	None
@@ -103,10 +110,32 @@ impl<'de> Deserialize<'de> for MediaSelection {
 	}
 }
 
+const fn enabled() -> bool { true }
+
 #[derive(Deserialize, Debug, Clone, Copy)]
 pub struct ConcreteMediaSpecificSelection {
-	pub media: MediaSelection
+	#[serde(default)]
+	pub media: MediaSelection,
+	/// Whether the action applies to pay-per-view content
+	#[serde(default = "enabled")]
+	pub paid: bool,
 }
 
 pub type PostSpecificSelection = ConcreteMediaSpecificSelection;
-pub type MessageSpecificSelection = ConcreteMediaSpecificSelection;
\ No newline at end of file
+pub type MessageSpecificSelection = ConcreteMediaSpecificSelection;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationSpecificSelection {
+	#[serde(default = "enabled")]
+	pub promotions: bool,
+	#[serde(default = "enabled")]
+	pub discounts: bool,
+	#[serde(default = "enabled")]
+	pub price_changes: bool,
+	#[serde(default = "enabled")]
+	pub other: bool,
+	/// Tips sent on the creator's stories and live streams, unlike the other kinds only enabled when set
+	#[serde(default)]
+	pub tips: bool,
+}
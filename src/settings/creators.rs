@@ -0,0 +1,223 @@
+use std::{collections::{HashMap, HashSet}, time::Duration};
+use rand::Rng;
+use serde::{de::Error, Deserialize, Deserializer};
+use serde_json::{Map, Value};
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiredContent {
+	/// Only flag the content in the catalog
+	#[default]
+	Keep,
+	/// Also move the downloaded files into an `Expired` subfolder
+	Move,
+	/// Also write an empty `<filename>.expired` marker next to each downloaded file
+	Mark,
+	/// Also show a notification
+	Notify,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MassMessages {
+	/// Handle them like any other message
+	#[default]
+	Notify,
+	/// Download without notifying
+	Download,
+	Ignore,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GifFormat {
+	/// Leave clips as they were downloaded
+	#[default]
+	Keep,
+	/// Give every clip the `.mp4` extension
+	Mp4,
+	Gif,
+	Webp,
+}
+
+impl GifFormat {
+	/// Whether clips have to be re-encoded with FFmpeg rather than kept or renamed
+	pub fn needs_ffmpeg(&self) -> bool {
+		matches!(self, GifFormat::Gif | GifFormat::Webp)
+	}
+
+	pub fn extension(&self) -> Option<&'static str> {
+		match self {
+			GifFormat::Keep => None,
+			GifFormat::Mp4 => Some("mp4"),
+			GifFormat::Gif => Some("gif"),
+			GifFormat::Webp => Some("webp"),
+		}
+	}
+}
+
+/// What to do with media that was already downloaded as part of other content, e.g. a post that is also sent as a message
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMedia {
+	#[default]
+	Download,
+	Skip,
+	/// Hard link the existing file, so it shows up in both folders without being stored twice
+	Link,
+}
+
+/// Random delay in seconds between receiving content and liking it
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct LikeDelay {
+	pub min: u64,
+	pub max: u64,
+}
+
+impl LikeDelay {
+	pub fn sample(&self) -> Duration {
+		let (min, max) = (self.min.min(self.max), self.max.max(self.min));
+		Duration::from_secs(rand::thread_rng().gen_range(min..=max))
+	}
+}
+
+const fn default_personal_only() -> bool {
+	true
+}
+
+const fn default_cooldown() -> u64 {
+	86400
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AutoReply {
+	/// `{name}` and `{username}` are replaced with the creator's
+	pub text: String,
+	#[serde(default = "default_personal_only")]
+	pub personal_only: bool,
+	/// Minimum number of seconds between replies to the same creator
+	#[serde(default = "default_cooldown")]
+	pub cooldown: u64,
+}
+
+impl AutoReply {
+	pub fn render(&self, name: &str, username: &str) -> String {
+		self.text
+		.replace("{name}", name)
+		.replace("{username}", username)
+	}
+}
+
+/// Options that aren't content actions but can still be overridden per creator
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CreatorOptions {
+	#[serde(default)]
+	pub notify_post_edits: bool,
+	#[serde(default)]
+	pub expired_posts: ExpiredContent,
+	#[serde(default)]
+	pub mass_messages: MassMessages,
+	#[serde(default)]
+	pub mark_messages_read: bool,
+	#[serde(default)]
+	pub auto_reply: Option<AutoReply>,
+	#[serde(default)]
+	pub gifs: GifFormat,
+	#[serde(default)]
+	pub duplicate_media: DuplicateMedia,
+	#[serde(default)]
+	pub like_delay: Option<LikeDelay>,
+	#[serde(default)]
+	pub max_likes_per_day: Option<usize>,
+	#[serde(default)]
+	pub download_highlights: bool,
+}
+
+/// An option set to different values by two exceptions listing the same creator, the first one wins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionConflict {
+	pub username: String,
+	pub option: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Creators {
+	default: CreatorOptions,
+	exceptions: HashMap<String, CreatorOptions>,
+	conflicts: Vec<ExceptionConflict>,
+}
+
+impl Creators {
+	pub fn get(&self, username: &str) -> &CreatorOptions {
+		self.exceptions.get(username).unwrap_or(&self.default)
+	}
+
+	/// Creators listed in exceptions
+	pub fn usernames(&self) -> impl Iterator<Item = &str> {
+		self.exceptions.keys().map(String::as_str)
+	}
+
+	/// Whether the default or any exception re-encodes GIF clips
+	pub fn convert_gifs(&self) -> bool {
+		std::iter::once(&self.default).chain(self.exceptions.values()).any(|options| options.gifs.needs_ffmpeg())
+	}
+
+	pub fn conflicts(&self) -> &[ExceptionConflict] {
+		&self.conflicts
+	}
+}
+
+impl<'de> Deserialize<'de> for Creators {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(Deserialize)]
+		#[serde(deny_unknown_fields)]
+		struct Exception {
+			users: HashSet<String>,
+			options: Map<String, Value>,
+		}
+
+		#[derive(Deserialize)]
+		#[serde(deny_unknown_fields)]
+		struct RawCreators {
+			#[serde(default)]
+			default: Map<String, Value>,
+			#[serde(default)]
+			exceptions: Vec<Exception>,
+		}
+
+		let raw = RawCreators::deserialize(deserializer)?;
+		let options = |map: Map<String, Value>| serde_json::from_value::<CreatorOptions>(Value::Object(map)).map_err(D::Error::custom);
+
+		// Exceptions listed first take precedence, everything they don't set falls back to the default
+		let mut merged: HashMap<String, Map<String, Value>> = HashMap::new();
+		let mut conflicts = Vec::new();
+		for exception in raw.exceptions {
+			for user in exception.users {
+				let entry = merged.entry(user.clone()).or_default();
+				for (key, value) in &exception.options {
+					match entry.get(key) {
+						Some(existing) if existing != value => conflicts.push(ExceptionConflict { username: user.clone(), option: key.clone() }),
+						Some(_) => (),
+						None => { entry.insert(key.clone(), value.clone()); }
+					}
+				}
+			}
+		}
+
+		let exceptions = merged
+			.into_iter()
+			.map(|(user, mut map)| {
+				for (key, value) in &raw.default {
+					map.entry(key.clone()).or_insert_with(|| value.clone());
+				}
+
+				options(map).map(|options| (user, options))
+			})
+			.collect::<Result<_, _>>()?;
+
+		Ok(Self { default: options(raw.default)?, exceptions, conflicts })
+	}
+}
@@ -1,33 +1,710 @@
 pub mod concrete;
 pub mod actions;
+pub mod creators;
+pub mod tags;
+pub mod validation;
 
-use std::sync::{Arc, RwLock};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::Duration};
 
-use concrete::{ConcreteSelection, MessageSpecificSelection, PostSpecificSelection, Toggle};
-use log::LevelFilter;
+use concrete::{ConcreteSelection, MessageSpecificSelection, NotificationSpecificSelection, PostSpecificSelection, Toggle};
+use log::{warn, LevelFilter};
+use rand::Rng;
 use serde::Deserialize;
 use actions::{Actions, ContentAction};
+use creators::{CreatorOptions, Creators};
+use tags::TagRule;
+use crate::{events::EventKind, FileParseError};
+use of_client::HttpVersion;
 
 const fn default_log_level() -> LevelFilter {
 	LevelFilter::Info
 }
 
+const fn default_max_concurrent_downloads() -> usize {
+	4
+}
+
+const fn default_sample_rate() -> f32 {
+	1.0
+}
+
+const fn default_rest_port() -> u16 {
+	8645
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RestSettings {
+	#[serde(default = "default_rest_port")]
+	pub port: u16,
+	pub token: String,
+}
+
+const fn default_web_port() -> u16 {
+	8648
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebSettings {
+	#[serde(default = "default_web_port")]
+	pub port: u16,
+	pub token: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ControlSettings {
+	#[serde(default)]
+	pub rest: Option<RestSettings>,
+	#[serde(default)]
+	pub web: Option<WebSettings>,
+	#[serde(default)]
+	pub grpc: Option<GrpcSettings>,
+	#[serde(default)]
+	pub dbus: bool,
+	#[serde(default)]
+	pub pipe: bool,
+}
+
+const fn default_relay_port() -> u16 {
+	8646
+}
+
+const fn default_grpc_port() -> u16 {
+	8647
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcSettings {
+	#[serde(default = "default_grpc_port")]
+	pub port: u16,
+	pub token: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramSettings {
+	pub bot_token: String,
+	/// Numeric ID of the chat, or `@username` of a channel
+	pub chat_id: String,
+}
+
+const fn default_true() -> bool {
+	true
+}
+
+/// Discord webhook, with a toggle for the notifications of each content type
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSettings {
+	pub url: String,
+	#[serde(default = "default_true")]
+	pub posts: bool,
+	#[serde(default = "default_true")]
+	pub messages: bool,
+	#[serde(default = "default_true")]
+	pub stories: bool,
+	#[serde(default = "default_true")]
+	pub streams: bool,
+	#[serde(default)]
+	pub tips: bool,
+	/// Notifications that aren't about new content, like failed payments or errors
+	#[serde(default)]
+	pub other: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RelaySettings {
+	#[serde(default = "default_relay_port")]
+	pub port: u16,
+	pub token: String,
+	/// Origins of the pages allowed to connect from a browser, clients outside of a browser don't send one
+	#[serde(default)]
+	pub allowed_origins: Vec<String>,
+}
+
+const fn default_mqtt_port() -> u16 {
+	1883
+}
+
+fn default_mqtt_client_id() -> String {
+	"of-notifier".to_string()
+}
+
+fn default_mqtt_topic_prefix() -> String {
+	"of-notifier".to_string()
+}
+
+const fn default_mqtt_qos() -> u8 {
+	1
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MqttSettings {
+	pub host: String,
+	#[serde(default = "default_mqtt_port")]
+	pub port: u16,
+	pub username: Option<String>,
+	pub password: Option<String>,
+	#[serde(default = "default_mqtt_client_id")]
+	pub client_id: String,
+	#[serde(default = "default_mqtt_topic_prefix")]
+	pub topic_prefix: String,
+	#[serde(default)]
+	pub topics: HashMap<EventKind, String>,
+	#[serde(default = "default_mqtt_qos")]
+	pub qos: u8,
+	#[serde(default)]
+	pub home_assistant: Option<HomeAssistantSettings>,
+}
+
+fn default_discovery_prefix() -> String {
+	"homeassistant".to_string()
+}
+
+fn default_device_name() -> String {
+	"OF Notifier".to_string()
+}
+
+const fn default_off_delay() -> u64 {
+	60
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HomeAssistantSettings {
+	#[serde(default = "default_discovery_prefix")]
+	pub discovery_prefix: String,
+	#[serde(default = "default_device_name")]
+	pub device_name: String,
+	#[serde(default)]
+	pub creators: Vec<String>,
+	#[serde(default = "default_off_delay")]
+	pub off_delay: u64,
+}
+
+const fn default_pool_idle_timeout() -> Option<u64> {
+	Some(90)
+}
+
+const fn default_tcp_keepalive() -> Option<u64> {
+	Some(60)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleSettings {
+	pub max_requests: usize,
+	/// In seconds
+	pub window: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkSettings {
+	#[serde(default = "default_pool_idle_timeout")]
+	pub pool_idle_timeout: Option<u64>,
+	#[serde(default)]
+	pub pool_max_idle_per_host: Option<usize>,
+	#[serde(default = "default_tcp_keepalive")]
+	pub tcp_keepalive: Option<u64>,
+	#[serde(default)]
+	pub http_version: HttpVersion,
+	#[serde(default)]
+	pub schedule: Option<ScheduleSettings>,
+}
+
+impl Default for NetworkSettings {
+	fn default() -> Self {
+		Self {
+			pool_idle_timeout: default_pool_idle_timeout(),
+			pool_max_idle_per_host: None,
+			tcp_keepalive: default_tcp_keepalive(),
+			http_version: HttpVersion::default(),
+			schedule: None,
+		}
+	}
+}
+
+const fn default_keep_alive() -> Option<u64> {
+	Some(900)
+}
+
+const fn default_persist_cookies() -> bool {
+	true
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SessionSettings {
+	#[serde(default = "default_keep_alive")]
+	pub keep_alive: Option<u64>,
+	#[serde(default = "default_persist_cookies")]
+	pub persist_cookies: bool,
+}
+
+impl Default for SessionSettings {
+	fn default() -> Self {
+		Self {
+			keep_alive: default_keep_alive(),
+			persist_cookies: default_persist_cookies(),
+		}
+	}
+}
+
+const fn default_reconcile_interval() -> u64 {
+	3600
+}
+
+const fn default_reconcile_sample_size() -> usize {
+	20
+}
+
+const fn default_catch_up_chats() -> usize {
+	20
+}
+
+const fn default_catch_up_messages() -> usize {
+	10
+}
+
+const fn default_catch_up_notifications() -> usize {
+	30
+}
+
+const fn default_backfill_posts() -> usize {
+	10
+}
+
+const fn default_backfill_chats() -> usize {
+	20
+}
+
+const fn default_backfill_messages() -> usize {
+	10
+}
+
+const fn default_renewal_interval() -> u64 {
+	21600
+}
+
+const fn default_renewal_transactions() -> usize {
+	20
+}
+
+const fn default_stall_timeout() -> u64 {
+	5
+}
+
+const fn default_stall_retries() -> u32 {
+	2
+}
+
+const fn default_quit_timeout() -> u64 {
+	120
+}
+
+fn default_download_root() -> PathBuf {
+	PathBuf::from("data")
+}
+
+fn default_path_template() -> String {
+	"{username}/{content_type}/{media_type}/{filename}".to_string()
+}
+
+/// Where downloaded media is stored
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PathSettings {
+	#[serde(default = "default_download_root")]
+	pub root: PathBuf,
+	/// Directories below the root, ending with the file name
+	#[serde(default = "default_path_template")]
+	pub template: String,
+}
+
+impl Default for PathSettings {
+	fn default() -> Self {
+		Self { root: default_download_root(), template: default_path_template() }
+	}
+}
+
+/// Network storage that downloads are pushed to once they finish
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageSettings {
+	S3(S3Settings),
+	Webdav(WebdavSettings),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct S3Settings {
+	pub bucket: String,
+	pub region: String,
+	/// For S3-compatible services other than AWS
+	pub endpoint: Option<String>,
+	pub access_key: String,
+	pub secret_key: String,
+	/// Prepended to the path of every file in the bucket
+	#[serde(default)]
+	pub prefix: String,
+	/// Addresses the bucket in the path rather than the host name, which most self-hosted services need
+	#[serde(default)]
+	pub path_style: bool,
+	#[serde(default)]
+	pub keep_local: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebdavSettings {
+	/// Collection the download directories are created in
+	pub url: String,
+	pub username: Option<String>,
+	pub password: Option<String>,
+	#[serde(default)]
+	pub keep_local: bool,
+}
+
+/// Watchdog of downloads that stop receiving data
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct DownloadSettings {
+	/// Minutes without progress after which a download is restarted
+	#[serde(default = "default_stall_timeout")]
+	pub stall_timeout: u64,
+	#[serde(default = "default_stall_retries")]
+	pub stall_retries: u32,
+	/// Seconds downloads are given to finish when quitting
+	#[serde(default = "default_quit_timeout")]
+	pub quit_timeout: u64,
+	/// Compares downloads against the MD5 digest the server announces, if any
+	#[serde(default = "default_true")]
+	pub verify_checksums: bool,
+}
+
+impl Default for DownloadSettings {
+	fn default() -> Self {
+		Self { stall_timeout: default_stall_timeout(), stall_retries: default_stall_retries(), quit_timeout: default_quit_timeout(), verify_checksums: true }
+	}
+}
+
+impl DownloadSettings {
+	pub fn stall_timeout(&self) -> Duration {
+		Duration::from_secs(self.stall_timeout.max(1) * 60)
+	}
+
+	pub fn quit_timeout(&self) -> Duration {
+		Duration::from_secs(self.quit_timeout)
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReconcileSettings {
+	#[serde(default = "default_reconcile_interval")]
+	pub interval: u64,
+	#[serde(default = "default_reconcile_sample_size")]
+	pub sample_size: usize,
+	#[serde(default)]
+	pub notify: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CatchUpSettings {
+	#[serde(default = "default_catch_up_chats")]
+	pub chats: usize,
+	#[serde(default = "default_catch_up_messages")]
+	pub messages: usize,
+	#[serde(default = "default_catch_up_notifications")]
+	pub notifications: usize,
+	#[serde(default)]
+	pub notify: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BackfillSettings {
+	#[serde(default = "default_backfill_posts")]
+	pub posts: usize,
+	#[serde(default = "default_backfill_chats")]
+	pub chats: usize,
+	#[serde(default = "default_backfill_messages")]
+	pub messages: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RenewalSettings {
+	#[serde(default = "default_renewal_interval")]
+	pub interval: u64,
+	#[serde(default = "default_renewal_transactions")]
+	pub transactions: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverBudget {
+	/// Send them once the budget allows it
+	#[default]
+	Queue,
+	Drop,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverLimit {
+	/// Don't download anything new
+	#[default]
+	Skip,
+	/// Delete the oldest downloads until the usage is back under the limit
+	Trim,
+}
+
+/// Disk usage limits of downloaded media, in megabytes
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LimitSettings {
+	#[serde(default)]
+	pub max_user_mb: Option<u64>,
+	#[serde(default)]
+	pub max_total_mb: Option<u64>,
+	#[serde(default)]
+	pub over_limit: OverLimit,
+}
+
+/// Limits on likes across all creators
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LikeSettings {
+	#[serde(default)]
+	pub max_per_hour: Option<usize>,
+	#[serde(default)]
+	pub max_per_day: Option<usize>,
+	/// Minimum number of seconds between two likes
+	#[serde(default)]
+	pub min_spacing: u64,
+	/// Up to this many seconds are randomly added to the spacing
+	#[serde(default)]
+	pub jitter: u64,
+	#[serde(default)]
+	pub over_budget: OverBudget,
+}
+
+impl LikeSettings {
+	pub fn spacing(&self) -> Duration {
+		Duration::from_secs(self.min_spacing + rand::thread_rng().gen_range(0..=self.jitter))
+	}
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthStorage {
+	#[default]
+	File,
+	Keyring,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ErrorReporting {
+	pub dsn: String,
+	pub environment: Option<String>,
+	#[serde(default = "default_sample_rate")]
+	pub sample_rate: f32,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct Settings {
 	actions: Actions,
+	#[serde(default)]
+	creators: Creators,
 	pub reconnect: bool,
 	#[serde(default = "default_log_level")]
-	pub log_level: LevelFilter
+	pub log_level: LevelFilter,
+	#[serde(default)]
+	pub profile: Option<String>,
+	/// Profiles that run alongside `profile`, each with a connection of its own
+	#[serde(default)]
+	pub accounts: Vec<String>,
+	#[serde(default)]
+	pub auth_storage: AuthStorage,
+	#[serde(default = "default_max_concurrent_downloads")]
+	pub max_concurrent_downloads: usize,
+	#[serde(default)]
+	pub network: NetworkSettings,
+	#[serde(default)]
+	pub session: SessionSettings,
+	#[serde(default)]
+	pub error_reporting: Option<ErrorReporting>,
+	#[serde(default)]
+	pub control: ControlSettings,
+	#[serde(default)]
+	pub relay: Option<RelaySettings>,
+	#[serde(default)]
+	pub mqtt: Option<MqttSettings>,
+	#[serde(default)]
+	pub telegram: Option<TelegramSettings>,
+	#[serde(default)]
+	pub storage: Option<StorageSettings>,
+	#[serde(default)]
+	pub webhook: Option<WebhookSettings>,
+	#[serde(default)]
+	pub plugins: HashMap<String, serde_json::Value>,
+	#[serde(default)]
+	pub script: Option<PathBuf>,
+	#[serde(default)]
+	pub tags: Vec<TagRule>,
+	#[serde(default)]
+	pub reconcile: Option<ReconcileSettings>,
+	#[serde(default)]
+	pub renewals: Option<RenewalSettings>,
+	#[serde(default)]
+	pub catch_up: Option<CatchUpSettings>,
+	#[serde(default)]
+	pub backfill: Option<BackfillSettings>,
+	#[serde(default)]
+	pub likes: LikeSettings,
+	#[serde(default)]
+	pub error_toasts: bool,
+	#[serde(default)]
+	pub site_toasts: bool,
+	/// Tip notifications used to be toggled for every creator at the root, this key is still honored
+	#[serde(default, rename = "tips")]
+	legacy_tips: bool,
+	#[serde(default)]
+	pub weekly_report: bool,
+	#[serde(default)]
+	pub save_metadata: bool,
+	#[serde(default)]
+	pub record_streams: bool,
+	#[serde(default)]
+	pub downloads: DownloadSettings,
+	#[serde(default)]
+	pub limits: LimitSettings,
+	#[serde(default)]
+	pub paths: PathSettings,
 }
 
 impl Default for Settings {
 	fn default() -> Self {
 		Self {
 			actions: Actions::default(),
+			creators: Creators::default(),
 			reconnect: true,
-			log_level: default_log_level()
+			log_level: default_log_level(),
+			profile: None,
+			accounts: Vec::new(),
+			auth_storage: AuthStorage::default(),
+			max_concurrent_downloads: default_max_concurrent_downloads(),
+			network: NetworkSettings::default(),
+			session: SessionSettings::default(),
+			error_reporting: None,
+			control: ControlSettings::default(),
+			relay: None,
+			mqtt: None,
+			telegram: None,
+			storage: None,
+			webhook: None,
+			plugins: HashMap::new(),
+			script: None,
+			tags: Vec::new(),
+			reconcile: None,
+			renewals: None,
+			catch_up: None,
+			backfill: None,
+			likes: LikeSettings::default(),
+			error_toasts: false,
+			site_toasts: false,
+			legacy_tips: false,
+			weekly_report: false,
+			save_metadata: false,
+			record_streams: false,
+			downloads: DownloadSettings::default(),
+			limits: LimitSettings::default(),
+			paths: PathSettings::default(),
+		}
+	}
+}
+
+impl Settings {
+	pub fn creator(&self, username: &str) -> &CreatorOptions {
+		self.creators.get(username)
+	}
+
+	/// Whether GIF clips of any creator are converted with FFmpeg
+	pub fn convert_gifs(&self) -> bool {
+		self.creators.convert_gifs()
+	}
+
+	/// Whether tips sent on `username`'s stories and live streams are notified
+	pub fn tips(&self, username: &str) -> bool {
+		self.legacy_tips || matches!(
+			ContentActions::<markers::NotificationMarker>::content_actions(self, username),
+			ConcreteSelection::Specific(notifications) if notifications.tips
+		)
+	}
+
+	pub fn parse(data: &str, format: SettingsFormat) -> Result<Self, FileParseError> {
+		Ok(match format {
+			SettingsFormat::Json => serde_json::from_str(data)?,
+			#[cfg(feature = "toml")]
+			SettingsFormat::Toml => toml::from_str(data)?,
+			#[cfg(feature = "yaml")]
+			SettingsFormat::Yaml => serde_yaml::from_str(data)?,
+		})
+	}
+
+	/// Reads the settings file found by [`SettingsFormat::detect`] in `dir`
+	pub fn read(dir: &Path) -> Result<Self, FileParseError> {
+		let (path, format) = SettingsFormat::detect(dir);
+		let data = fs::read_to_string(path)?;
+		Self::parse(&data, format)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsFormat {
+	Json,
+	#[cfg(feature = "toml")]
+	Toml,
+	#[cfg(feature = "yaml")]
+	Yaml,
+}
+
+impl SettingsFormat {
+	/// File names of every supported format, in order of precedence
+	const FILES: &[(&str, SettingsFormat)] = &[
+		("settings.json", SettingsFormat::Json),
+		#[cfg(feature = "toml")]
+		("settings.toml", SettingsFormat::Toml),
+		#[cfg(feature = "yaml")]
+		("settings.yaml", SettingsFormat::Yaml),
+		#[cfg(feature = "yaml")]
+		("settings.yml", SettingsFormat::Yaml),
+	];
+
+	/// The settings file in `dir` and its format. When several exist the first in order of precedence is used,
+	/// when none do it's `settings.json`
+	pub fn detect(dir: &Path) -> (PathBuf, Self) {
+		let mut found = Self::FILES
+			.iter()
+			.map(|(name, format)| (dir.join(name), *format))
+			.filter(|(path, _)| path.is_file());
+
+		let Some(first) = found.next() else { return (dir.join(Self::FILES[0].0), Self::Json) };
+		for (ignored, _) in found {
+			warn!("Using {:?}, {ignored:?} is ignored", first.0);
 		}
+
+		first
 	}
 }
 
@@ -155,12 +832,12 @@ impl ContentActions<markers::StreamMarker> for Settings {
 }
 
 impl ContentActions<markers::NotificationMarker> for Settings {
-	type Actions = Toggle;
+	type Actions = ConcreteSelection<NotificationSpecificSelection>;
 
 	fn content_actions(&self, username: &str) -> Self::Actions {
 		let actions = self.actions.get_actions_for(username);
 		match actions.notify {
-			ContentAction::General(toggle) => toggle,
+			ContentAction::General(toggle) => ConcreteSelection::Toggle(toggle),
 			ContentAction::Specific(specific) => specific.notifications
 		}
 	}
@@ -0,0 +1,34 @@
+use std::collections::HashSet;
+use serde::Deserialize;
+use of_client::media::MediaType;
+
+/// Attaches `tag` to downloaded media that matches every criterion that is set
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TagRule {
+	pub tag: String,
+	/// Matches when the content's text contains any of them, ignoring case
+	#[serde(default)]
+	pub keywords: Vec<String>,
+	#[serde(default)]
+	pub users: HashSet<String>,
+	#[serde(default)]
+	pub media: Vec<MediaType>,
+	#[serde(default)]
+	pub min_price: Option<f32>,
+	#[serde(default)]
+	pub max_price: Option<f32>,
+}
+
+impl TagRule {
+	pub fn matches(&self, username: &str, text: Option<&str>, price: Option<f32>, media_type: &MediaType) -> bool {
+		let text = text.map(str::to_lowercase).unwrap_or_default();
+		let price = price.unwrap_or(0f32);
+
+		(self.users.is_empty() || self.users.contains(username)) &&
+		(self.media.is_empty() || self.media.contains(media_type)) &&
+		(self.keywords.is_empty() || self.keywords.iter().any(|keyword| text.contains(&keyword.to_lowercase()))) &&
+		self.min_price.is_none_or(|min| price >= min) &&
+		self.max_price.is_none_or(|max| price <= max)
+	}
+}
@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+use thiserror::Error;
+use crate::FileParseError;
+use super::Settings;
+
+const TEMPLATE_PLACEHOLDERS: [&str; 8] = ["username", "content_type", "media_type", "date", "year", "month", "content_id", "filename"];
+
+/// A problem with the settings, worded so it can be fixed without reading the documentation
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SettingsError {
+	#[error("Line {line}, column {column}: {message}")]
+	Parse { line: usize, column: usize, message: String },
+	#[error("\"{username}\" is listed in creator exceptions but is not a subscription, check the spelling of the username")]
+	UnknownCreator { username: String },
+	#[error("\"{username}\" is listed in several creator exceptions that set \"{option}\" differently, only the first one applies")]
+	ConflictingException { username: String, option: String },
+	#[error("{setting}: {reason}")]
+	Invalid { setting: &'static str, reason: String },
+}
+
+impl From<&serde_json::Error> for SettingsError {
+	fn from(err: &serde_json::Error) -> Self {
+		Self::Parse { line: err.line(), column: err.column(), message: err.to_string() }
+	}
+}
+
+impl From<&FileParseError> for SettingsError {
+	fn from(err: &FileParseError) -> Self {
+		match err {
+			FileParseError::Parse(err) => err.into(),
+			#[cfg(feature = "yaml")]
+			FileParseError::Yaml(err) if err.location().is_some() => {
+				let location = err.location().unwrap();
+				Self::Parse { line: location.line(), column: location.column(), message: err.to_string() }
+			},
+			// TOML errors already point at the line and column in their message
+			err => Self::Invalid { setting: "settings file", reason: err.to_string() }
+		}
+	}
+}
+
+impl SettingsError {
+	fn invalid(setting: &'static str, reason: impl Into<String>) -> Self {
+		Self::Invalid { setting, reason: reason.into() }
+	}
+}
+
+fn template_errors(template: &str) -> Vec<SettingsError> {
+	let mut errors = Vec::new();
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		let Some(end) = rest[start..].find('}') else {
+			errors.push(SettingsError::invalid("paths.template", "a '{' is never closed"));
+			break;
+		};
+
+		let name = &rest[start + 1 .. start + end];
+		if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+			errors.push(SettingsError::invalid("paths.template", format!("unknown placeholder {{{name}}}, expected one of {}", TEMPLATE_PLACEHOLDERS.map(|p| format!("{{{p}}}")).join(", "))));
+		}
+		rest = &rest[start + end + 1..];
+	}
+
+	if template.split('/').last() != Some("{filename}") {
+		errors.push(SettingsError::invalid("paths.template", "must end with /{filename}"));
+	}
+
+	errors
+}
+
+impl Settings {
+	/// Problems that deserializing alone does not catch: options that contradict each other or can never apply
+	pub fn validate(&self) -> Vec<SettingsError> {
+		let mut errors = self.creators.conflicts()
+			.iter()
+			.map(|conflict| SettingsError::ConflictingException { username: conflict.username.clone(), option: conflict.option.clone() })
+			.collect::<Vec<_>>();
+
+		for (i, account) in self.accounts.iter().enumerate() {
+			if self.profile.as_ref() == Some(account) || self.accounts[..i].contains(account) {
+				errors.push(SettingsError::invalid("accounts", format!("\"{account}\" is listed more than once, every account can only run once")));
+			}
+		}
+
+		let mut ports: Vec<(&'static str, u16)> = Vec::new();
+		if let Some(rest) = &self.control.rest {
+			if rest.token.is_empty() { errors.push(SettingsError::invalid("control.rest.token", "must not be empty, anyone could control the application")) }
+			ports.push(("control.rest.port", rest.port));
+		}
+		if let Some(web) = &self.control.web {
+			if web.token.is_empty() { errors.push(SettingsError::invalid("control.web.token", "must not be empty, anyone could control the application")) }
+			ports.push(("control.web.port", web.port));
+		}
+		if let Some(grpc) = &self.control.grpc {
+			if grpc.token.is_empty() { errors.push(SettingsError::invalid("control.grpc.token", "must not be empty, anyone could control the application")) }
+			ports.push(("control.grpc.port", grpc.port));
+		}
+		if let Some(relay) = &self.relay {
+			if relay.token.is_empty() { errors.push(SettingsError::invalid("relay.token", "must not be empty, anyone could follow your events")) }
+			ports.push(("relay.port", relay.port));
+		}
+
+		for (i, (setting, port)) in ports.iter().enumerate() {
+			if let Some((other, _)) = ports[..i].iter().find(|(_, other)| other == port) {
+				errors.push(SettingsError::invalid(setting, format!("port {port} is already used by {other}")));
+			}
+		}
+
+		if let Some(mqtt) = &self.mqtt && mqtt.qos > 2 {
+			errors.push(SettingsError::invalid("mqtt.qos", format!("must be 0, 1 or 2, not {}", mqtt.qos)));
+		}
+
+		if let Some(webhook) = &self.webhook && !(webhook.posts || webhook.messages || webhook.stories || webhook.streams || webhook.tips || webhook.other) {
+			errors.push(SettingsError::invalid("webhook", "every kind of notification is turned off, nothing will be sent"));
+		}
+
+		if let (Some(hour), Some(day)) = (self.likes.max_per_hour, self.likes.max_per_day) && hour > day {
+			errors.push(SettingsError::invalid("likes.max_per_hour", format!("{hour} is more than likes.max_per_day ({day})")));
+		}
+
+		if let Some(limit) = self.limits.max_user_mb && self.limits.max_total_mb.is_some_and(|total| limit > total) {
+			errors.push(SettingsError::invalid("limits.max_user_mb", "is more than limits.max_total_mb"));
+		}
+
+		errors.extend(template_errors(&self.paths.template));
+		errors
+	}
+
+	/// Creators listed in exceptions that are not among `subscriptions`. Usernames are compared case-insensitively
+	pub fn unknown_creators<'a>(&self, subscriptions: impl IntoIterator<Item = &'a str>) -> Vec<SettingsError> {
+		let subscriptions = subscriptions.into_iter().map(str::to_lowercase).collect::<HashSet<_>>();
+		let mut unknown = self.creators.usernames()
+			.filter(|username| !subscriptions.contains(&username.to_lowercase()))
+			.map(|username| SettingsError::UnknownCreator { username: username.to_string() })
+			.collect::<Vec<_>>();
+
+		unknown.sort_by(|a, b| a.to_string().cmp(&b.to_string()));
+		unknown
+	}
+}
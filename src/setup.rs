@@ -0,0 +1,68 @@
+use std::io::{self, BufRead, Write};
+use crate::{doctor, import::{self, ImportError}, settings::NetworkSettings};
+
+const INSTRUCTIONS: &str = "\
+Log in to onlyfans.com, open the developer tools (F12) on the Network tab and reload the page.
+Select any request to onlyfans.com/api2 and copy the following request headers from it.";
+
+/// The value of a copied header, without the header name if it was copied along
+pub fn header_value<'a>(input: &'a str, name: &str) -> &'a str {
+	let input = input.trim();
+	input.split_once(':')
+		.filter(|(header, _)| header.trim().eq_ignore_ascii_case(name))
+		.map_or(input, |(_, value)| value.trim())
+}
+
+fn prompt(question: &str) -> io::Result<String> {
+	print!("{question}: ");
+	io::stdout().flush()?;
+
+	let mut line = String::new();
+	if io::stdin().lock().read_line(&mut line)? == 0 {
+		return Err(io::ErrorKind::UnexpectedEof.into());
+	}
+	Ok(line)
+}
+
+/// A header value, or None to keep the current one
+fn prompt_header(name: &str) -> io::Result<Option<String>> {
+	let line = prompt(&format!("{name} (empty to keep the current one)"))?;
+	let value = header_value(&line, name);
+	Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+fn confirm(question: &str) -> io::Result<bool> {
+	let answer = prompt(&format!("{question} [Y/n]"))?;
+	Ok(!answer.trim().to_lowercase().starts_with('n'))
+}
+
+/// Asks for the cookie, user agent and x-bc on the terminal, writes them into the auth file of `profile`
+/// and checks them against OnlyFans until they work or the user gives up. Returns whether they work
+pub async fn run(profile: Option<&str>, network: &NetworkSettings) -> Result<bool, ImportError> {
+	println!("{INSTRUCTIONS}\n");
+
+	loop {
+		let cookie = loop {
+			let line = prompt("Cookie")?;
+			let cookie = header_value(&line, "cookie");
+			if !cookie.is_empty() { break cookie.to_string() }
+			println!("The cookie is required");
+		};
+		let user_agent = prompt_header("User-Agent")?;
+		let x_bc = prompt_header("x-bc")?;
+
+		let imported = import::write_auth(cookie, profile, user_agent, x_bc)?;
+		match imported.problem {
+			Some(problem) => println!("\nThe auth data is not usable: {problem}"),
+			None => {
+				println!("\nSaved to {:?}, checking the session…", imported.path);
+				let report = doctor::check_auth(profile, network).await;
+				println!("{report}");
+				if report.is_healthy() { return Ok(true) }
+			}
+		}
+
+		if !confirm("\nTry again?")? { return Ok(false) }
+		println!();
+	}
+}
@@ -0,0 +1,270 @@
+use log::*;
+use std::{collections::{BTreeMap, HashMap, VecDeque}, fs, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex}, time::Duration};
+use chrono::{DateTime, Local, Utc};
+use of_client::content::ContentType;
+use serde::Serialize;
+use tokio::time::interval;
+use crate::catalog::Catalog;
+
+const RECENT_EVENTS: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentEvent {
+	pub timestamp: DateTime<Utc>,
+	pub username: String,
+	pub content_type: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct EventCounts {
+	pub posts: u64,
+	pub messages: u64,
+	pub stories: u64,
+	pub streams: u64,
+	pub notifications: u64,
+}
+
+impl EventCounts {
+	pub fn add(&mut self, content_type: ContentType, count: u64) {
+		match content_type {
+			ContentType::Posts => self.posts += count,
+			ContentType::Chats => self.messages += count,
+			ContentType::Stories => self.stories += count,
+			ContentType::Streams => self.streams += count,
+			ContentType::Notifications => self.notifications += count,
+		}
+	}
+
+	pub fn total(&self) -> u64 {
+		self.posts + self.messages + self.stories + self.streams + self.notifications
+	}
+}
+
+pub struct DownloadRecord {
+	pub bytes: u64,
+	pub duration: Duration,
+	pub retries: u32,
+	pub success: bool,
+}
+
+impl DownloadRecord {
+	pub fn success(bytes: u64, duration: Duration, retries: u32) -> Self {
+		Self { bytes, duration, retries, success: true }
+	}
+
+	pub fn failure(duration: Duration, retries: u32) -> Self {
+		Self { bytes: 0, duration, retries, success: false }
+	}
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DownloadTotals {
+	pub downloads: u64,
+	pub failures: u64,
+	pub retries: u64,
+	pub bytes: u64,
+	pub seconds: f64,
+}
+
+impl DownloadTotals {
+	fn add(&mut self, record: &DownloadRecord) {
+		if record.success { self.downloads += 1; }
+		else { self.failures += 1; }
+
+		self.retries += record.retries as u64;
+		self.bytes += record.bytes;
+		self.seconds += record.duration.as_secs_f64();
+	}
+
+	pub fn average_speed(&self) -> f64 {
+		if self.seconds > 0.0 { self.bytes as f64 / self.seconds } else { 0.0 }
+	}
+
+	pub fn failure_rate(&self) -> f64 {
+		let total = self.downloads + self.failures;
+		if total > 0 { self.failures as f64 / total as f64 } else { 0.0 }
+	}
+}
+
+#[derive(Default)]
+struct DownloadWindows {
+	lifetime: DownloadTotals,
+	period: DownloadTotals,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsSnapshot {
+	pub downloads: DownloadTotals,
+	pub events: BTreeMap<String, EventCounts>,
+	/// Events whose handler failed or panicked
+	pub handler_failures: u64,
+	/// Downloads cancelled by the watchdog for making no progress
+	pub stalled_downloads: u64,
+}
+
+/// A period the websocket was disconnected, `end` is `None` while it still is
+#[derive(Debug, Clone, Serialize)]
+pub struct Outage {
+	pub start: DateTime<Utc>,
+	pub end: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+pub struct Stats {
+	downloads: Mutex<DownloadWindows>,
+	events: Mutex<HashMap<String, EventCounts>>,
+	recent_events: Mutex<VecDeque<RecentEvent>>,
+	handler_failures: AtomicU64,
+	stalled_downloads: AtomicU64,
+	outages: Mutex<Vec<Outage>>,
+	connected: AtomicBool,
+}
+
+impl Stats {
+	pub fn record_event(&self, username: &str, content_type: ContentType) {
+		{
+			let mut recent_events = self.recent_events.lock().unwrap();
+			if recent_events.len() == RECENT_EVENTS { recent_events.pop_front(); }
+			recent_events.push_back(RecentEvent {
+				timestamp: Utc::now(),
+				username: username.to_string(),
+				content_type: content_type.to_string()
+			});
+		}
+
+		let mut events = self.events.lock().unwrap();
+		match events.get_mut(username) {
+			Some(counts) => counts.add(content_type, 1),
+			None => {
+				let mut counts = EventCounts::default();
+				counts.add(content_type, 1);
+				events.insert(username.to_string(), counts);
+			}
+		}
+	}
+
+	pub fn recent_events(&self) -> Vec<RecentEvent> {
+		self.recent_events.lock().unwrap().iter().rev().cloned().collect()
+	}
+
+	pub fn creator_counts(&self) -> BTreeMap<String, EventCounts> {
+		self.events.lock().unwrap()
+		.iter()
+		.map(|(username, counts)| (username.clone(), counts.clone()))
+		.collect()
+	}
+
+	pub fn record_download(&self, record: DownloadRecord) {
+		let mut downloads = self.downloads.lock().unwrap();
+		downloads.lifetime.add(&record);
+		downloads.period.add(&record);
+	}
+
+	pub fn downloads(&self) -> DownloadTotals {
+		self.downloads.lock().unwrap().lifetime.clone()
+	}
+
+	pub fn record_handler_failure(&self) {
+		self.handler_failures.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn handler_failures(&self) -> u64 {
+		self.handler_failures.load(Ordering::Relaxed)
+	}
+
+	pub fn record_stall(&self) {
+		self.stalled_downloads.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn stalled_downloads(&self) -> u64 {
+		self.stalled_downloads.load(Ordering::Relaxed)
+	}
+
+	pub fn record_disconnected(&self) {
+		self.connected.store(false, Ordering::Relaxed);
+		let mut outages = self.outages.lock().unwrap();
+		if outages.last().is_none_or(|outage| outage.end.is_some()) {
+			outages.push(Outage { start: Utc::now(), end: None });
+		}
+	}
+
+	/// Ends the current outage, returns when it started
+	pub fn record_connected(&self) -> Option<DateTime<Utc>> {
+		self.connected.store(true, Ordering::Relaxed);
+		if let Some(outage) = self.outages.lock().unwrap().last_mut() && outage.end.is_none() {
+			outage.end = Some(Utc::now());
+			return Some(outage.start)
+		}
+
+		None
+	}
+
+	/// Disconnected periods that ended after `since`
+	pub fn is_connected(&self) -> bool {
+		self.connected.load(Ordering::Relaxed)
+	}
+
+	pub fn outages_since(&self, since: DateTime<Utc>) -> Vec<Outage> {
+		self.outages.lock().unwrap()
+		.iter()
+		.filter(|outage| outage.end.is_none_or(|end| end >= since))
+		.cloned()
+		.collect()
+	}
+
+	pub fn snapshot(&self) -> StatsSnapshot {
+		StatsSnapshot {
+			downloads: self.downloads(),
+			events: self.creator_counts(),
+			handler_failures: self.handler_failures(),
+			stalled_downloads: self.stalled_downloads()
+		}
+	}
+
+	fn take_period(&self) -> DownloadTotals {
+		std::mem::take(&mut self.downloads.lock().unwrap().period)
+	}
+
+	pub async fn log_rollups(self: Arc<Self>, period: Duration) {
+		let mut interval = interval(period);
+		interval.tick().await;
+
+		loop {
+			interval.tick().await;
+			let totals = self.take_period();
+			if totals.downloads + totals.failures == 0 { continue }
+
+			info!(
+				"Downloads in the last {} minutes: {} completed, {} failed ({:.1}% failure rate), {} retries, {:.3} GB at an average of {:.2} MB/s",
+				period.as_secs() / 60,
+				totals.downloads,
+				totals.failures,
+				totals.failure_rate() * 100.0,
+				totals.retries,
+				totals.bytes as f64 / 1e9,
+				totals.average_speed() / 1e6
+			);
+		}
+	}
+}
+
+pub fn export(stats: &Stats, catalog: &Catalog, dir: &Path) -> anyhow::Result<PathBuf> {
+	#[derive(Serialize)]
+	struct Export {
+		session: StatsSnapshot,
+		lifetime: BTreeMap<String, EventCounts>,
+	}
+
+	let export = Export {
+		session: stats.snapshot(),
+		lifetime: catalog.event_totals()?.into_iter().collect()
+	};
+
+	fs::create_dir_all(dir)?;
+	let path = dir
+		.join(format!("statistics_{}", Local::now().format("%Y%m%d_%H%M%S")))
+		.with_extension("json");
+
+	fs::write(&path, serde_json::to_vec_pretty(&export)?)?;
+	Ok(path)
+}
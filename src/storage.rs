@@ -0,0 +1,177 @@
+use std::{collections::HashSet, path::Path, sync::{Arc, Mutex}};
+use anyhow::{anyhow, bail};
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{Method, RequestBuilder, StatusCode, Url};
+use tokio::fs as tfs;
+use crate::settings::{StorageSettings, WebdavSettings};
+#[cfg(feature = "s3")]
+use crate::settings::S3Settings;
+
+/// Where finished downloads end up
+pub trait Storage: Send + Sync {
+	/// Takes over the finished download at `path`. `key` is its path below the download root, separated by `/`
+	fn store<'a>(&'a self, path: &'a Path, key: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+
+	/// Whether the file stays on disk once it is stored
+	fn keeps_local(&self) -> bool { true }
+}
+
+/// Leaves downloads where they were written
+pub struct Local;
+
+impl Storage for Local {
+	fn store<'a>(&'a self, _path: &'a Path, _key: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+		async { Ok(()) }.boxed()
+	}
+}
+
+/// Uploads downloads to a WebDAV collection, creating the directories of the path template as collections
+pub struct Webdav {
+	client: reqwest::Client,
+	url: Url,
+	username: Option<String>,
+	password: Option<String>,
+	keep_local: bool,
+	// Collections known to exist, so they aren't created again for every file
+	collections: Mutex<HashSet<String>>,
+}
+
+impl Webdav {
+	pub fn new(settings: &WebdavSettings) -> anyhow::Result<Self> {
+		let url = Url::parse(&settings.url)?;
+		if url.cannot_be_a_base() { bail!("{url} can't contain files") }
+
+		Ok(Self {
+			client: reqwest::Client::new(),
+			url,
+			username: settings.username.clone(),
+			password: settings.password.clone(),
+			keep_local: settings.keep_local,
+			collections: Mutex::default(),
+		})
+	}
+
+	fn url(&self, segments: &[&str]) -> Url {
+		let mut url = self.url.clone();
+		url.path_segments_mut().unwrap().pop_if_empty().extend(segments);
+		url
+	}
+
+	fn request(&self, method: Method, url: Url) -> RequestBuilder {
+		let request = self.client.request(method, url);
+		match &self.username {
+			Some(username) => request.basic_auth(username, self.password.as_ref()),
+			None => request
+		}
+	}
+
+	async fn create_collections(&self, directories: &[&str]) -> anyhow::Result<()> {
+		let mkcol = Method::from_bytes(b"MKCOL").unwrap();
+		for depth in 1..=directories.len() {
+			let collection = directories[..depth].join("/");
+			if self.collections.lock().unwrap().contains(&collection) { continue }
+
+			// The trailing slash marks a collection
+			let mut url = self.url(&directories[..depth]);
+			url.path_segments_mut().unwrap().push("");
+
+			let status = self.request(mkcol.clone(), url).send().await?.status();
+			// 405 means it already exists
+			if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+				bail!("creating collection {collection} failed with {status}");
+			}
+
+			self.collections.lock().unwrap().insert(collection);
+		}
+
+		Ok(())
+	}
+}
+
+impl Storage for Webdav {
+	fn store<'a>(&'a self, path: &'a Path, key: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+		async move {
+			let segments = key.split('/').collect::<Vec<_>>();
+			let (_, directories) = segments.split_last().ok_or_else(|| anyhow!("empty path"))?;
+			self.create_collections(directories).await?;
+
+			let file = tfs::File::open(path).await?;
+			self.request(Method::PUT, self.url(&segments))
+				.body(file)
+				.send()
+				.await?
+				.error_for_status()?;
+
+			Ok(())
+		}.boxed()
+	}
+
+	fn keeps_local(&self) -> bool {
+		self.keep_local
+	}
+}
+
+/// Uploads downloads to an S3 bucket, or any service with an S3-compatible API
+#[cfg(feature = "s3")]
+pub struct S3 {
+	bucket: Box<s3::Bucket>,
+	prefix: String,
+	keep_local: bool,
+}
+
+#[cfg(feature = "s3")]
+impl S3 {
+	pub fn new(settings: &S3Settings) -> anyhow::Result<Self> {
+		let region = match &settings.endpoint {
+			Some(endpoint) => s3::Region::Custom { region: settings.region.clone(), endpoint: endpoint.clone() },
+			None => settings.region.parse()?
+		};
+
+		let credentials = s3::creds::Credentials::new(Some(&settings.access_key), Some(&settings.secret_key), None, None, None)?;
+		let mut bucket = s3::Bucket::new(&settings.bucket, region, credentials)?;
+		if settings.path_style {
+			bucket = bucket.with_path_style();
+		}
+
+		Ok(Self { bucket, prefix: settings.prefix.trim_matches('/').to_string(), keep_local: settings.keep_local })
+	}
+}
+
+#[cfg(feature = "s3")]
+impl Storage for S3 {
+	fn store<'a>(&'a self, path: &'a Path, key: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+		async move {
+			let key = match self.prefix.is_empty() {
+				true => format!("/{key}"),
+				false => format!("/{}/{key}", self.prefix)
+			};
+
+			let mut file = tfs::File::open(path).await?;
+			let response = self.bucket.put_object_stream(&mut file, &key).await?;
+			if response.status_code() != 200 {
+				bail!("uploading {key} failed with status {}", response.status_code());
+			}
+
+			Ok(())
+		}.boxed()
+	}
+
+	fn keeps_local(&self) -> bool {
+		self.keep_local
+	}
+}
+
+/// The storage selected in the settings, the local disk if none is
+pub fn open(settings: Option<&StorageSettings>) -> anyhow::Result<Arc<dyn Storage>> {
+	Ok(match settings {
+		None => Arc::new(Local),
+		Some(StorageSettings::Webdav(settings)) => Arc::new(Webdav::new(settings)?),
+		#[cfg(feature = "s3")]
+		Some(StorageSettings::S3(settings)) => Arc::new(S3::new(settings)?),
+		#[cfg(not(feature = "s3"))]
+		Some(StorageSettings::S3(_)) => {
+			log::warn!("S3 storage is configured, but this build was compiled without the \"s3\" feature");
+			Arc::new(Local)
+		}
+	})
+}
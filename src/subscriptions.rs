@@ -0,0 +1,70 @@
+use std::{collections::HashMap, sync::RwLock};
+use of_client::user::Subscription;
+
+#[derive(Debug, Clone)]
+struct Entry {
+	username: String,
+	active: bool,
+}
+
+/// The account's subscriptions as of the last refresh, by user id
+#[derive(Default)]
+pub struct SubscriptionCache {
+	entries: RwLock<Option<HashMap<u64, Entry>>>,
+}
+
+impl SubscriptionCache {
+	/// Replaces the cached subscriptions with `subscriptions`
+	pub fn update(&self, subscriptions: &[Subscription]) {
+		let entries = subscriptions
+			.iter()
+			.map(|subscription| (subscription.user.id, Entry { username: subscription.user.username.clone(), active: !subscription.expired }))
+			.collect();
+
+		*self.entries.write().unwrap() = Some(entries);
+	}
+
+	pub fn is_loaded(&self) -> bool {
+		self.entries.read().unwrap().is_some()
+	}
+
+	/// Whether the subscription to `user_id` is active. Everyone counts as subscribed until the cache is first loaded
+	pub fn is_subscribed(&self, user_id: u64) -> bool {
+		match &*self.entries.read().unwrap() {
+			Some(entries) => entries.get(&user_id).is_some_and(|entry| entry.active),
+			None => true
+		}
+	}
+
+	/// Username of a creator the account is or was subscribed to
+	pub fn username(&self, user_id: u64) -> Option<String> {
+		self.entries.read().unwrap()
+			.as_ref()?
+			.get(&user_id)
+			.map(|entry| entry.username.clone())
+	}
+
+	/// Id of a creator the account is or was subscribed to
+	pub fn user_id(&self, username: &str) -> Option<u64> {
+		self.entries.read().unwrap()
+			.as_ref()?
+			.iter()
+			.find_map(|(user_id, entry)| (entry.username == username).then_some(*user_id))
+	}
+
+	/// Usernames of every creator the account is or was subscribed to
+	pub fn usernames(&self) -> Vec<String> {
+		self.entries.read().unwrap()
+			.as_ref()
+			.map(|entries| entries.values().map(|entry| entry.username.clone()).collect())
+			.unwrap_or_default()
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.read().unwrap().as_ref().map_or(0, HashMap::len)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
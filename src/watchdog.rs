@@ -0,0 +1,66 @@
+use std::{future::Future, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::Duration};
+use thiserror::Error;
+use tokio::{select, time::{sleep, Instant}};
+
+#[derive(Debug)]
+struct ProgressState {
+	last: Mutex<Instant>,
+	bytes: AtomicU64,
+}
+
+/// When a download last made progress and how much it received, shared between the download, its watchdog and the download manager
+#[derive(Debug, Clone)]
+pub struct Progress(Arc<ProgressState>);
+
+impl Default for Progress {
+	fn default() -> Self {
+		Self(Arc::new(ProgressState { last: Mutex::new(Instant::now()), bytes: AtomicU64::new(0) }))
+	}
+}
+
+impl Progress {
+	pub fn touch(&self) {
+		*self.0.last.lock().unwrap() = Instant::now();
+	}
+
+	pub fn advance(&self, bytes: u64) {
+		self.0.bytes.fetch_add(bytes, Ordering::Relaxed);
+		self.touch();
+	}
+
+	/// Starts over for another attempt
+	pub fn restart(&self) {
+		self.0.bytes.store(0, Ordering::Relaxed);
+		self.touch();
+	}
+
+	pub fn idle(&self) -> Duration {
+		self.0.last.lock().unwrap().elapsed()
+	}
+
+	pub fn bytes(&self) -> u64 {
+		self.0.bytes.load(Ordering::Relaxed)
+	}
+}
+
+#[derive(Error, Debug)]
+#[error("stalled without progress for {}s", .0.as_secs())]
+pub struct Stalled(pub Duration);
+
+/// Runs `job` until it completes or makes no progress for `timeout`, in which case it is dropped.
+/// Child processes of the job have to be spawned with `kill_on_drop` to be stopped as well
+pub async fn watch<T, F>(progress: &Progress, timeout: Duration, job: F) -> anyhow::Result<T>
+where F: Future<Output = anyhow::Result<T>> {
+	let watchdog = async {
+		loop {
+			let idle = progress.idle();
+			if idle >= timeout { return idle }
+			sleep(timeout - idle).await;
+		}
+	};
+
+	select! {
+		result = job => result,
+		idle = watchdog => Err(Stalled(idle).into())
+	}
+}
@@ -0,0 +1,44 @@
+use std::collections::HashSet;
+use chrono::{TimeDelta, Utc};
+use of_client::content::{Content, Post};
+use of_notifier::{catalog::Catalog, stats::Stats};
+
+#[test]
+fn reconnecting_returns_the_start_of_the_outage() {
+	let stats = Stats::default();
+	assert!(stats.record_connected().is_none());
+
+	let before = Utc::now();
+	stats.record_disconnected();
+	let since = stats.record_connected().unwrap();
+	assert!(since >= before && since <= Utc::now());
+
+	// Only the first connection after a disconnection has something to backfill
+	assert!(stats.record_connected().is_none());
+}
+
+#[test]
+fn user_posts_are_parsed() {
+	let posts = serde_json::from_str::<Vec<Post>>(r#"[{
+		"id": 1234,
+		"text": "New post",
+		"price": 0,
+		"author": { "id": 15585607, "name": "OnlyFans", "username": "onlyfans" },
+		"postedAt": "2024-05-01T12:00:00+00:00"
+	}]"#).unwrap();
+
+	assert_eq!(posts[0].id(), 1234);
+	assert!(posts[0].timestamp() < Utc::now() - TimeDelta::days(1));
+}
+
+#[test]
+fn archived_highlights_are_kept_per_creator() {
+	let catalog = Catalog::open(":memory:").unwrap();
+	catalog.record_highlight(15585607, 1).unwrap();
+	catalog.record_highlight(15585607, 1).unwrap();
+	catalog.record_highlight(15585607, 2).unwrap();
+	catalog.record_highlight(1, 3).unwrap();
+
+	assert_eq!(catalog.archived_highlights(15585607).unwrap(), HashSet::from([1, 2]));
+	assert!(catalog.archived_highlights(2).unwrap().is_empty());
+}
@@ -0,0 +1,30 @@
+use of_client::{content::{ChatMessage, ContentType}, user::User};
+use of_notifier::catalog::Catalog;
+use of_daemon::structs::Chat;
+
+#[test]
+fn fetched_messages_are_marked_as_caught_up() {
+	let message = serde_json::from_str::<ChatMessage>(r#"{
+		"id": 1234,
+		"text": "Hi",
+		"price": 0,
+		"fromUser": { "id": 15585607, "_view": "s" },
+		"queueId": 5678
+	}"#).unwrap();
+
+	let user = User { id: 15585607, name: "OnlyFans".to_string(), username: "onlyfans".to_string(), avatar: None };
+	let chat = Chat::caught_up(user, message);
+
+	assert!(chat.catch_up);
+	assert!(chat.is_mass_message());
+}
+
+#[test]
+fn archived_content_is_known() {
+	let catalog = Catalog::open(":memory:").unwrap();
+	catalog.record_content("onlyfans", ContentType::Chats, 1234, "Hi").unwrap();
+
+	assert!(catalog.has_content(ContentType::Chats, 1234).unwrap());
+	assert!(!catalog.has_content(ContentType::Chats, 5678).unwrap());
+	assert!(!catalog.has_content(ContentType::Posts, 1234).unwrap());
+}
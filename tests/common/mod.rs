@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+use of_notifier::{catalog::Catalog, plugins::DownloadedFile};
+
+/// An in-memory catalog holding one post image per `(media_id, username, bytes)`
+pub fn catalog(media: &[(u64, &str, u64)]) -> Catalog {
+	let catalog = Catalog::open(":memory:").unwrap();
+	for &(media_id, username, bytes) in media {
+		catalog.record_media(&DownloadedFile {
+			path: PathBuf::from(format!("{media_id}.jpg")),
+			username: username.to_string(),
+			content_type: "Posts".to_string(),
+			content_id: media_id,
+			media_id,
+			bytes
+		}).unwrap();
+	}
+
+	catalog
+}
@@ -0,0 +1,9 @@
+use of_notifier::control::token_matches;
+
+#[test]
+fn tokens_must_match_exactly() {
+	assert!(token_matches("s3cret", "s3cret"));
+	assert!(!token_matches("s3cres", "s3cret"));
+	assert!(!token_matches("s3cre", "s3cret"));
+	assert!(!token_matches("", "s3cret"));
+}
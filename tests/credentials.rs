@@ -0,0 +1,26 @@
+#![cfg(target_os = "linux")]
+
+use std::{env, fs};
+use of_notifier::{auth_path, credentials};
+
+// Needs an unlocked Secret Service, like a desktop session has, run with `cargo test -- --ignored`
+#[test]
+#[ignore]
+fn auth_data_survives_a_round_trip_through_the_keyring() {
+	let dir = tempfile::tempdir().unwrap();
+	env::set_current_dir(dir.path()).unwrap();
+
+	let profile = Some("keyring-round-trip-test");
+	let data = r#"{ "auth": { "user_id": "1", "x_bc": "x", "user_agent": "tests", "cookie": "sess=1" } }"#;
+	fs::create_dir_all(auth_path(profile).parent().unwrap()).unwrap();
+	fs::write(auth_path(profile), data).unwrap();
+
+	// Migrating only succeeds once the secret is read back from the Secret Service itself
+	assert!(credentials::migrate(profile).unwrap());
+	assert_ne!(fs::read_to_string(auth_path(profile)).unwrap(), data);
+	assert_eq!(credentials::read(profile).unwrap(), data);
+	assert!(!credentials::migrate(profile).unwrap());
+
+	assert!(credentials::restore(profile).unwrap());
+	assert_eq!(fs::read_to_string(auth_path(profile)).unwrap(), data);
+}
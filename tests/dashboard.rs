@@ -0,0 +1,70 @@
+use chrono::Utc;
+use of_client::user::{Transaction, User};
+use of_notifier::{catalog::Catalog, dashboard, stats::Stats};
+
+mod common;
+
+fn catalog() -> Catalog {
+	common::catalog(&[(1, "alice", 1000), (2, "alice", 500), (3, "bob, \"the\" builder", 2000)])
+}
+
+fn transaction(amount: f32, status: &str, description: &str) -> Transaction {
+	Transaction {
+		created_at: Utc::now(),
+		amount,
+		status: status.to_string(),
+		description: description.to_string(),
+		user: Some(User { id: 1, name: "Alice".to_string(), username: "alice".to_string(), avatar: None }),
+	}
+}
+
+#[test]
+fn history_is_grouped_per_creator_and_day() {
+	let history = catalog().download_history().unwrap();
+
+	assert_eq!(history.len(), 2);
+	assert_eq!(history[0].username, "alice");
+	assert_eq!((history[0].downloads, history[0].bytes), (2, 1500));
+}
+
+#[test]
+fn csv_quotes_usernames() {
+	let csv = dashboard::history_csv(&catalog()).unwrap();
+	assert!(csv.starts_with("day,username,downloads,bytes\n"));
+	assert!(csv.contains(",\"bob, \"\"the\"\" builder\",1,2000\n"));
+}
+
+#[test]
+fn dashboard_escapes_usernames() {
+	let html = dashboard::render(&catalog(), &Stats::default()).unwrap();
+	assert!(html.contains("bob, &quot;the&quot; builder"));
+}
+
+#[test]
+fn ppv_spend_skips_tips_and_failed_payments() {
+	let catalog = catalog();
+	let transactions = [
+		transaction(10.0, "done", "Payment for message from Alice"),
+		transaction(5.5, "done", "Payment for post from Alice"),
+		transaction(20.0, "done", "Tip from you to Alice"),
+		transaction(8.0, "failed", "Payment for message from Alice"),
+	];
+	catalog.record_transactions(&transactions).unwrap();
+	catalog.record_transactions(&transactions).unwrap();
+
+	assert_eq!(catalog.ppv_spend().unwrap(), vec![("alice".to_string(), 15.5)]);
+	assert!(dashboard::render(&catalog, &Stats::default()).unwrap().contains("$15.50"));
+}
+
+#[test]
+fn failures_are_counted_per_day() {
+	let catalog = catalog();
+	catalog.record_download_result("alice", true, 0).unwrap();
+	catalog.record_download_result("alice", false, 3).unwrap();
+	catalog.record_download_result("bob", false, 1).unwrap();
+
+	let history = catalog.failure_history().unwrap();
+	assert_eq!(history.len(), 1);
+	assert_eq!((history[0].downloads, history[0].failures), (1, 2));
+	assert!(dashboard::render(&catalog, &Stats::default()).unwrap().contains("2 (66.7%)"));
+}
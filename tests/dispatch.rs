@@ -0,0 +1,83 @@
+use std::{env, sync::{Arc, Once, RwLock}, time::Duration};
+use of_client::{reqwest_cookie_store::{CookieStore, CookieStoreRwLock}, OFClient, RequestHeaders};
+use of_daemon::{structs::{Message, StreamStart, TaggedMessage}, InFlight};
+use of_notifier::{handlers::{Context, Handler}, pending::PendingWork, settings::Settings};
+use serde_json::json;
+use tokio::sync::Semaphore;
+
+static WORKDIR: Once = Once::new();
+
+/// A context logged in as nobody, keeping its catalog and downloads in a temporary working directory shared by the tests
+fn context(settings: Settings) -> Context {
+	WORKDIR.call_once(|| env::set_current_dir(tempfile::tempdir().unwrap().into_path()).unwrap());
+
+	let client = OFClient::new(RequestHeaders {
+		cookie: Arc::new(CookieStoreRwLock::new(CookieStore::new(None))),
+		user_id: "1".to_string(),
+		x_bc: String::new(),
+		user_agent: "of-notifier tests".to_string(),
+	}).unwrap();
+
+	Context::new(client, None, Arc::new(RwLock::new(settings))).unwrap()
+}
+
+#[tokio::test]
+async fn long_running_handlers_do_not_hold_up_the_next_message() {
+	let mut settings = Settings::default();
+	settings.record_streams = true;
+	let context = context(settings);
+
+	let in_flight = Arc::new(Semaphore::new(1));
+	let permit = in_flight.clone().try_acquire_owned().unwrap();
+	let recording = Message::Tagged(TaggedMessage::StreamStart(StreamStart { stream_id: 1, user_id: 2 }));
+	context.dispatch_in_flight(recording, InFlight::new(permit));
+
+	// The recording has not even started yet, the next message can already be dispatched
+	assert_eq!(context.active_tasks(), 1);
+	assert!(in_flight.try_acquire().is_ok());
+
+	context.shutdown(Duration::ZERO).await;
+}
+
+#[tokio::test]
+async fn content_stays_pending_until_all_media_is_downloaded() {
+	let settings = serde_json::from_value(json!({
+		"actions": {
+			"default": { "notify": false, "download": true, "like": false },
+			"exceptions": []
+		},
+		"reconnect": true
+	})).unwrap();
+	let context = context(settings);
+
+	let message = serde_json::from_value::<Message>(json!({
+		"api2_chat_message": {
+			"id": 4748,
+			"text": "<p>One of these is gone</p>",
+			"fromUser": { "id": 15585607, "name": "OnlyFans", "username": "onlyfans" },
+			"media": [
+				{
+					"id": 1,
+					"canView": true,
+					"files": { "full": { "url": "https://raw.githubusercontent.com/allenbenz/winrt-notification/main/resources/test/chick.jpeg" } },
+					"type": "photo"
+				},
+				{
+					"id": 2,
+					"canView": true,
+					"files": { "full": { "url": "https://raw.githubusercontent.com/allenbenz/winrt-notification/main/resources/test/missing.jpeg" } },
+					"type": "photo"
+				}
+			]
+		}
+	})).unwrap();
+
+	if let Some(handle) = message.handle(&context).unwrap() {
+		handle.await.unwrap();
+	}
+
+	let pending = context.catalog.pending_work(None).unwrap();
+	assert!(matches!(pending.as_slice(), [PendingWork::Download { content_id: 4748, .. }]), "{pending:?}");
+
+	context.shutdown(Duration::ZERO).await;
+}
@@ -0,0 +1,84 @@
+use std::{path::Path, sync::{Arc, Mutex}, time::Duration};
+use of_client::reqwest::header::{self, HeaderMap, HeaderValue};
+use of_notifier::{downloads::{DownloadManager, DownloadPriority}, helpers::expected_md5};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn previews_start_before_videos_and_bulk() {
+	let manager = Arc::new(DownloadManager::new(1, CancellationToken::new()));
+	let started = Arc::new(Mutex::new(Vec::new()));
+	let permit = manager.acquire(Path::new("first"), DownloadPriority::Video).await.unwrap();
+
+	let waiters = [DownloadPriority::Bulk, DownloadPriority::Video, DownloadPriority::Preview].map(|priority| {
+		let (manager, started) = (manager.clone(), started.clone());
+		tokio::spawn(async move {
+			let _permit = manager.acquire(Path::new("next"), priority).await.unwrap();
+			started.lock().unwrap().push(priority);
+			sleep(Duration::from_millis(10)).await;
+		})
+	});
+
+	sleep(Duration::from_millis(20)).await;
+	assert_eq!(manager.transfers().iter().filter(|transfer| !transfer.active).count(), 3);
+
+	drop(permit);
+	for waiter in waiters { waiter.await.unwrap() }
+	assert_eq!(*started.lock().unwrap(), [DownloadPriority::Preview, DownloadPriority::Video, DownloadPriority::Bulk]);
+	assert!(manager.transfers().is_empty());
+}
+
+#[tokio::test]
+async fn downloads_can_be_cancelled() {
+	let manager = DownloadManager::new(1, CancellationToken::new());
+	let permit = manager.acquire(Path::new("running"), DownloadPriority::Preview).await.unwrap();
+	let id = manager.transfers()[0].id;
+
+	assert!(manager.cancel(id));
+	permit.cancelled().await;
+	assert!(!manager.cancel(id + 1));
+}
+
+#[tokio::test]
+async fn shutdown_cancels_waiting_downloads() {
+	let shutdown = CancellationToken::new();
+	let manager = DownloadManager::new(1, shutdown.clone());
+	let _permit = manager.acquire(Path::new("running"), DownloadPriority::Preview).await.unwrap();
+
+	shutdown.cancel();
+	assert!(manager.acquire(Path::new("waiting"), DownloadPriority::Preview).await.is_err());
+}
+
+#[test]
+fn md5_digests_are_read_from_headers() {
+	let mut headers = HeaderMap::new();
+	headers.insert(header::ETAG, HeaderValue::from_static("\"9e107d9d372bb6826bd81d3542a419d6\""));
+	assert_eq!(expected_md5(&headers, false).map(base16::encode_lower).as_deref(), Some("9e107d9d372bb6826bd81d3542a419d6"));
+
+	// Content-MD5 takes precedence, but only describes the whole file in full responses
+	headers.insert("content-md5", HeaderValue::from_static("1B2M2Y8AsgTpgAmY7PhCfg=="));
+	assert_eq!(expected_md5(&headers, false).map(base16::encode_lower).as_deref(), Some("d41d8cd98f00b204e9800998ecf8427e"));
+	assert_eq!(expected_md5(&headers, true).map(base16::encode_lower).as_deref(), Some("9e107d9d372bb6826bd81d3542a419d6"));
+}
+
+#[test]
+fn other_etags_are_not_digests() {
+	let mut headers = HeaderMap::new();
+	headers.insert(header::ETAG, HeaderValue::from_static("W/\"9e107d9d372bb6826bd81d3542a419d6\""));
+	assert_eq!(expected_md5(&headers, false), None);
+
+	// Multipart uploads have a part count appended
+	headers.insert(header::ETAG, HeaderValue::from_static("\"9e107d9d372bb6826bd81d3542a419d6-3\""));
+	assert_eq!(expected_md5(&headers, false), None);
+}
+
+#[tokio::test]
+async fn permits_count_retries() {
+	let manager = DownloadManager::new(1, CancellationToken::new());
+	let permit = manager.acquire(Path::new("running"), DownloadPriority::Video).await.unwrap();
+	assert_eq!(permit.retries(), 0);
+
+	permit.record_retry();
+	permit.record_retry();
+	assert_eq!(permit.retries(), 2);
+}
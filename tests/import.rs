@@ -0,0 +1,38 @@
+use of_notifier::import::{detect_format, parse_cookies, CookieFormat, ImportError};
+
+const COOKIE_EDITOR: &str = r#"[
+	{"domain": ".onlyfans.com", "name": "sess", "value": "abc", "path": "/", "secure": true, "httpOnly": true, "expirationDate": 1767225600},
+	{"domain": "onlyfans.com", "name": "auth_id", "value": "123", "path": "/", "secure": true, "httpOnly": false},
+	{"domain": ".example.com", "name": "tracker", "value": "nope", "path": "/"}
+]"#;
+
+const NETSCAPE: &str = "# Netscape HTTP Cookie File\n\
+# This is a generated file! Do not edit.\n\
+\n\
+#HttpOnly_.onlyfans.com\tTRUE\t/\tTRUE\t1767225600\tsess\tabc\n\
+onlyfans.com\tFALSE\t/\tTRUE\t1767225600\tauth_id\t123\r\n\
+.example.com\tTRUE\t/\tFALSE\t0\ttracker\tnope\n";
+
+#[test]
+fn detects_formats() {
+	assert_eq!(detect_format(COOKIE_EDITOR), Some(CookieFormat::CookieEditor));
+	assert_eq!(detect_format(NETSCAPE), Some(CookieFormat::Netscape));
+	assert_eq!(detect_format("sess=abc; auth_id=123"), None);
+}
+
+#[test]
+fn parses_cookie_editor() {
+	assert_eq!(parse_cookies(COOKIE_EDITOR).unwrap(), "auth_id=123; sess=abc");
+}
+
+#[test]
+fn parses_netscape() {
+	assert_eq!(parse_cookies(NETSCAPE).unwrap(), "auth_id=123; sess=abc");
+}
+
+#[test]
+fn rejects_exports_without_onlyfans_cookies() {
+	let data = r#"[{"domain": ".example.com", "name": "tracker", "value": "nope"}]"#;
+	assert!(matches!(parse_cookies(data), Err(ImportError::NoCookies)));
+	assert!(matches!(parse_cookies("not cookies"), Err(ImportError::UnknownFormat)));
+}
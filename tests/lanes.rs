@@ -0,0 +1,71 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+use of_notifier::lanes::Lanes;
+use tokio::time::sleep;
+
+#[tokio::test]
+async fn same_key_runs_in_order() {
+	let lanes = Arc::new(Lanes::default());
+	let order = Arc::new(Mutex::new(Vec::new()));
+
+	let first = lanes.run(1, {
+		let order = order.clone();
+		async move {
+			sleep(Duration::from_millis(50)).await;
+			order.lock().unwrap().push("published");
+		}
+	});
+
+	let second = lanes.run(1, {
+		let order = order.clone();
+		async move { order.lock().unwrap().push("edited"); }
+	});
+
+	let _ = tokio::join!(first, second);
+	assert_eq!(*order.lock().unwrap(), ["published", "edited"]);
+	assert!(lanes.is_empty());
+}
+
+#[tokio::test]
+async fn different_keys_run_concurrently() {
+	let lanes = Arc::new(Lanes::default());
+	let order = Arc::new(Mutex::new(Vec::new()));
+
+	let slow = lanes.run(1, {
+		let order = order.clone();
+		async move {
+			sleep(Duration::from_millis(50)).await;
+			order.lock().unwrap().push(1);
+		}
+	});
+
+	let fast = lanes.run(2, {
+		let order = order.clone();
+		async move { order.lock().unwrap().push(2); }
+	});
+
+	let _ = tokio::join!(slow, fast);
+	assert_eq!(*order.lock().unwrap(), [2, 1]);
+}
+
+#[tokio::test]
+async fn a_panicking_task_does_not_block_its_lane() {
+	let lanes = Arc::new(Lanes::default());
+
+	let failed = lanes.run(1, async { panic!("handler failed") });
+	let next = lanes.run(1, async {});
+
+	assert!(failed.await.is_err());
+	assert!(next.await.is_ok());
+	assert!(!lanes.is_busy(&1));
+}
+
+#[tokio::test]
+async fn lane_is_released_when_the_queued_task_finishes() {
+	let lanes = Arc::new(Lanes::default());
+
+	let follow_up = lanes.queue(1, async { async { sleep(Duration::from_millis(50)).await } }).await;
+	assert!(!lanes.is_busy(&1));
+
+	lanes.run(1, async {}).await.unwrap();
+	follow_up.await;
+}
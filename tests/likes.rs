@@ -0,0 +1,50 @@
+use std::time::Duration;
+use of_notifier::{likes::LikeHistory, settings::LikeSettings};
+use tokio::time::Instant;
+
+fn settings(max_per_hour: Option<usize>, max_per_day: Option<usize>) -> LikeSettings {
+	LikeSettings { max_per_hour, max_per_day, ..LikeSettings::default() }
+}
+
+#[test]
+fn likes_within_budget_are_sent_immediately() {
+	let now = Instant::now();
+	let mut history = LikeHistory::default();
+	history.record(now);
+
+	assert_eq!(history.budget_wait(&settings(Some(2), None), now), Duration::ZERO);
+	assert_eq!(history.budget_wait(&LikeSettings::default(), now), Duration::ZERO);
+}
+
+#[test]
+fn hourly_budget_waits_for_the_oldest_like() {
+	let start = Instant::now();
+	let mut history = LikeHistory::default();
+	history.record(start);
+	history.record(start + Duration::from_secs(600));
+
+	let now = start + Duration::from_secs(1200);
+	assert_eq!(history.budget_wait(&settings(Some(2), None), now), Duration::from_secs(2400));
+}
+
+#[test]
+fn daily_budget_outlasts_the_hourly_one() {
+	let start = Instant::now();
+	let mut history = LikeHistory::default();
+	history.record(start);
+	history.record(start + Duration::from_secs(7200));
+
+	let now = start + Duration::from_secs(10800);
+	assert_eq!(history.budget_wait(&settings(Some(5), Some(2)), now), Duration::from_secs(24 * 3600 - 10800));
+}
+
+#[test]
+fn spacing_is_measured_from_the_last_like() {
+	let start = Instant::now();
+	let mut history = LikeHistory::default();
+	assert_eq!(history.spacing_wait(Duration::from_secs(30), start), Duration::ZERO);
+
+	history.record(start);
+	assert_eq!(history.spacing_wait(Duration::from_secs(30), start + Duration::from_secs(10)), Duration::from_secs(20));
+	assert_eq!(history.spacing_wait(Duration::from_secs(30), start + Duration::from_secs(40)), Duration::ZERO);
+}
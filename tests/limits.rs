@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+use of_client::content::ContentType;
+use of_notifier::{catalog::Catalog, plugins::DownloadedFile};
+
+mod common;
+
+const MEDIA: &[(u64, &str, u64)] = &[(1, "alice", 1000), (2, "bob", 2000), (3, "alice", 500)];
+
+#[test]
+fn usage_is_summed_per_creator_and_overall() {
+	let catalog = common::catalog(MEDIA);
+	assert_eq!(catalog.disk_usage(Some("alice")).unwrap(), 1500);
+	assert_eq!(catalog.disk_usage(None).unwrap(), 3500);
+	assert_eq!(catalog.disk_usage(Some("carol")).unwrap(), 0);
+}
+
+#[test]
+fn removed_media_no_longer_counts() {
+	let catalog = common::catalog(MEDIA);
+	let oldest = catalog.oldest_media(Some("alice"), 10).unwrap();
+	assert_eq!(oldest.len(), 2);
+	assert!(oldest.iter().all(|(record, _)| record.username == "alice"));
+
+	catalog.remove_media(&oldest[0].0).unwrap();
+	assert_eq!(catalog.disk_usage(Some("alice")).unwrap(), 1500 - oldest[0].1);
+}
+
+#[test]
+fn media_is_kept_per_content() {
+	let catalog = common::catalog(MEDIA);
+	catalog.record_media(&DownloadedFile {
+		path: PathBuf::from("message/1.jpg"),
+		username: "alice".to_string(),
+		content_type: "Messages".to_string(),
+		content_id: 10,
+		media_id: 1,
+		bytes: 1000
+	}).unwrap();
+
+	assert!(catalog.media_ids(ContentType::Posts, 1).unwrap().contains(&1));
+	assert!(catalog.media_ids(ContentType::Chats, 10).unwrap().contains(&1));
+	assert_eq!(catalog.disk_usage(Some("alice")).unwrap(), 2500);
+}
+
+#[test]
+fn catalogs_keyed_by_media_id_are_migrated() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("catalog.sqlite3");
+	rusqlite::Connection::open(&path).unwrap().execute_batch("
+		CREATE TABLE media (
+			media_id INTEGER PRIMARY KEY,
+			content_type TEXT NOT NULL,
+			content_id INTEGER NOT NULL,
+			username TEXT NOT NULL,
+			path TEXT NOT NULL,
+			bytes INTEGER NOT NULL,
+			downloaded_at TEXT NOT NULL
+		);
+		CREATE INDEX media_content ON media (content_type, content_id);
+		INSERT INTO media VALUES (1, 'Posts', 5, 'alice', '1.jpg', 1000, '2024-01-01T00:00:00+00:00');
+	").unwrap();
+
+	let catalog = Catalog::open(&path).unwrap();
+	assert!(catalog.media_ids(ContentType::Posts, 5).unwrap().contains(&1));
+	assert_eq!(catalog.disk_usage(None).unwrap(), 1000);
+}
+
+#[test]
+fn stored_media_is_off_disk() {
+	let catalog = common::catalog(MEDIA);
+	catalog.mark_stored(&PathBuf::from("1.jpg"), "alice/1.jpg").unwrap();
+
+	assert_eq!(catalog.disk_usage(Some("alice")).unwrap(), 500);
+	assert_eq!(catalog.media_path(1).unwrap(), None);
+	assert_eq!(catalog.remote_key(1).unwrap().as_deref(), Some("alice/1.jpg"));
+	assert!(catalog.oldest_media(None, 10).unwrap().iter().all(|(record, _)| record.media_id != 1));
+}
@@ -0,0 +1,37 @@
+use of_client::content::Post;
+
+#[test]
+fn posts_serialize_without_drm_signatures() {
+	let post = serde_json::from_str::<Post>(r#"{
+		"id": 1234,
+		"text": "New video",
+		"price": 4.99,
+		"author": { "id": 15585607, "name": "OnlyFans", "username": "onlyfans" },
+		"postedAt": "2024-05-01T12:00:00+00:00",
+		"media": [{
+			"id": 5678,
+			"type": "video",
+			"canView": true,
+			"files": {
+				"full": { "url": null },
+				"drm": {
+					"manifest": { "hls": "https://cdn3.onlyfans.com/hls/manifest.m3u8", "dash": "https://cdn3.onlyfans.com/dash/manifest.mpd" },
+					"signature": {
+						"hls": { "CloudFront-Policy": "policy", "CloudFront-Signature": "signature", "CloudFront-Key-Pair-Id": "key" },
+						"dash": { "CloudFront-Policy": "policy", "CloudFront-Signature": "signature", "CloudFront-Key-Pair-Id": "key" }
+					}
+				}
+			}
+		}]
+	}"#).unwrap();
+
+	let metadata = serde_json::to_value(&post).unwrap();
+	assert_eq!(metadata["text"], "New video");
+	assert_eq!(metadata["author"]["username"], "onlyfans");
+	assert_eq!(metadata["media"][0]["id"], 5678);
+	assert_eq!(metadata["media"][0]["type"], "video");
+	assert!(!metadata.to_string().contains("CloudFront"));
+
+	// Sidecars can be read back
+	serde_json::from_value::<Post>(metadata).unwrap();
+}
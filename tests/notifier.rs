@@ -0,0 +1,11 @@
+use of_notifier::notifier::NotificationContent;
+
+#[test]
+fn plain_text_skips_empty_lines() {
+	let mut content = NotificationContent::new();
+	content.title("OnlyFans").body("New post");
+	assert_eq!(content.plain_text(), "OnlyFans\nNew post");
+
+	content.body("").attribution("$4.99");
+	assert_eq!(content.plain_text(), "OnlyFans\n$4.99");
+}
@@ -0,0 +1,40 @@
+use std::path::Path;
+use chrono::{TimeZone, Utc};
+use of_client::{content::ContentType, media::MediaType};
+use of_notifier::{helpers::{MediaLocation, PathResolver}, settings::PathSettings};
+
+fn location(username: &str) -> MediaLocation<'_> {
+	MediaLocation {
+		username,
+		content_type: ContentType::Chats,
+		media_type: &MediaType::Gif,
+		content_id: 1234,
+		timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+	}
+}
+
+#[test]
+fn default_layout_is_unchanged() {
+	let resolver = PathResolver::from(&PathSettings::default());
+	assert_eq!(resolver.media_dir(&location("onlyfans")), Path::new("data").join("onlyfans").join("Messages").join("Videos"));
+}
+
+#[test]
+fn templates_are_resolved() {
+	let settings = PathSettings { root: "archive".into(), template: "{year}/{username}/{date}_{content_id}/{filename}".to_string() };
+	let resolver = PathResolver::from(&settings);
+	assert_eq!(resolver.media_dir(&location("onlyfans")), Path::new("archive").join("2024").join("onlyfans").join("2024-05-01_1234"));
+}
+
+#[test]
+fn values_cannot_add_directories() {
+	let settings = PathSettings { root: "data".into(), template: "{username}/{filename}".to_string() };
+	let resolver = PathResolver::from(&settings);
+	assert_eq!(resolver.media_dir(&location("../x")), Path::new("data").join(".._x"));
+}
+
+#[test]
+fn accounts_are_kept_apart() {
+	let resolver = PathResolver::from(&PathSettings::default()).within(Some("second"));
+	assert_eq!(resolver.media_dir(&location("onlyfans")), Path::new("data").join("second").join("onlyfans").join("Messages").join("Videos"));
+}
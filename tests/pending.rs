@@ -0,0 +1,51 @@
+use of_notifier::{catalog::Catalog, pending::PendingWork};
+
+fn download(content_id: u64) -> PendingWork {
+	PendingWork::Download { account: None, content_type: "Posts".to_string(), content_id, user_id: 15585607 }
+}
+
+#[test]
+fn unfinished_work_is_kept_in_order() {
+	let catalog = Catalog::open(":memory:").unwrap();
+	let like = PendingWork::Like { account: None, username: "onlyfans".to_string(), url: "https://onlyfans.com/api2/v2/messages/1/like".to_string() };
+
+	catalog.record_pending(&download(1)).unwrap();
+	catalog.record_pending(&like).unwrap();
+	catalog.record_pending(&download(2)).unwrap();
+	catalog.clear_pending(&download(2)).unwrap();
+
+	assert_eq!(catalog.pending_work(None).unwrap(), [download(1), like]);
+}
+
+#[test]
+fn queuing_work_again_keeps_one_entry() {
+	let catalog = Catalog::open(":memory:").unwrap();
+	catalog.record_pending(&download(1)).unwrap();
+	catalog.record_pending(&download(1)).unwrap();
+
+	assert_eq!(catalog.pending_work(None).unwrap().len(), 1);
+}
+
+#[test]
+fn work_is_restored_by_the_account_that_queued_it() {
+	let catalog = Catalog::open(":memory:").unwrap();
+	let like = |account: Option<&str>| PendingWork::Like {
+		account: account.map(str::to_string),
+		username: "onlyfans".to_string(),
+		url: "https://onlyfans.com/api2/v2/messages/1/like".to_string()
+	};
+
+	catalog.record_pending(&like(None)).unwrap();
+	catalog.record_pending(&like(Some("alt"))).unwrap();
+
+	assert_eq!(catalog.pending_work(None).unwrap(), [like(None)]);
+	assert_eq!(catalog.pending_work(Some("alt")).unwrap(), [like(Some("alt"))]);
+	assert!(catalog.pending_work(Some("other")).unwrap().is_empty());
+}
+
+#[test]
+fn entries_without_an_account_belong_to_the_main_one() {
+	let work = serde_json::from_str::<PendingWork>(r#"{"kind": "like", "username": "onlyfans", "url": "https://onlyfans.com"}"#).unwrap();
+	assert_eq!(work.account(), None);
+	assert_eq!(work.key(), "like:https://onlyfans.com");
+}
@@ -0,0 +1,28 @@
+use of_client::content::{Content, Purchased};
+
+#[test]
+fn purchases_are_told_apart() {
+	let purchased = serde_json::from_str::<Vec<Purchased>>(r#"[
+		{
+			"responseType": "post",
+			"id": 1234,
+			"text": "Paid post",
+			"price": 10,
+			"author": { "id": 15585607, "_view": "u" },
+			"media": []
+		},
+		{
+			"responseType": "message",
+			"id": 5678,
+			"text": "Paid message",
+			"price": 5,
+			"fromUser": { "id": 15585607, "_view": "s" },
+			"media": []
+		}
+	]"#).unwrap();
+
+	assert!(matches!(purchased[0], Purchased::Post { id: 1234, author } if author.id == 15585607));
+	let Purchased::Message(message) = &purchased[1] else { panic!("expected a message") };
+	assert_eq!(message.from_user.id, 15585607);
+	assert_eq!(message.content.id(), 5678);
+}
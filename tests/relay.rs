@@ -0,0 +1,46 @@
+use std::time::Duration;
+use of_notifier::{relay, settings::RelaySettings};
+use tokio::{sync::broadcast, time::sleep};
+use tokio_tungstenite::{connect_async, tungstenite::{client::IntoClientRequest, http::{HeaderValue, StatusCode}, Error}};
+
+const PORT: u16 = 18646;
+
+async fn connect(query: &str, origin: Option<&str>) -> Result<(), Error> {
+	// The relay may not be listening yet
+	for _ in 0..50 {
+		let mut request = format!("ws://127.0.0.1:{PORT}/{query}").into_client_request().unwrap();
+		if let Some(origin) = origin {
+			request.headers_mut().insert("Origin", HeaderValue::from_str(origin).unwrap());
+		}
+
+		match connect_async(request).await {
+			Err(Error::Io(_)) => sleep(Duration::from_millis(20)).await,
+			result => return result.map(|_| ())
+		}
+	}
+	panic!("the relay never started listening")
+}
+
+fn status(result: Result<(), Error>) -> Option<StatusCode> {
+	match result {
+		Err(Error::Http(response)) => Some(response.status()),
+		_ => None
+	}
+}
+
+#[tokio::test]
+async fn handshake_requires_the_token_and_an_allowed_origin() {
+	let events = broadcast::channel(1).0;
+	tokio::spawn(relay::serve(events, RelaySettings {
+		port: PORT,
+		token: "secret".to_string(),
+		allowed_origins: vec!["http://localhost:3000".to_string()]
+	}));
+
+	assert_eq!(status(connect("", None).await), Some(StatusCode::UNAUTHORIZED));
+	assert_eq!(status(connect("?token=wrong", None).await), Some(StatusCode::UNAUTHORIZED));
+	assert!(connect("?token=secret", None).await.is_ok());
+
+	assert_eq!(status(connect("?token=secret", Some("https://example.com")).await), Some(StatusCode::FORBIDDEN));
+	assert!(connect("?token=secret", Some("http://localhost:3000")).await.is_ok());
+}
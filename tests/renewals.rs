@@ -0,0 +1,42 @@
+use of_client::user::Subscription;
+use of_notifier::renewals::{RenewalAlert, RenewalMonitor};
+
+fn subscription(id: u64, expired: bool, auto_renew: bool) -> Subscription {
+	serde_json::from_value(serde_json::json!({
+		"id": id,
+		"name": "OnlyFans",
+		"username": format!("creator{id}"),
+		"subscribedByAutoprolong": auto_renew,
+		"subscribedByExpireDate": "2024-01-01T00:00:00+00:00",
+		"subscribedIsExpiredNow": expired
+	})).unwrap()
+}
+
+#[test]
+fn renewing_subscriptions_that_expire_are_reported_once() {
+	let mut monitor = RenewalMonitor::default();
+	assert!(monitor.check(&[subscription(1, false, true)]).is_empty());
+
+	let alerts = monitor.check(&[subscription(1, true, true)]);
+	assert_eq!(alerts.len(), 1);
+	assert_eq!(alerts[0].1, RenewalAlert::Lapsed);
+
+	assert!(monitor.check(&[subscription(1, true, true)]).is_empty());
+}
+
+#[test]
+fn disabled_auto_renew_is_reported() {
+	let mut monitor = RenewalMonitor::default();
+	monitor.check(&[subscription(1, false, true), subscription(2, false, false)]);
+
+	let alerts = monitor.check(&[subscription(1, false, false), subscription(2, true, false)]);
+	assert_eq!(alerts.iter().map(|(user, alert)| (user.id, *alert)).collect::<Vec<_>>(), [(1, RenewalAlert::AutoRenewDisabled)]);
+}
+
+#[test]
+fn expired_subscriptions_set_to_renew_are_reported_on_start() {
+	let mut monitor = RenewalMonitor::default();
+	let alerts = monitor.check(&[subscription(1, true, true), subscription(2, true, false)]);
+	assert_eq!(alerts.len(), 1);
+	assert_eq!(alerts[0].0.id, 1);
+}
@@ -0,0 +1,33 @@
+use chrono::{TimeDelta, Utc};
+use of_client::content::ContentType;
+use of_notifier::{catalog::Catalog, report, stats::Stats};
+
+#[test]
+fn creators_are_ranked_by_activity() {
+	let catalog = Catalog::open(":memory:").unwrap();
+	catalog.record_event("alice", ContentType::Posts).unwrap();
+	for _ in 0..3 {
+		catalog.record_event("bob", ContentType::Chats).unwrap();
+	}
+
+	let report = report::render(&catalog, &Stats::default(), Utc::now() - TimeDelta::days(7)).unwrap();
+	let bob = report.find("| bob | 3 |").unwrap();
+	let alice = report.find("| alice | 1 |").unwrap();
+	assert!(bob < alice);
+	assert!(report.contains("- Messages: 3"));
+}
+
+#[test]
+fn outages_are_listed() {
+	let stats = Stats::default();
+	stats.record_disconnected();
+	stats.record_connected();
+	stats.record_disconnected();
+
+	let outages = stats.outages_since(Utc::now() - TimeDelta::days(7));
+	assert_eq!(outages.len(), 2);
+	assert!(outages[1].end.is_none());
+
+	let report = report::render(&Catalog::open(":memory:").unwrap(), &stats, Utc::now() - TimeDelta::days(7)).unwrap();
+	assert!(report.contains("still disconnected"));
+}
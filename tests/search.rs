@@ -0,0 +1,30 @@
+use of_client::content::ContentType;
+use of_notifier::search::SearchIndex;
+
+fn index() -> SearchIndex {
+	let index = SearchIndex::in_memory().unwrap();
+	index.add("alice", ContentType::Posts, 1, "See you at the con, schedule below").unwrap();
+	index.add("bob", ContentType::Chats, 2, "New set is up").unwrap();
+	index
+}
+
+#[test]
+fn text_is_found() {
+	let hits = index().search("con schedule", 10).unwrap();
+	assert_eq!(hits.len(), 1);
+	assert_eq!((hits[0].username.as_str(), hits[0].content_type.as_str(), hits[0].content_id), ("alice", "Posts", 1));
+}
+
+#[test]
+fn edited_text_replaces_the_old_one() {
+	let index = index();
+	index.add("alice", ContentType::Posts, 1, "Con was cancelled").unwrap();
+
+	assert!(index.search("schedule", 10).unwrap().is_empty());
+	assert_eq!(index.search("cancelled", 10).unwrap().len(), 1);
+}
+
+#[test]
+fn malformed_queries_still_search() {
+	assert_eq!(index().search("set AND (", 10).unwrap().len(), 1);
+}
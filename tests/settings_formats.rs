@@ -0,0 +1,84 @@
+use std::fs;
+use of_notifier::settings::{Settings, SettingsFormat};
+
+const JSON: &str = r#"{
+	"actions": {
+		"default": { "notify": true, "download": { "posts": "all", "messages": false, "stories": true }, "like": false },
+		"exceptions": []
+	},
+	"reconnect": false,
+	"max_concurrent_downloads": 2
+}"#;
+
+#[test]
+fn json_is_used_when_nothing_exists() {
+	let dir = tempfile::tempdir().unwrap();
+	assert_eq!(SettingsFormat::detect(dir.path()), (dir.path().join("settings.json"), SettingsFormat::Json));
+}
+
+#[test]
+fn json_is_read() {
+	let dir = tempfile::tempdir().unwrap();
+	fs::write(dir.path().join("settings.json"), JSON).unwrap();
+
+	let settings = Settings::read(dir.path()).unwrap();
+	assert!(!settings.reconnect);
+	assert_eq!(settings.max_concurrent_downloads, 2);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn toml_is_read() {
+	let dir = tempfile::tempdir().unwrap();
+	fs::write(dir.path().join("settings.toml"), r#"
+reconnect = false
+max_concurrent_downloads = 2
+
+[actions]
+exceptions = []
+
+[actions.default]
+notify = true
+like = false
+download = { posts = "all", messages = false, stories = true }
+"#).unwrap();
+
+	assert_eq!(SettingsFormat::detect(dir.path()).1, SettingsFormat::Toml);
+	let settings = Settings::read(dir.path()).unwrap();
+	assert!(!settings.reconnect);
+	assert_eq!(settings.max_concurrent_downloads, 2);
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn yaml_is_read() {
+	let dir = tempfile::tempdir().unwrap();
+	fs::write(dir.path().join("settings.yaml"), "
+reconnect: false
+max_concurrent_downloads: 2
+actions:
+  default:
+    notify: true
+    download:
+      posts: all
+      messages: false
+      stories: true
+    like: false
+  exceptions: []
+").unwrap();
+
+	assert_eq!(SettingsFormat::detect(dir.path()).1, SettingsFormat::Yaml);
+	let settings = Settings::read(dir.path()).unwrap();
+	assert!(!settings.reconnect);
+	assert_eq!(settings.max_concurrent_downloads, 2);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn json_takes_precedence() {
+	let dir = tempfile::tempdir().unwrap();
+	fs::write(dir.path().join("settings.json"), JSON).unwrap();
+	fs::write(dir.path().join("settings.toml"), "").unwrap();
+
+	assert_eq!(SettingsFormat::detect(dir.path()).1, SettingsFormat::Json);
+}
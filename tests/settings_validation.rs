@@ -0,0 +1,124 @@
+use of_notifier::settings::{validation::SettingsError, Settings};
+use serde_json::json;
+
+fn settings(extra: serde_json::Value) -> Settings {
+	let mut settings = json!({
+		"actions": {
+			"default": { "notify": true, "download": false, "like": false },
+			"exceptions": []
+		},
+		"reconnect": true
+	});
+
+	settings.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+	serde_json::from_value(settings).unwrap()
+}
+
+#[test]
+fn defaults_are_valid() {
+	assert!(settings(json!({})).validate().is_empty());
+}
+
+#[test]
+fn conflicting_exceptions_are_reported() {
+	let settings = settings(json!({
+		"creators": {
+			"exceptions": [
+				{ "users": ["alice", "bob"], "options": { "notify_post_edits": true } },
+				{ "users": ["alice"], "options": { "notify_post_edits": false, "mark_messages_read": true } }
+			]
+		}
+	}));
+
+	assert_eq!(settings.validate(), vec![SettingsError::ConflictingException { username: "alice".to_string(), option: "notify_post_edits".to_string() }]);
+}
+
+#[test]
+fn invalid_combinations_are_reported() {
+	let settings = settings(json!({
+		"control": {
+			"rest": { "port": 9000, "token": "" },
+			"web": { "port": 9000, "token": "secret" }
+		},
+		"likes": { "max_per_hour": 50, "max_per_day": 10 },
+		"paths": { "template": "{username}/{kind}/{filename}" }
+	}));
+
+	let settings = settings.validate().into_iter().map(|err| match err {
+		SettingsError::Invalid { setting, .. } => setting,
+		err => panic!("unexpected {err}")
+	}).collect::<Vec<_>>();
+
+	assert_eq!(settings, ["control.rest.token", "control.web.port", "likes.max_per_hour", "paths.template"]);
+}
+
+#[test]
+fn grpc_requires_a_token() {
+	let errors = settings(json!({ "control": { "grpc": { "token": "" } } })).validate();
+	assert!(matches!(&errors[..], [SettingsError::Invalid { setting, .. }] if *setting == "control.grpc.token"));
+}
+
+#[test]
+fn template_must_end_with_filename() {
+	let errors = settings(json!({ "paths": { "template": "{filename}/{username}" } })).validate();
+	assert_eq!(errors.len(), 1);
+	assert!(errors[0].to_string().contains("must end with"));
+}
+
+#[test]
+fn unknown_creators_are_reported() {
+	let settings = settings(json!({
+		"creators": {
+			"exceptions": [{ "users": ["Alice", "bobb"], "options": { "notify_post_edits": true } }]
+		}
+	}));
+
+	assert_eq!(settings.unknown_creators(["alice", "bob"]), vec![SettingsError::UnknownCreator { username: "bobb".to_string() }]);
+}
+
+#[test]
+fn parse_errors_have_a_position() {
+	let err = serde_json::from_str::<Settings>("{\n  \"reconnect\": 1\n}").unwrap_err();
+	assert!(matches!(SettingsError::from(&err), SettingsError::Parse { line: 2, .. }));
+}
+
+#[test]
+fn accounts_run_once() {
+	let errors = settings(json!({ "profile": "main", "accounts": ["second", "main"] })).validate();
+	assert_eq!(errors.len(), 1);
+	assert!(matches!(&errors[0], SettingsError::Invalid { setting: "accounts", .. }));
+}
+
+#[test]
+fn tips_are_toggled_with_the_notifications() {
+	assert!(!settings(json!({})).tips("alice"));
+
+	let settings = settings(json!({
+		"actions": {
+			"default": { "notify": { "notifications": { "tips": true } }, "download": false, "like": false },
+			"exceptions": [{ "users": ["bob"], "actions": { "notify": { "notifications": { "tips": false } } } }]
+		}
+	}));
+	assert!(settings.tips("alice"));
+	assert!(!settings.tips("bob"));
+}
+
+#[test]
+fn root_tips_key_is_still_read() {
+	assert!(settings(json!({ "tips": true })).tips("alice"));
+}
+
+#[test]
+fn gif_conversion_is_detected_in_exceptions() {
+	assert!(!settings(json!({})).convert_gifs());
+	assert!(!settings(json!({ "creators": { "default": { "gifs": "mp4" } } })).convert_gifs());
+	assert!(settings(json!({
+		"creators": { "exceptions": [{ "users": ["alice"], "options": { "gifs": "webp" } }] }
+	})).convert_gifs());
+}
+
+#[test]
+fn relay_requires_a_token() {
+	let errors = settings(json!({ "relay": { "token": "" } })).validate();
+	assert!(matches!(&errors[..], [SettingsError::Invalid { setting, .. }] if *setting == "relay.token"));
+}
@@ -0,0 +1,15 @@
+use of_notifier::setup::header_value;
+
+#[test]
+fn header_names_are_stripped() {
+	assert_eq!(header_value("Cookie: sess=abc; auth_id=1\n", "cookie"), "sess=abc; auth_id=1");
+	assert_eq!(header_value("  x-bc:0123abcd ", "x-bc"), "0123abcd");
+}
+
+#[test]
+fn values_are_kept_as_pasted() {
+	assert_eq!(header_value("sess=abc; auth_id=1\n", "cookie"), "sess=abc; auth_id=1");
+	// The user agent contains colons of its own
+	let user_agent = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0";
+	assert_eq!(header_value(user_agent, "user-agent"), user_agent);
+}
@@ -291,4 +291,15 @@ socket_test!(test_chat_count_message, r#"{
 	"unread_tips": 1
 }"#, Message::ChatCount(_));
 
-socket_test!(test_new_hints_message, r#"{"has_new_hints":true}"#, Message::Tagged(TaggedMessage::HasNewHints(_)));
\ No newline at end of file
+socket_test!(test_new_hints_message, r#"{"has_new_hints":true}"#, Message::Tagged(TaggedMessage::HasNewHints(_)));
+
+socket_test!(test_toasts_message, r#"{
+	"toasts": [
+		{
+			"id": 1234,
+			"title": "Scheduled maintenance",
+			"text": "OnlyFans will be unavailable for a short time",
+			"link": null
+		}
+	]
+}"#, Message::Tagged(TaggedMessage::Toasts(_)));
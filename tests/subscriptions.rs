@@ -0,0 +1,31 @@
+use of_client::user::Subscription;
+use of_notifier::subscriptions::SubscriptionCache;
+
+fn subscription(id: u64, expired: bool) -> Subscription {
+	serde_json::from_value(serde_json::json!({
+		"id": id,
+		"name": "OnlyFans",
+		"username": format!("creator{id}"),
+		"subscribedIsExpiredNow": expired
+	})).unwrap()
+}
+
+#[test]
+fn everyone_is_subscribed_until_loaded() {
+	let cache = SubscriptionCache::default();
+	assert!(!cache.is_loaded());
+	assert!(cache.is_subscribed(1));
+	assert_eq!(cache.username(1), None);
+}
+
+#[test]
+fn only_active_subscriptions_count() {
+	let cache = SubscriptionCache::default();
+	cache.update(&[subscription(1, false), subscription(2, true)]);
+
+	assert!(cache.is_subscribed(1));
+	assert!(!cache.is_subscribed(2));
+	assert!(!cache.is_subscribed(3));
+	assert_eq!(cache.username(2).as_deref(), Some("creator2"));
+	assert_eq!(cache.user_id("creator1"), Some(1));
+}
@@ -1,6 +1,7 @@
 mod init;
 
 use init::init_log;
+use futures::{pin_mut, TryStreamExt};
 use of_notifier::init_client;
 
 #[tokio::test]
@@ -9,7 +10,10 @@ async fn get_subscriptions() {
 
 	let client = init_client().unwrap();
 
-	for user in client.get_subscriptions().await.unwrap() {
+	let subscriptions = client.get_subscriptions();
+	pin_mut!(subscriptions);
+
+	while let Some(user) = subscriptions.try_next().await.unwrap() {
 		println!("{user:#?}");
 	}
 }
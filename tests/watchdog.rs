@@ -0,0 +1,30 @@
+use std::time::Duration;
+use of_notifier::watchdog::{watch, Progress, Stalled};
+use tokio::time::sleep;
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+#[tokio::test]
+async fn downloads_making_progress_are_left_alone() {
+	let progress = Progress::default();
+	let result = watch(&progress, TIMEOUT, async {
+		for _ in 0..10 {
+			sleep(Duration::from_millis(10)).await;
+			progress.touch();
+		}
+		Ok(())
+	}).await;
+
+	assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn stalled_downloads_are_cancelled() {
+	let progress = Progress::default();
+	let result = watch(&progress, TIMEOUT, async {
+		sleep(Duration::from_secs(60)).await;
+		Ok(())
+	}).await;
+
+	assert!(result.unwrap_err().is::<Stalled>());
+}